@@ -10,9 +10,10 @@ fn main() {
 
     let (page2, _) = doc.add_page(Mm(297.0), Mm(210.0), "Page 2, Layer 1");
     let _ = doc.get_page(page2).add_layer("Layer 3");
-    doc.add_bookmark("This is another bookmark", page2);
+    let bookmark2 = doc.add_bookmark("This is another bookmark", page2);
+    doc.add_child_bookmark("This is a nested bookmark", page2, bookmark2);
 
-    // If this is successful, you should see a PDF with two blank A4 pages and 2 bookmarks
+    // If this is successful, you should see a PDF with two blank A4 pages and 3 bookmarks
     doc.save(&mut BufWriter::new(
         File::create("test_bookmark.pdf").unwrap(),
     ))
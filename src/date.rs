@@ -5,6 +5,18 @@ pub use self::js_sys_date::OffsetDateTime;
 #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 pub use time::OffsetDateTime;
 
+/// Returns the offset of the date to UTC in minutes (positive east of UTC)
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) fn utc_offset_minutes(date: &OffsetDateTime) -> i32 {
+    i32::from(date.offset().as_minutes())
+}
+
+/// Returns the offset of the date to UTC in minutes (positive east of UTC)
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) fn utc_offset_minutes(date: &OffsetDateTime) -> i32 {
+    date.offset_minutes()
+}
+
 #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
 mod js_sys_date {
     use js_sys::Date;
@@ -29,6 +41,12 @@ mod js_sys_date {
             "".into()
         }
 
+        /// The JS date is in local time, `getTimezoneOffset` is UTC - local time
+        #[inline(always)]
+        pub(crate) fn offset_minutes(&self) -> i32 {
+            -(self.0.get_timezone_offset() as i32)
+        }
+
         #[inline(always)]
         pub fn year(&self) -> u32 {
             self.0.get_full_year()
@@ -4,6 +4,7 @@ use std::error::Error as IError;
 use std::io::Error as IoError;
 use rusttype::Error as RusttypeError;
 use std::fmt;
+use ContentError;
 
 /// error_chain and failure are certainly nice, but completely overengineered
 /// for this use-case. For example, neither of them allow error localization.
@@ -34,16 +35,73 @@ pub enum Error {
     Pdf(PdfError),
     /// Indexing error (please report if this happens, shouldn't happen)
     Index(IndexError),
+    /// Malformed content streams, see `PdfDocumentReference::validate_content`
+    Content(Vec<ContentError>),
+    /// Several errors, see `PdfDocumentReference::check_for_errors`
+    Multiple(Vec<Error>),
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum PdfError {
     FontFaceError,
+    ImageTooLarge,
+    DuplicateOutputIntent,
+    InvalidThumbnail,
+    TooManyGlyphs,
+    InvalidSvg,
+    UnresolvedStructureRole,
+    InvalidImageMask,
+    NonFullScreenPageModeWithoutFullScreen,
+    MissingGlyph,
+    InvalidJpeg,
+    ProgressiveJpeg,
+    InvalidPng,
+    UnsupportedPngBitDepth,
+    InvalidRotation,
+    FontNotEmbedded,
+    TransparencyNotAllowed,
+    MissingOutputIntentProfile,
+    DeviceColorNotAllowed,
+    EncryptionNotAllowed,
+    NoPages,
+    InvalidPageSize,
+    UnknownFont,
+    UnknownXObject,
+    InvalidXmpProperty,
+    InvalidPageLabelStart,
 }
 
 impl fmt::Display for PdfError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Invalid or corrupt font face")
+        use self::PdfError::*;
+        write!(f, "{}", match *self {
+            FontFaceError => "Invalid or corrupt font face",
+            ImageTooLarge => "Image exceeds the maximum image dimension of the document",
+            DuplicateOutputIntent => "More than one output intent for the same PDF standard",
+            InvalidThumbnail => "Page thumbnails must be RGB, greyscale or indexed images",
+            TooManyGlyphs => "Type3 fonts can have at most 256 glyphs",
+            InvalidSvg => "Invalid SVG data or the size of the SVG could not be determined",
+            UnresolvedStructureRole => "Custom structure type is not mapped to a standard structure type",
+            InvalidImageMask => "Image mask is empty or its data doesn't contain all pixels",
+            NonFullScreenPageModeWithoutFullScreen => "The non-full-screen page mode is only used with the full-screen page mode",
+            MissingGlyph => "Text contains characters the font has no glyph for",
+            InvalidJpeg => "Invalid JPEG data or unsupported JPEG variant (only 8-bit greyscale / RGB baseline JPEGs are supported)",
+            ProgressiveJpeg => "Progressive JPEGs can't be embedded without re-encoding them",
+            InvalidPng => "Invalid or corrupt PNG data",
+            UnsupportedPngBitDepth => "Only PNG images with 8 or 16 bits per component are supported",
+            InvalidRotation => "Page rotation must be a multiple of 90 degrees",
+            FontNotEmbedded => "The PDF conformance requires all fonts to be embedded, but a builtin font is used",
+            TransparencyNotAllowed => "The PDF conformance doesn't allow transparency, see flatten_transparency",
+            MissingOutputIntentProfile => "The PDF conformance requires an output intent with an ICC profile",
+            DeviceColorNotAllowed => "The PDF conformance only allows device colors in the color space of the output intent (or DeviceGray)",
+            EncryptionNotAllowed => "The PDF conformance doesn't allow encryption",
+            NoPages => "The document has no pages",
+            InvalidPageSize => "Page width and height must be positive and at most 14400 points",
+            UnknownFont => "A layer uses a font that was not added to the document",
+            UnknownXObject => "A layer uses an image or XObject that was not added to the page",
+            InvalidXmpProperty => "Custom XMP properties need a valid prefix and key and must not belong to a built-in schema",
+            InvalidPageLabelStart => "Page labels must start at 1 or higher",
+        })
     }
 }
 
@@ -62,7 +120,7 @@ impl fmt::Display for IndexError {
         write!(f, "{}", match *self {
             PdfPageIndexError => "Page index out of bounds",
             PdfLayerIndexError => "PDF layer index out of bounds",
-            PdfMarkerIndexError => "PDF layer index out of bounds",
+            PdfMarkerIndexError => "PDF marker index out of bounds",
         })
     }
 }
@@ -82,8 +140,25 @@ impl fmt::Display for Error {
             Rusttype(ref e) => write!(f, "{}", e),
             Pdf(ref e) => write!(f, "{}", e),
             Index(ref e) => write!(f, "{}", e),
+            Content(ref errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "Invalid content stream: {}", errors.join("; "))
+            },
+            Multiple(ref errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", errors.join("\n"))
+            },
         }
     }
 }
 
 impl IError for Error {}
+
+#[test]
+fn test_index_error_messages() {
+    let messages = [IndexError::PdfPageIndexError, IndexError::PdfLayerIndexError, IndexError::PdfMarkerIndexError]
+        .iter()
+        .map(|e| Error::Index(*e).to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["Page index out of bounds", "PDF layer index out of bounds", "PDF marker index out of bounds"]);
+}
@@ -86,9 +86,9 @@ pub(crate) const PDF_TAG_4BEZIER_CURVE: &'static str          = "c";
 /// Set line width
 pub(crate) const OP_PATH_STATE_SET_LINE_WIDTH: &str                 = "w";
 /// Set line join
-pub(crate) const OP_PATH_STATE_SET_LINE_JOIN: &str                  = "J";
+pub(crate) const OP_PATH_STATE_SET_LINE_JOIN: &str                  = "j";
 /// Set line cap
-pub(crate) const OP_PATH_STATE_SET_LINE_CAP: &str                   = "j";
+pub(crate) const OP_PATH_STATE_SET_LINE_CAP: &str                   = "J";
 /// Set miter limit
 pub(crate) const OP_PATH_STATE_SET_MITER_LIMIT: &str                = "M";
 /// Set line dash pattern
@@ -148,7 +148,7 @@ pub(crate) const OP_PATH_CONST_RECT: &str                           = "re";
 pub(crate) const OP_PATH_CONST_CLOSE_SUBPATH: &str                  = "h";
 /// Current path is a clip path, non-zero winding order (usually in like `h W S`)
 pub(crate) const OP_PATH_CONST_CLIP_NZ: &str = "W";
-/// Current path is a clip path, non-zero winding order
+/// Current path is a clip path, even-odd rule
 pub(crate) const OP_PATH_CONST_CLIP_EO: &str = "W*";
 
 /// Path painting
@@ -174,5 +174,8 @@ pub(crate) const OP_PATH_PAINT_FILL_STROKE_CLOSE_EO: &str           = "b*";
 /// End path without filling or stroking
 pub(crate) const OP_PATH_PAINT_END: &str                            = "n";
 
+/// Maximum width / height of a page in points (200 inches), larger pages are rejected by PDF viewers
+pub(crate) const MAX_PAGE_LENGTH_PT: f64 = 14400.0;
+
 /// These color profiles are included in the binary
 pub const ICC_PROFILE_ECI_V2: &[u8] = include_bytes!("templates/CoatedFOGRA39.icc");
\ No newline at end of file
@@ -8,6 +8,10 @@ pub struct PdfPageIndex(pub(crate) usize);
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct PdfLayerIndex(pub(crate) usize);
 
+/// Index of a bookmark (outline item) in the document
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BookmarkIndex(pub(crate) usize);
+
 /// Index of the arbitrary content data
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct PdfContentIndex(pub(crate) usize);
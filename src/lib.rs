@@ -37,6 +37,9 @@
 //! doc.save(&mut BufWriter::new(File::create("test_working.pdf").unwrap())).unwrap();
 //! ```
 //!
+//! Page sizes and points can be given in millimeter (`Mm`), point (`Pt`) or inch (`In`),
+//! for example `doc.add_page(In(8.5), In(11.0), "Letter")`.
+//!
 //! ### Adding graphical shapes
 //!
 //! ```rust
@@ -142,19 +145,19 @@
 //!     image.add_to_layer(current_layer.clone(), None, None, None, None, None, None);
 //!
 //!     // you can also construct images manually from your data:
-//!     let mut image_file_2 = ImageXObject {
-//!         width: Px(200),
-//!         height: Px(200),
-//!         color_space: ColorSpace::Greyscale,
-//!         bits_per_component: ColorBits::Bit8,
-//!         interpolate: true,
+//!     let mut image_file_2 = ImageXObject::new(
+//!         Px(200),
+//!         Px(200),
+//!         ColorSpace::Greyscale,
+//!         ColorBits::Bit8,
+//!         true, /* interpolate */
+//!         None, /* image filter, does not work yet */
+//!         None, /* clipping bbox, doesn't work either, untested */
 //!         /* put your bytes here. Make sure the total number of bytes =
 //!            width * height * (bytes per component * number of components)
 //!            (e.g. 2 (bytes) x 3 (colors) for RGB 16bit) */
-//!         image_data: Vec::new(),
-//!         image_filter: None, /* does not work yet */
-//!         clipping_bbox: None, /* doesn't work either, untested */
-//!     };
+//!         Vec::new(),
+//!     );
 //!
 //!     let image2 = Image::from(image_file_2);
 //! }
@@ -231,6 +234,13 @@
 //! this wherever possible, by making the document only public to the crate
 //! so you cannot lock it from outside of this library.
 //!
+//! The `PdfDocumentReference` owns the document, pages and layers are addressed by
+//! their index (`PdfPageIndex` / `PdfLayerIndex`, use `doc.get_layer(page, layer)`).
+//! The page and layer references are only handles (a weak reference plus the index):
+//! this is what allows keeping references to several layers at the same time and drawing
+//! on them in any order, which a `&mut` based API would forbid. Everything is single-threaded
+//! (`Rc` / `RefCell`, no locks), so there is nothing that could deadlock.
+//!
 //! Images have to be added to the pages resources before using them. Meaning,
 //! you can only use an image on the page that you added it to. Otherwise,
 //! you may end up with a corrupt PDF.
@@ -342,6 +352,8 @@ extern crate js_sys;
 pub mod date;
 #[cfg(feature = "embedded_images")]
 pub extern crate image;
+#[cfg(feature = "embedded_images")]
+extern crate png;
 
 pub mod types;
 pub mod scale;
@@ -356,23 +368,35 @@ pub use date::*;
 pub use self::errors::IndexError;
 pub use rusttype::Error as RusttypeError;
 
-pub use self::scale::{Mm, Pt, Px};
+pub use self::scale::{Mm, Pt, Px, In};
 pub use self::types::pdf_conformance::{CustomPdfConformance, PdfConformance};
 pub use self::types::pdf_document::{PdfDocumentReference, PdfDocument};
-pub use self::types::pdf_metadata::PdfMetadata;
-pub use self::types::pdf_page::{PdfPage, PdfPageReference};
-pub use self::types::pdf_layer::{PdfLayer, PdfLayerReference};
+pub use self::types::pdf_metadata::{PdfMetadata, MetadataMergeStrategy};
+pub use self::types::pdf_page::{PdfPage, PdfPageReference, MAX_THUMBNAIL_DIMENSION};
+pub use self::types::pdf_layer::{PdfLayer, PdfLayerReference, ChangeBarSide};
 
-pub use self::types::plugins::xmp::xmp_metadata::XmpMetadata;
+pub use self::types::plugins::xmp::xmp_metadata::{XmpMetadata, XmpProperty, XmpThumbnail};
+pub use self::types::plugins::misc::arbitrary_content::{IntoPdfObjects, PdfObjects, StreamFilter};
 pub use self::types::plugins::misc::document_info::DocumentInfo;
+pub use self::types::plugins::security::encrypt::{Encryption, Permissions};
+pub use self::types::plugins::misc::optimize::{OptimizeOptions, OptimizeReport, SaveOptions};
+pub use self::types::plugins::misc::outline::Bookmark;
+pub use self::types::plugins::misc::output_intent::{OutputIntent, OutputIntentSubtype};
+pub use self::types::plugins::misc::page_label::{PageLabel, PageLabelStyle};
+pub use self::types::plugins::misc::structure::StructureRole;
+pub use self::types::plugins::misc::viewer_preferences::{PageMode, NonFullScreenPageMode};
 
 /// Stub module for 3D content in a PDF
 pub use self::types::plugins::graphics::three_dimensional;
 pub use self::types::plugins::graphics::two_dimensional::font::{
-    Font, FontData, BuiltinFont, ExternalFont, TextRenderingMode, IndirectFontRef, DirectFontRef, FontList
+    Font, FontData, FontFamily, BuiltinFont, ExternalFont, TextRenderingMode, TextOptions, TextAlignment, MissingGlyphPolicy, IndirectFontRef, DirectFontRef, FontList
 };
-pub use self::types::plugins::graphics::two_dimensional::image::Image;
+pub use self::types::plugins::graphics::two_dimensional::type3_font::{Type3Font, GlyphProc};
+pub use self::types::plugins::graphics::two_dimensional::image::{Image, OversizedImagePolicy, DEFAULT_MAX_IMAGE_DIMENSION};
 pub use self::types::plugins::graphics::two_dimensional::line::Line;
+pub use self::types::plugins::graphics::two_dimensional::path::{Path, PathSegment};
+pub use self::types::plugins::graphics::two_dimensional::rect::{Rect, DrawResult};
+pub use self::types::plugins::graphics::two_dimensional::chart::{BarChart, LineChart, ChartSeries};
 pub use self::types::plugins::graphics::two_dimensional::point::Point;
 
 pub use self::types::plugins::graphics::color::{
@@ -384,16 +408,21 @@ pub use self::types::plugins::graphics::extgstate::{
     OverprintMode, BlackGenerationFunction, BlackGenerationExtraFunction, UnderColorRemovalFunction,
     UnderColorRemovalExtraFunction, TransferFunction, TransferExtraFunction, HalftoneType,
     SpotFunction, BlendMode, SeperableBlendMode, NonSeperableBlendMode, RenderingIntent, SoftMask,
-    SoftMaskFunction, LineJoinStyle, LineCapStyle, LineDashPattern,
+    SoftMaskFunction, LineJoinStyle, LineCapStyle, LineDashPattern, DashPattern,
 };
 pub use self::types::plugins::graphics::icc_profile::{
     IccProfileType, IccProfile, IccProfileRef, IccProfileList
 };
 pub use self::types::plugins::graphics::ocg::{OCGList, OCGRef};
 pub use self::types::plugins::graphics::pattern::{Pattern, PatternRef, PatternList};
+pub use self::types::plugins::graphics::gradient::{Gradient, GradientStop, GradientShape};
 pub use self::types::plugins::graphics::pdf_resources::PdfResources;
+pub use self::types::plugins::graphics::palette::Palette;
+pub use self::types::plugins::graphics::transparency::UnflattenedTransparency;
+pub use self::types::plugins::graphics::content_validation::{ContentError, ContentErrorKind};
+pub use self::types::plugins::graphics::image_report::ImageReport;
 pub use self::types::plugins::graphics::xobject::{
-    XObject, XObjectList, XObjectRef, ImageXObject, ImageXObjectRef,
+    XObject, XObjectList, XObjectRef, ImageXObject, ImageXObjectRef, ImageMaskXObject,
     ImageFilter, FormXObject, FormXObjectRef, FormType, SMask, GroupXObject,
     GroupXObjectType, ReferenceXObject, OptionalContentGroup, OCGIntent, PostScriptXObject,
 };
@@ -404,5 +433,5 @@ pub use self::types::plugins::media::audio;
 pub use self::types::plugins::media::video;
 /// Stub module for interactive (JavaScript) content, embedded in PDF files
 pub use self::types::plugins::interactive;
-/// Stub module for encryption (passwords). Not implemented yet.
+/// Encryption (password protection, see `SaveOptions::encryption`)
 pub use self::types::plugins::security;
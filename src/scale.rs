@@ -46,6 +46,36 @@ impl From<Pt> for ::lopdf::Object {
 
 impl_partialeq!(Pt);
 
+/// Scale in inch
+#[derive(Debug, Copy, Clone, PartialOrd)]
+pub struct In(pub f64);
+
+impl From<In> for Pt {
+    fn from(value: In) -> Pt {
+        Pt(value.0 * 72.0)
+    }
+}
+
+impl From<In> for Mm {
+    fn from(value: In) -> Mm {
+        Mm(value.0 * 25.4)
+    }
+}
+
+impl From<Pt> for In {
+    fn from(value: Pt) -> In {
+        In(value.0 / 72.0)
+    }
+}
+
+impl From<Mm> for In {
+    fn from(value: Mm) -> In {
+        In(value.0 / 25.4)
+    }
+}
+
+impl_partialeq!(In);
+
 /// Scale in pixels
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Px(pub usize);
@@ -155,31 +185,39 @@ macro_rules! impl_div_assign_f64 {
 
 impl_add_self!(Mm);
 impl_add_self!(Pt);
+impl_add_self!(In);
 impl_add_self!(Px);
 
 impl_add_assign_self!(Mm);
 impl_add_assign_self!(Pt);
+impl_add_assign_self!(In);
 impl_add_assign_self!(Px);
 
 impl_sub_assign_self!(Mm);
 impl_sub_assign_self!(Pt);
+impl_sub_assign_self!(In);
 impl_sub_assign_self!(Px);
 
 impl_sub_self!(Mm);
 impl_sub_self!(Pt);
+impl_sub_self!(In);
 impl_sub_self!(Px);
 
 impl_mul_f64!(Mm);
 impl_mul_f64!(Pt);
+impl_mul_f64!(In);
 
 impl_mul_assign_f64!(Mm);
 impl_mul_assign_f64!(Pt);
+impl_mul_assign_f64!(In);
 
 impl_div!(Mm);
 impl_div!(Pt);
+impl_div!(In);
 
 impl_div_assign_f64!(Mm);
 impl_div_assign_f64!(Pt);
+impl_div_assign_f64!(In);
 
 #[test]
 fn point_to_mm_conversion() {
@@ -196,3 +234,12 @@ fn mm_to_point_conversion() {
     assert_eq!(mm1, Pt(2.83464745483286));
     assert_eq!(mm2, Pt(65.1969));
 }
+
+#[test]
+fn inch_conversion() {
+    let pt: Pt = In(1.0).into();
+    let mm: Mm = In(2.0).into();
+    assert_eq!(pt, Pt(72.0));
+    assert_eq!(mm, Mm(50.8));
+    assert_eq!(In::from(Mm(25.4)), In(1.0));
+}
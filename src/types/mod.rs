@@ -8,8 +8,8 @@ pub mod pdf_page;
 pub mod plugins;
 
 pub use self::pdf_document::{PdfDocument, PdfDocumentReference};
-pub use self::pdf_layer::{PdfLayer, PdfLayerReference};
+pub use self::pdf_layer::{PdfLayer, PdfLayerReference, ChangeBarSide};
 pub use self::pdf_page::{PdfPage, PdfPageReference};
 pub use self::pdf_conformance::{PdfConformance, CustomPdfConformance};
-pub use self::pdf_metadata::PdfMetadata;
+pub use self::pdf_metadata::{PdfMetadata, MetadataMergeStrategy};
 pub use self::plugins::*;
\ No newline at end of file
@@ -7,7 +7,7 @@
 //! [PDF/A Versions](https://en.wikipedia.org/wiki/PDF/A)
 
 /// List of (relevant) PDF versions
-/// Please note the difference between **PDF/A** (archiving), **PDF/UA** (universal acessibility),
+/// Please note the difference between **PDF/A** (archiving), **PDF/UA** (universal accessibility),
 /// **PDF/X** (printing), **PDF/E** (engineering / CAD), **PDF/VT** (large volume transactions with
 /// repeated content)
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -238,10 +238,126 @@ impl PdfConformance {
             PdfConformance::X5G_2010_PDF_1_6  => { true },
             PdfConformance::X5PG_2010_PDF_1_6 => { true },
             PdfConformance::Custom(ref c)     => { c.requires_xmp_metadata }
-            _                                 => { false },
+            _                                 => { self.get_pdfa_part_and_conformance().is_some() },
         }
     }
 
+    /// Returns the part and the conformance level (`pdfaid:part` / `pdfaid:conformance` in the
+    /// XMP metadata) if this is a PDF/A standard, e.g. `(1, "B")` for PDF/A-1b
+    pub fn get_pdfa_part_and_conformance(&self)
+    -> Option<(u32, &'static str)>
+    {
+        match *self {
+            PdfConformance::A1B_2005_PDF_1_4  => Some((1, "B")),
+            PdfConformance::A1A_2005_PDF_1_4  => Some((1, "A")),
+            PdfConformance::A2_2011_PDF_1_7   => Some((2, "B")),
+            PdfConformance::A2A_2011_PDF_1_7  => Some((2, "A")),
+            PdfConformance::A2B_2011_PDF_1_7  => Some((2, "B")),
+            PdfConformance::A2U_2011_PDF_1_7  => Some((2, "U")),
+            PdfConformance::A3_2012_PDF_1_7   => Some((3, "B")),
+            _                                 => None,
+        }
+    }
+
+    /// Does the standard require all fonts to be embedded (i.e. no builtin fonts)?
+    /// Only checked for PDF/A and custom conformances.
+    pub fn must_embed_fonts(&self)
+    -> bool
+    {
+        match *self {
+            PdfConformance::Custom(ref c) => !c.allows_default_fonts,
+            _                             => self.get_pdfa_part_and_conformance().is_some(),
+        }
+    }
+
+    /// Is this a PDF/X standard? Only PDF/X documents get the `/GTS_PDFXVersion` entry
+    /// in the document info dictionary.
+    pub fn is_pdfx(&self)
+    -> bool
+    {
+        matches!(*self,
+            PdfConformance::X1A_2001_PDF_1_3  |
+            PdfConformance::X3_2002_PDF_1_3   |
+            PdfConformance::X1A_2003_PDF_1_4  |
+            PdfConformance::X3_2003_PDF_1_4   |
+            PdfConformance::X4_2010_PDF_1_4   |
+            PdfConformance::X4P_2010_PDF_1_6  |
+            PdfConformance::X5G_2010_PDF_1_6  |
+            PdfConformance::X5PG_2010_PDF_1_6 |
+            PdfConformance::X5N_2010_PDF_1_6)
+    }
+
+    /// Returns the PDF version the standard is based on (`"1.3"` for custom conformances).
+    /// Saved documents declare at least this version in the file header.
+    pub fn get_pdf_version(&self)
+    -> &'static str
+    {
+        match *self {
+            PdfConformance::X1A_2001_PDF_1_3  |
+            PdfConformance::X3_2002_PDF_1_3   |
+            PdfConformance::Custom(_)         => "1.3",
+            PdfConformance::A1B_2005_PDF_1_4  |
+            PdfConformance::A1A_2005_PDF_1_4  |
+            PdfConformance::X1A_2003_PDF_1_4  |
+            PdfConformance::X3_2003_PDF_1_4   |
+            PdfConformance::X4_2010_PDF_1_4   |
+            PdfConformance::VT_2010_PDF_1_4   => "1.4",
+            PdfConformance::UA_2014_PDF_1_6   |
+            PdfConformance::X4P_2010_PDF_1_6  |
+            PdfConformance::X5G_2010_PDF_1_6  |
+            PdfConformance::X5PG_2010_PDF_1_6 |
+            PdfConformance::X5N_2010_PDF_1_6  |
+            PdfConformance::E1_2008_PDF_1_6   => "1.6",
+            PdfConformance::A2_2011_PDF_1_7   |
+            PdfConformance::A2A_2011_PDF_1_7  |
+            PdfConformance::A2B_2011_PDF_1_7  |
+            PdfConformance::A2U_2011_PDF_1_7  |
+            PdfConformance::A3_2012_PDF_1_7   => "1.7",
+        }
+    }
+
+    /// Does the standard allow device-dependent RGB colors? PDF/X only allows CMYK
+    /// (or colors with an ICC profile) for a CMYK output intent. PDF/A allows device RGB
+    /// only with an RGB output intent, which depends on the document and is not checked here
+    /// (the default output intent is CMYK, see `PdfDocumentReference::repair_errors`).
+    pub fn is_device_rgb_allowed(&self)
+    -> bool
+    {
+        !self.is_pdfx()
+    }
+
+    /// Does the standard allow live transparency (alpha values, soft masks, blend modes)?
+    /// See `PdfDocumentReference::flatten_transparency` for removing it.
+    pub fn is_transparency_allowed(&self)
+    -> bool
+    {
+        !matches!(*self,
+            PdfConformance::A1B_2005_PDF_1_4 |
+            PdfConformance::A1A_2005_PDF_1_4 |
+            PdfConformance::X1A_2001_PDF_1_3 |
+            PdfConformance::X3_2002_PDF_1_3  |
+            PdfConformance::X1A_2003_PDF_1_4 |
+            PdfConformance::X3_2003_PDF_1_4)
+    }
+
+    /// Does the standard allow encrypting the document? PDF/A and PDF/X (including PDF/VT,
+    /// which is based on PDF/X-4) forbid it.
+    pub fn is_encryption_allowed(&self)
+    -> bool
+    {
+        self.get_pdfa_part_and_conformance().is_none() && !matches!(*self,
+            PdfConformance::X1A_2001_PDF_1_3  |
+            PdfConformance::X3_2002_PDF_1_3   |
+            PdfConformance::X1A_2003_PDF_1_4  |
+            PdfConformance::X3_2003_PDF_1_4   |
+            PdfConformance::X4_2010_PDF_1_4   |
+            PdfConformance::X4P_2010_PDF_1_6  |
+            PdfConformance::X5G_2010_PDF_1_6  |
+            PdfConformance::X5PG_2010_PDF_1_6 |
+            PdfConformance::X5N_2010_PDF_1_6  |
+            PdfConformance::VT_2010_PDF_1_4)
+    }
+
     /// Check if the conformance level must have an ICC Profile
     pub fn must_have_icc_profile(&self)
     -> bool
@@ -6,6 +6,7 @@ use std::io::BufWriter;
 use std::io::Write;
 use std::rc::Rc;
 use utils::random_character_string_32;
+use glob_defines::MAX_PAGE_LENGTH_PT;
 
 use crate::OffsetDateTime;
 use lopdf;
@@ -13,8 +14,30 @@ use lopdf;
 use indices::*;
 use {
     ExternalFont, Font, PdfPage, FontList, IccProfileList, PdfMetadata, PdfConformance, IndirectFontRef,
-    DirectFontRef, BuiltinFont, PdfPageReference, Error, Mm, FontData
+    DirectFontRef, BuiltinFont, PdfPageReference, PdfLayerReference, Error, Pt, FontData,
+    OversizedImagePolicy, DEFAULT_MAX_IMAGE_DIMENSION, XmpThumbnail, StructureRole, UnflattenedTransparency,
+    OutputIntent, OutputIntentSubtype, IccProfile, ContentError, Type3Font, GlyphProc, ImageReport, ColorSpace, IntoPdfObjects, StreamFilter, PdfError,
+    OptimizeOptions, OptimizeReport, SaveOptions, PageMode, NonFullScreenPageMode, MissingGlyphPolicy,
+    IndexError, PageLabel, PageLabelStyle
 };
+#[cfg(test)]
+use Mm;
+use types::plugins::misc::structure::add_structure_tree;
+use types::plugins::misc::arbitrary_content::encode_stream;
+use types::plugins::misc::optimize::{downsample_images, optimize_document};
+use types::plugins::security::encrypt::encrypt_document;
+use types::plugins::misc::outline::{Bookmark, add_outline, remap_bookmarks};
+use types::plugins::misc::page_label::page_labels_tree;
+use types::plugins::misc::pdf_import::{import_info, import_pages, offset_references};
+use types::plugins::graphics::two_dimensional::svg_import::import_svg;
+use types::plugins::misc::resource_names::{ResourceKind, ResourceNameAllocator};
+use types::plugins::misc::output_intent::check_output_intents;
+use types::plugins::xmp::xmp_metadata::check_custom_properties;
+use types::plugins::graphics::transparency::{flatten_page_transparency, page_has_transparency};
+use types::plugins::graphics::content_validation::validate_operations;
+use types::plugins::graphics::image_report::page_image_report;
+use types::plugins::graphics::color::{convert_page_colors, page_has_other_device_colors, page_has_unconvertible_colors};
+use types::plugins::graphics::two_dimensional::font_subset::collect_used_glyphs;
 
 /// PDF document
 #[derive(Debug, Clone)]
@@ -31,12 +54,52 @@ pub struct PdfDocument {
     pub document_id: String,
     /// Metadata for this document
     pub metadata: PdfMetadata,
-    /// The bookmarks in the document. A HashMap<Page Number, Bookmark Name>
-    pub bookmarks: HashMap<usize, String>,
+    /// The bookmarks (outline) of the document, in the order they were added
+    pub(crate) bookmarks: Vec<Bookmark>,
+    /// Maximum width / height (in pixels) of images added to this document
+    pub max_image_dimension: usize,
+    /// What to do with images that exceed `max_image_dimension`
+    pub oversized_image_policy: OversizedImagePolicy,
+    /// Should text, images and shapes automatically be tagged (see `with_tagged`)?
+    pub tagged: bool,
+    /// Should pages with identical content share one content stream (see `with_deduplicate_content`)?
+    pub deduplicate_content: bool,
+    /// Color space that colors set on layers are converted into (see `with_default_colorspace`)
+    pub default_colorspace: Option<ColorSpace>,
+    /// Magnification (in percent) the document is opened with (see `with_initial_zoom`)
+    pub initial_zoom: Option<f64>,
+    /// Natural language of the document (`/Lang`), for example `en-US`
+    pub lang: Option<String>,
+    /// Maps custom structure types to standard structure types (see `with_role_map`)
+    pub role_map: HashMap<String, String>,
+    /// What the viewer shows when the document is opened (see `with_page_mode`)
+    pub page_mode: Option<PageMode>,
+    /// What the viewer shows after leaving full-screen mode (see `with_non_full_screen_page_mode`)
+    pub non_full_screen_page_mode: Option<NonFullScreenPageMode>,
+    /// Display duration of each page in seconds, for presentations (see `with_auto_advance`)
+    pub auto_advance: Option<f64>,
+    /// Maximum size (in bytes) of a content stream, before the page content is split
+    /// into several streams (see `with_content_split_threshold`)
+    pub content_split_threshold: Option<usize>,
+    /// What happens to characters the font has no glyph for (see `with_missing_glyph_policy`)
+    pub missing_glyph_policy: MissingGlyphPolicy,
+    /// Characters that could not be written with `MissingGlyphPolicy::Error`
+    pub(super) missing_glyphs: Vec<char>,
+    /// Should the page boxes be drawn on every page when saving (see `with_box_guides`)?
+    pub box_guides: bool,
+    /// Size reductions applied on save, overriding the default optimization (see `optimize`)
+    pub optimize_options: Option<OptimizeOptions>,
+    /// Hands out the names of fonts, XObjects, graphics states and patterns
+    pub(crate) resource_names: ResourceNameAllocator,
 }
 
 /// Marker struct for a document. Used to make the API a bit nicer.
 /// It simply calls `PdfDocument` functions.
+///
+/// The document reference is the only owner of the document: the page and layer references
+/// only hold a weak reference to it, so several of them can be used at the same time (for
+/// example drawing on two layers in turn) without fighting the borrow checker. Page and layer
+/// references must not be used after the document was saved (the document is gone by then).
 pub struct PdfDocumentReference {
     /// A wrapper for a document, so actions from outside this library
     /// are restricted to functions inside this crate (only functions in `lopdf`
@@ -46,16 +109,17 @@ pub struct PdfDocumentReference {
 
 impl PdfDocument {
 
-    /// Creates a new PDF document
+    /// Creates a new PDF document, the size of the initial page
+    /// can be in any unit (`Mm`, `Pt` or `In`)
     #[inline]
     #[cfg_attr(feature = "cargo-clippy", allow(new_ret_no_self))]
-    pub fn new<S1, S2>(
+    pub fn new<S1, S2, W, H>(
         document_title: S1,
-        initial_page_width: Mm,
-        initial_page_height: Mm,
+        initial_page_width: W,
+        initial_page_height: H,
         initial_layer_name: S2,
     ) -> (PdfDocumentReference, PdfPageIndex, PdfLayerIndex)
-    where S1: Into<String>, S2: Into<String>
+    where S1: Into<String>, S2: Into<String>, W: Into<Pt>, H: Into<Pt>
     {
         let doc = Self {
             pages: Vec::new(),
@@ -64,7 +128,24 @@ impl PdfDocument {
             icc_profiles: IccProfileList::new(),
             inner_doc: lopdf::Document::with_version("1.3"),
             metadata: PdfMetadata::new(document_title, 1, false, PdfConformance::X3_2002_PDF_1_3),
-            bookmarks: HashMap::new(),
+            bookmarks: Vec::new(),
+            max_image_dimension: DEFAULT_MAX_IMAGE_DIMENSION,
+            oversized_image_policy: OversizedImagePolicy::default(),
+            tagged: false,
+            deduplicate_content: false,
+            default_colorspace: None,
+            initial_zoom: None,
+            lang: None,
+            role_map: HashMap::new(),
+            optimize_options: None,
+            page_mode: None,
+            non_full_screen_page_mode: None,
+            auto_advance: None,
+            content_split_threshold: None,
+            missing_glyph_policy: MissingGlyphPolicy::default(),
+            missing_glyphs: Vec::new(),
+            box_guides: false,
+            resource_names: ResourceNameAllocator::default(),
         };
 
         let doc_ref = Rc::new(RefCell::new(doc));
@@ -88,12 +169,67 @@ impl PdfDocument {
             icc_profiles: IccProfileList::new(),
             inner_doc: lopdf::Document::with_version("1.3"),
             metadata: PdfMetadata::new(document_title, 1, false, PdfConformance::X3_2002_PDF_1_3),
-            bookmarks: HashMap::new(),
+            bookmarks: Vec::new(),
+            max_image_dimension: DEFAULT_MAX_IMAGE_DIMENSION,
+            oversized_image_policy: OversizedImagePolicy::default(),
+            tagged: false,
+            deduplicate_content: false,
+            default_colorspace: None,
+            initial_zoom: None,
+            lang: None,
+            role_map: HashMap::new(),
+            optimize_options: None,
+            page_mode: None,
+            non_full_screen_page_mode: None,
+            auto_advance: None,
+            content_split_threshold: None,
+            missing_glyph_policy: MissingGlyphPolicy::default(),
+            missing_glyphs: Vec::new(),
+            box_guides: false,
+            resource_names: ResourceNameAllocator::default(),
         };
 
         let doc_ref = Rc::new(RefCell::new(doc));
         PdfDocumentReference { document: doc_ref }
     }
+
+    /// Returns the bookmarks in the order they were added, with their nesting. The position
+    /// of a bookmark in the slice is its `BookmarkIndex` and the index its children refer to.
+    pub fn outline(&self)
+    -> &[Bookmark]
+    {
+        &self.bookmarks
+    }
+
+    /// Returns the bookmarks as a map from the page index to the bookmark title, the shape
+    /// of the former `bookmarks` field. Nesting is lost and if a page has several bookmarks,
+    /// only the last one added is returned, use `outline` to get all bookmarks.
+    pub fn bookmarks(&self)
+    -> HashMap<usize, String>
+    {
+        self.bookmarks.iter().map(|bookmark| (bookmark.page, bookmark.title.clone())).collect()
+    }
+
+    /// Sets the index of every page to its position, after pages were removed or moved
+    fn renumber_pages(&mut self) {
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            page.index = index;
+        }
+    }
+
+    /// Returns a new resource index, so that the resource name (e.g. `X4` for an XObject with
+    /// index 4) is unique across all resource categories of the document. If the resource
+    /// is added to a page, the resources already on that page are skipped.
+    pub(crate) fn allocate_resource_index(&mut self, kind: ResourceKind, page: Option<PdfPageIndex>)
+    -> usize
+    {
+        let fonts = &self.fonts;
+        let pages = &self.pages;
+        let page_resources = page.map(|page| &pages[page.0].resources);
+        self.resource_names.allocate(kind, |name| {
+            fonts.contains_name(name) || page_resources.map_or(false, |r| r.contains_name(name))
+        })
+    }
 }
 
 macro_rules! implement_adding_fonts {
@@ -136,6 +272,57 @@ impl PdfDocumentReference {
         self.document.borrow_mut().metadata.document_title = new_title.into();
     }
 
+    /// Sets the keywords of the document, written to both the document info dictionary
+    /// (comma-separated) and the XMP metadata
+    #[inline]
+    pub fn with_keywords<S>(self, keywords: Vec<S>)
+    -> Self where S: Into<String>
+    {
+        self.document.borrow_mut().metadata.keywords = keywords.into_iter().map(|k| k.into()).collect();
+        self
+    }
+
+    /// Sets the subject of the document, written to both the document info dictionary
+    /// as well as the XMP metadata
+    #[inline]
+    pub fn with_subject<S>(self, subject: S)
+    -> Self where S: Into<String>
+    {
+        self.document.borrow_mut().metadata.subject = subject.into();
+        self
+    }
+
+    /// Sets the author of the document, written to both the document info dictionary
+    /// as well as the XMP metadata (`dc:creator`)
+    #[inline]
+    pub fn with_author<S>(self, author: S)
+    -> Self where S: Into<String>
+    {
+        self.document.borrow_mut().metadata.author = author.into();
+        self
+    }
+
+    /// Sets the name of the application that created the original document
+    /// (`/Creator` in the document info dictionary, `xmp:CreatorTool` in the XMP metadata)
+    #[inline]
+    pub fn with_creator<S>(self, creator: S)
+    -> Self where S: Into<String>
+    {
+        self.document.borrow_mut().metadata.creator = creator.into();
+        self
+    }
+
+    /// Sets the name of the application that wrote the PDF (`/Producer` in the document info
+    /// dictionary, `pdf:Producer` in the XMP metadata). Default: `printpdf <version>`,
+    /// an empty string leaves the producer out.
+    #[inline]
+    pub fn with_producer<S>(self, producer: S)
+    -> Self where S: Into<String>
+    {
+        self.document.borrow_mut().metadata.producer = producer.into();
+        self
+    }
+
     /// Set the trapping of the document
     #[inline]
     pub fn with_trapping(self, trapping: bool)
@@ -145,12 +332,17 @@ impl PdfDocumentReference {
         self
     }
 
-    /// Sets the document ID (for comparing two PDF documents for equality)
+    /// Sets the document ID (for comparing two PDF documents for equality),
+    /// in both the trailer `/ID` and the XMP metadata
     #[inline]
     pub fn with_document_id(self, id: String)
     -> Self
     {
-        self.document.borrow_mut().metadata.xmp_metadata.document_id = id;
+        {
+            let mut doc = self.document.borrow_mut();
+            doc.document_id = id.clone();
+            doc.metadata.xmp_metadata.document_id = id;
+        }
         self
     }
 
@@ -194,35 +386,488 @@ impl PdfDocumentReference {
         self
     }
 
+    /// Sets the date the XMP metadata was last changed. Per default, this is the current
+    /// time, set it together with the creation and modification date for reproducible output.
+    #[inline]
+    pub fn with_metadata_date(self, metadata_date: OffsetDateTime)
+    -> Self
+    {
+        self.document.borrow_mut().metadata.metadata_date = metadata_date;
+        self
+    }
+
+    /// Sets the target ICC profile of the document (e.g. GRACoL or SWOP instead of the default
+    /// Coated FOGRA39 profile), embedded in the output intent if the conformance requires one.
+    /// Set the identifier, description and registry on the profile for the output intent.
+    #[inline]
+    pub fn with_icc_profile(self, icc_profile: IccProfile)
+    -> Self
+    {
+        self.document.borrow_mut().metadata.target_icc_profile = Some(icc_profile);
+        self
+    }
+
+    /// Embeds a (JPEG) preview image of the document in the XMP metadata, for asset management
+    /// systems. Only has an effect if the conformance of the document requires XMP metadata.
+    #[inline]
+    pub fn with_xmp_thumbnail(self, thumbnail: XmpThumbnail)
+    -> Self
+    {
+        self.document.borrow_mut().metadata.xmp_metadata.thumbnail = Some(thumbnail);
+        self
+    }
+
+    /// Adds a property of a custom schema to the XMP metadata, see `PdfMetadata::add_xmp_property`.
+    /// The XMP metadata is written even if the conformance of the document doesn't require it. `save()`
+    /// fails if the prefix or key is not a valid XML name or the namespace is a built-in one.
+    #[inline]
+    pub fn with_xmp_property<N, P, K, V>(self, namespace: N, prefix: P, key: K, value: V)
+    -> Self where N: Into<String>, P: Into<String>, K: Into<String>, V: Into<String>
+    {
+        self.document.borrow_mut().metadata.add_xmp_property(namespace, prefix, key, value);
+        self
+    }
+
+    /// Sets the output intents of the document, e.g. a CMYK profile for print and an RGB
+    /// profile for screen display. Replaces the default output intent, which is only generated
+    /// if the PDF conformance requires an ICC profile. Only one output intent per standard
+    /// (PDF/X, PDF/A, PDF/E) is allowed, otherwise `save()` fails.
+    #[inline]
+    pub fn with_output_intents(self, output_intents: Vec<OutputIntent>)
+    -> Self
+    {
+        self.document.borrow_mut().metadata.output_intents = output_intents;
+        self
+    }
+
+    /// Sets a document-wide color space, for consistent output (e.g. only CMYK for print).
+    /// Fill and outline colors set on layers are converted into this color space.
+    ///
+    /// Colors with an attached ICC profile and spot colors are never converted, so
+    /// attach an ICC profile to a color to keep it in its own color space.
+    #[inline]
+    pub fn with_default_colorspace(self, color_space: ColorSpace)
+    -> Self
+    {
+        self.document.borrow_mut().default_colorspace = Some(color_space);
+        self
+    }
+
+    /// Opens the document on the first page with the given magnification
+    /// (in percent, e.g. `100.0` for the actual size), by setting the `/OpenAction`
+    /// of the document. The scroll position is left to the viewer.
+    #[inline]
+    pub fn with_initial_zoom(self, percent: f64)
+    -> Self
+    {
+        self.document.borrow_mut().initial_zoom = Some(percent);
+        self
+    }
+
+    /// Enables automatic tagging: text is tagged as paragraph (`/P`), images as figures (`/Figure`)
+    /// and shapes are marked as artifacts. The tagged content is collected into a
+    /// structure tree, which makes the document (minimally) accessible.
+    ///
+    /// Roles can be overridden by wrapping content in `PdfLayerReference::begin_tagged_content()`.
+    #[inline]
+    pub fn with_tagged(self, tagged: bool)
+    -> Self
+    {
+        self.document.borrow_mut().tagged = tagged;
+        self
+    }
+
+    /// Sets the natural language of the document (`/Lang` in the document catalog),
+    /// as a language tag such as `en-US`. Required by PDF/UA, so that screen readers
+    /// pronounce the text correctly.
+    #[inline]
+    pub fn with_lang<S>(self, lang: S)
+    -> Self where S: Into<String>
+    {
+        self.document.borrow_mut().lang = Some(lang.into());
+        self
+    }
+
+    /// Sets the role map of the structure tree, which maps custom structure types
+    /// (`StructureRole::Custom`) to standard structure types, e.g. `"Chapter"` to `"Sect"`.
+    /// PDF/UA requires that every custom structure type used in the document is mapped,
+    /// see `unresolved_structure_roles`.
+    #[inline]
+    pub fn with_role_map(self, role_map: HashMap<String, String>)
+    -> Self
+    {
+        self.document.borrow_mut().role_map = role_map;
+        self
+    }
+
+    /// Sets what the viewer shows when the document is opened. By default, the outline
+    /// is shown if the document has bookmarks.
+    #[inline]
+    pub fn with_page_mode(self, page_mode: PageMode)
+    -> Self
+    {
+        self.document.borrow_mut().page_mode = Some(page_mode);
+        self
+    }
+
+    /// Sets what the viewer shows after leaving full-screen mode (for example for a
+    /// presentation that is opened in full-screen mode). Only has an effect together with
+    /// `PageMode::FullScreen`, otherwise `check_for_errors` returns an error.
+    #[inline]
+    pub fn with_non_full_screen_page_mode(self, mode: NonFullScreenPageMode)
+    -> Self
+    {
+        self.document.borrow_mut().non_full_screen_page_mode = Some(mode);
+        self
+    }
+
+    /// Advances to the next page automatically after `seconds` in presentation mode,
+    /// by setting the display duration (`/Dur`) of every page. Pages can override the
+    /// duration with `PdfPageReference::set_duration`. `None` or zero disables auto-advance.
+    #[inline]
+    pub fn with_auto_advance(self, seconds: Option<f64>)
+    -> Self
+    {
+        self.document.borrow_mut().auto_advance = seconds;
+        self
+    }
+
+    /// Splits the content of a page into several content streams once it exceeds `bytes`,
+    /// since viewers parse very large content streams slowly (e.g. for huge vector maps).
+    /// The content is only split between two operations, so a stream can be a bit larger
+    /// than the threshold. By default, the content of a page is written into one stream.
+    #[inline]
+    pub fn with_content_split_threshold(self, bytes: usize)
+    -> Self
+    {
+        self.document.borrow_mut().content_split_threshold = Some(bytes);
+        self
+    }
+
+    /// Makes pages with byte-identical content streams share a single content object
+    /// when the document is saved. Each page keeps its own page dictionary and resources,
+    /// so the resource names in the shared stream are still resolved per page.
+    /// Shrinks documents that repeat the same (template) page many times.
+    #[inline]
+    pub fn with_deduplicate_content(self, deduplicate_content: bool)
+    -> Self
+    {
+        self.document.borrow_mut().deduplicate_content = deduplicate_content;
+        self
+    }
+
+    /// Decides what happens to characters that the font has no glyph for, for all text
+    /// written to the document: leave them out (default), draw the `.notdef` glyph, write them
+    /// with a fallback font or fail in strict mode (see `MissingGlyphPolicy`).
+    /// The policy only applies to text written after this call.
+    #[inline]
+    pub fn with_missing_glyph_policy(self, policy: MissingGlyphPolicy)
+    -> Self
+    {
+        self.document.borrow_mut().missing_glyph_policy = policy;
+        self
+    }
+
+    /// Draws the page boxes on every page when the document is saved, for proofing
+    /// the bleed / trim setup (see `PdfPageReference::add_box_guides`)
+    #[inline]
+    pub fn with_box_guides(self, box_guides: bool)
+    -> Self
+    {
+        self.document.borrow_mut().box_guides = box_guides;
+        self
+    }
+
+    /// Sets the maximum width / height (in pixels) of images added to this document.
+    /// Protects against accidentally embedding huge images, e.g. from untrusted input.
+    ///
+    /// Per default, the limit is 10000 pixels.
+    #[inline]
+    pub fn with_max_image_dimension(self, max_px: usize)
+    -> Self
+    {
+        self.document.borrow_mut().max_image_dimension = max_px;
+        self
+    }
+
+    /// Sets whether images exceeding the maximum image dimension are rejected
+    /// with an error or downsampled
+    #[inline]
+    pub fn with_oversized_image_policy(self, policy: OversizedImagePolicy)
+    -> Self
+    {
+        self.document.borrow_mut().oversized_image_policy = policy;
+        self
+    }
+
     // ----- ADD FUNCTIONS
 
-    /// Create a new pdf page and returns the index of the page
+    /// Create a new pdf page and returns the index of the page.
+    /// The width / height can be in any unit (`Mm`, `Pt` or `In`).
     #[inline]
-    pub fn add_page<S>(
+    pub fn add_page<W, H, S>(
         &self,
-        x_mm: Mm,
-        y_mm: Mm,
+        width: W,
+        height: H,
         inital_layer_name: S,
     ) -> (PdfPageIndex, PdfLayerIndex)
     where
+        W: Into<Pt>,
+        H: Into<Pt>,
         S: Into<String>,
     {
         let mut doc = self.document.borrow_mut();
         let (pdf_page, pdf_layer_index) =
-            PdfPage::new(x_mm, y_mm, inital_layer_name, doc.pages.len());
+            PdfPage::new(width, height, inital_layer_name, doc.pages.len());
         doc.pages.push(pdf_page);
         let page_index = PdfPageIndex(doc.pages.len() - 1);
         (page_index, pdf_layer_index)
     }
-    /// Create a new pdf page and returns the index of the page.
-    /// If the page already has a bookmark, overwrites it.
-    #[inline]
-    pub fn add_bookmark<S>(&self, name: S, page: PdfPageIndex)
+
+    /// Creates several pages at once, from their width / height (in any unit, like `add_page`)
+    /// and the name of their initial layer. Returns the indices of the pages and their initial
+    /// layers, in the same order.
+    pub fn add_pages<W, H, S>(&self, pages: Vec<(W, H, S)>)
+    -> Vec<(PdfPageIndex, PdfLayerIndex)>
     where
+        W: Into<Pt>,
+        H: Into<Pt>,
         S: Into<String>,
     {
         let mut doc = self.document.borrow_mut();
-        doc.bookmarks.insert(page.0, name.into());
+        doc.pages.reserve(pages.len());
+
+        pages.into_iter().map(|(x_mm, y_mm, initial_layer_name)| {
+            let (pdf_page, pdf_layer_index) =
+                PdfPage::new(x_mm, y_mm, initial_layer_name, doc.pages.len());
+            doc.pages.push(pdf_page);
+            (PdfPageIndex(doc.pages.len() - 1), pdf_layer_index)
+        }).collect()
+    }
+
+    /// Creates a new page with the size of the SVG image (in the units of the `width` / `height`
+    /// of the SVG, or the size of the `viewBox` in px) and draws the image onto it, on a layer
+    /// called "SVG". Only the basic shapes of SVG are supported (see `svg_import`).
+    pub fn add_page_from_svg(&self, svg_data: &str)
+    -> ::std::result::Result<(PdfPageIndex, PdfLayerIndex), Error>
+    {
+        let svg = import_svg(svg_data)?;
+        let mut doc = self.document.borrow_mut();
+        let (mut pdf_page, pdf_layer_index) =
+            PdfPage::new(svg.width, svg.height, "SVG", doc.pages.len());
+        for op in svg.operations {
+            pdf_page.layers[pdf_layer_index.0].add_operation(op);
+        }
+        doc.pages.push(pdf_page);
+        Ok((PdfPageIndex(doc.pages.len() - 1), pdf_layer_index))
+    }
+
+    /// Adds an existing page (e.g. one created with `PdfPage::with_operations`)
+    /// to the end of the document and returns the index of the page
+    #[inline]
+    pub fn add_pdf_page(&self, mut page: PdfPage)
+    -> PdfPageIndex
+    {
+        let mut doc = self.document.borrow_mut();
+        page.index = doc.pages.len();
+        doc.pages.push(page);
+        PdfPageIndex(doc.pages.len() - 1)
+    }
+
+    /// Removes the page from the document and returns it, for example to add it to another
+    /// document with `add_pdf_page`. Bookmarks of the page are removed as well.
+    ///
+    /// The pages after the removed page move up by one, so `PdfPageIndex` values of these
+    /// pages (and page / layer references to them) now refer to the next page. The indices
+    /// of bookmarks may change as well.
+    pub fn remove_page(&self, page: PdfPageIndex)
+    -> ::std::result::Result<PdfPage, Error>
+    {
+        let mut doc = self.document.borrow_mut();
+        if page.0 >= doc.pages.len() {
+            return Err(IndexError::PdfPageIndexError.into());
+        }
+
+        let removed = doc.pages.remove(page.0);
+        doc.renumber_pages();
+        remap_bookmarks(&mut doc.bookmarks, |old| match old {
+            old if old == page.0 => None,
+            old if old > page.0 => Some(old - 1),
+            old => Some(old),
+        });
+        Ok(removed)
+    }
+
+    /// Moves the page to the position `to` (0-based, in the order after the page was taken
+    /// out), for example to reorder pages before saving. Bookmarks keep pointing to their page.
+    ///
+    /// The pages between the old and the new position shift by one, so outstanding
+    /// `PdfPageIndex` values (and page / layer references) may refer to a different page
+    /// afterwards. Use `get_pages` to get the pages in their new order.
+    pub fn move_page(&self, from: PdfPageIndex, to: usize)
+    -> ::std::result::Result<(), Error>
+    {
+        let mut doc = self.document.borrow_mut();
+        if from.0 >= doc.pages.len() || to >= doc.pages.len() {
+            return Err(IndexError::PdfPageIndexError.into());
+        }
+
+        let page = doc.pages.remove(from.0);
+        doc.pages.insert(to, page);
+        doc.renumber_pages();
+        remap_bookmarks(&mut doc.bookmarks, |old| Some(match old {
+            old if old == from.0 => to,
+            old if from.0 < old && old <= to => old - 1,
+            old if to <= old && old < from.0 => old + 1,
+            old => old,
+        }));
+        Ok(())
+    }
+
+    /// Appends the pages of another document (with their fonts, images and other resources)
+    /// to this document, for example to combine documents that were generated separately.
+    /// Fonts whose names collide with the fonts of this document are renamed, bookmarks are
+    /// moved to the new page indices. The metadata of this document is kept.
+    pub fn merge(&self, other: PdfDocumentReference)
+    -> ::std::result::Result<(), Error>
+    {
+        let other = match Rc::try_unwrap(other.document) {
+            Ok(other) => other.into_inner(),
+            Err(other) => other.borrow().clone(),
+        };
+
+        let mut doc = self.document.borrow_mut();
+        let doc = &mut *doc;
+
+        // move the objects of the other document behind the objects of this document
+        let id_offset = doc.inner_doc.max_id;
+        for (id, mut object) in other.inner_doc.objects {
+            offset_references(&mut object, id_offset);
+            doc.inner_doc.objects.insert((id.0 + id_offset, id.1), object);
+        }
+        doc.inner_doc.max_id += other.inner_doc.max_id;
+
+        // fonts are shared by all pages, so colliding names have to be changed in the text
+        let other_fonts = other.fonts.into_fonts();
+        let mut renamed_fonts = HashMap::<String, String>::new();
+        for (font_ref, mut font) in other_fonts.iter().cloned() {
+            font.inner_obj.0 += id_offset;
+            if doc.fonts.contains_name(&font_ref.name) {
+                // built-in fonts with the same name are the same font
+                if let Font::BuiltinFont(_) = font.data { continue; }
+                let fonts = &doc.fonts;
+                let index = doc.resource_names.allocate(ResourceKind::Font, |name| {
+                    fonts.contains_name(name) || other_fonts.iter().any(|(other_ref, _)| other_ref.name == name)
+                });
+                let new_name = ResourceKind::Font.name(index);
+                font.data.set_face_name(new_name.clone());
+                renamed_fonts.insert(font_ref.name.clone(), new_name.clone());
+                doc.fonts.add_font(IndirectFontRef::new(new_name), font);
+            } else {
+                doc.fonts.add_font(font_ref, font);
+            }
+        }
+
+        let page_offset = doc.pages.len();
+        for mut page in other.pages {
+            page.index = doc.pages.len();
+            page.imported_content = page.imported_content.map(|(id, generation)| (id + id_offset, generation));
+
+            let operations = page.layers.iter_mut().flat_map(|layer| layer.operations.iter_mut())
+                .chain(page.operations.iter_mut());
+            for op in operations.filter(|op| op.operator == "Tf") {
+                let new_name = match op.operands.first().and_then(|name| name.as_name_str().ok()) {
+                    Some(name) => renamed_fonts.get(name).cloned(),
+                    None => None,
+                };
+                if let Some(new_name) = new_name {
+                    op.operands[0] = lopdf::Object::Name(new_name.into_bytes());
+                }
+            }
+
+            doc.pages.push(page);
+        }
+
+        let bookmark_offset = doc.bookmarks.len();
+        doc.bookmarks.extend(other.bookmarks.into_iter().map(|bookmark| Bookmark {
+            title: bookmark.title,
+            page: bookmark.page + page_offset,
+            parent: bookmark.parent.map(|parent| parent + bookmark_offset),
+        }));
+        for (role, standard_role) in other.role_map {
+            doc.role_map.entry(role).or_insert(standard_role);
+        }
+        doc.missing_glyphs.extend(other.missing_glyphs);
+
+        Ok(())
+    }
+
+    /// Adds a top-level bookmark (outline item) that jumps to the page and returns its index.
+    /// Bookmarks are shown in the order of their pages, use `add_child_bookmark` to nest them.
+    ///
+    /// Note: before nested bookmarks were supported, adding a bookmark for a page that already
+    /// had one replaced it. Now both bookmarks are kept.
+    #[inline]
+    pub fn add_bookmark<S>(&self, name: S, page: PdfPageIndex)
+    -> BookmarkIndex where S: Into<String>
+    {
+        self.push_bookmark(name.into(), page, None)
+    }
+
+    /// Adds a bookmark below the `parent` bookmark (e.g. a section of a chapter)
+    /// that jumps to the page and returns its index
+    #[inline]
+    pub fn add_child_bookmark<S>(&self, name: S, page: PdfPageIndex, parent: BookmarkIndex)
+    -> BookmarkIndex where S: Into<String>
+    {
+        self.push_bookmark(name.into(), page, Some(parent))
+    }
+
+    fn push_bookmark(&self, title: String, page: PdfPageIndex, parent: Option<BookmarkIndex>)
+    -> BookmarkIndex
+    {
+        let mut doc = self.document.borrow_mut();
+        doc.bookmarks.push(Bookmark { title, page: page.0, parent: parent.map(|parent| parent.0) });
+        BookmarkIndex(doc.bookmarks.len() - 1)
+    }
+
+    /// Sets the page labels (`/PageLabels`) that viewers show instead of the page index, starting
+    /// at the page and continuing up to the next page with a label. For example, lowercase roman
+    /// numerals at the first page and decimal numbers at the first page of the body give
+    /// "i, ii, iii, 1, 2, 3". `start` is the number of the page, `prefix` is shown in front of
+    /// the number. Pages before the first label are numbered 1, 2, 3, ...
+    ///
+    /// The label belongs to the page, so it moves with the page (see `move_page`).
+    /// Returns `PdfError::InvalidPageLabelStart` if `start` is less than 1.
+    pub fn set_page_label(&self, page: PdfPageIndex, style: PageLabelStyle, prefix: Option<String>, start: i64)
+    -> ::std::result::Result<(), Error>
+    {
+        if start < 1 {
+            return Err(PdfError::InvalidPageLabelStart.into());
+        }
+        let mut doc = self.document.borrow_mut();
+        match doc.pages.get_mut(page.0) {
+            Some(page) => {
+                page.label = Some(PageLabel { style, prefix, start });
+                Ok(())
+            },
+            None => Err(IndexError::PdfPageIndexError.into()),
+        }
+    }
+
+    /// Removes the page label of the page (see `set_page_label`), the page then continues
+    /// the numbering of the previous label
+    pub fn remove_page_label(&self, page: PdfPageIndex)
+    -> ::std::result::Result<(), Error>
+    {
+        let mut doc = self.document.borrow_mut();
+        match doc.pages.get_mut(page.0) {
+            Some(page) => {
+                page.label = None;
+                Ok(())
+            },
+            None => Err(IndexError::PdfPageIndexError.into()),
+        }
     }
 
     /// Add a font from a font stream
@@ -233,120 +878,494 @@ impl PdfDocumentReference {
     where
         R: ::std::io::Read,
     {
-        let last_font_index = {
-            let doc = self.document.borrow();
-            doc.fonts.len()
-        };
+        let last_font_index = self.document.borrow_mut().allocate_resource_index(ResourceKind::Font, None);
         let external_font = ExternalFont::new(font_stream, last_font_index)?;
         let external_font_name = external_font.face_name.clone();
         let font = Font::ExternalFont(external_font);
         implement_adding_fonts!(&self, external_font_name, font)
     }
 
-    /// Add a font from a custom font backend
-    pub fn add_external_font_data<F>(&self, bytes: Vec<u8>, data: F)
-    -> ::std::result::Result<IndirectFontRef, Error>
-    where
-        F: FontData + 'static,
+    /// Add a font from a custom font backend
+    pub fn add_external_font_data<F>(&self, bytes: Vec<u8>, data: F)
+    -> ::std::result::Result<IndirectFontRef, Error>
+    where
+        F: FontData + 'static,
+    {
+        let last_font_index = self.document.borrow_mut().allocate_resource_index(ResourceKind::Font, None);
+        let external_font = ExternalFont::with_font_data(bytes, last_font_index, Box::new(data));
+        let external_font_name = external_font.face_name.clone();
+        let font = Font::ExternalFont(external_font);
+        implement_adding_fonts!(&self, external_font_name, font)
+    }
+
+    /// Add a Type3 font, made of custom glyphs (e.g. icons or symbols), to the document.
+    /// The font can be used like any other font, characters without a glyph are ignored.
+    /// A Type3 font can have at most 256 glyphs.
+    pub fn add_type3_font(&self, glyphs: HashMap<char, GlyphProc>)
+    -> ::std::result::Result<IndirectFontRef, Error>
+    {
+        let last_font_index = self.document.borrow_mut().allocate_resource_index(ResourceKind::Font, None);
+        let type3_font = Type3Font::new(glyphs, last_font_index)?;
+        let type3_font_name = type3_font.face_name.clone();
+        implement_adding_fonts!(&self, type3_font_name, Font::Type3Font(type3_font))
+    }
+
+    /// Adds custom content, made of one or more PDF objects, to the document and returns
+    /// the ID of its top-level object. All objects are added at once, so that objects of the
+    /// content can reference each other.
+    ///
+    /// Objects that are not referenced from anywhere in the document may be removed when saving.
+    pub fn add_arbitrary_content<T>(&self, content: T)
+    -> lopdf::ObjectId where T: IntoPdfObjects
+    {
+        let mut doc = self.document.borrow_mut();
+        let inner_doc = &mut doc.inner_doc;
+        let content = content.into_pdf_objects(&mut || inner_doc.new_object_id());
+        inner_doc.objects.extend(content.objects);
+        content.root
+    }
+
+    /// Adds a stream with custom data (e.g. an embedded data set) to the document and returns
+    /// its ID. The `filter` controls how the data is encoded, the `dict` contains the other
+    /// entries of the stream dictionary (`/Length` and `/Filter` are set automatically).
+    ///
+    /// The stream is not compressed again when saving. Streams that are not referenced from
+    /// anywhere in the document may be removed when saving.
+    pub fn add_stream(&self, data: Vec<u8>, filter: StreamFilter, dict: lopdf::Dictionary)
+    -> lopdf::ObjectId
+    {
+        let stream = encode_stream(data, filter, dict);
+        self.document.borrow_mut().inner_doc.add_object(stream)
+    }
+
+    /// Add a built-in font to the document
+    ///
+    /// Built-in fonts can only be used to print characters that are supported by the
+    /// [Windows-1252][] encoding.  All other characters will be ignored. The symbolic fonts
+    /// (`Symbol`, `ZapfDingbats`) use their own encoding instead (see `BuiltinFont::is_symbolic`).
+    ///
+    /// [Windows-1252]: https://en.wikipedia.org/wiki/Windows-1252
+    pub fn add_builtin_font(&self, builtin_font: BuiltinFont)
+    -> ::std::result::Result<IndirectFontRef, Error>
+    {
+        let builtin_font_name: &'static str = builtin_font.clone().into();
+        implement_adding_fonts!(&self, builtin_font_name, Font::BuiltinFont(builtin_font))
+    }
+
+    // ----- GET FUNCTIONS
+
+    /// Returns the page (for inserting content)
+    #[inline]
+    #[cfg_attr(feature = "cargo-clippy", allow(unnecessary_operation))]
+    pub fn get_page(&self, page: PdfPageIndex)
+    -> PdfPageReference
+    {
+        &self.document.borrow_mut().pages[page.0];
+        PdfPageReference { document: Rc::downgrade(&self.document).clone(), page }
+    }
+
+    /// Returns the layer of a page (for inserting content), shorthand for
+    /// `doc.get_page(page).get_layer(layer)`. Panics if the page or layer doesn't exist.
+    #[inline]
+    pub fn get_layer(&self, page: PdfPageIndex, layer: PdfLayerIndex)
+    -> PdfLayerReference
+    {
+        self.get_page(page).get_layer(layer)
+    }
+
+    /// Returns all pages of the document in page order, for example to draw on the
+    /// pages of a loaded document
+    pub fn get_pages(&self)
+    -> Vec<PdfPageReference>
+    {
+        let page_count = self.document.borrow().pages.len();
+        (0..page_count).map(|page| PdfPageReference { document: Rc::downgrade(&self.document), page: PdfPageIndex(page) }).collect()
+    }
+
+    /// Returns the width of the text (in points), if it was written with the font in the given
+    /// size, for example to break lines. Returns `None` if the font was not added to this document.
+    pub fn text_width(&self, text: &str, font_size: f64, font: &IndirectFontRef)
+    -> Option<Pt>
+    {
+        let doc = self.document.borrow();
+        doc.fonts.get_font(font).map(|font| font.data.text_width(text, font_size))
+    }
+
+    /// Returns a direct reference (object ID) to the font from an
+    /// indirect reference (postscript name)
+    #[inline]
+    pub fn get_font(&self, font: &IndirectFontRef)
+    -> Option<DirectFontRef>
+    {
+        let doc = self.document.borrow();
+        doc.fonts.get_font(font)
+    }
+
+    /// Drops the PDFDocument, returning the inner `lopdf::Document`.
+    /// Document may be only half-written, use only in extreme cases
+    #[inline]
+    pub unsafe fn get_inner(self)
+    -> lopdf::Document
+    {
+        let doc = Rc::try_unwrap(self.document).unwrap().into_inner();
+        doc.inner_doc
+    }
+
+    // --- MISC FUNCTIONS
+
+    /// Checks for invalid settings in the document
+    ///
+    /// Currently checks the content streams of all layers (see `validate_content`),
+    /// that all structure roles resolve to a standard type (see `unresolved_structure_roles`),
+    /// that the non-full-screen page mode is only set for the full-screen page mode,
+    /// that the document has pages of a valid size, that all fonts and XObjects used by the
+    /// layers were added to the document, as well as the rules of the PDF conformance of the
+    /// document (embedded fonts, transparency and output intents, e.g. for PDF/A-1b).
+    ///
+    /// Every problem is reported: if there is more than one, they are returned
+    /// together as `Error::Multiple`.
+    pub fn check_for_errors(&self)
+    -> ::std::result::Result<(), Error>
+    {
+        let mut errors = Vec::<Error>::new();
+
+        let content_errors = self.validate_content();
+        if !content_errors.is_empty() {
+            errors.push(Error::Content(content_errors));
+        }
+
+        if !self.unresolved_structure_roles().is_empty() {
+            errors.push(PdfError::UnresolvedStructureRole.into());
+        }
+
+        if !self.document.borrow().missing_glyphs.is_empty() {
+            errors.push(PdfError::MissingGlyph.into());
+        }
+
+        {
+            let doc = self.document.borrow();
+            if doc.non_full_screen_page_mode.is_some() && doc.page_mode != Some(PageMode::FullScreen) {
+                errors.push(PdfError::NonFullScreenPageModeWithoutFullScreen.into());
+            }
+        }
+
+        errors.extend(self.page_errors().into_iter().map(Error::from));
+        errors.extend(self.conformance_errors().into_iter().map(Error::from));
+
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+
+    /// Checks that the document has at least one page, that the pages are not empty or larger
+    /// than 14400 points (the limit of PDF viewers) and that the fonts and XObjects used by the
+    /// layers exist in the document / page resources
+    fn page_errors(&self)
+    -> Vec<PdfError>
+    {
+        let doc = self.document.borrow();
+        let mut errors = Vec::new();
+
+        if doc.pages.is_empty() {
+            errors.push(PdfError::NoPages);
+        }
+
+        let is_valid_length = |length: Pt| length.0 > 0.0 && length.0 <= MAX_PAGE_LENGTH_PT;
+        if doc.pages.iter().any(|page| !is_valid_length(page.width) || !is_valid_length(page.height)) {
+            errors.push(PdfError::InvalidPageSize);
+        }
+
+        let mut unknown_font = false;
+        let mut unknown_xobject = false;
+        for page in &doc.pages {
+            for op in page.layers.iter().flat_map(|layer| layer.operations.iter()) {
+                let name = match op.operands.first().and_then(|name| name.as_name_str().ok()) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                match op.operator.as_str() {
+                    "Tf" => unknown_font |= !doc.fonts.contains_name(name),
+                    "Do" => unknown_xobject |= !page.resources.xobjects.objects.contains_key(name),
+                    _ => { },
+                }
+            }
+        }
+
+        if unknown_font {
+            errors.push(PdfError::UnknownFont);
+        }
+
+        if unknown_xobject {
+            errors.push(PdfError::UnknownXObject);
+        }
+
+        errors
+    }
+
+    /// Checks the document against the rules of its PDF conformance: fonts must be embedded
+    /// (PDF/A), no live transparency (PDF/A-1, PDF/X-1a, PDF/X-3), device colors must match
+    /// the output intent (PDF/A, PDF/X) and the output intents must contain an ICC profile
+    /// if the conformance requires one. Encryption is a save option,
+    /// it is checked when saving (see `SaveOptions::encryption`).
+    fn conformance_errors(&self)
+    -> Vec<PdfError>
+    {
+        let doc = self.document.borrow();
+        let conformance = &doc.metadata.conformance;
+        let mut errors = Vec::new();
+
+        if conformance.must_embed_fonts() && doc.fonts.has_builtin_fonts() {
+            errors.push(PdfError::FontNotEmbedded);
+        }
+
+        if !conformance.is_transparency_allowed() && doc.pages.iter().any(page_has_transparency) {
+            errors.push(PdfError::TransparencyNotAllowed);
+        }
+
+        if let Some(color_space) = doc.metadata.output_intent_color_space(conformance) {
+            if doc.pages.iter().any(|page| page_has_other_device_colors(page, color_space)) {
+                errors.push(PdfError::DeviceColorNotAllowed);
+            }
+        }
+
+        // without output intents, the default one is generated on save
+        let output_intents = &doc.metadata.output_intents;
+        if conformance.must_have_icc_profile() && !output_intents.is_empty()
+            && output_intents.iter().all(|intent| intent.icc_profile.is_none())
+        {
+            errors.push(PdfError::MissingOutputIntentProfile);
+        }
+
+        errors
+    }
+
+    /// Checks that the operator sequence of each layer is well-formed: balanced `BT` / `ET`
+    /// and `q` / `Q`, no text operators outside of `BT` / `ET` and color operators with the
+    /// right number of operands. Returns every problem found, pointing at the offending operation.
+    pub fn validate_content(&self)
+    -> Vec<ContentError>
+    {
+        let doc = self.document.borrow();
+        let mut errors = Vec::new();
+
+        for (page_index, page) in doc.pages.iter().enumerate() {
+            for (layer_index, layer) in page.layers.iter().enumerate() {
+                errors.extend(validate_operations(&layer.operations).into_iter().map(|(operation_index, kind)| {
+                    ContentError {
+                        page: PdfPageIndex(page_index),
+                        layer: PdfLayerIndex(layer_index),
+                        operation_index,
+                        kind,
+                    }
+                }));
+            }
+        }
+
+        errors
+    }
+
+    /// Returns the names of all custom structure types used in the document
+    /// that are not mapped to a standard structure type by the role map
+    pub fn unresolved_structure_roles(&self)
+    -> Vec<String>
+    {
+        let doc = self.document.borrow();
+        let mut unresolved = Vec::<String>::new();
+
+        for role in doc.pages.iter().flat_map(|page| page.structure_roles.iter()) {
+            if role.resolve(&doc.role_map).is_none() && !unresolved.iter().any(|r| r == role.as_str()) {
+                unresolved.push(role.as_str().to_string());
+            }
+        }
+
+        unresolved
+    }
+
+    /// Returns the characters that could not be written because the font has no glyph
+    /// for them, in the order they were encountered (only recorded with `MissingGlyphPolicy::Error`).
+    /// As long as this is not empty, `save` fails with `PdfError::MissingGlyph`.
+    pub fn missing_glyphs(&self)
+    -> Vec<char>
+    {
+        self.document.borrow().missing_glyphs.clone()
+    }
+
+    /// Forgets the recorded missing glyphs (see `missing_glyphs`), for example after
+    /// the offending text has been replaced, so that the document can be saved again
+    pub fn clear_missing_glyphs(&self)
     {
-        let last_font_index = { let doc = self.document.borrow(); doc.fonts.len() };
-        let external_font = ExternalFont::with_font_data(bytes, last_font_index, Box::new(data));
-        let external_font_name = external_font.face_name.clone();
-        let font = Font::ExternalFont(external_font);
-        implement_adding_fonts!(&self, external_font_name, font)
+        self.document.borrow_mut().missing_glyphs.clear();
     }
 
-    /// Add a built-in font to the document
+    /// Tries to match the document to the given conformance (and sets the conformance
+    /// of the document). Errors only on an unrecoverable error.
     ///
-    /// Built-in fonts can only be used to print characters that are supported by the
-    /// [Windows-1252][] encoding.  All other characters will be ignored.
+    /// - Live transparency is flattened if the conformance doesn't allow it (see `flatten_transparency`)
+    /// - Device colors of the layers and gradients are converted into the color space of the
+    ///   output intent: CMYK for PDF/X, the color space of the output intent for PDF/A (CMYK
+    ///   by default). Colors set afterwards are converted as well (see `with_default_colorspace`).
+    /// - Output intents without an ICC profile get the target ICC profile of the document
     ///
-    /// [Windows-1252]: https://en.wikipedia.org/wiki/Windows-1252
-    pub fn add_builtin_font(&self, builtin_font: BuiltinFont)
-    -> ::std::result::Result<IndirectFontRef, Error>
+    /// Builtin fonts can't be embedded, since printpdf has no font data for them, so
+    /// `PdfError::FontNotEmbedded` is returned if the conformance requires embedded fonts.
+    /// Images are not converted, `PdfError::DeviceColorNotAllowed` is returned if an image
+    /// (or a shading that is not a gradient) doesn't match the output intent.
+    pub fn repair_errors(&self, conformance: PdfConformance)
+    -> ::std::result::Result<(), Error>
     {
-        let builtin_font_name: &'static str = builtin_font.clone().into();
-        implement_adding_fonts!(&self, builtin_font_name, Font::BuiltinFont(builtin_font))
-    }
+        {
+            let doc = self.document.borrow();
+            if conformance.must_embed_fonts() && doc.fonts.has_builtin_fonts() {
+                return Err(PdfError::FontNotEmbedded.into());
+            }
+            if let Some(color_space) = doc.metadata.output_intent_color_space(&conformance) {
+                if doc.pages.iter().any(|page| page_has_unconvertible_colors(page, color_space)) {
+                    return Err(PdfError::DeviceColorNotAllowed.into());
+                }
+            }
+        }
 
-    // ----- GET FUNCTIONS
+        if !conformance.is_transparency_allowed() {
+            let _issues = self.flatten_transparency();
+            #[cfg(feature = "logging")] {
+                for issue in &_issues {
+                    warn!("transparency on page {} was only approximated: {}", issue.page.0, issue.reason);
+                }
+            }
+        }
 
-    /// Returns the page (for inserting content)
-    #[inline]
-    #[cfg_attr(feature = "cargo-clippy", allow(unnecessary_operation))]
-    pub fn get_page(&self, page: PdfPageIndex)
-    -> PdfPageReference
-    {
-        &self.document.borrow_mut().pages[page.0];
-        PdfPageReference { document: Rc::downgrade(&self.document).clone(), page }
+        let mut doc = self.document.borrow_mut();
+
+        if let Some(color_space) = doc.metadata.output_intent_color_space(&conformance) {
+            doc.default_colorspace = Some(color_space);
+            for page in doc.pages.iter_mut() {
+                convert_page_colors(page, color_space);
+            }
+        }
+
+        if conformance.must_have_icc_profile() {
+            let profile = doc.metadata.target_icc_profile_or_default();
+            for intent in doc.metadata.output_intents.iter_mut().filter(|intent| intent.icc_profile.is_none()) {
+                intent.icc_profile = Some(profile.clone());
+            }
+        }
+
+        doc.metadata.conformance = conformance;
+        Ok(())
     }
 
-    /// Returns a direct reference (object ID) to the font from an
-    /// indirect reference (postscript name)
-    #[inline]
-    pub fn get_font(&self, font: &IndirectFontRef)
-    -> Option<DirectFontRef>
+    /// Makes the document as small as possible, applying all size reductions selected in the
+    /// `options` in one call: shared content streams, downsampled images, font subsetting,
+    /// removal of unused objects and stream compression. Object streams are not supported,
+    /// since lopdf can't write them.
+    ///
+    /// The optimizations are applied when the document is saved, the document itself (e.g. its
+    /// images) is not changed. It is saved (into memory) before and after the optimization
+    /// to report the sizes, so this is as expensive as saving the document twice.
+    pub fn optimize(&self, options: OptimizeOptions)
+    -> ::std::result::Result<OptimizeReport, Error>
     {
-        let doc = self.document.borrow();
-        doc.fonts.get_font(font)
+        let size_before = self.saved_size()?;
+        self.document.borrow_mut().optimize_options = Some(options);
+        Ok(OptimizeReport { size_before, size_after: self.saved_size()? })
     }
 
-    /// Drops the PDFDocument, returning the inner `lopdf::Document`.
-    /// Document may be only half-written, use only in extreme cases
-    #[inline]
-    pub unsafe fn get_inner(self)
-    -> lopdf::Document
+    /// Saves a copy of the document into memory and returns its size in bytes
+    fn saved_size(&self)
+    -> ::std::result::Result<usize, Error>
     {
-        let doc = Rc::try_unwrap(self.document).unwrap().into_inner();
-        doc.inner_doc
+        let copy = PdfDocumentReference { document: Rc::new(RefCell::new(self.document.borrow().clone())) };
+        Ok(copy.save_to_bytes()?.len())
     }
 
-    // --- MISC FUNCTIONS
-
-    /// Checks for invalid settings in the document
-    pub fn check_for_errors(&self)
-    -> ::std::result::Result<(), Error>
+    /// Returns the placement of every image in the document, with its size on the page and
+    /// the resulting effective resolution. Use `ImageReport::is_below_dpi` to find images
+    /// with a resolution too low for printing.
+    pub fn image_report(&self)
+    -> Vec<ImageReport>
     {
-        // TODO
-        #[cfg(feature = "logging")] {
-            warn!("Checking PDFs for errors is currently not supported!");
-        }
-
-        Ok(())
+        let doc = self.document.borrow();
+        doc.pages.iter().enumerate()
+            .flat_map(|(index, page)| page_image_report(page, PdfPageIndex(index)))
+            .collect()
     }
 
-    /// Tries to match the document to the given conformance.
-    /// Errors only on an unrecoverable error.
-    pub fn repair_errors(&self, _conformance: PdfConformance)
-    -> ::std::result::Result<(), Error>
+    /// Removes live transparency (alpha values, soft masks, blend modes and image alpha channels)
+    /// from all pages, since PDF/X-3 and PDF/A-1 forbid transparency. Call this before `save()`.
+    ///
+    /// Transparent colors and images are composited onto white paper, which is only exact
+    /// as long as the transparent content doesn't overlap other content. Returns the
+    /// transparency that could not be flattened (or only approximated).
+    pub fn flatten_transparency(&self)
+    -> Vec<UnflattenedTransparency>
     {
-        // TODO
-        #[cfg(feature = "logging")] {
-            warn!("Reparing PDFs is currently not supported!");
-        }
+        let mut doc = self.document.borrow_mut();
+        doc.pages.iter_mut().enumerate().flat_map(|(index, page)| {
+            flatten_page_transparency(page, PdfPageIndex(index))
+        }).collect()
+    }
 
-        Ok(())
+    /// Save PDF Document into memory and return the bytes of the file, for example
+    /// to send the document as a HTTP response without writing it to disk
+    pub fn save_to_bytes(self)
+    -> ::std::result::Result<Vec<u8>, Error>
+    {
+        let mut bytes = Vec::new();
+        // the writer is flushed when it is dropped at the end of the statement
+        self.save(&mut BufWriter::new(&mut bytes))?;
+        Ok(bytes)
     }
 
     /// Save PDF Document, writing the contents to the target
+    #[inline]
     pub fn save<W: Write>(self, target: &mut BufWriter<W>) -> ::std::result::Result<(), Error> {
+        self.save_with_options(target, SaveOptions::default())
+    }
+
+    /// Save PDF Document with the given options (e.g. without compression),
+    /// writing the contents to the target
+    pub fn save_with_options<W: Write>(self, target: &mut BufWriter<W>, options: SaveOptions)
+    -> ::std::result::Result<(), Error>
+    {
         use lopdf::Object::*;
         use lopdf::StringFormat::Literal;
         use lopdf::{Dictionary as LoDictionary, Object as LoObject};
         use std::iter::FromIterator;
 
+        let (box_guides, page_count) = {
+            let doc = self.document.borrow();
+            if !doc.missing_glyphs.is_empty() {
+                return Err(Error::Pdf(PdfError::MissingGlyph));
+            }
+            if options.encryption.is_some() && !doc.metadata.conformance.is_encryption_allowed() {
+                return Err(Error::Pdf(PdfError::EncryptionNotAllowed));
+            }
+            (doc.box_guides, doc.pages.len())
+        };
+        if box_guides {
+            for page in 0..page_count {
+                self.get_page(PdfPageIndex(page)).add_box_guides();
+            }
+        }
+
         // todo: remove unwrap, handle error
         let mut doc = Rc::try_unwrap(self.document).unwrap().into_inner();
+        let optimize_options = doc.optimize_options;
+        if let Some(max_dimension) = optimize_options.and_then(|o| o.max_image_dimension) {
+            downsample_images(&mut doc.pages, max_dimension);
+        }
+        let pdf_version = doc.metadata.conformance.get_pdf_version();
+        if doc.inner_doc.version.as_str() < pdf_version {
+            doc.inner_doc.version = pdf_version.into();
+        }
         let pages_id = doc.inner_doc.new_object_id();
         let bookmarks_id = doc.inner_doc.new_object_id();
-        let mut bookmarks_list = LoDictionary::from_iter(vec![
-            ("Type", "Outlines".into()),
-            ("Count", Integer(doc.bookmarks.len() as i64)),
-            /* First and Last will be filled in once they are created from the pages */
-        ]);
 
         // extra pdf infos
-        let (xmp_metadata, document_info, icc_profile) = doc.metadata.clone().into_obj();
+        let instance_id = options.instance_id.clone().unwrap_or_else(random_character_string_32);
+        let (xmp_metadata, document_info, icc_profile) = doc.metadata.clone().into_obj_with_instance_id(&instance_id);
 
         let xmp_metadata_id = match xmp_metadata {
             Some(metadata) => Some(doc.inner_doc.add_object(metadata)),
@@ -355,37 +1374,33 @@ impl PdfDocumentReference {
 
         let document_info_id = doc.inner_doc.add_object(document_info);
 
-        // add catalog
-        let icc_profile_descr = "Commercial and special offset print acccording to ISO \
-                                 12647-2:2004 / Amd 1, paper type 1 or 2 (matte or gloss-coated \
-                                 offset paper, 115 g/m2), screen ruling 60/cm";
-        let icc_profile_str = "Coated FOGRA39 (ISO 12647-2:2004)";
-        let icc_profile_short = "FOGRA39";
-
-        let mut output_intents = LoDictionary::from_iter(vec![
-            ("S", Name("GTS_PDFX".into())),
-            ("OutputCondition", String(icc_profile_descr.into(), Literal)),
-            ("Type", Name("OutputIntent".into())),
-            (
-                "OutputConditionIdentifier",
-                String(icc_profile_short.into(), Literal),
-            ),
-            (
-                "RegistryName",
-                String("http://www.color.org".into(), Literal),
-            ),
-            ("Info", String(icc_profile_str.into(), Literal)),
-        ]);
+        // use the default output intent for the target ICC profile if none were set
+        let output_intents = if doc.metadata.output_intents.is_empty() {
+            {
+                let subtype = if doc.metadata.conformance.get_pdfa_part_and_conformance().is_some() {
+                    OutputIntentSubtype::PdfA
+                } else {
+                    OutputIntentSubtype::PdfX
+                };
+                icc_profile.map(|profile| OutputIntent::for_target_profile(profile, subtype)).into_iter().collect()
+            }
+        } else {
+            ::std::mem::take(&mut doc.metadata.output_intents)
+        };
+
+        check_output_intents(&output_intents)?;
+        check_custom_properties(&doc.metadata.xmp_metadata.custom_properties)?;
 
+        // add catalog
         let mut catalog = LoDictionary::from_iter(vec![
             ("Type", "Catalog".into()),
             ("PageLayout", "OneColumn".into()),
             (
                 "PageMode",
-                if doc.bookmarks.len() > 0 {
-                    "UseOutlines"
-                } else {
-                    "UseNone"
+                match doc.page_mode {
+                    Some(page_mode) => page_mode.as_str(),
+                    None if !doc.bookmarks.is_empty() => "UseOutlines",
+                    None => "UseNone",
                 }
                 .into(),
             ),
@@ -393,11 +1408,18 @@ impl PdfDocumentReference {
             ("Pages", Reference(pages_id)),
         ]);
 
-        if let Some(profile) = icc_profile {
-            let icc_profile: lopdf::Stream = profile.into();
-            let icc_profile_id = doc.inner_doc.add_object(Stream(icc_profile));
-            output_intents.set("DestinationOutputProfile", Reference(icc_profile_id));
-            catalog.set("OutputIntents", Array(vec![Dictionary(output_intents)]));
+        // the non-full-screen page mode is ignored by viewers for other page modes
+        if let (Some(PageMode::FullScreen), Some(mode)) = (doc.page_mode, doc.non_full_screen_page_mode) {
+            catalog.set("ViewerPreferences", Dictionary(LoDictionary::from_iter(vec![
+                ("NonFullScreenPageMode", Name(mode.as_str().into())),
+            ])));
+        }
+
+        if !output_intents.is_empty() {
+            let output_intents = output_intents.into_iter()
+                .map(|intent| Dictionary(intent.into_with_document(&mut doc.inner_doc)))
+                .collect();
+            catalog.set("OutputIntents", Array(output_intents));
         }
 
         if let Some(metadata_id) = xmp_metadata_id {
@@ -463,15 +1485,22 @@ impl PdfDocumentReference {
                 layers.iter().map(|&(_, ref obj)| obj.clone())
             ).collect();
 
+        // same order as `flattened_ocg_list`
+        let layer_visibility: Vec<bool> =
+            doc.pages.iter().flat_map(|page| page.layers.iter().map(|layer| layer.visible)).collect();
+        let (visible_ocgs, hidden_ocgs): (Vec<_>, Vec<_>) =
+            flattened_ocg_list.iter().cloned().zip(layer_visibility).partition(|&(_, visible)| visible);
+
         catalog.set("OCProperties", Dictionary(LoDictionary::from_iter(vec![
             ("OCGs", Array(flattened_ocg_list.clone())),
             // optional content configuration dictionary, page 376
             ("D", Dictionary(LoDictionary::from_iter(vec![
-                ("Order", Array(flattened_ocg_list.clone())),
+                ("Order", Array(flattened_ocg_list)),
                 // "radio button groups"
                 ("RBGroups", Array(vec![])),
-                // initially visible OCG
-                ("ON", Array(flattened_ocg_list)),
+                // initially visible / hidden OCG
+                ("ON", Array(visible_ocgs.into_iter().map(|(ocg, _)| ocg).collect())),
+                ("OFF", Array(hidden_ocgs.into_iter().map(|(ocg, _)| ocg).collect())),
             ])))
         ])));
 
@@ -483,25 +1512,44 @@ impl PdfDocumentReference {
         let mut font_dict_id = None;
 
         // add all fonts / other resources shared in the whole document
-        let fonts_dict: lopdf::Dictionary = doc.fonts.into_with_document(&mut doc.inner_doc);
+        let subset_fonts = options.subset_fonts || optimize_options.map_or(false, |o| o.subset_fonts);
+        let used_glyphs = if subset_fonts { Some(collect_used_glyphs(&doc.pages)) } else { None };
+        let fonts_dict: lopdf::Dictionary = doc.fonts.into_with_document(&mut doc.inner_doc, used_glyphs.as_ref());
 
         if fonts_dict.len() > 0 {
             font_dict_id = Some(doc.inner_doc.add_object(Dictionary(fonts_dict)));
         }
 
-        let mut page_id_to_obj: HashMap<usize, (u32, u16)> = HashMap::new();
+        let mut page_objs = Vec::<lopdf::ObjectId>::new();
+
+        // content streams that were already added, for deduplicating the page contents
+        let mut content_ids = HashMap::<Vec<u8>, LoObject>::new();
 
-        for (idx, page) in doc.pages.into_iter().enumerate() {
+        // page object + roles of the tagged content, for the structure tree
+        let mut tagged_pages = Vec::<(lopdf::ObjectId, Vec<StructureRole>)>::new();
+
+        let page_labels = doc.pages.iter().enumerate()
+            .filter_map(|(index, page)| page.label.clone().map(|label| (index, label)))
+            .collect::<Vec<_>>();
+        if !page_labels.is_empty() {
+            catalog.set("PageLabels", Dictionary(page_labels_tree(page_labels)));
+        }
+
+        for (idx, mut page) in doc.pages.into_iter().enumerate() {
+            let structure_roles = ::std::mem::take(&mut page.structure_roles);
+            let (trim_llx, trim_lly, trim_urx, trim_ury) = page.trim_box();
+            let thumbnail = page.thumbnail.take();
+            let duration = page.duration.or(doc.auto_advance).filter(|seconds| *seconds > 0.0);
             let mut p = LoDictionary::from_iter(vec![
                 ("Type", "Page".into()),
-                ("Rotate", Integer(0)),
+                ("Rotate", Integer(page.rotation)),
                 (
                     "MediaBox",
                     vec![0.into(), 0.into(), page.width.into(), page.height.into()].into(),
                 ),
                 (
                     "TrimBox",
-                    vec![0.into(), 0.into(), page.width.into(), page.height.into()].into(),
+                    vec![trim_llx.into(), trim_lly.into(), trim_urx.into(), trim_ury.into()].into(),
                 ),
                 (
                     "CropBox",
@@ -510,120 +1558,118 @@ impl PdfDocumentReference {
                 ("Parent", Reference(pages_id)),
             ]);
 
+            for &(name, page_box) in &[("BleedBox", page.bleedbox), ("ArtBox", page.artbox)] {
+                if let Some((llx, lly, urx, ury)) = page_box {
+                    p.set(name, vec![llx.into(), lly.into(), urx.into(), ury.into()]);
+                }
+            }
+
+            if !structure_roles.is_empty() {
+                p.set("StructParents", Integer(tagged_pages.len() as i64));
+            }
+
+            if let Some(seconds) = duration {
+                p.set("Dur", Real(seconds));
+            }
+
+            let links = ::std::mem::take(&mut page.links);
+            if !links.is_empty() {
+                let inner_doc = &mut doc.inner_doc;
+                let annotations = links.into_iter()
+                    .map(|link| Reference(inner_doc.add_object(Dictionary(link.into_dictionary()))))
+                    .collect();
+                p.set("Annots", Array(annotations));
+            }
+
             // this will collect the resources needed for rendering this page
             let layers_temp = ocg_list.iter().find(|e| e.0 == idx).unwrap();
             let (mut resources_page, layer_streams) =
-                page.collect_resources_and_streams(&mut doc.inner_doc, &layers_temp.1);
+                page.collect_resources_and_streams(&mut doc.inner_doc, &layers_temp.1, doc.content_split_threshold);
 
             if let Some(f) = font_dict_id {
                 resources_page.set("Font", Reference(f));
             }
 
-            if resources_page.len() > 0 {
-                let resources_page_id = doc.inner_doc.add_object(Dictionary(resources_page));
-                p.set("Resources", Reference(resources_page_id));
-            }
+            // the resource dictionary is required, even if it is empty
+            // (e.g. for pages without layers, see `PdfPage::with_operations`)
+            let resources_page_id = doc.inner_doc.add_object(Dictionary(resources_page));
+            p.set("Resources", Reference(resources_page_id));
 
-            // merge all streams of the individual layers into one big stream
-            let mut layer_streams_merged_vec = Vec::<u8>::new();
+            // merge all streams of the individual layers into one big stream,
+            // or into streams of up to `content_split_threshold` bytes
+            let mut layer_streams_merged = vec![Vec::<u8>::new()];
             for mut stream in layer_streams {
-                layer_streams_merged_vec.append(&mut stream.content);
+                if let Some(threshold) = doc.content_split_threshold {
+                    let current_len = layer_streams_merged.last().unwrap().len();
+                    if current_len > 0 && current_len + stream.content.len() > threshold {
+                        layer_streams_merged.push(Vec::new());
+                    }
+                }
+                layer_streams_merged.last_mut().unwrap().append(&mut stream.content);
             }
 
-            let merged_layer_stream =
-                lopdf::Stream::new(lopdf::Dictionary::new(), layer_streams_merged_vec)
-                    .with_compression(false);
-            let page_content_id = doc.inner_doc.add_object(merged_layer_stream);
+            let deduplicate_content = doc.deduplicate_content || optimize_options.map_or(false, |o| o.deduplicate_content);
+            let content_key = if deduplicate_content { Some(layer_streams_merged.concat()) } else { None };
+            let existing_contents = content_key.as_ref().and_then(|key| content_ids.get(key).cloned());
+
+            let page_contents = match existing_contents {
+                Some(contents) => contents,
+                None => {
+                    let inner_doc = &mut doc.inner_doc;
+                    let mut ids = layer_streams_merged.into_iter().map(|content| {
+                        let stream = lopdf::Stream::new(lopdf::Dictionary::new(), content).with_compression(false);
+                        Reference(inner_doc.add_object(stream))
+                    }).collect::<Vec<LoObject>>();
+                    let contents = if ids.len() == 1 { ids.remove(0) } else { Array(ids) };
+                    if let Some(key) = content_key {
+                        content_ids.insert(key, contents.clone());
+                    }
+                    contents
+                },
+            };
+
+            p.set("Contents", page_contents);
+
+            if let Some(thumbnail) = thumbnail {
+                let thumbnail: lopdf::Stream = thumbnail.into();
+                p.set("Thumb", Reference(doc.inner_doc.add_object(thumbnail)));
+            }
 
-            p.set("Contents", Reference(page_content_id));
             let page_obj = doc.inner_doc.add_object(p);
-            if doc.bookmarks.len() > 0 {
-                if let Some(_) = doc.bookmarks.get(&idx) {
-                    page_id_to_obj.insert(idx, page_obj);
-                }
+            if !structure_roles.is_empty() {
+                tagged_pages.push((page_obj, structure_roles));
             }
+            page_objs.push(page_obj);
             page_ids.push(Reference(page_obj))
         }
 
-        if doc.bookmarks.len() > 0 {
-            let len = doc.bookmarks.len();
-            if len == 1 {
-                let page_index = doc.bookmarks.iter().next().unwrap().0.to_owned();
-                let title = doc.bookmarks.iter().next().unwrap().1.to_owned();
-                let obj_ref = doc
-                    .inner_doc
-                    .add_object(Dictionary(LoDictionary::from_iter(vec![
-                        ("Parent", Reference(bookmarks_id)),
-                        ("Title", String(title.into(), Literal)),
-                        (
-                            "Dest",
-                            Array(vec![
-                                Reference(page_id_to_obj.get(&page_index).unwrap().to_owned()),
-                                "XYZ".into(),
-                                Null,
-                                Null,
-                                Null,
-                            ]),
-                        ),
-                    ])));
-                bookmarks_list.set("First", Reference(obj_ref));
-                bookmarks_list.set("Last", Reference(obj_ref));
-            } else {
-                let mut sorted_bmarks: Vec<(&usize, &std::string::String)> = doc.bookmarks.iter().collect();
-                sorted_bmarks.sort();
-                for (i, (page_index, b_name)) in sorted_bmarks.iter().enumerate() {
-                    let dest = (
-                        "Dest",
-                        Array(vec![
-                            Reference(page_id_to_obj.get(page_index).unwrap().to_owned()),
-                                "XYZ".into(),
-                                Null,
-                                Null,
-                                Null,
-                        ]),
-                    );
-                    doc.inner_doc
-                        .add_object(Dictionary(LoDictionary::from_iter(if i == 0 {
-                            bookmarks_list.set("First", Reference((doc.inner_doc.max_id + 1, 0)));
-                            vec![
-                                ("Parent", Reference(bookmarks_id)),
-                                ("Title", String(b_name.to_owned().to_owned().into(), Literal)),
-                                ("Next", Reference((doc.inner_doc.max_id + 2, 0))),
-                                dest,
-                            ]
-                        } else if i == len - 1 {
-                            bookmarks_list.set("Last", Reference((doc.inner_doc.max_id + 1, 0)));
-                            vec![
-                                ("Parent", Reference(bookmarks_id)),
-                                ("Title", String(b_name.to_owned().to_owned().into(), Literal)),
-                                ("Prev", Reference((doc.inner_doc.max_id, 0))),
-                                dest,
-                            ]
-                        } else {
-                            vec![
-                                ("Parent", Reference(bookmarks_id)),
-                                ("Title", String(b_name.to_owned().to_owned().into(), Literal)),
-                                ("Prev", Reference((doc.inner_doc.max_id, 0))),
-                                ("Next", Reference((doc.inner_doc.max_id + 2, 0))),
-                                dest,
-                            ]
-                        })));
-                }
-            }
+        let outlines = add_outline(&mut doc.inner_doc, bookmarks_id, &doc.bookmarks, &page_objs);
+
+        if let (Some(zoom), Some(first_page)) = (doc.initial_zoom, page_ids.first()) {
+            catalog.set("OpenAction", Array(vec![first_page.clone(), "XYZ".into(), Null, Null, Real(zoom / 100.0)]));
         }
 
         pages.set::<_, LoObject>("Kids".to_string(), page_ids.into());
 
+        if let Some(ref lang) = doc.lang {
+            catalog.set("Lang", LoObject::string_literal(lang.as_str()));
+        }
+
+        if !tagged_pages.is_empty() {
+            let struct_tree_root_id = add_structure_tree(&mut doc.inner_doc, &tagged_pages, &doc.role_map);
+            catalog.set("StructTreeRoot", Reference(struct_tree_root_id));
+            catalog.set("MarkInfo", Dictionary(LoDictionary::from_iter(vec![("Marked", Boolean(true))])));
+        }
+
         // ----- END PAGE CONTENT
 
         doc.inner_doc.objects.insert(pages_id, Dictionary(pages));
         doc.inner_doc
             .objects
-            .insert(bookmarks_id, Dictionary(bookmarks_list));
+            .insert(bookmarks_id, Dictionary(outlines));
 
         // save inner document
         let catalog_id = doc.inner_doc.add_object(catalog);
-        let instance_id = random_character_string_32();
 
         doc.inner_doc.trailer.set("Root", Reference(catalog_id));
         doc.inner_doc
@@ -637,8 +1683,21 @@ impl PdfDocumentReference {
             ]),
         );
 
-        // does nothing in debug mode, optimized in release mode
-        Self::optimize(&mut doc.inner_doc);
+        match doc.optimize_options {
+            Some(ref optimize_options) => optimize_document(&mut doc.inner_doc, optimize_options, &options),
+            None => {
+                // pruning does nothing in debug mode
+                Self::optimize_default(&mut doc.inner_doc);
+                if options.compress {
+                    doc.inner_doc.compress();
+                }
+            },
+        }
+
+        // encrypted streams can't be compressed anymore, so this has to be the last step
+        if let Some(ref encryption) = options.encryption {
+            encrypt_document(&mut doc.inner_doc, encryption, doc.document_id.as_bytes(), instance_id.as_bytes());
+        }
         doc.inner_doc.save_to(target)?;
 
         Ok(())
@@ -646,14 +1705,688 @@ impl PdfDocumentReference {
 
     #[cfg(any(debug_assertions, feature="less-optimization"))]
     #[inline]
-    fn optimize(_: &mut lopdf::Document) { }
+    fn optimize_default(_: &mut lopdf::Document) { }
 
     #[cfg(all(not(debug_assertions), not(feature="less-optimization")))]
     #[inline]
-    fn optimize(doc: &mut lopdf::Document)
+    fn optimize_default(doc: &mut lopdf::Document)
     {
         doc.prune_objects();
         doc.delete_zero_length_streams();
-        doc.compress();
     }
 }
+
+impl From<lopdf::Document> for PdfDocumentReference {
+
+    /// Loads an existing document, for example to append pages to it. Every page becomes a
+    /// page with one (empty) layer, the original content of the page is drawn below the layers.
+    /// The content is not parsed, it is kept as it is. The title, subject, keywords, author,
+    /// creator, creation date and trapping are taken from the document information dictionary,
+    /// the producer and the modification date are set anew. The document gets a new document ID.
+    fn from(mut inner_doc: lopdf::Document)
+    -> Self
+    {
+        let info = import_info(&inner_doc);
+        let imported_pages = import_pages(&inner_doc);
+
+        // the catalog, page tree and info dictionary are created anew when saving,
+        // the old ones are not referenced anymore. The old info dictionary is removed, so
+        // that the file doesn't contain two versions of the metadata.
+        if let Ok(info_id) = inner_doc.trailer.get(b"Info").and_then(|info| info.as_reference()) {
+            inner_doc.objects.remove(&info_id);
+        }
+        inner_doc.trailer = lopdf::Dictionary::new();
+
+        let document = PdfDocument::empty(info.title.unwrap_or_default());
+        {
+            let mut doc = document.document.borrow_mut();
+            doc.metadata.keywords = info.keywords;
+            doc.metadata.subject = info.subject.unwrap_or_default();
+            doc.metadata.author = info.author.unwrap_or_default();
+            doc.metadata.creator = info.creator.unwrap_or_default();
+            if let Some(creation_date) = info.creation_date {
+                doc.metadata.creation_date = creation_date;
+            }
+            if let Some(trapping) = info.trapping {
+                doc.metadata.trapping = trapping;
+            }
+
+            for imported_page in imported_pages {
+                let (mut page, _) = PdfPage::new(imported_page.width, imported_page.height, "Layer 1", doc.pages.len());
+                page.imported_content = Some(inner_doc.add_object(imported_page.content));
+                page.rotation = imported_page.rotation;
+                doc.pages.push(page);
+            }
+
+            doc.inner_doc = inner_doc;
+        }
+
+        document
+    }
+}
+
+#[test]
+fn test_save_to_bytes() {
+    let (doc, _, _) = PdfDocument::new("bytes", Mm(210.0), Mm(297.0), "Layer 1");
+    let bytes = doc.save_to_bytes().unwrap();
+    assert!(bytes.starts_with(b"%PDF-"));
+    assert!(bytes.ends_with(b"%%EOF"));
+}
+
+#[test]
+fn test_hidden_layer() {
+    let (doc, page, _) = PdfDocument::new("layers", Mm(210.0), Mm(297.0), "Layer 1");
+    doc.get_page(page).add_layer("Layer 2").set_visible(false);
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+
+    // number of OCG references in the /ON or /OFF array of the default configuration
+    let count = |key: &str| {
+        let start = pdf.find(key).unwrap() + key.len();
+        pdf[start..start + pdf[start..].find(']').unwrap()].matches(" R").count()
+    };
+    assert_eq!(count("/ON["), 1);
+    assert_eq!(count("/OFF["), 1);
+}
+
+#[test]
+fn test_merge() {
+    let document = |title: &str| {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(210.0), Mm(297.0), "Layer 1");
+        let font = doc.add_external_font(::std::fs::File::open("assets/fonts/RobotoMedium.ttf").unwrap()).unwrap();
+        doc.get_page(page).get_layer(layer).use_text(title, 12.0, Mm(10.0), Mm(10.0), &font);
+        doc.add_bookmark(title, page);
+        doc
+    };
+
+    let doc = document("first");
+    doc.merge(document("second")).unwrap();
+
+    let doc = doc.document.borrow();
+    assert_eq!(doc.pages.len(), 2);
+    assert_eq!(doc.pages[1].index, 1);
+    assert_eq!(doc.fonts.len(), 2);
+    assert_eq!((doc.bookmarks[1].title.as_str(), doc.bookmarks[1].page), ("second", 1));
+    assert_eq!(doc.bookmarks().get(&1).map(|name| name.as_str()), Some("second"));
+
+    // the font of the second document was renamed, the text has to use the new name
+    let font_name = |page: usize| doc.pages[page].layers[0].operations.iter()
+        .find(|op| op.operator == "Tf")
+        .and_then(|op| op.operands[0].as_name_str().ok())
+        .map(|name| name.to_string())
+        .unwrap();
+    assert_ne!(font_name(0), font_name(1));
+    assert!(doc.fonts.contains_name(&font_name(1)));
+}
+
+#[test]
+fn test_load_and_append() {
+    use lopdf::Object::{Dictionary, Name, Reference};
+    use lopdf::StringFormat::Literal;
+    use std::iter::FromIterator;
+    use utils::text_string;
+
+    let literal = |text: &str| lopdf::Object::String(text.as_bytes().to_vec(), Literal);
+
+    // a document with one page and a complete info dictionary
+    let mut inner_doc = lopdf::Document::with_version("1.5");
+    let content_id = inner_doc.add_object(lopdf::Stream::new(lopdf::Dictionary::new(), b"0 0 m 10 10 l S".to_vec()));
+    let pages_id = inner_doc.new_object_id();
+    let page_id = inner_doc.add_object(lopdf::Dictionary::from_iter(vec![
+        ("Type", Name(b"Page".to_vec())),
+        ("Parent", Reference(pages_id)),
+        ("MediaBox", vec![0.into(), 0.into(), 595.into(), 842.into()].into()),
+        ("Contents", Reference(content_id)),
+    ]));
+    inner_doc.objects.insert(pages_id, Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Name(b"Pages".to_vec())),
+        ("Kids", vec![Reference(page_id)].into()),
+        ("Count", 1.into()),
+    ])));
+    let catalog_id = inner_doc.add_object(lopdf::Dictionary::from_iter(vec![("Type", Name(b"Catalog".to_vec())), ("Pages", Reference(pages_id))]));
+    let info_id = inner_doc.add_object(lopdf::Dictionary::from_iter(vec![
+        ("Title", literal("Report")),
+        ("Subject", text_string("Übersicht")),
+        ("Keywords", literal("sales; 2020")),
+        ("Author", literal("Jane Doe")),
+        ("Creator", literal("Spreadsheet")),
+        ("Producer", literal("Old Producer")),
+        ("CreationDate", literal("D:20200102030405+01'00'")),
+        ("ModDate", literal("D:20200102030405+01'00'")),
+        ("Trapped", Name(b"True".to_vec())),
+    ]));
+    inner_doc.trailer.set("Root", Reference(catalog_id));
+    inner_doc.trailer.set("Info", Reference(info_id));
+
+    let doc = PdfDocumentReference::from(inner_doc);
+    {
+        let metadata = &doc.document.borrow().metadata;
+        assert_eq!(metadata.document_title, "Report");
+        assert_eq!(metadata.subject, "Übersicht");
+        assert_eq!(metadata.keywords, vec!["sales".to_string(), "2020".to_string()]);
+        assert_eq!((metadata.author.as_str(), metadata.creator.as_str()), ("Jane Doe", "Spreadsheet"));
+        assert_eq!((metadata.creation_date.year(), metadata.creation_date.hour()), (2020, 3));
+        assert!(metadata.trapping);
+    }
+
+    doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+
+    // the old info dictionary is replaced by one with the same values, but printpdf as producer
+    assert!(pdf.contains("/Count 2"));
+    assert!(pdf.contains("(Jane Doe)"));
+    assert!(pdf.contains("(Spreadsheet)"));
+    assert!(pdf.contains("D:20200102030405+01'00'"));
+    assert!(pdf.contains("/Trapped/True"));
+    assert!(!pdf.contains("Old Producer"));
+    assert!(pdf.contains("0 0 m 10 10 l S"));
+}
+
+#[test]
+fn test_save_without_compression() {
+    let save = |compress: bool| {
+        let (doc, _, _) = PdfDocument::new("compression", Mm(210.0), Mm(297.0), "Layer 1");
+        let mut bytes = Vec::new();
+        doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress, .. Default::default() }).unwrap();
+        bytes
+    };
+    let (compressed, uncompressed) = (save(true), save(false));
+    // the XMP metadata is only readable without compression
+    let contains = |bytes: &[u8], text: &[u8]| bytes.windows(text.len()).any(|window| window == text);
+    assert!(contains(&uncompressed, b"x:xmpmeta"));
+    assert!(!contains(&compressed, b"x:xmpmeta"));
+}
+
+#[test]
+fn test_keywords_and_subject() {
+    let (doc, _, _) = PdfDocument::new("keywords", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_keywords(vec!["cosmology", "starlight"]).with_subject("Ripples & starlight");
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+
+    assert!(pdf.contains("/Keywords(cosmology, starlight)"));
+    assert!(pdf.contains("/Subject(Ripples & starlight)"));
+    assert!(pdf.contains("<rdf:li>starlight</rdf:li>"));
+    assert!(pdf.contains("Ripples &amp; starlight</rdf:li>"));
+}
+
+#[test]
+fn test_author_and_creator() {
+    let (doc, _, _) = PdfDocument::new("author", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_author("Craig J. Hogan").with_creator("Report Generator");
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+
+    assert!(pdf.contains("/Author(Craig J. Hogan)"));
+    assert!(pdf.contains("/Creator(Report Generator)"));
+    assert!(pdf.contains("<rdf:li>Craig J. Hogan</rdf:li>"));
+    assert!(pdf.contains("<xmp:CreatorTool>Report Generator</xmp:CreatorTool>"));
+}
+
+#[test]
+fn test_producer() {
+    let save = |doc: PdfDocumentReference| {
+        let mut bytes = Vec::new();
+        doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+        ::std::string::String::from_utf8_lossy(&bytes).into_owned()
+    };
+
+    let (doc, _, _) = PdfDocument::new("producer", Mm(210.0), Mm(297.0), "Layer 1");
+    let pdf = save(doc);
+    let producer = format!("printpdf {}", env!("CARGO_PKG_VERSION"));
+    assert!(pdf.contains(&format!("/Producer({})", producer)));
+    assert!(pdf.contains(&format!("<pdf:Producer>{}</pdf:Producer>", producer)));
+
+    let (doc, _, _) = PdfDocument::new("producer", Mm(210.0), Mm(297.0), "Layer 1");
+    let pdf = save(doc.with_producer("Report Generator 2.1"));
+    assert!(pdf.contains("/Producer(Report Generator 2.1)"));
+    assert!(pdf.contains("<pdf:Producer>Report Generator 2.1</pdf:Producer>"));
+}
+
+#[test]
+fn test_xmp_property() {
+    use CustomPdfConformance;
+
+    let (doc, _, _) = PdfDocument::new("xmp", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_xmp_property("http://prismstandard.org/namespaces/basic/2.0/", "prism", "issn", "1234-5678");
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+
+    assert!(pdf.contains("xmlns:prism=\"http://prismstandard.org/namespaces/basic/2.0/\">"));
+    assert!(pdf.contains("<prism:issn>1234-5678</prism:issn>"));
+
+    // the XMP metadata is written for the custom properties, even if the conformance doesn't need it
+    let (doc, _, _) = PdfDocument::new("xmp", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_conformance(PdfConformance::Custom(CustomPdfConformance::default()))
+        .with_xmp_property("http://prismstandard.org/namespaces/basic/2.0/", "prism", "issn", "1234-5678");
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/Metadata "));
+    assert!(pdf.contains("<prism:issn>1234-5678</prism:issn>"));
+
+    // properties of the built-in schemas are rejected
+    let (doc, _, _) = PdfDocument::new("xmp", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_xmp_property("http://purl.org/dc/elements/1.1/", "dc", "title", "Other title");
+    match doc.save(&mut BufWriter::new(Vec::new())) {
+        Err(Error::Pdf(PdfError::InvalidXmpProperty)) => { },
+        other => panic!("expected InvalidXmpProperty, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_deduplicate_content() {
+    use CustomPdfConformance;
+
+    let save = |deduplicate_content: bool| {
+        let (doc, page1, layer1) = PdfDocument::new("template", Mm(210.0), Mm(297.0), "Layer 1");
+        let doc = doc.with_conformance(PdfConformance::Custom(CustomPdfConformance::default()))
+            .with_deduplicate_content(deduplicate_content);
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        let (page2, layer2) = doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
+        for &(page, layer) in &[(page1, layer1), (page2, layer2)] {
+            doc.get_page(page).get_layer(layer).use_text("template page", 12.0, Mm(10.0), Mm(10.0), &font);
+        }
+        let mut bytes = Vec::new();
+        doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+        ::std::string::String::from_utf8_lossy(&bytes).matches("<74656D706C6174652070616765> Tj").count()
+    };
+
+    // the identical content stream (with the hex-encoded text) is only written once
+    assert_eq!(save(false), 2);
+    assert_eq!(save(true), 1);
+}
+
+#[test]
+fn test_optimize() {
+    use {ColorBits, ColorSpace, Image, ImageXObject, Px, XObject};
+
+    let (doc, page, layer) = PdfDocument::new("optimize", Mm(210.0), Mm(297.0), "Layer 1");
+    let image = ImageXObject::new(Px(64), Px(64), ColorSpace::Greyscale, ColorBits::Bit8, false, None, None, vec![128; 64 * 64]);
+    Image::from(image).add_to_layer(doc.get_page(page).get_layer(layer), None, None, None, None, None, None);
+
+    let report = doc.optimize(OptimizeOptions { max_image_dimension: Some(16), .. Default::default() }).unwrap();
+    assert!(report.size_after < report.size_before);
+
+    // only the saved document is downsampled
+    let widths = doc.document.borrow().pages[0].resources.xobjects.objects.values().map(|xobject| match *xobject {
+        XObject::Image(ref image) => image.width.0,
+        _ => 0,
+    }).collect::<Vec<_>>();
+    assert_eq!(widths, vec![64]);
+
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/Width 16"));
+}
+
+#[test]
+fn test_reproducible_save() {
+    let save = || {
+        let date = OffsetDateTime::unix_epoch();
+        let (doc, _, _) = PdfDocument::new("reproducible", Mm(210.0), Mm(297.0), "Layer 1");
+        let doc = doc.with_document_id("document".into())
+            .with_creation_date(date)
+            .with_mod_date(date)
+            .with_metadata_date(date);
+        let mut bytes = Vec::new();
+        let options = SaveOptions { instance_id: Some("instance".into()), .. Default::default() };
+        doc.save_with_options(&mut BufWriter::new(&mut bytes), options).unwrap();
+        bytes
+    };
+    assert_eq!(save(), save());
+}
+
+#[test]
+fn test_pdfa_conformance() {
+    use {Color, Rgb};
+
+    let (doc, page, layer) = PdfDocument::new("archive", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_conformance(PdfConformance::A1B_2005_PDF_1_4);
+    assert!(doc.check_for_errors().is_ok());
+
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    doc.get_page(page).get_layer(layer).use_text("archived", 12.0, Mm(10.0), Mm(10.0), &font);
+    match doc.check_for_errors() {
+        Err(Error::Pdf(PdfError::FontNotEmbedded)) => { },
+        other => panic!("expected FontNotEmbedded, got {:?}", other),
+    }
+
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("<pdfaid:part>1</pdfaid:part>"));
+    assert!(pdf.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+    assert!(!pdf.contains("pdfx:GTS_PDFXVersion>"));
+    assert!(!pdf.contains("/GTS_PDFXVersion"));
+    assert!(pdf.contains("/S/GTS_PDFA1"));
+    assert!(pdf.starts_with("%PDF-1.4"));
+
+    // device RGB doesn't match the default (CMYK) output intent
+    let (doc, page, layer) = PdfDocument::new("archive", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_conformance(PdfConformance::A1B_2005_PDF_1_4);
+    doc.get_page(page).get_layer(layer).set_fill_color(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None)));
+    match doc.check_for_errors() {
+        Err(Error::Pdf(PdfError::DeviceColorNotAllowed)) => { },
+        other => panic!("expected DeviceColorNotAllowed, got {:?}", other),
+    }
+    doc.repair_errors(PdfConformance::A1B_2005_PDF_1_4).unwrap();
+    assert!(doc.check_for_errors().is_ok());
+}
+
+#[test]
+fn test_check_for_errors() {
+    let (doc, page, layer) = PdfDocument::new("errors", Mm(210.0), Mm(297.0), "Layer 1");
+    assert!(doc.check_for_errors().is_ok());
+
+    doc.add_page(Mm(6000.0), Mm(297.0), "Layer 1");
+    let layer = doc.get_page(page).get_layer(layer);
+    layer.begin_text_section();
+    layer.set_font(&IndirectFontRef::new("F99"), 12.0);
+    layer.end_text_section();
+    match doc.check_for_errors() {
+        Err(Error::Multiple(errors)) => {
+            assert_eq!(errors.len(), 2);
+            assert!(matches!(errors[0], Error::Pdf(PdfError::InvalidPageSize)));
+            assert!(matches!(errors[1], Error::Pdf(PdfError::UnknownFont)));
+        },
+        other => panic!("expected two errors, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_transparency_not_allowed() {
+    let (doc, page, layer) = PdfDocument::new("alpha", Mm(210.0), Mm(297.0), "Layer 1");
+    doc.get_page(page).get_layer(layer).set_fill_alpha(0.5);
+    let doc = doc.with_conformance(PdfConformance::X4_2010_PDF_1_4);
+    assert!(doc.check_for_errors().is_ok());
+
+    let doc = doc.with_conformance(PdfConformance::X3_2002_PDF_1_3);
+    match doc.check_for_errors() {
+        Err(Error::Pdf(PdfError::TransparencyNotAllowed)) => { },
+        other => panic!("expected TransparencyNotAllowed, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_repair_errors() {
+    use {BlendMode, SeperableBlendMode, Color, Rgb};
+
+    let (doc, page, layer) = PdfDocument::new("repair", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer = doc.get_page(page).get_layer(layer);
+    layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None)));
+    layer.set_blend_mode(BlendMode::Seperable(SeperableBlendMode::Multiply));
+
+    doc.repair_errors(PdfConformance::X3_2002_PDF_1_3).unwrap();
+    assert!(doc.check_for_errors().is_ok());
+    {
+        let inner = doc.document.borrow();
+        let operators: Vec<_> = inner.pages[0].layers[0].operations.iter().map(|op| op.operator.as_str()).collect();
+        assert!(operators.contains(&"k"));
+        assert!(!operators.contains(&"rg"));
+    }
+
+    // PDF/A with the default (CMYK) output intent doesn't allow DeviceRGB either
+    let (doc, page, layer) = PdfDocument::new("repair", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer = doc.get_page(page).get_layer(layer);
+    layer.set_fill_color(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None)));
+    doc.repair_errors(PdfConformance::A1B_2005_PDF_1_4).unwrap();
+    {
+        let inner = doc.document.borrow();
+        let operators: Vec<_> = inner.pages[0].layers[0].operations.iter().map(|op| op.operator.as_str()).collect();
+        assert!(operators.contains(&"k"));
+        assert!(!operators.contains(&"rg"));
+        assert_eq!(inner.default_colorspace, Some(ColorSpace::Cmyk));
+    }
+
+    // RGB images can't be converted
+    {
+        use {ImageXObject, ColorBits, Px, XObject};
+        let image = ImageXObject::new(Px(1), Px(1), ColorSpace::Rgb, ColorBits::Bit8, false, None, None, vec![255, 0, 0]);
+        doc.document.borrow_mut().pages[0].add_xobject(XObject::Image(image));
+    }
+    match doc.repair_errors(PdfConformance::X3_2002_PDF_1_3) {
+        Err(Error::Pdf(PdfError::DeviceColorNotAllowed)) => { },
+        other => panic!("expected DeviceColorNotAllowed, got {:?}", other),
+    }
+
+    doc.add_builtin_font(BuiltinFont::Courier).unwrap();
+    match doc.repair_errors(PdfConformance::A1B_2005_PDF_1_4) {
+        Err(Error::Pdf(PdfError::FontNotEmbedded)) => { },
+        other => panic!("expected FontNotEmbedded, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_get_layer() {
+    let (doc, page, layer) = PdfDocument::new("layers", Mm(210.0), Mm(297.0), "Layer 1");
+    let background = doc.get_page(page).add_layer("Background");
+    let (first, second) = (doc.get_layer(page, layer), doc.get_layer(page, background.layer));
+    first.set_outline_thickness(1.0);
+    second.set_outline_thickness(2.0);
+    first.set_outline_thickness(3.0);
+
+    let inner = doc.document.borrow();
+    assert_eq!(inner.pages[0].layers[0].operations.len(), 2);
+    assert_eq!(inner.pages[0].layers[1].operations.len(), 1);
+}
+
+#[test]
+fn test_remove_and_move_page() {
+    let (doc, first, _) = PdfDocument::new("pages", Mm(210.0), Mm(297.0), "Layer 1");
+    let (second, _) = doc.add_page(Mm(100.0), Mm(100.0), "Layer 1");
+    let (third, _) = doc.add_page(Mm(50.0), Mm(50.0), "Layer 1");
+    doc.add_bookmark("First", first);
+    doc.add_bookmark("Third", third);
+
+    doc.move_page(first, 2).unwrap();
+    {
+        let inner = doc.document.borrow();
+        assert_eq!(inner.pages.iter().map(|page| page.index).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(inner.pages[2].width, Mm(210.0).into());
+        assert_eq!(inner.bookmarks.iter().map(|bookmark| bookmark.page).collect::<Vec<_>>(), vec![2, 1]);
+    }
+
+    let removed = doc.remove_page(second).unwrap();
+    assert_eq!(removed.width, Mm(50.0).into());
+    let inner = doc.document.borrow();
+    assert_eq!(inner.pages.len(), 2);
+    assert_eq!(inner.bookmarks.len(), 1);
+    assert_eq!(inner.bookmarks[0].page, 1);
+    drop(inner);
+
+    assert!(doc.remove_page(PdfPageIndex(2)).is_err());
+    assert!(doc.move_page(PdfPageIndex(0), 2).is_err());
+}
+
+#[test]
+fn test_subset_fonts() {
+    let save = |subset_fonts: bool| {
+        let (doc, page, layer) = PdfDocument::new("subset", Mm(210.0), Mm(297.0), "Layer 1");
+        let font = doc.add_external_font(::std::fs::File::open("assets/fonts/RobotoMedium.ttf").unwrap()).unwrap();
+        doc.get_layer(page, layer).use_text("Hello World", 12.0, Mm(10.0), Mm(10.0), &font);
+        let mut bytes = Vec::new();
+        let options = SaveOptions { subset_fonts, .. Default::default() };
+        doc.save_with_options(&mut BufWriter::new(&mut bytes), options).unwrap();
+        bytes
+    };
+
+    // size of the embedded font program
+    let font_length = |bytes: Vec<u8>| {
+        let pdf = ::std::string::String::from_utf8_lossy(&bytes).into_owned();
+        let start = pdf.find("/Length1 ").unwrap() + "/Length1 ".len();
+        let length = pdf[start..].split(|c: char| !c.is_ascii_digit()).next().unwrap().parse::<usize>().unwrap();
+        (pdf, length)
+    };
+
+    let (_, full_length) = font_length(save(false));
+    let (subset, subset_length) = font_length(save(true));
+    assert_eq!(full_length, 162588);
+    assert!(subset_length < 10_000);
+    assert!(subset.contains("/CIDToGIDMap"));
+    assert!(subset.contains("+F"));
+}
+
+#[test]
+fn test_page_labels() {
+    let (doc, first_page, _) = PdfDocument::new("labels", Mm(210.0), Mm(297.0), "Layer 1");
+    let (body, _) = doc.add_page(Mm(210.0), Mm(297.0), "Layer 1");
+    doc.set_page_label(first_page, PageLabelStyle::LowercaseRoman, None, 1).unwrap();
+    doc.set_page_label(body, PageLabelStyle::Decimal, Some("Page ".into()), 1).unwrap();
+    assert!(doc.set_page_label(PdfPageIndex(2), PageLabelStyle::Decimal, None, 1).is_err());
+
+    let mut bytes = Vec::new();
+    doc.save_with_options(&mut BufWriter::new(&mut bytes), SaveOptions { compress: false, .. Default::default() }).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/PageLabels<</Nums[0<</S/r>> 1<</S/D/P(Page )>>]>>"));
+
+    let (doc, first_page, _) = PdfDocument::new("labels", Mm(210.0), Mm(297.0), "Layer 1");
+    match doc.set_page_label(first_page, PageLabelStyle::Decimal, None, 0) {
+        Err(Error::Pdf(PdfError::InvalidPageLabelStart)) => { },
+        other => panic!("expected InvalidPageLabelStart, got {:?}", other),
+    }
+
+    // without labels, no /PageLabels are written
+    doc.set_page_label(first_page, PageLabelStyle::UppercaseRoman, None, 1).unwrap();
+    doc.remove_page_label(first_page).unwrap();
+    assert!(doc.remove_page_label(PdfPageIndex(1)).is_err());
+    let bytes = doc.save_to_bytes().unwrap();
+    assert!(!::std::string::String::from_utf8_lossy(&bytes).contains("/PageLabels"));
+}
+
+#[test]
+fn test_encryption() {
+    use {Encryption, CustomPdfConformance};
+
+    let save = |conformance: PdfConformance| {
+        let (doc, page, layer) = PdfDocument::new("Confidential report", Mm(210.0), Mm(297.0), "Layer 1");
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        doc.get_layer(page, layer).use_text("Secret text", 12.0, Mm(10.0), Mm(10.0), &font);
+        let mut bytes = Vec::new();
+        let options = SaveOptions { encryption: Some(Encryption::new("user", "owner")), compress: false, .. Default::default() };
+        let result = doc.with_conformance(conformance).save_with_options(&mut BufWriter::new(&mut bytes), options);
+        result.map(|_| bytes)
+    };
+
+    match save(PdfConformance::A2B_2011_PDF_1_7) {
+        Err(Error::Pdf(PdfError::EncryptionNotAllowed)) => { },
+        other => panic!("expected EncryptionNotAllowed, got {:?}", other.map(|bytes| bytes.len())),
+    }
+
+    let bytes = save(PdfConformance::Custom(CustomPdfConformance::default())).unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.starts_with("%PDF-1.6"));
+    assert!(pdf.contains("/Encrypt"));
+    assert!(pdf.contains("/CFM/AESV2"));
+    assert!(!pdf.contains("Confidential report"));
+    // builtin fonts write the text as hex string
+    assert!(!pdf.contains("5365637265742074657874"));
+}
+
+#[test]
+fn test_initial_zoom() {
+    let (doc, _, _) = PdfDocument::new("zoom", Mm(210.0), Mm(297.0), "Layer 1");
+    let bytes = doc.save_to_bytes().unwrap();
+    assert!(!::std::string::String::from_utf8_lossy(&bytes).contains("/OpenAction"));
+
+    let (doc, _, _) = PdfDocument::new("zoom", Mm(210.0), Mm(297.0), "Layer 1");
+    let bytes = doc.with_initial_zoom(150.0).save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    // the first page at 150%, the position is left to the viewer
+    let open_action = &pdf[pdf.find("/OpenAction[").unwrap()..];
+    let open_action = &open_action[..open_action.find(']').unwrap() + 1];
+    assert!(open_action.ends_with("R/XYZ null null 1.50]"), "{}", open_action);
+}
+
+#[test]
+fn test_add_pages() {
+    let (doc, _, _) = PdfDocument::new("pages", Mm(210.0), Mm(297.0), "Layer 1");
+    let pages = doc.add_pages(vec![(Mm(148.0), Mm(210.0), "A5"), (Mm(105.0), Mm(148.0), "A6")]);
+    assert_eq!(pages, vec![(PdfPageIndex(1), PdfLayerIndex(0)), (PdfPageIndex(2), PdfLayerIndex(0))]);
+    doc.add_pages(vec![(Pt(100.0), Pt(50.0), "Points")]);
+
+    {
+        let document = doc.document.borrow();
+        let layer_names = document.pages.iter().map(|page| page.layers[0].name.clone()).collect::<Vec<_>>();
+        assert_eq!(layer_names, vec!["Layer 1", "A5", "A6", "Points"]);
+    }
+
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert_eq!(pdf.matches("/Type/Page/").count(), 4);
+    assert!(pdf.contains("/Count 4"));
+    assert!(pdf.contains("/MediaBox[0 0 100.00 50.00]"));
+}
+
+#[test]
+fn test_non_full_screen_page_mode() {
+    let (doc, _, _) = PdfDocument::new("presentation", Mm(297.0), Mm(210.0), "Layer 1");
+    let doc = doc.with_non_full_screen_page_mode(NonFullScreenPageMode::UseThumbs);
+    match doc.check_for_errors() {
+        Err(Error::Pdf(PdfError::NonFullScreenPageModeWithoutFullScreen)) => { },
+        other => panic!("expected NonFullScreenPageModeWithoutFullScreen, got {:?}", other),
+    }
+
+    let bytes = doc.with_page_mode(PageMode::FullScreen).save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/PageMode/FullScreen"));
+    assert!(pdf.contains("/ViewerPreferences<</NonFullScreenPageMode/UseThumbs>>"));
+}
+
+#[test]
+fn test_auto_advance() {
+    let (doc, _, _) = PdfDocument::new("slides", Mm(297.0), Mm(210.0), "Layer 1");
+    let (second, _) = doc.add_page(Mm(297.0), Mm(210.0), "Layer 1");
+    let (third, _) = doc.add_page(Mm(297.0), Mm(210.0), "Layer 1");
+    doc.get_page(second).set_duration(10.0);
+    doc.get_page(third).set_duration(0.0);
+    let bytes = doc.with_auto_advance(Some(5.0)).save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+
+    // the first page uses the document-wide duration, the third page doesn't advance
+    let durations = pdf.split("/Type/Page/").skip(1)
+        .map(|page| page.split(">>").next().unwrap())
+        .map(|page| page.find("/Dur ").map(|dur| page[dur + 5..].split(|c| c == '/' || c == '>').next().unwrap().to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(durations, vec![Some("5.00".to_string()), Some("10.00".to_string()), None]);
+}
+
+#[test]
+fn test_clear_missing_glyphs() {
+    let (doc, page, layer) = PdfDocument::new("strict", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_missing_glyph_policy(MissingGlyphPolicy::Error);
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer = doc.get_page(page).get_layer(layer);
+    layer.use_text("a\u{4e16}b\u{2603}", 12.0, Mm(10.0), Mm(10.0), &font);
+    assert_eq!(doc.missing_glyphs(), vec!['\u{4e16}', '\u{2603}']);
+
+    doc.clear_missing_glyphs();
+    assert!(doc.missing_glyphs().is_empty());
+    assert!(doc.save_to_bytes().is_ok());
+}
+
+#[test]
+fn test_outline() {
+    let (doc, page, _) = PdfDocument::new("outline", Mm(210.0), Mm(297.0), "Layer 1");
+    let chapter = doc.add_bookmark("Kapitel 1", page);
+    doc.add_child_bookmark("Übersicht", page, chapter);
+
+    {
+        let doc = doc.document.borrow();
+        let outline = doc.outline();
+        assert_eq!(outline.len(), 2);
+        assert_eq!((outline[1].title.as_str(), outline[1].page, outline[1].parent), ("Übersicht", 0, Some(0)));
+    }
+
+    // titles that are not ASCII are written as UTF-16BE text strings
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/Title<FEFF00DC0062"));
+    assert!(pdf.contains("/Title(Kapitel 1)"));
+}
@@ -5,11 +5,23 @@ use lopdf;
 use indices::{PdfPageIndex, PdfLayerIndex};
 use std::rc::Weak;
 use std::cell::RefCell;
+use std::iter::FromIterator;
 use lopdf::content::Operation;
-use glob_defines::OP_PATH_STATE_SET_LINE_WIDTH;
+use glob_defines::{
+    OP_PATH_STATE_SET_LINE_WIDTH, OP_PATH_STATE_SET_MITER_LIMIT, OP_PATH_CONST_CLIP_NZ, OP_PATH_CONST_CLIP_EO, OP_PATH_PAINT_END,
+};
+use types::plugins::graphics::two_dimensional::svg_export;
+use types::plugins::graphics::two_dimensional::svg_import::import_svg;
+use utils::{calculate_points_for_arc, calculate_points_for_star};
+use types::plugins::graphics::two_dimensional::text_layout::wrap_text;
+use types::plugins::misc::resource_names::ResourceKind;
+use types::plugins::interactive::link::LinkAnnotation;
+use types::plugins::graphics::two_dimensional::font::{SYNTHETIC_BOLD_STROKE_WIDTH, SYNTHETIC_ITALIC_ANGLE};
 use {
-    Font, XObject, PdfColor,  PdfDocument, ExtendedGraphicsStateBuilder, Line, ImageXObject, XObjectRef, Color, IndirectFontRef, BlendMode,
-    LineJoinStyle, LineCapStyle, LineDashPattern, CurTransMat, TextMatrix, TextRenderingMode, Mm, Pt
+    XObject, PdfColor,  PdfDocument, ExtendedGraphicsState, ExtendedGraphicsStateBuilder, Line, Path, ImageXObject, XObjectRef, Color, IndirectFontRef, BlendMode,
+    LineJoinStyle, LineCapStyle, LineDashPattern, DashPattern, CurTransMat, TextMatrix, TextRenderingMode, Mm, Pt,
+    Error, PdfError, OversizedImagePolicy, TextOptions, Point, StructureRole, DrawResult, Rect, ImageMaskXObject, MissingGlyphPolicy, FormXObject, Font, TextAlignment,
+    Gradient, Pattern
 };
 
 /// One layer of PDF data
@@ -18,7 +30,50 @@ pub struct PdfLayer {
     /// Name of the layer. Must be present for the optional content group
     pub(crate) name: String,
     /// Stream objects in this layer. Usually, one layer == one stream
-    pub(super) operations: Vec<Operation>,
+    pub(crate) operations: Vec<Operation>,
+    /// Number of currently open tagged content sections (see `begin_tagged_content`)
+    pub(crate) tagged_content_depth: usize,
+    /// Union of the bounding boxes of everything drawn on this layer (see `content_bounds`),
+    /// extended on every draw call
+    pub(crate) content_bounds: Option<Rect>,
+    /// Whether the optional content group of the layer is visible when the document is opened
+    pub(crate) visible: bool,
+    /// Graphics state after the last operation, see `add_operation`
+    pub(crate) graphics_state: TrackedGraphicsState,
+    /// Graphics states saved with `q`, restored with `Q`
+    pub(crate) saved_graphics_states: Vec<TrackedGraphicsState>,
+}
+
+/// Parts of the graphics state that the drawing functions depend on, tracked while
+/// operations are added to a layer (see `PdfLayer::add_operation`)
+#[derive(Debug, Clone)]
+pub(crate) struct TrackedGraphicsState {
+    /// Operations that set the current fill color (`g`, `rg`, `k` or `cs` followed by
+    /// `sc` / `scn`), empty for the initial fill color (black)
+    pub(crate) fill_color: Vec<Operation>,
+    /// Word spacing (`Tw`), 0 initially
+    pub(crate) word_spacing: f64,
+    /// Line width (`w`), 1 initially
+    pub(crate) line_width: f64,
+}
+
+impl Default for TrackedGraphicsState {
+    fn default() -> Self {
+        Self {
+            fill_color: Vec::new(),
+            word_spacing: 0.0,
+            line_width: 1.0,
+        }
+    }
+}
+
+/// Margin of the page in which a change bar is drawn, see `PdfLayerReference::add_change_bar`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChangeBarSide {
+    /// Left margin of the page
+    Left,
+    /// Right margin of the page
+    Right,
 }
 
 /// A "reference" to the current layer, allows for inner mutability
@@ -43,8 +98,67 @@ impl PdfLayer {
         Self {
             name: name.into(),
             operations: Vec::new(),
+            tagged_content_depth: 0,
+            content_bounds: None,
+            visible: true,
+            graphics_state: TrackedGraphicsState::default(),
+            saved_graphics_states: Vec::new(),
         }
     }
+
+    /// Returns the bounding box of everything drawn on this layer (including the outline
+    /// thickness of stroked shapes), or `None` if nothing was drawn yet.
+    ///
+    /// Only content drawn through the drawing functions of `PdfLayerReference` (shapes, text,
+    /// images) is taken into account, operations added directly are not. Clipping paths
+    /// are ignored, so the visible content may be smaller.
+    #[inline]
+    pub fn content_bounds(&self)
+    -> Option<Rect>
+    {
+        self.content_bounds
+    }
+
+    /// Removes all content from this layer. The layer itself (and its position in
+    /// the page) stays the same, so it can be drawn again, e.g. after a measuring pass.
+    ///
+    /// Note: this doesn't touch the page resources or the structure tree entries of the
+    /// tagged content, see `PdfLayerReference::clear()`
+    #[inline]
+    pub fn clear(&mut self)
+    {
+        self.operations.clear();
+        self.tagged_content_depth = 0;
+        self.content_bounds = None;
+        self.graphics_state = TrackedGraphicsState::default();
+        self.saved_graphics_states.clear();
+    }
+
+    /// Adds an operation to the end of the layer and updates the tracked graphics state
+    pub(crate) fn add_operation(&mut self, op: Operation)
+    {
+        match op.operator.as_str() {
+            "q" => self.saved_graphics_states.push(self.graphics_state.clone()),
+            "Q" => if let Some(state) = self.saved_graphics_states.pop() {
+                self.graphics_state = state;
+            },
+            "g" | "rg" | "k" | "cs" => self.graphics_state.fill_color = vec![op.clone()],
+            "sc" | "scn" => {
+                // the color space stays the same, only the color changes
+                let fill_color = &mut self.graphics_state.fill_color;
+                fill_color.retain(|color| color.operator == "cs");
+                fill_color.push(op.clone());
+            },
+            "Tw" => if let Some(spacing) = operand_number(op.operands.first()) {
+                self.graphics_state.word_spacing = spacing;
+            },
+            OP_PATH_STATE_SET_LINE_WIDTH => if let Some(width) = operand_number(op.operands.first()) {
+                self.graphics_state.line_width = width;
+            },
+            _ => { },
+        }
+        self.operations.push(op);
+    }
 }
 
 impl Into<lopdf::Stream> for PdfLayer {
@@ -63,38 +177,308 @@ impl PdfLayerReference {
 
     /// Add a shape to the layer. Use `closed` to indicate whether the line is a closed line
     /// Use has_fill to determine if the line should be filled.
+    ///
+    /// If the document is tagged, the shape is marked as an artifact (decorative content).
     pub fn add_shape(&self, line: Line)
+    -> DrawResult
     {
-        let line_ops = line.into_stream_op();
-        for op in line_ops {
+        let bbox = line.bbox().unwrap_or_else(|| Rect::new(Mm(0.0), Mm(0.0), Mm(0.0), Mm(0.0)));
+        let (has_fill, has_stroke) = (line.has_fill, line.has_stroke);
+        self.add_path_operations(bbox, has_fill, has_stroke, line.into_stream_op())
+    }
+
+    /// Add a path built from explicit segments (moves, lines and cubic bezier curves) to the
+    /// layer. Works like `add_shape`, but curves are always written with both control points.
+    pub fn add_path(&self, path: Path)
+    -> DrawResult
+    {
+        let bbox = path.bbox().unwrap_or_else(|| Rect::new(Mm(0.0), Mm(0.0), Mm(0.0), Mm(0.0)));
+        let (has_fill, has_stroke) = (path.has_fill, path.has_stroke);
+        self.add_path_operations(bbox, has_fill, has_stroke, path.into_stream_op())
+    }
+
+    /// Adds the operations of a shape or path and extends the content bounds by its `bbox`
+    fn add_path_operations(&self, bbox: Rect, has_fill: bool, has_stroke: bool, operations: Vec<Operation>)
+    -> DrawResult
+    {
+        // the stroke extends the shape by half of the line width on each side
+        if has_stroke {
+            let half_width: Mm = Pt(self.current_line_width() / 2.0).into();
+            self.extend_content_bounds(Rect::new(bbox.x - half_width, bbox.y - half_width,
+                                                 bbox.width + half_width * 2.0, bbox.height + half_width * 2.0));
+        } else if has_fill {
+            self.extend_content_bounds(bbox);
+        }
+
+        let tagged = self.begin_auto_tagged_content(None);
+        for op in operations {
             self.internal_add_operation(op);
         }
+        if tagged { self.end_tagged_content(); }
+        DrawResult { bbox }
+    }
+
+    /// Clips everything drawn in `draw` to the inside of the path (non-zero winding rule).
+    /// The clipping path is scoped with `save_graphics_state` / `restore_graphics_state`, so
+    /// it (and any other graphics state changed in `draw`) is reset afterwards. The fill and
+    /// stroke settings of the path are ignored.
+    ///
+    /// ```rust,ignore
+    /// layer.with_clip(Line::rectangle(Mm(10.0), Mm(10.0), Mm(50.0), Mm(30.0)), |layer| {
+    ///     image.add_to_layer(layer.clone(), ImageTransform::default());
+    /// });
+    /// ```
+    pub fn with_clip<P, F>(&self, path: P, draw: F)
+    where P: Into<Path>, F: FnOnce(&PdfLayerReference)
+    {
+        self.clip_and_draw(path.into(), OP_PATH_CONST_CLIP_NZ, draw);
+    }
+
+    /// Same as `with_clip`, but uses the even-odd rule to determine the inside of the path,
+    /// so that e.g. a path with an inner subpath clips to a ring
+    pub fn with_clip_even_odd<P, F>(&self, path: P, draw: F)
+    where P: Into<Path>, F: FnOnce(&PdfLayerReference)
+    {
+        self.clip_and_draw(path.into(), OP_PATH_CONST_CLIP_EO, draw);
+    }
+
+    /// Sets the path as clipping path (with the `W` or `W*` operator) inside of `q` / `Q`
+    fn clip_and_draw<F>(&self, path: Path, clip_operator: &str, draw: F)
+    where F: FnOnce(&PdfLayerReference)
+    {
+        self.save_graphics_state();
+        if !path.segments.is_empty() {
+            for op in path.segment_operations() {
+                self.internal_add_operation(op);
+            }
+            self.internal_add_operation(Operation::new(clip_operator, Vec::new()));
+            self.internal_add_operation(Operation::new(OP_PATH_PAINT_END, Vec::new()));
+        }
+        draw(self);
+        self.restore_graphics_state();
+    }
+
+    /// Adds an arc around the `center` to the layer, going counter-clockwise from `start_deg`
+    /// to `end_deg` (in degrees, starting at the positive x axis). Angles wrap around at 360°.
+    /// Use `outline` to draw the arc with the current outline color and thickness.
+    pub fn add_arc(&self, center: Point, radius: Mm, start_deg: f64, end_deg: f64, outline: bool)
+    -> DrawResult
+    {
+        let radius: Pt = radius.into();
+        let points = calculate_points_for_arc(radius, center.x, center.y, start_deg, end_deg);
+
+        self.add_shape(Line {
+            points,
+            is_closed: false,
+            has_fill: false,
+            has_stroke: outline,
+            is_clipping_path: false,
+        })
+    }
+
+    /// Adds a pie slice (an arc connected to its `center`) to the layer, for example for pie charts.
+    /// See `add_arc` for the angles. Use `fill` / `outline` to fill the slice with the current
+    /// fill color or draw it with the current outline color.
+    pub fn add_pie_slice(&self, center: Point, radius: Mm, start_deg: f64, end_deg: f64, fill: bool, outline: bool)
+    -> DrawResult
+    {
+        let radius: Pt = radius.into();
+        let mut points = vec![(center, false)];
+        points.append(&mut calculate_points_for_arc(radius, center.x, center.y, start_deg, end_deg));
+
+        self.add_shape(Line {
+            points,
+            is_closed: true,
+            has_fill: fill,
+            has_stroke: outline,
+            is_clipping_path: false,
+        })
+    }
+
+    /// Adds a regular polygon with `sides` corners on a circle with the `radius` around the `center`.
+    /// The first corner is at the top, rotated counter-clockwise by `rotation_deg` (in degrees).
+    /// Use `fill` / `outline` to fill the polygon with the current fill color or draw it with
+    /// the current outline color.
+    pub fn add_regular_polygon(&self, center: Point, radius: Mm, sides: usize, rotation_deg: f64, fill: bool, outline: bool)
+    -> DrawResult
+    {
+        let radius: Pt = radius.into();
+        self.add_shape(Line {
+            points: calculate_points_for_star(radius, None, center.x, center.y, sides.max(3), rotation_deg),
+            is_closed: true,
+            has_fill: fill,
+            has_stroke: outline,
+            is_clipping_path: false,
+        })
+    }
+
+    /// Adds a star with `points` points around the `center`, whose corners alternate between
+    /// `outer_radius` and `inner_radius`. The first point is at the top, rotated counter-clockwise
+    /// by `rotation_deg` (in degrees). See `add_regular_polygon` for `fill` / `outline`.
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    pub fn add_star(&self, center: Point, outer_radius: Mm, inner_radius: Mm, points: usize, rotation_deg: f64, fill: bool, outline: bool)
+    -> DrawResult
+    {
+        let (outer_radius, inner_radius): (Pt, Pt) = (outer_radius.into(), inner_radius.into());
+        self.add_shape(Line {
+            points: calculate_points_for_star(outer_radius, Some(inner_radius), center.x, center.y, points.max(2), rotation_deg),
+            is_closed: true,
+            has_fill: fill,
+            has_stroke: outline,
+            is_clipping_path: false,
+        })
+    }
+
+    /// Adds a change bar (a vertical rule in the page margin, used to mark revised content)
+    /// from `y_top` to `y_bottom`. The distance of the bar to the page edge scales with
+    /// the page width (4% of the width, between 5 and 12 mm). The bar is drawn with the
+    /// current outline color and thickness. The positions can be in any unit (`Mm`, `Pt` or `In`).
+    pub fn add_change_bar<T, B>(&self, y_top: T, y_bottom: B, side: ChangeBarSide)
+    -> DrawResult where T: Into<Pt>, B: Into<Pt>
+    {
+        let page_width: Mm = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            doc.pages[self.page.0].width.into()
+        };
+
+        let offset = (page_width.0 * 0.04).clamp(5.0, 12.0);
+        let x = match side {
+            ChangeBarSide::Left => Mm(offset),
+            ChangeBarSide::Right => Mm(page_width.0 - offset),
+        };
+
+        self.add_shape(Line {
+            points: vec![(Point::new(x, y_top), false), (Point::new(x, y_bottom), false)],
+            is_closed: false,
+            has_fill: false,
+            has_stroke: true,
+            is_clipping_path: false,
+        })
+    }
+
+    /// Adds a link to the page that opens the `uri` (e.g. a web address) when the area `rect`
+    /// is clicked. The link has no visible border, draw the link text or a frame separately.
+    ///
+    /// Links are annotations of the page, so they stay active if the layer is hidden.
+    pub fn add_link<S>(&self, rect: Rect, uri: S)
+    where S: Into<String>
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].links.push(LinkAnnotation { rect, uri: uri.into() });
     }
 
     /// Add an image to the layer
     /// To be called from the `image.add_to_layer()` class (see `use_xobject` documentation)
-    pub(crate) fn add_image<T>(&self, image: T)
-    -> XObjectRef where T: Into<ImageXObject>
+    ///
+    /// Checks the image against the maximum image dimension of the document and
+    /// either downsamples the image or returns an error, depending on the policy.
+    /// If `enforce_limit` is false, an image that can't be downsampled is added as it is.
+    pub(crate) fn add_image<T>(&self, image: T, enforce_limit: bool)
+    -> Result<XObjectRef, Error> where T: Into<ImageXObject>
     {
         let doc = self.document.upgrade().unwrap();
         let mut doc = doc.borrow_mut();
+        let mut image = image.into();
+        let max_dimension = doc.max_image_dimension;
+
+        if image.width.0 > max_dimension || image.height.0 > max_dimension {
+            let downsampled = match doc.oversized_image_policy {
+                OversizedImagePolicy::Error => None,
+                OversizedImagePolicy::Downsample => image.downsample(max_dimension),
+            };
+            image = match downsampled {
+                Some(downsampled) => downsampled,
+                None if enforce_limit => return Err(PdfError::ImageTooLarge.into()),
+                None => image,
+            };
+        }
+
+        let index = doc.allocate_resource_index(ResourceKind::XObject, Some(self.page));
         let page_mut = &mut doc.pages[self.page.0];
+        Ok(page_mut.resources.xobjects.add_xobject_with_index(index, XObject::Image(image)))
+    }
 
-        page_mut.add_xobject(XObject::Image(image.into()))
+    /// Paints the current color through a 1-bit stencil mask: the mask is stretched to
+    /// `width` / `height` with its lower left corner at `position` and the painted pixels
+    /// of the mask (see `ImageMaskXObject::invert`) are filled with `color`.
+    ///
+    /// Returns an error if the mask data doesn't contain all pixels or the mask is larger
+    /// than the maximum image dimension of the document (masks can't be downsampled).
+    pub fn add_image_mask(&self, mask: ImageMaskXObject, position: Point, width: Mm, height: Mm, color: Color)
+    -> Result<DrawResult, Error>
+    {
+        if !mask.is_valid() {
+            return Err(PdfError::InvalidImageMask.into());
+        }
+
+        let mask_ref = {
+            let doc = self.document.upgrade().unwrap();
+            let mut doc = doc.borrow_mut();
+            if mask.width.0 > doc.max_image_dimension || mask.height.0 > doc.max_image_dimension {
+                return Err(PdfError::ImageTooLarge.into());
+            }
+            let index = doc.allocate_resource_index(ResourceKind::XObject, Some(self.page));
+            doc.pages[self.page.0].resources.xobjects.add_xobject_with_index(index, XObject::ImageMask(mask))
+        };
+
+        let (width_pt, height_pt): (Pt, Pt) = (width.into(), height.into());
+        let tagged = self.begin_auto_tagged_content(Some(StructureRole::Figure));
+        self.save_graphics_state();
+        self.set_fill_color(color);
+        // the mask is drawn into the unit square, like an image
+        self.internal_add_operation(Operation::new("cm", vec![
+            width_pt.into(), 0.into(), 0.into(), height_pt.into(), position.x.into(), position.y.into(),
+        ]));
+        self.internal_invoke_xobject(mask_ref.name);
+        self.restore_graphics_state();
+        if tagged { self.end_tagged_content(); }
+
+        let bbox = Rect::new(position.x.into(), position.y.into(), width, height);
+        self.extend_content_bounds(bbox);
+        Ok(DrawResult { bbox })
     }
-/*
-    /// Add an svg element to the layer
-    /// To be called from the `svg.add_to_layer()` class (see `use_xobject` documentation)
-    pub(crate) fn add_svg(&self, svg: Svg)
-    -> std::result::Result<XObjectRef, ::std::io::Error>
+
+    /// Draws an SVG image, stretched to `width` / `height` with its lower left corner at
+    /// `position`. The image is added to the page as a form XObject. Only the basic shapes
+    /// and paths of SVG are supported: text, gradients, clipping and CSS stylesheets are
+    /// ignored (see `PdfDocumentReference::add_page_from_svg`).
+    ///
+    /// Returns an error if the SVG can't be parsed or has no size.
+    pub fn add_svg<P>(&self, svg_data: &str, position: Point, width: P, height: P)
+    -> Result<DrawResult, Error> where P: Into<Pt>
     {
-        let doc = self.document.upgrade().unwrap();
-        let mut doc = doc.borrow_mut();
-        let page_mut = &mut doc.pages[self.page.0];
-        let form_data = svg.try_into()?;
-        Ok(page_mut.add_xobject(XObject::Form(Box::new(form_data))))
+        let svg = import_svg(svg_data)?;
+        let (svg_width, svg_height) = (svg.width.0, svg.height.0);
+        if svg_width <= 0.0 || svg_height <= 0.0 {
+            return Err(PdfError::InvalidSvg.into());
+        }
+
+        let form_ref = {
+            let doc = self.document.upgrade().unwrap();
+            let mut doc = doc.borrow_mut();
+            let form = FormXObject::from_operations((0.0, 0.0, svg_width, svg_height), svg.operations);
+            let index = doc.allocate_resource_index(ResourceKind::XObject, Some(self.page));
+            doc.pages[self.page.0].resources.xobjects.add_xobject_with_index(index, XObject::Form(Box::new(form)))
+        };
+
+        let (width_pt, height_pt): (Pt, Pt) = (width.into(), height.into());
+        let tagged = self.begin_auto_tagged_content(Some(StructureRole::Figure));
+        self.save_graphics_state();
+        self.internal_add_operation(Operation::new("cm", vec![
+            (width_pt.0 / svg_width).into(), 0.into(), 0.into(), (height_pt.0 / svg_height).into(),
+            position.x.into(), position.y.into(),
+        ]));
+        self.internal_invoke_xobject(form_ref.name);
+        self.restore_graphics_state();
+        if tagged { self.end_tagged_content(); }
+
+        let bbox = Rect::new(position.x.into(), position.y.into(), width_pt.into(), height_pt.into());
+        self.extend_content_bounds(bbox);
+        Ok(DrawResult { bbox })
     }
-*/
+
     /// Begins a new text section
     /// You have to make sure to call `end_text_section` afterwards
     #[inline]
@@ -118,6 +502,7 @@ impl PdfLayerReference {
     pub fn set_fill_color(&self, fill_color: Color)
     -> ()
     {
+        let fill_color = self.convert_to_default_colorspace(fill_color);
         self.internal_add_operation(PdfColor::FillColor(fill_color));
     }
 
@@ -136,8 +521,38 @@ impl PdfLayerReference {
     #[inline]
     pub fn set_outline_color(&self, color: Color)
     {
+        let color = self.convert_to_default_colorspace(color);
         self.internal_add_operation(PdfColor::OutlineColor(color));
     }
+
+    /// Set a gradient as the current fill color for the layer (as shading pattern). The
+    /// coordinates of the gradient are page coordinates: the gradient doesn't move with
+    /// the shapes that are filled with it. Reset with `set_fill_color`.
+    pub fn set_fill_gradient(&self, gradient: Gradient)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        let pattern = Pattern::from_gradient(gradient, doc.default_colorspace);
+        let index = doc.allocate_resource_index(ResourceKind::Pattern, Some(self.page));
+        let page_mut = &mut doc.pages[self.page.0];
+        let pattern_ref = page_mut.resources.patterns.add_pattern_with_index(index, pattern);
+
+        let layer = &mut page_mut.layers[self.layer.0];
+        layer.add_operation(Operation::new("cs", vec![lopdf::Object::Name(b"Pattern".to_vec())]));
+        layer.add_operation(Operation::new("scn", vec![lopdf::Object::Name(pattern_ref.name.as_bytes().to_vec())]));
+    }
+
+    /// Converts the color into the default color space of the document, if there is one
+    fn convert_to_default_colorspace(&self, color: Color)
+    -> Color
+    {
+        let doc = self.document.upgrade().unwrap();
+        let default_colorspace = doc.borrow().default_colorspace;
+        match default_colorspace {
+            Some(color_space) => color.convert_to(color_space),
+            None => color,
+        }
+    }
     /// Instantiate layers, forms and postscript items on the page
     /// __WARNING__: Object must be added to the same page, since the XObjectRef is just a
     /// String, essentially, it can't be checked that this is the case. The caller is
@@ -185,29 +600,99 @@ impl PdfLayerReference {
 
         // restore graphics state
         self.restore_graphics_state();
+
+        // XObjects are drawn into the unit square, which is scaled, then rotated, then translated
+        let scale = if s_x != 0.0 || s_y != 0.0 { CurTransMat::Scale(s_x, s_y) } else { CurTransMat::Identity };
+        let matrices: [[f64; 6]; 3] = [
+            scale.into(),
+            CurTransMat::Rotate(rotate_cw.unwrap_or(0.0)).into(),
+            CurTransMat::Translate(t_x, t_y).into(),
+        ];
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].iter().map(|&corner| {
+            matrices.iter().fold(corner, |(x, y), m| (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]))
+        }).collect::<Vec<_>>();
+        let min_x = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+        let min_y = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+        let max_x = corners.iter().map(|c| c.0).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = corners.iter().map(|c| c.1).fold(f64::NEG_INFINITY, f64::max);
+        self.extend_content_bounds(Rect::from_extents(Pt(min_x), Pt(min_y), Pt(max_x), Pt(max_y)));
     }
 
-    /// Set the overprint mode of the stroke color to true (overprint) or false (no overprint)
-    pub fn set_overprint_fill(&self, overprint: bool)
+    /// Returns the bounding box of everything drawn on this layer so far,
+    /// see `PdfLayer::content_bounds`
+    pub fn content_bounds(&self)
+    -> Option<Rect>
     {
-        let new_overprint_state = ExtendedGraphicsStateBuilder::new()
-                                      .with_overprint_fill(overprint)
-                                      .build();
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.pages[self.page.0].layers[self.layer.0].content_bounds()
+    }
 
+    /// Sets whether the layer is initially visible when the document is opened in a viewer
+    /// (default: visible). Hidden layers can be turned on in the layers panel of the viewer.
+    pub fn set_visible(&self, visible: bool)
+    {
         let doc = self.document.upgrade().unwrap();
         let mut doc = doc.borrow_mut();
-        let page_mut = &mut doc.pages[self.page.0];
+        doc.pages[self.page.0].layers[self.layer.0].visible = visible;
+    }
 
-        let new_ref = page_mut.add_graphics_state(new_overprint_state);
+    /// Adds the bounding box of newly drawn content to the content bounds of the layer
+    fn extend_content_bounds(&self, bbox: Rect)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        let layer = &mut doc.pages[self.page.0].layers[self.layer.0];
+        layer.content_bounds = Some(match layer.content_bounds {
+            Some(bounds) => bounds.union(&bbox),
+            None => bbox,
+        });
+    }
 
-        // add gs operator to stream
-        page_mut.layers[self.layer.0]
-            .operations.push(lopdf::content::Operation::new(
-                "gs", vec![lopdf::Object::Name(new_ref.gs_name.as_bytes().to_vec())]
-        ));
+    /// Returns the current line width of this layer
+    fn current_line_width(&self)
+    -> f64
+    {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.pages[self.page.0].layers[self.layer.0].graphics_state.line_width
+    }
+
+    /// Returns the font size set by the last `Tf` operation on this layer
+    fn current_font_size(&self)
+    -> Option<f64>
+    {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.pages[self.page.0].layers[self.layer.0].operations.iter().rev()
+            .find(|op| op.operator == "Tf")
+            .and_then(|op| match op.operands.get(1) {
+                Some(&lopdf::Object::Real(size)) => Some(size),
+                Some(&lopdf::Object::Integer(size)) => Some(size as f64),
+                _ => None,
+            })
+    }
+
+    /// Returns the current word spacing of this layer
+    fn current_word_spacing(&self)
+    -> f64
+    {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.pages[self.page.0].layers[self.layer.0].graphics_state.word_spacing
     }
 
     /// Set the overprint mode of the fill color to true (overprint) or false (no overprint)
+    pub fn set_overprint_fill(&self, overprint: bool)
+    {
+        let new_overprint_state = ExtendedGraphicsStateBuilder::new()
+                                      .with_overprint_fill(overprint)
+                                      .build();
+
+        self.internal_add_graphics_state(new_overprint_state);
+    }
+
+    /// Set the overprint mode of the stroke color to true (overprint) or false (no overprint)
     /// This changes the graphics state of the current page, don't do it too often or you'll bloat the file size
     pub fn set_overprint_stroke(&self, overprint: bool)
     {
@@ -216,19 +701,13 @@ impl PdfLayerReference {
                                       .with_overprint_stroke(overprint)
                                       .build();
 
-        let doc = self.document.upgrade().unwrap();
-        let mut doc = doc.borrow_mut();
-        let page_mut = &mut doc.pages[self.page.0];
-
-        let new_ref = page_mut.add_graphics_state(new_overprint_state);
-        page_mut.layers[self.layer.0]
-            .operations.push(lopdf::content::Operation::new(
-                "gs", vec![lopdf::Object::Name(new_ref.gs_name.as_bytes().to_vec())]
-        ));
+        self.internal_add_graphics_state(new_overprint_state);
     }
 
-    /// Set the overprint mode of the fill color to true (overprint) or false (no overprint)
-    /// This changes the graphics state of the current page, don't do it too often or you'll bloat the file size
+    /// Set the blend mode (`/BM`), which determines how the following content is composited
+    /// with the content below it. For example, `SeperableBlendMode::Multiply` tints the
+    /// content below instead of covering it. Transparency is not allowed in PDF/A-1 and
+    /// PDF/X-1a / PDF/X-3, see `check_for_errors`.
     pub fn set_blend_mode(&self, blend_mode: BlendMode)
     {
         // this is technically an operation on the page level
@@ -236,16 +715,54 @@ impl PdfLayerReference {
                                       .with_blend_mode(blend_mode)
                                       .build();
 
-        let doc = self.document.upgrade().unwrap();
-        let mut doc = doc.borrow_mut();
-        let page_mut = &mut doc.pages[self.page.0];
+        self.internal_add_graphics_state(new_blend_mode_state);
+    }
+
+    /// Set the constant alpha (opacity) of fills, from 0.0 (invisible) to 1.0 (opaque).
+    /// Transparency is not allowed in PDF/A-1 and PDF/X-1a / PDF/X-3, see `check_for_errors`.
+    pub fn set_fill_alpha(&self, alpha: f64)
+    {
+        let new_alpha_state = ExtendedGraphicsStateBuilder::new()
+                                  .with_current_fill_alpha(alpha.clamp(0.0, 1.0))
+                                  .build();
 
-        let new_ref = page_mut.add_graphics_state(new_blend_mode_state);
+        self.internal_add_graphics_state(new_alpha_state);
+    }
 
-        page_mut.layers[self.layer.0]
-            .operations.push(lopdf::content::Operation::new(
-                "gs", vec![lopdf::Object::Name(new_ref.gs_name.as_bytes().to_vec())]
-        ));
+    /// Set the constant alpha (opacity) of outlines, from 0.0 (invisible) to 1.0 (opaque)
+    pub fn set_outline_alpha(&self, alpha: f64)
+    {
+        let new_alpha_state = ExtendedGraphicsStateBuilder::new()
+                                  .with_current_stroke_alpha(alpha.clamp(0.0, 1.0))
+                                  .build();
+
+        self.internal_add_graphics_state(new_alpha_state);
+    }
+
+    /// Set the constant alpha (opacity) of both fills and outlines, in one graphics state
+    pub fn set_alpha(&self, alpha: f64)
+    {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let new_alpha_state = ExtendedGraphicsStateBuilder::new()
+                                  .with_current_fill_alpha(alpha)
+                                  .with_current_stroke_alpha(alpha)
+                                  .build();
+
+        self.internal_add_graphics_state(new_alpha_state);
+    }
+
+    /// Set a graphics state built with `ExtendedGraphicsStateBuilder`, e.g. to combine a
+    /// blend mode with alpha values in one `/ExtGState`:
+    ///
+    /// ```rust,ignore
+    /// layer.set_graphics_state(ExtendedGraphicsStateBuilder::new()
+    ///     .with_blend_mode(BlendMode::Seperable(SeperableBlendMode::Multiply))
+    ///     .with_current_fill_alpha(0.5)
+    ///     .build());
+    /// ```
+    pub fn set_graphics_state(&self, graphics_state: ExtendedGraphicsState)
+    {
+        self.internal_add_graphics_state(graphics_state);
     }
 
     /// Set the current line thickness, in points
@@ -265,21 +782,41 @@ impl PdfLayerReference {
         self.internal_add_operation(line_join);
     }
 
-    /// Set the current line join style for outlines
+    /// Set the current line cap style for outlines
     #[inline]
     pub fn set_line_cap_style(&self, line_cap: LineCapStyle) {
         self.internal_add_operation(line_cap);
     }
 
-    /// Set the current line join style for outlines
+    /// Set the miter limit for outlines with `LineJoinStyle::Miter` (`M` operator): corners
+    /// whose miter would be longer than `miter_limit` times the line width are beveled,
+    /// which avoids spikes at sharp corners. The PDF default is 10, values below 1 are
+    /// raised to 1.
+    #[inline]
+    pub fn set_miter_limit(&self, miter_limit: f64) {
+        use lopdf::Object::*;
+        self.internal_add_operation(Operation::new(OP_PATH_STATE_SET_MITER_LIMIT, vec![Real(miter_limit.max(1.0))]));
+    }
+
+    /// Set the current line dash pattern for outlines (`d` operator).
+    /// `LineDashPattern::default()` (or `DashPattern::Solid`) resets it to a solid line.
     #[inline]
     pub fn set_line_dash_pattern(&self, dash_pattern: LineDashPattern) {
         self.internal_add_operation(dash_pattern);
     }
 
+    /// Set one of the common line dash patterns (dotted, dashed, ...) for outlines.
+    /// `DashPattern::Solid` resets the dash pattern.
+    #[inline]
+    pub fn set_line_dash_preset(&self, preset: DashPattern) {
+        self.set_line_dash_pattern(preset.into());
+    }
+
     /// Sets (adds to) the current transformation matrix
     /// Use `save_graphics_state()` and `restore_graphics_state()`
-    /// to "scope" the transformation matrix to a specific function
+    /// to "scope" the transformation matrix to a specific function.
+    /// Several calls apply in order (translate, then rotate rotates around the translated
+    /// origin), see `CurTransMat::then` to combine them into one matrix.
     #[inline]
     pub fn set_ctm(&self, ctm: CurTransMat) {
         self.internal_add_operation(ctm);
@@ -335,12 +872,11 @@ impl PdfLayerReference {
 
     /// Sets the word spacing inside a text block.
     /// Same as `set_character_spacing`, just for words.
-    /// __Note:__ This currently does not work for external
-    /// fonts. External fonts are encoded with Unicode, and
-    /// PDF does not recognize unicode fonts. It only
-    /// recognizes builtin fonts done with PDFDoc encoding.
-    /// However, the function itself is valid and _will work_
-    /// with builtin fonts.
+    /// __Note:__ PDF only applies the word spacing to single-byte
+    /// spaces, so it has no effect on external fonts (which use
+    /// 2-byte glyph IDs). For external fonts, `write_text` adds the
+    /// spacing after every space itself. Text written with
+    /// `write_codepoints` doesn't get the word spacing.
     #[inline]
     pub fn set_word_spacing(&self, spacing: f64) {
         self.internal_add_operation(Operation::new("Tw",
@@ -371,6 +907,13 @@ impl PdfLayerReference {
         ));
     }
 
+    /// Sets the text rendering mode for the following text. For the clipping modes,
+    /// the text adds to the clipping path when the text section ends, so the content drawn
+    /// after `end_text_section` is clipped to the glyphs (see `TextRenderingMode`).
+    ///
+    /// The mode stays active for all following text (also in later text sections) until it is
+    /// changed again or the graphics state is restored. To write a single line of text in a
+    /// mode, e.g. invisible text over a scanned image, use `TextOptions::rendering_mode`.
     #[inline]
     pub fn set_text_rendering_mode(&self, mode: TextRenderingMode) {
         self.internal_add_operation(Operation::new("Tr",
@@ -430,8 +973,9 @@ impl PdfLayerReference {
 
     /// Add text to the file at the current position
     ///
-    /// If the given font is a built-in font and the given text contains characters that are not
-    /// supported by the [Windows-1252][] encoding, these characters will be ignored.
+    /// Characters that the font has no glyph for are handled according to the
+    /// `MissingGlyphPolicy` of the document (by default, they are ignored). For built-in fonts,
+    /// this applies to all characters that are not supported by the [Windows-1252][] encoding.
     ///
     /// [Windows-1252]: https://en.wikipedia.org/wiki/Windows-1252
     #[inline]
@@ -446,57 +990,121 @@ impl PdfLayerReference {
 
         let text = text.into();
 
+        // switching to a fallback font needs the size the text is written in
+        let font_size = self.current_font_size();
+        let word_spacing = self.current_word_spacing();
+
         // we need to transform the characters into glyph ids and then add them to the layer
         let doc = self.document.upgrade().unwrap();
         let mut doc = doc.borrow_mut();
 
-        // glyph IDs that make up this string
-
-        // kerning for each glyph id. If no kerning is present, will be 0
-        // must be the same length as list_gid
-        // let mut kerning_data = Vec::<freetype::Vector>::new();
+        let primary = doc.fonts.get_font(font).unwrap().data;
+        let fallback = match doc.missing_glyph_policy {
+            MissingGlyphPolicy::Fallback(ref fallback) => {
+                doc.fonts.get_font(fallback).and_then(|f| font_size.map(|size| (fallback.clone(), f.data, size)))
+            },
+            _ => None,
+        };
+        let policy = doc.missing_glyph_policy.clone();
 
-        let bytes: Vec<u8> = {
-            if let Font::ExternalFont(face_direct_ref) = doc.fonts.get_font(font).unwrap().data {
+        // runs of encoded characters: `false` for the font of the text, `true` for the fallback font
+        let mut runs = Vec::<(bool, Vec<u8>)>::new();
+        let mut push = |is_fallback: bool, bytes: Vec<u8>| match runs.last_mut() {
+            Some(run) if run.0 == is_fallback => run.1.extend(bytes),
+            _ => runs.push((is_fallback, bytes)),
+        };
 
-                let mut list_gid = Vec::<u16>::new();
-                let font = &face_direct_ref.font_data;
+        for ch in text.chars() {
+            if let Some(bytes) = primary.encode_char(ch) {
+                push(false, bytes);
+                continue;
+            }
 
-                for ch in text.chars() {
-                    if let Some(glyph_id) = font.glyph_id(ch) {
-                        list_gid.push(glyph_id);
-                    }
+            let substitute = match policy {
+                MissingGlyphPolicy::Skip => None,
+                MissingGlyphPolicy::Notdef => primary.encode_notdef().map(|bytes| (false, bytes)),
+                MissingGlyphPolicy::Fallback(_) => fallback.as_ref()
+                    .and_then(|(_, fallback_font, _)| fallback_font.encode_char(ch))
+                    .map(|bytes| (true, bytes)),
+                MissingGlyphPolicy::Error => { doc.missing_glyphs.push(ch); None },
+            };
+
+            #[cfg(feature = "logging")] {
+                match substitute {
+                    Some(_) => warn!("No glyph for {:?} in font {}, character was substituted", ch, font.name),
+                    None => warn!("No glyph for {:?} in font {}, character was left out", ch, font.name),
                 }
+            }
 
-                list_gid.iter()
-                    .flat_map(|x| vec!((x >> 8) as u8, (x & 255) as u8))
-                    .collect::<Vec<u8>>()
-            } else {
-                // For built-in fonts, we selected the WinAnsiEncoding, see the Into<LoDictionary>
-                // implementation for BuiltinFont.
-                lopdf::Document::encode_text(Some("WinAnsiEncoding"), &text)
+            if let Some((is_fallback, bytes)) = substitute {
+                push(is_fallback, bytes);
             }
-        };
+        }
 
-        doc.pages[self.page.0]
-            .layers[self.layer.0]
-                .operations.push(Operation::new("Tj",
-                    vec![String(bytes, Hexadecimal)]
-        ));
+        if runs.is_empty() {
+            runs.push((false, Vec::new()));
+        }
+
+        let operations = &mut doc.pages[self.page.0].layers[self.layer.0].operations;
+        let mut in_fallback = false;
+        for (is_fallback, bytes) in runs {
+            if let Some((ref fallback_ref, _, font_size)) = fallback {
+                if is_fallback != in_fallback {
+                    let run_font = if is_fallback { fallback_ref } else { font };
+                    operations.push(Operation::new("Tf", vec![run_font.name.clone().into(), font_size.into()]));
+                    in_fallback = is_fallback;
+                }
+            }
+            let (run_font, run_font_size) = match fallback {
+                Some((_, ref fallback_font, size)) if is_fallback => (fallback_font, Some(size)),
+                _ => (&primary, font_size),
+            };
+            let space = run_font.encode_char(' ');
+            match (run_font, run_font_size, space) {
+                (&Font::ExternalFont(_), Some(size), Some(space)) if word_spacing != 0.0 && size != 0.0 => {
+                    operations.push(word_spaced_text(bytes, &space, word_spacing, size));
+                },
+                _ => operations.push(Operation::new("Tj", vec![String(bytes, Hexadecimal)])),
+            }
+        }
+
+        // switch back, so that following text is written with the font of this text again
+        if let (true, Some((_, _, font_size))) = (in_fallback, fallback) {
+            operations.push(Operation::new("Tf", vec![font.name.clone().into(), font_size.into()]));
+        }
     }
 
-    /// Saves the current graphic state
+    /// Saves the current graphic state. Everything that is changed until the matching
+    /// `restore_graphics_state` (colors, line styles, transformation matrix, clipping path,
+    /// extended graphics states) only affects the content drawn in between.
     #[inline]
     pub fn save_graphics_state(&self) {
         self.internal_add_operation(Operation::new("q", Vec::new()));
     }
 
-    /// Restores the previous graphic state
+    /// Restores the graphic state saved by the last `save_graphics_state`. Calls without a
+    /// matching `save_graphics_state` on this layer are ignored, since they would make the
+    /// content stream invalid.
     #[inline]
     pub fn restore_graphics_state(&self) {
+        if self.graphics_state_depth() == 0 {
+            #[cfg(feature = "logging")] {
+                warn!("restore_graphics_state called without a matching save_graphics_state, ignored");
+            }
+            return;
+        }
         self.internal_add_operation(Operation::new("Q", Vec::new()));
     }
 
+    /// Returns the number of graphics states saved on this layer that were not restored yet
+    fn graphics_state_depth(&self)
+    -> usize
+    {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.pages[self.page.0].layers[self.layer.0].saved_graphics_states.len()
+    }
+
     /// Add text to the file, x and y are measure in millimeter from the bottom left corner
     ///
     /// If the given font is a built-in font and the given text contains characters that are not
@@ -506,37 +1114,453 @@ impl PdfLayerReference {
     #[inline]
     pub fn use_text<S>(&self, text: S, font_size: f64,
                        x: Mm, y: Mm, font: &IndirectFontRef)
-    -> () where S: Into<String>
+    -> DrawResult where S: Into<String>
     {
+            let text = text.into();
+            let bbox = self.text_bbox(&text, font_size, x, y, font);
+            let tagged = self.begin_auto_tagged_content(Some(StructureRole::Paragraph));
             self.begin_text_section();
             self.set_font(font, font_size);
             self.set_text_cursor(x, y);
             self.write_text(text, font);
             self.end_text_section();
+            if tagged { self.end_tagged_content(); }
+            self.extend_content_bounds(bbox);
+            DrawResult { bbox }
+    }
+
+    /// Same as `use_text`, but takes the start of the baseline as a `Point` and returns the
+    /// point where the text ends (advanced by the measured width of the text). Pass it to the
+    /// next call to continue the line, e.g. with another font or color.
+    ///
+    /// Character / word spacing and text scaling are not taken into account when measuring.
+    pub fn add_text_at<S>(&self, text: S, font_size: f64, position: Point, font: &IndirectFontRef)
+    -> Point where S: Into<String>
+    {
+        let text = text.into();
+        let width = self.text_width(&text, font, font_size);
+        self.use_text(text, font_size, position.x.into(), position.y.into(), font);
+        Point { x: position.x + width, y: position.y }
+    }
+
+    /// Begins a section of tagged content with the given role. The content is added
+    /// to the structure tree of the document (which makes the document accessible).
+    /// You have to make sure to call `end_tagged_content` afterwards.
+    ///
+    /// While a tagged content section is open, text, images and shapes are not tagged
+    /// automatically (see `PdfDocumentReference::with_tagged`), so this can be used to
+    /// override the inferred roles.
+    pub fn begin_tagged_content(&self, role: StructureRole)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        let page = &mut doc.pages[self.page.0];
+
+        let mcid = page.structure_roles.len();
+        let tag = Operation::new("BDC", vec![
+            lopdf::Object::Name(role.as_str().into()),
+            lopdf::Object::Dictionary(lopdf::Dictionary::from_iter(vec![("MCID", lopdf::Object::Integer(mcid as i64))])),
+        ]);
+
+        page.structure_roles.push(role);
+        let layer = &mut page.layers[self.layer.0];
+        layer.operations.push(tag);
+        layer.tagged_content_depth += 1;
+    }
+
+    /// Begins a section of content that is not part of the structure tree, because it is only
+    /// decorative (such as page numbers, lines or backgrounds). You have to make sure
+    /// to call `end_tagged_content` afterwards.
+    pub fn begin_artifact(&self)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        let layer = &mut doc.pages[self.page.0].layers[self.layer.0];
+        layer.operations.push(Operation::new("BMC", vec![lopdf::Object::Name("Artifact".into())]));
+        layer.tagged_content_depth += 1;
+    }
+
+    /// Ends a section started with `begin_tagged_content` or `begin_artifact`
+    pub fn end_tagged_content(&self)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        let layer = &mut doc.pages[self.page.0].layers[self.layer.0];
+        layer.operations.push(Operation::new("EMC", vec![]));
+        layer.tagged_content_depth = layer.tagged_content_depth.saturating_sub(1);
+    }
+
+    /// If the document is tagged and no tagged content is currently open, begins tagged
+    /// content with the given role (or an artifact for `None`) and returns true.
+    /// The caller has to call `end_tagged_content` if this returns true.
+    pub(crate) fn begin_auto_tagged_content(&self, role: Option<StructureRole>)
+    -> bool
+    {
+        let should_tag = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            doc.tagged && doc.pages[self.page.0].layers[self.layer.0].tagged_content_depth == 0
+        };
+
+        if should_tag {
+            match role {
+                Some(role) => self.begin_tagged_content(role),
+                None => self.begin_artifact(),
+            }
+        }
+
+        should_tag
+    }
+
+    /// Same as `use_text`, but additionally draws the decorations (underline,
+    /// strikethrough) and uses the rendering mode given in the `options`
+    ///
+    /// The decorations are drawn with the current fill color, using the underline metrics
+    /// of the font. Their length is the measured width of the text, so character / word spacing
+    /// and text scaling (which are not taken into account when measuring) should not be used.
+    #[inline]
+    pub fn use_text_with_options<S>(&self, text: S, font_size: f64,
+                                    x: Mm, y: Mm, font: &IndirectFontRef, options: TextOptions)
+    -> DrawResult where S: Into<String>
+    {
+        let text = text.into();
+
+        #[cfg(feature = "logging")] {
+            if let Some(mode) = options.rendering_mode {
+                if !mode.is_visible() && (options.underline || options.strikethrough) {
+                    warn!("Text rendering mode {:?} does not paint the text, but the decorations are still drawn", mode);
+                }
+            }
+        }
+
+        let result = if options.synthetic_bold || options.synthetic_italic {
+            self.use_synthetic_text(&text, font_size, x, y, font, options)
+        } else if let Some(mode) = options.rendering_mode {
+            // the clipping path is set when the text section ends and isn't affected by the reset
+            self.set_text_rendering_mode(mode);
+            let result = self.use_text(text.clone(), font_size, x, y, font);
+            self.set_text_rendering_mode(TextRenderingMode::Fill);
+            result
+        } else {
+            self.use_text(text.clone(), font_size, x, y, font)
+        };
+        self.add_text_decorations(&text, font_size, x, y, font, options);
+        result
+    }
+
+    /// Same as `use_text`, but emboldens / slants the text (see `TextOptions::synthetic_bold`)
+    fn use_synthetic_text(&self, text: &str, font_size: f64, x: Mm, y: Mm, font: &IndirectFontRef, options: TextOptions)
+    -> DrawResult
+    {
+        let (width, (ascent, descent)) = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            let font = doc.fonts.get_font(font).unwrap().data;
+            (font.text_width(text, font_size), font.vertical_metrics())
+        };
+
+        let slant = if options.synthetic_italic { SYNTHETIC_ITALIC_ANGLE.to_radians().tan() } else { 0.0 };
+        let stroke_width = if options.synthetic_bold { SYNTHETIC_BOLD_STROKE_WIDTH * font_size } else { 0.0 };
+
+        // the slant moves the top of the glyphs to the right and the descenders to the left,
+        // the stroke grows the outlines by half of the stroke width
+        let x_pt: Pt = x.into();
+        let y_pt: Pt = y.into();
+        let bbox = Rect::from_extents(
+            Pt(x_pt.0 + slant * descent * font_size - stroke_width / 2.0),
+            Pt(y_pt.0 + descent * font_size - stroke_width / 2.0),
+            Pt(x_pt.0 + width.0 + slant * ascent * font_size + stroke_width / 2.0),
+            Pt(y_pt.0 + ascent * font_size + stroke_width / 2.0),
+        );
+
+        let tagged = self.begin_auto_tagged_content(Some(StructureRole::Paragraph));
+        self.save_graphics_state();
+
+        if options.synthetic_bold {
+            // stroke the outlines in the current fill color
+            let fill_color = self.current_fill_color_operations();
+            if fill_color.is_empty() {
+                self.internal_add_operation(Operation::new("G", vec![lopdf::Object::Integer(0)]));
+            }
+            for op in fill_color {
+                self.internal_add_operation(Operation::new(&op.operator.to_uppercase(), op.operands));
+            }
+            self.set_outline_thickness(stroke_width);
+            self.set_line_join_style(LineJoinStyle::Round);
+        }
+
+        self.begin_text_section();
+        self.set_font(font, font_size);
+
+        if options.synthetic_bold {
+            self.set_text_rendering_mode(TextRenderingMode::FillStroke);
+        }
+
+        if options.synthetic_italic {
+            self.internal_add_operation(Operation::new("Tm", vec![
+                1.into(), 0.into(), slant.into(), 1.into(), x_pt.into(), y_pt.into(),
+            ]));
+        } else {
+            self.set_text_cursor(x, y);
+        }
+
+        self.write_text(text.to_string(), font);
+        self.end_text_section();
+        self.restore_graphics_state();
+        if tagged { self.end_tagged_content(); }
+
+        self.extend_content_bounds(bbox);
+        DrawResult { bbox }
     }
 
-/*
-    /// Instantiate SVG data
+    /// Returns the operations that set the current fill color of this layer (empty for black)
+    fn current_fill_color_operations(&self)
+    -> Vec<Operation>
+    {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.pages[self.page.0].layers[self.layer.0].graphics_state.fill_color.clone()
+    }
+
+    /// Flows the text into `columns` columns inside of the `region`, separated by `gutter`
+    /// (in any unit).
+    /// The text is wrapped at whitespace and filled top to bottom, left to right, with the
+    /// recommended leading of the font (see `Font::recommended_leading`) as the line height.
+    /// Line breaks in the text are kept.
+    ///
+    /// Returns the text that didn't fit into the region (empty if everything fit),
+    /// for example to continue it on the next page.
     #[inline]
-    pub fn use_svg(&self, width_mm: f64, height_mm: f64,
-                   x_mm: f64, y_mm: f64, svg_data_index: SvgIndex)
+    pub fn add_multicolumn_text<S, G>(&self, text: S, font: &IndirectFontRef, font_size: f64,
+                                      region: Rect, columns: usize, gutter: G)
+    -> String where S: Into<String>, G: Into<Pt>
+    {
+        self.add_multicolumn_text_with_line_height(text, font, font_size, region, columns, gutter, None)
+    }
+
+    /// Same as `add_multicolumn_text`, but with the given line height. If no `line_height`
+    /// is given, the recommended leading of the font is used.
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    pub fn add_multicolumn_text_with_line_height<S, G>(&self, text: S, font: &IndirectFontRef, font_size: f64,
+                                                       region: Rect, columns: usize, gutter: G, line_height: Option<Pt>)
+    -> String where S: Into<String>, G: Into<Pt>
+    {
+        let text = text.into();
+        let gutter: Pt = gutter.into();
+        let columns = columns.max(1);
+        let ((ascent, descent), recommended_leading) = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            let font = doc.fonts.get_font(font).unwrap().data;
+            (font.vertical_metrics(), font.recommended_leading(font_size))
+        };
+
+        let region_width: Pt = region.width.into();
+        let column_width = Pt((region_width.0 - gutter.0 * (columns - 1) as f64) / columns as f64);
+        let lines = wrap_text(&text, column_width, |line| self.text_width(line, font, font_size));
+
+        // the first baseline is placed so that the ascent touches the top of the region,
+        // the last line must not go below the bottom of the region with its descent
+        let region_height: Pt = region.height.into();
+        let line_height = line_height.unwrap_or(recommended_leading).0;
+        let first_line_height = (ascent - descent) * font_size;
+        let lines_per_column = if region_height.0 < first_line_height {
+            0
+        } else {
+            ((region_height.0 - first_line_height) / line_height).floor() as usize + 1
+        };
+
+        let top: Pt = (region.y + region.height).into();
+
+        for (index, line) in lines.iter().enumerate() {
+            let column = index.checked_div(lines_per_column).unwrap_or(columns);
+            if column >= columns {
+                return text[line.start..].to_string();
+            }
+
+            if line.text.is_empty() {
+                continue;
+            }
+
+            let row = index % lines_per_column;
+            let x = region.x + Pt((column_width.0 + gutter.0) * column as f64).into();
+            let y = Pt(top.0 - ascent * font_size - row as f64 * line_height);
+            self.use_text(line.text.clone(), font_size, x, y.into(), font);
+        }
+
+        String::new()
+    }
+
+    /// Writes the text as a paragraph that is at most `max_width` (in any unit) wide, with the top left corner
+    /// at `position`. The text is wrapped at whitespace (runs of whitespace are written as a
+    /// single space), line breaks in the text are kept. If no `line_height` is given, the
+    /// recommended leading of the font is used (see `Font::recommended_leading`).
+    ///
+    /// Returns the box of the paragraph (`max_width` wide), its height is the height the
+    /// paragraph takes up, for example to place the next paragraph below it.
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    #[inline]
+    pub fn add_paragraph<S, W>(&self, text: S, font: &IndirectFontRef, font_size: f64,
+                               position: Point, max_width: W, line_height: Option<Pt>)
+    -> DrawResult where S: Into<String>, W: Into<Pt>
+    {
+        self.add_aligned_paragraph(text, font, font_size, position, max_width, line_height, TextAlignment::Left)
+    }
+
+    /// Same as `add_paragraph`, but aligns the lines inside of the paragraph (see `TextAlignment`).
+    /// Justified lines are stretched with the word spacing, which is reset afterwards.
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    pub fn add_aligned_paragraph<S, W>(&self, text: S, font: &IndirectFontRef, font_size: f64,
+                                       position: Point, max_width: W, line_height: Option<Pt>, alignment: TextAlignment)
+    -> DrawResult where S: Into<String>, W: Into<Pt>
+    {
+        let text = text.into();
+        let ((ascent, descent), recommended_leading) = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            let font = doc.fonts.get_font(font).unwrap().data;
+            (font.vertical_metrics(), font.recommended_leading(font_size))
+        };
+
+        let max_width_pt: Pt = max_width.into();
+        let lines = wrap_text(&text, max_width_pt, |line| self.text_width(line, font, font_size));
+        let line_height = line_height.unwrap_or(recommended_leading).0;
+        let initial_word_spacing = self.current_word_spacing();
+        let mut word_spacing = initial_word_spacing;
+
+        // the ascent of the first line touches the top, the descent of the last line the bottom
+        let first_baseline = position.y.0 - ascent * font_size;
+        for (row, line) in lines.iter().enumerate().filter(|(_, line)| !line.text.is_empty()) {
+            let free_space = max_width_pt.0 - self.text_width(&line.text, font, font_size).0;
+            let spaces = line.text.matches(' ').count();
+            let is_last_of_paragraph = lines.get(row + 1).map_or(true, |next| text[line.start..next.start].contains('\n'));
+
+            let (offset, line_spacing) = match alignment {
+                TextAlignment::Left => (0.0, 0.0),
+                TextAlignment::Right => (free_space, 0.0),
+                TextAlignment::Center => (free_space / 2.0, 0.0),
+                TextAlignment::Justify if spaces > 0 && !is_last_of_paragraph => (0.0, free_space.max(0.0) / spaces as f64),
+                TextAlignment::Justify => (0.0, 0.0),
+            };
+
+            if alignment == TextAlignment::Justify && line_spacing != word_spacing {
+                self.set_word_spacing(line_spacing);
+                word_spacing = line_spacing;
+            }
+
+            let x = Pt(position.x.0 + offset);
+            let y = Pt(first_baseline - row as f64 * line_height);
+            self.use_text(line.text.clone(), font_size, x.into(), y.into(), font);
+        }
+
+        if word_spacing != initial_word_spacing {
+            self.set_word_spacing(initial_word_spacing);
+        }
+
+        let height = Pt((ascent - descent) * font_size + lines.len().saturating_sub(1) as f64 * line_height);
+        let bbox = Rect::new(position.x.into(), Pt(position.y.0 - height.0).into(), max_width_pt.into(), height.into());
+        DrawResult { bbox }
+    }
+
+    /// Returns the width of the text (in points), if it was written with the font in the given size
+    pub(crate) fn text_width(&self, text: &str, font: &IndirectFontRef, font_size: f64)
+    -> Pt
     {
-        let svg_element_ref = {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        doc.fonts.get_font(font).unwrap().data.text_width(text, font_size)
+    }
+
+    /// Returns the bounding box of a line of text that starts at `x` / `y` (the baseline),
+    /// from the descent to the ascent of the font
+    pub(crate) fn text_bbox(&self, text: &str, font_size: f64, x: Mm, y: Mm, font: &IndirectFontRef)
+    -> Rect
+    {
+        let (width, (ascent, descent)) = {
             let doc = self.document.upgrade().unwrap();
-            let doc = doc.borrow_mut();
-            let element = doc.contents.get((svg_data_index.0).0).expect("invalid svg reference");
-            (*element).clone()
+            let doc = doc.borrow();
+            let font = doc.fonts.get_font(font).unwrap().data;
+            (font.text_width(text, font_size), font.vertical_metrics())
         };
 
+        let x: Pt = x.into();
+        let y: Pt = y.into();
+        Rect::from_extents(x, Pt(y.0 + descent * font_size), x + width, Pt(y.0 + ascent * font_size))
+    }
+
+    /// Draws the decorations for a line of text that starts at `x` / `y` (the baseline).
+    /// If the text is aligned, `x` has to include the alignment offset.
+    pub(crate) fn add_text_decorations(&self, text: &str, font_size: f64,
+                                       x: Mm, y: Mm, font: &IndirectFontRef, options: TextOptions)
+    {
+        if !options.underline && !options.strikethrough {
+            return;
+        }
+
+        let (width, metrics) = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            let font = doc.fonts.get_font(font).unwrap().data;
+            (font.text_width(text, font_size), font.text_decoration_metrics())
+        };
+
+        let mut decorations = Vec::new();
+        if options.underline {
+            decorations.push((metrics.underline_position, metrics.underline_thickness));
+        }
+        if options.strikethrough {
+            decorations.push((metrics.strikeout_position, metrics.strikeout_thickness));
+        }
+
+        let x: Pt = x.into();
+        let y: Pt = y.into();
+
+        for (position, thickness) in decorations {
+            // the position is the center of the line
+            let bottom = y.0 + (position - thickness / 2.0) * font_size;
+            let top = bottom + thickness * font_size;
+            let points = vec![(x.0, bottom), (x.0 + width.0, bottom), (x.0 + width.0, top), (x.0, top)];
+
+            self.add_shape(Line {
+                points: points.into_iter().map(|(x, y)| (Point { x: Pt(x), y: Pt(y) }, false)).collect(),
+                is_closed: true,
+                has_fill: true,
+                has_stroke: false,
+                is_clipping_path: false,
+            });
+        }
+    }
+
+    /// Removes all content from this layer, so it can be drawn again (for example in a
+    /// two-pass layout, where the first pass measures and the second pass draws).
+    ///
+    /// Images and graphics states that were added to the page and are not used by any other
+    /// layer on the page are removed as well, and so is the tagged content of the layer
+    /// in the structure tree. Fonts are shared across the whole document and are not affected.
+    pub fn clear(&self)
+    {
         let doc = self.document.upgrade().unwrap();
         let mut doc = doc.borrow_mut();
+        let page = &mut doc.pages[self.page.0];
+        page.clear_layer(self.layer.0);
+        page.remove_unused_resources();
+    }
 
-        // todo: what about width / height?
-        doc.pages.get_mut(self.page.0).unwrap()
-            .layers.get_mut(self.layer.0).unwrap()
-                .layer.push(PdfResource::ReferencedResource(svg_data_index.0.clone()));
+    /// Exports the content of this layer as an SVG string, for debugging purposes
+    ///
+    /// Only the operators that printpdf emits (paths, colors, line styles, transformation
+    /// matrices and simple text) are translated. Images, marked content and graphics state
+    /// dictionaries are written as XML comments. Consecutive `Tj` operators are not advanced
+    /// by the width of the previous text, so text written without a cursor change will overlap.
+    pub fn to_svg(&self)
+    -> String
+    {
+        let doc = self.document.upgrade().unwrap();
+        let doc = doc.borrow();
+        let page = &doc.pages[self.page.0];
+        let layer = &page.layers[self.layer.0];
+        svg_export::operations_to_svg(&layer.operations, page.width, page.height, &doc.fonts)
     }
-*/
 
     // internal function to invoke an xobject
     fn internal_invoke_xobject(&self, name: String)
@@ -551,6 +1575,29 @@ impl PdfLayerReference {
         ));
     }
 
+    // internal function to add a graphics state to the page and set it with the `gs` operator.
+    // A graphics state that is already on the page is reused instead of being added again.
+    fn internal_add_graphics_state(&self, state: ExtendedGraphicsState)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        let existing_name = doc.pages[self.page.0].resources.graphics_states.all_graphics_states.iter()
+            .find(|(_, (_, existing))| *existing == state)
+            .map(|(name, _)| name.clone());
+        let gs_name = match existing_name {
+            Some(name) => name,
+            None => {
+                let index = doc.allocate_resource_index(ResourceKind::ExtGState, Some(self.page));
+                doc.pages[self.page.0].resources.graphics_states.add_graphics_state_with_index(index, state).gs_name
+            },
+        };
+
+        doc.pages[self.page.0].layers[self.layer.0]
+            .operations.push(Operation::new(
+                "gs", vec![lopdf::Object::Name(gs_name.as_bytes().to_vec())]
+        ));
+    }
+
     // internal function to add an operation (prevents locking)
     fn internal_add_operation<T>(&self, op: T)
     -> () where T: Into<Operation>
@@ -558,6 +1605,670 @@ impl PdfLayerReference {
         let doc = self.document.upgrade().unwrap();
         let mut doc = doc.borrow_mut();
         let layer = &mut doc.pages[self.page.0].layers[self.layer.0];
-        layer.operations.push(op.into());
+        layer.add_operation(op.into());
+    }
+}
+
+/// Returns the value of a number operand
+fn operand_number(operand: Option<&lopdf::Object>)
+-> Option<f64>
+{
+    match operand {
+        Some(&lopdf::Object::Real(value)) => Some(value),
+        Some(&lopdf::Object::Integer(value)) => Some(value as f64),
+        _ => None,
+    }
+}
+
+/// Writes the text of an external font as `TJ` operation with an adjustment after every space,
+/// because PDF only applies the word spacing (`Tw`) to single-byte spaces
+fn word_spaced_text(bytes: Vec<u8>, space: &[u8], word_spacing: f64, font_size: f64)
+-> Operation
+{
+    use lopdf::Object::*;
+    use lopdf::StringFormat::Hexadecimal;
+
+    // adjustments are in thousandths of the font size and move the next glyph to the left
+    let adjustment = -word_spacing * 1000.0 / font_size;
+    let mut items = Vec::new();
+    let mut current = Vec::new();
+    for glyph in bytes.chunks(2) {
+        current.extend_from_slice(glyph);
+        if glyph == space {
+            items.push(String(::std::mem::take(&mut current), Hexadecimal));
+            items.push(Real(adjustment));
+        }
+    }
+    if !current.is_empty() || items.is_empty() {
+        items.push(String(current, Hexadecimal));
+    }
+    Operation::new("TJ", vec![Array(items)])
+}
+
+#[test]
+fn test_use_text_operations() {
+    use {PdfDocument, BuiltinFont};
+
+    let (doc, page, layer) = PdfDocument::new("text", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    doc.get_page(page).get_layer(layer).use_text("a(b)\\", 12.0, Mm(10.0), Mm(20.0), &font);
+
+    let document = doc.document.borrow();
+    let operations = &document.pages[page.0].layers[layer.0].operations;
+    let operators = operations.iter().map(|op| op.operator.as_str()).collect::<Vec<_>>();
+    assert_eq!(operators, vec!["BT", "Tf", "Td", "Tj", "ET"]);
+    assert_eq!(operations[1].operands[0].as_name_str().ok(), Some(font.name.as_str()));
+    // the text is written as a hex string, so parentheses and backslashes need no escaping
+    match operations[3].operands[0] {
+        lopdf::Object::String(ref bytes, lopdf::StringFormat::Hexadecimal) => assert_eq!(bytes, b"a(b)\\"),
+        ref other => panic!("expected a hex string, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_use_text_unicode() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("unicode", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_external_font(::std::fs::File::open("assets/fonts/RobotoMedium.ttf").unwrap()).unwrap();
+    let text = "Grüße Ωμέγα Привет";
+    doc.get_page(page).get_layer(layer).use_text(text, 12.0, Mm(10.0), Mm(20.0), &font);
+
+    // external fonts are Type0 fonts, every character is written as a 2-byte glyph ID
+    let document = doc.document.borrow();
+    let operations = &document.pages[page.0].layers[layer.0].operations;
+    let tj = operations.iter().find(|op| op.operator == "Tj").unwrap();
+    match tj.operands[0] {
+        lopdf::Object::String(ref bytes, _) => {
+            assert_eq!(bytes.len(), text.chars().count() * 2);
+            assert!(bytes.chunks(2).all(|gid| gid != [0, 0]));
+        },
+        ref other => panic!("expected a string, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_invisible_text_is_scoped() {
+    use {PdfDocument, BuiltinFont};
+
+    let (doc, page, layer) = PdfDocument::new("ocr", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let options = TextOptions { rendering_mode: Some(TextRenderingMode::Invisible), .. Default::default() };
+    layer_ref.use_text_with_options("scanned", 12.0, Mm(10.0), Mm(20.0), &font, options);
+    layer_ref.use_text("visible", 12.0, Mm(10.0), Mm(10.0), &font);
+
+    let document = doc.document.borrow();
+    let modes = document.pages[page.0].layers[layer.0].operations.iter()
+        .filter(|op| op.operator == "Tr")
+        .map(|op| op.operands[0].as_i64().unwrap())
+        .collect::<Vec<_>>();
+    // invisible for the first text, reset to fill before the second text
+    assert_eq!(modes, vec![3, 0]);
+}
+
+#[test]
+fn test_synthetic_bold_stroke_color() {
+    use {PdfDocument, BuiltinFont, GradientStop, Greyscale, Rgb};
+
+    let (doc, page, layer) = PdfDocument::new("synthetic", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let options = TextOptions { synthetic_bold: true, .. Default::default() };
+    let stroke_colors = || {
+        let document = doc.document.borrow();
+        document.pages[page.0].layers[layer.0].operations.iter()
+            .filter(|op| matches!(op.operator.as_str(), "G" | "RG" | "K" | "CS" | "SCN"))
+            .map(|op| op.operator.clone())
+            .collect::<Vec<_>>()
+    };
+
+    // the color set inside of the saved state is not the current color anymore
+    layer_ref.set_fill_color(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None)));
+    layer_ref.save_graphics_state();
+    layer_ref.set_fill_color(Color::Greyscale(Greyscale::new(0.5, None)));
+    layer_ref.restore_graphics_state();
+    layer_ref.use_text_with_options("bold", 12.0, Mm(10.0), Mm(20.0), &font, options);
+    assert_eq!(stroke_colors(), vec!["RG"]);
+
+    layer_ref.set_fill_gradient(Gradient::axial(Point::new(Mm(0.0), Mm(0.0)), Point::new(Mm(10.0), Mm(0.0)), vec![
+        GradientStop::new(0.0, Color::Rgb(Rgb::new(0.0, 0.0, 1.0, None))),
+    ]));
+    layer_ref.use_text_with_options("bold", 12.0, Mm(10.0), Mm(20.0), &font, options);
+    assert_eq!(stroke_colors(), vec!["RG", "CS", "SCN"]);
+}
+
+#[test]
+fn test_content_bounds() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("bounds", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let square = |x: f64| Line {
+        points: vec![
+            (Point::new(Mm(x), Mm(10.0)), false),
+            (Point::new(Mm(x + 10.0), Mm(10.0)), false),
+            (Point::new(Mm(x + 10.0), Mm(20.0)), false),
+        ],
+        is_closed: true,
+        has_fill: false,
+        has_stroke: true,
+        is_clipping_path: false,
+    };
+    let bounds = || doc.document.borrow().pages[page.0].layers[layer.0].content_bounds().unwrap();
+
+    // the line width set inside of the saved state doesn't apply to the square
+    layer_ref.set_outline_thickness(Pt::from(Mm(2.0)).0);
+    layer_ref.save_graphics_state();
+    layer_ref.set_outline_thickness(Pt::from(Mm(10.0)).0);
+    layer_ref.restore_graphics_state();
+    layer_ref.add_shape(square(10.0));
+
+    let first = bounds();
+    assert_eq!(first.x, Mm(9.0));
+    assert_eq!(first.y, Mm(9.0));
+    assert_eq!(first.width, Mm(12.0));
+
+    // the bounds grow with every shape
+    layer_ref.add_shape(square(50.0));
+    assert_eq!(bounds().width, Mm(52.0));
+    assert_eq!(bounds().height, Mm(12.0));
+}
+
+#[test]
+fn test_restore_graphics_state_underflow() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("graphics state", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.restore_graphics_state();
+    layer_ref.save_graphics_state();
+    layer_ref.save_graphics_state();
+    assert_eq!(layer_ref.graphics_state_depth(), 2);
+    layer_ref.restore_graphics_state();
+    layer_ref.restore_graphics_state();
+    layer_ref.restore_graphics_state();
+    assert_eq!(layer_ref.graphics_state_depth(), 0);
+
+    let document = doc.document.borrow();
+    let operators = document.pages[page.0].layers[layer.0].operations.iter()
+        .map(|op| op.operator.as_str()).collect::<Vec<_>>();
+    assert_eq!(operators, vec!["q", "q", "Q", "Q"]);
+}
+
+#[test]
+fn test_line_style_operations() {
+    use {PdfDocument, LineCapStyle, LineJoinStyle};
+
+    let (doc, page, layer) = PdfDocument::new("line style", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_line_cap_style(LineCapStyle::Round);
+    layer_ref.set_line_join_style(LineJoinStyle::Bevel);
+    layer_ref.set_miter_limit(0.5);
+
+    let document = doc.document.borrow();
+    let operations = &document.pages[page.0].layers[layer.0].operations;
+    let operators = operations.iter().map(|op| op.operator.as_str()).collect::<Vec<_>>();
+    assert_eq!(operators, vec!["J", "j", "M"]);
+    assert!(matches!(operations[0].operands[..], [lopdf::Object::Integer(1)]));
+    assert!(matches!(operations[1].operands[..], [lopdf::Object::Integer(2)]));
+    assert!(matches!(operations[2].operands[..], [lopdf::Object::Real(limit)] if limit == 1.0));
+}
+
+#[test]
+fn test_add_svg() {
+    use PdfDocument;
+
+    // 100 x 50 px = 75 x 37.5 pt
+    let svg = r#"<svg width="100" height="50" xmlns="http://www.w3.org/2000/svg">
+        <rect x="0" y="0" width="100" height="50" fill="red"/>
+    </svg>"#;
+    let (doc, page, layer) = PdfDocument::new("svg", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let result = layer_ref.add_svg(svg, Point::new(Mm(10.0), Mm(20.0)), Mm(50.0), Mm(25.0)).unwrap();
+    assert_eq!(result.bbox.width, Mm(50.0));
+    assert!(layer_ref.add_svg("<svg/>", Point::new(Mm(0.0), Mm(0.0)), Mm(1.0), Mm(1.0)).is_err());
+
+    // the size can be given in any unit
+    let result = layer_ref.add_svg(svg, Point::new(Mm(10.0), Mm(20.0)), Pt(150.0), Pt(75.0)).unwrap();
+    assert_eq!(Pt::from(result.bbox.height), Pt(75.0));
+
+    let document = doc.document.borrow();
+    let operators = document.pages[page.0].layers[layer.0].operations.iter()
+        .map(|op| op.operator.as_str()).collect::<Vec<_>>();
+    assert_eq!(operators, vec!["q", "cm", "Do", "Q", "q", "cm", "Do", "Q"]);
+    match document.pages[page.0].resources.xobjects.objects.values().next() {
+        Some(XObject::Form(ref form)) => assert_eq!(form.bbox(), (0.0, 0.0, 75.0, 37.5)),
+        other => panic!("expected a form XObject, got {:?}", other),
     }
 }
+
+#[test]
+fn test_word_spacing_external_font() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("spacing", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_external_font(::std::fs::File::open("assets/fonts/RobotoMedium.ttf").unwrap()).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_word_spacing(2.0);
+    // the word spacing set inside of the saved state is not the current word spacing anymore
+    layer_ref.save_graphics_state();
+    layer_ref.set_word_spacing(5.0);
+    layer_ref.restore_graphics_state();
+    layer_ref.begin_text_section();
+    layer_ref.set_font(&font, 10.0);
+    layer_ref.write_text("a b", &font);
+    layer_ref.end_text_section();
+
+    let document = doc.document.borrow();
+    let tj = document.pages[page.0].layers[layer.0].operations.iter().find(|op| op.operator == "TJ").unwrap();
+    let items = tj.operands[0].as_array().unwrap();
+    // "a " + adjustment + "b"
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[1].as_f64().ok(), Some(-200.0));
+}
+
+#[test]
+fn test_add_paragraph() {
+    use {PdfDocument, BuiltinFont};
+
+    let (doc, page, layer) = PdfDocument::new("paragraph", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let text = "The quick brown fox jumps over the lazy dog.\nSecond   paragraph";
+    let result = layer_ref.add_paragraph(text, &font, 10.0, Point::new(Mm(10.0), Mm(280.0)), Mm(40.0), Some(Pt(12.0)));
+
+    let document = doc.document.borrow();
+    let lines = document.pages[page.0].layers[layer.0].operations.iter()
+        .filter(|op| op.operator == "Tj")
+        .map(|op| match op.operands[0] {
+            lopdf::Object::String(ref bytes, _) => ::std::string::String::from_utf8_lossy(bytes).into_owned(),
+            _ => ::std::string::String::new(),
+        })
+        .collect::<Vec<_>>();
+    assert!(lines.len() >= 3);
+    assert_eq!(lines.last().map(|line| line.as_str()), Some("Second paragraph"));
+
+    // the top of the box is at the position, every line after the first adds the line height
+    let height: Pt = result.bbox.height.into();
+    let top: Pt = (result.bbox.y + result.bbox.height).into();
+    assert!((top.0 - Pt::from(Mm(280.0)).0).abs() < 0.001);
+    assert!(height.0 > (lines.len() - 1) as f64 * 12.0);
+    assert!(height.0 < lines.len() as f64 * 12.0);
+}
+
+#[test]
+fn test_aligned_paragraph() {
+    use {PdfDocument, BuiltinFont};
+
+    let (doc, page, layer) = PdfDocument::new("alignment", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let text = "aaa bbb ccc ddd eee fff\nlast line";
+    layer_ref.add_aligned_paragraph(text, &font, 10.0, Point::new(Mm(10.0), Mm(280.0)), Mm(30.0), None, TextAlignment::Justify);
+
+    let document = doc.document.borrow();
+    let spacings = document.pages[page.0].layers[layer.0].operations.iter()
+        .filter(|op| op.operator == "Tw")
+        .map(|op| op.operands[0].as_f64().unwrap())
+        .collect::<Vec<_>>();
+    // the wrapped lines are stretched, the last line of each paragraph is not
+    assert!(spacings.len() >= 2);
+    assert!(spacings[0] > 0.0);
+    assert_eq!(spacings.last(), Some(&0.0));
+}
+
+#[test]
+fn test_right_and_centered_paragraph() {
+    use {PdfDocument, BuiltinFont};
+
+    // returns the start and the width of each written line (in points)
+    let lines = |alignment: TextAlignment| {
+        let (doc, page, layer) = PdfDocument::new("alignment", Mm(210.0), Mm(297.0), "Layer 1");
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+        let layer_ref = doc.get_page(page).get_layer(layer);
+        layer_ref.add_aligned_paragraph("aaa bbb ccc ddd eee fff", &font, 10.0, Point::new(Mm(10.0), Mm(280.0)), Mm(30.0), None, alignment);
+
+        let document = doc.document.borrow();
+        let operations = &document.pages[page.0].layers[layer.0].operations;
+        let starts = operations.iter().filter(|op| op.operator == "Td").map(|op| match op.operands[0] {
+            lopdf::Object::Real(x) => x,
+            lopdf::Object::Integer(x) => x as f64,
+            _ => panic!("expected a number"),
+        });
+        let texts = operations.iter().filter(|op| op.operator == "Tj").map(|op| match op.operands[0] {
+            lopdf::Object::String(ref bytes, _) => ::std::string::String::from_utf8_lossy(bytes).into_owned(),
+            _ => panic!("expected a string"),
+        });
+        starts.zip(texts).map(|(x, text)| (x, document.fonts.get_font(&font).unwrap().data.text_width(&text, 10.0).0)).collect::<Vec<_>>()
+    };
+
+    let left = Pt::from(Mm(10.0)).0;
+    let right = Pt::from(Mm(40.0)).0;
+
+    let right_aligned = lines(TextAlignment::Right);
+    assert!(right_aligned.len() >= 2);
+    assert!(right_aligned.iter().all(|&(x, width)| (x + width - right).abs() < 0.01));
+
+    let centered = lines(TextAlignment::Center);
+    assert_eq!(centered.len(), right_aligned.len());
+    assert!(centered.iter().all(|&(x, width)| (x - left - (right - x - width)).abs() < 0.01));
+    assert!(centered.iter().all(|&(x, _)| x > left));
+    assert_eq!(TextAlignment::default(), TextAlignment::Left);
+}
+
+#[test]
+fn test_fill_gradient() {
+    use {PdfDocument, GradientStop, Rgb};
+
+    let (doc, page, layer) = PdfDocument::new("gradient", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_fill_gradient(Gradient::axial(Point::new(Mm(0.0), Mm(297.0)), Point::new(Mm(0.0), Mm(0.0)), vec![
+        GradientStop::new(0.0, Color::Rgb(Rgb::new(0.0, 0.0, 1.0, None))),
+        GradientStop::new(1.0, Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None))),
+    ]));
+
+    {
+        let document = doc.document.borrow();
+        let operations = &document.pages[page.0].layers[layer.0].operations;
+        assert_eq!(operations.iter().map(|op| op.operator.as_str()).collect::<Vec<_>>(), vec!["cs", "scn"]);
+        let name = operations[1].operands[0].as_name_str().unwrap();
+        assert!(document.pages[page.0].resources.patterns.contains(name));
+    }
+
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/PatternType 2"));
+    assert!(pdf.contains("/ShadingType 2"));
+}
+
+#[test]
+fn test_set_alpha() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("alpha", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_alpha(0.5);
+    layer_ref.set_fill_alpha(1.0);
+    layer_ref.set_alpha(0.5);
+
+    // the same graphics state is only added once
+    let document = doc.document.borrow();
+    let operations = &document.pages[page.0].layers[layer.0].operations;
+    assert_eq!(operations[0].operands[0].as_name_str().ok(), operations[2].operands[0].as_name_str().ok());
+    assert_eq!(document.pages[page.0].resources.graphics_states.all_graphics_states.len(), 2);
+
+    let name = operations[0].operands[0].as_name_str().unwrap();
+    let (_, state) = &document.pages[page.0].resources.graphics_states.all_graphics_states[name];
+    let state: lopdf::Object = state.clone().into();
+    let state = state.as_dict().unwrap();
+    assert_eq!(state.get(b"ca").and_then(|ca| ca.as_f64()).ok(), Some(0.5));
+    assert_eq!(state.get(b"CA").and_then(|ca| ca.as_f64()).ok(), Some(0.5));
+}
+
+#[test]
+fn test_blend_mode_with_alpha() {
+    use {PdfDocument, SeperableBlendMode};
+
+    let (doc, page, layer) = PdfDocument::new("blend mode", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_graphics_state(ExtendedGraphicsStateBuilder::new()
+        .with_blend_mode(BlendMode::Seperable(SeperableBlendMode::Multiply))
+        .with_current_fill_alpha(0.5)
+        .build());
+
+    let document = doc.document.borrow();
+    let graphics_states = &document.pages[page.0].resources.graphics_states.all_graphics_states;
+    assert_eq!(graphics_states.len(), 1);
+    let (_, state) = graphics_states.values().next().unwrap();
+    let state: lopdf::Object = state.clone().into();
+    let state = state.as_dict().unwrap();
+    assert_eq!(state.get(b"BM").and_then(|bm| bm.as_name_str()).ok(), Some("Multiply"));
+    assert_eq!(state.get(b"ca").and_then(|ca| ca.as_f64()).ok(), Some(0.5));
+}
+
+#[test]
+fn test_with_clip() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("clip", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.with_clip_even_odd(Line::rectangle(Mm(10.0), Mm(10.0), Mm(50.0), Mm(30.0)), |layer| {
+        let mut line = Line::rectangle(Mm(0.0), Mm(0.0), Mm(100.0), Mm(100.0));
+        line.set_fill(true);
+        layer.add_shape(line);
+    });
+
+    let document = doc.document.borrow();
+    let operators = document.pages[page.0].layers[layer.0].operations.iter()
+        .map(|op| op.operator.as_str()).collect::<Vec<_>>();
+    assert_eq!(operators, vec!["q", "m", "l", "l", "l", "W*", "n", "m", "l", "l", "l", "f", "Q"]);
+}
+
+#[test]
+fn test_clear_tagged_layer() {
+    use {PdfDocument, PdfDocumentReference, BuiltinFont};
+    use types::plugins::misc::structure::add_structure_tree;
+
+    let (doc, page, layer) = PdfDocument::new("tagged", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_tagged(true);
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let first = doc.get_page(page).get_layer(layer);
+    let second = doc.get_page(page).add_layer("Layer 2");
+    first.use_text("measured", 12.0, Mm(10.0), Mm(20.0), &font);
+    second.begin_tagged_content(StructureRole::Heading(1));
+    second.use_text("heading", 12.0, Mm(10.0), Mm(40.0), &font);
+    second.end_tagged_content();
+
+    // roles of the structure elements in the parent tree of the page, with their MCID
+    let structure_roles = |doc: &PdfDocumentReference| {
+        let roles = doc.document.borrow().pages[page.0].structure_roles.clone();
+        let mut tree = lopdf::Document::new();
+        let page_id = tree.new_object_id();
+        let root_id = add_structure_tree(&mut tree, &[(page_id, roles)], &Default::default());
+        let root = tree.get_dictionary(root_id).unwrap();
+        let parent_tree = root.get(b"ParentTree").and_then(|parent_tree| parent_tree.as_dict()).unwrap();
+        let nums = parent_tree.get(b"Nums").and_then(|nums| nums.as_array()).unwrap();
+        nums[1].as_array().unwrap().iter()
+            .map(|element| tree.get_dictionary(element.as_reference().unwrap()).unwrap())
+            .map(|element| (element.get(b"S").and_then(|s| s.as_name_str()).unwrap().to_string(),
+                            element.get(b"K").and_then(|k| k.as_i64()).unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(structure_roles(&doc), vec![("P".to_string(), 0), ("H1".to_string(), 1)]);
+
+    first.clear();
+    // the heading moved up to MCID 0, in the structure tree and in the content
+    assert_eq!(structure_roles(&doc), vec![("H1".to_string(), 0)]);
+    let document = doc.document.borrow();
+    let bdc = document.pages[page.0].layers[1].operations.iter().find(|op| op.operator == "BDC").unwrap();
+    assert_eq!(bdc.operands[1].as_dict().and_then(|d| d.get(b"MCID")).and_then(|mcid| mcid.as_i64()).ok(), Some(0));
+}
+
+#[test]
+fn test_clear_removes_unused_resources() {
+    use {PdfDocument, PdfDocumentReference, BuiltinFont, Px, Greyscale, SeperableBlendMode};
+
+    let (doc, page, layer) = PdfDocument::new("clear", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let first = doc.get_page(page).get_layer(layer);
+    let second = doc.get_page(page).add_layer("Layer 2");
+    let black = Color::Greyscale(Greyscale::new(0.0, None));
+
+    // the mask and the blend mode are only used on the first layer, the alpha on both
+    first.add_image_mask(ImageMaskXObject::new(Px(8), Px(1), vec![0x0f]), Point::new(Mm(10.0), Mm(10.0)), Mm(8.0), Mm(1.0), black).unwrap();
+    first.set_blend_mode(BlendMode::Seperable(SeperableBlendMode::Multiply));
+    first.set_fill_alpha(0.5);
+    second.set_fill_alpha(0.5);
+    first.use_text("measured", 12.0, Mm(10.0), Mm(20.0), &font);
+
+    let resource_names = |doc: &PdfDocumentReference| {
+        let document = doc.document.borrow();
+        let resources = &document.pages[page.0].resources;
+        let mut names = resources.xobjects.objects.keys().chain(resources.graphics_states.all_graphics_states.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    assert_eq!(resource_names(&doc).len(), 3);
+
+    first.clear();
+    assert!(doc.document.borrow().pages[page.0].layers[layer.0].operations.is_empty());
+
+    // only the graphics state that the second layer still uses is left on the page
+    let gs = doc.document.borrow().pages[page.0].layers[1].operations.iter()
+        .find(|op| op.operator == "gs")
+        .and_then(|op| op.operands[0].as_name_str().ok().map(|name| name.to_string()))
+        .unwrap();
+    assert_eq!(resource_names(&doc), vec![gs.clone()]);
+
+    // the font is a document-level resource and is kept, so the layer can be drawn again
+    assert_eq!(doc.document.borrow().fonts.len(), 1);
+    first.use_text("drawn", 12.0, Mm(10.0), Mm(20.0), &font);
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains(&format!("/{}", gs)));
+    assert!(!pdf.contains("/ImageMask"));
+}
+
+#[test]
+fn test_text_decorations() {
+    use {PdfDocument, BuiltinFont};
+
+    let (doc, page, layer) = PdfDocument::new("decorations", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let options = TextOptions { underline: true, strikethrough: true, .. Default::default() };
+    doc.get_page(page).get_layer(layer).use_text_with_options("Hello", 10.0, Mm(10.0), Mm(20.0), &font, options);
+
+    // "Hello" in Helvetica: H 722, e 556, l 222, l 222, o 556
+    let width = (722.0 + 556.0 + 222.0 + 222.0 + 556.0) / 1000.0 * 10.0;
+    let (x, y) = (Pt::from(Mm(10.0)).0, Pt::from(Mm(20.0)).0);
+
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    let content = &pdf[pdf.find("ET\n").unwrap() + 3..];
+
+    // the decorations are filled rectangles after the text, as wide as the text
+    let rectangles = content.split("\nf").take(2).map(|path| {
+        path.split_whitespace()
+            .filter_map(|value| value.parse::<f64>().ok())
+            .collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    assert_eq!(rectangles.len(), 2);
+
+    // underline: position -100, thickness 50; strikeout: half of the x-height (523), thickness 50
+    for (rectangle, bottom) in rectangles.iter().zip(&[y - 1.25, y + 2.365]) {
+        let expected = [x, *bottom, x + width, *bottom, x + width, bottom + 0.5, x, bottom + 0.5];
+        assert_eq!(rectangle.len(), expected.len());
+        for (value, expected) in rectangle.iter().zip(expected.iter()) {
+            assert!((value - expected).abs() < 0.01, "{} != {}", value, expected);
+        }
+    }
+}
+
+#[test]
+fn test_multicolumn_text_line_height() {
+    use {PdfDocument, BuiltinFont};
+
+    let (doc, page, layer) = PdfDocument::new("columns", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    let region = Rect::new(Mm(10.0), Mm(10.0), Mm(100.0), Mm(50.0));
+    let baselines = || {
+        let document = doc.document.borrow();
+        document.pages[page.0].layers[layer.0].operations.iter()
+            .filter(|op| op.operator == "Td")
+            .map(|op| op.operands[1].as_f64().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // the recommended leading of Helvetica: ascent 718, descent -207 and the default line gap of 0.2
+    let overflow = layer_ref.add_multicolumn_text("one\ntwo", &font, 10.0, region, 2, Mm(5.0));
+    assert!(overflow.is_empty());
+    let default = baselines();
+    assert_eq!(default.len(), 2);
+    assert!((default[0] - default[1] - 11.25).abs() < 0.01);
+
+    layer_ref.clear();
+    layer_ref.add_multicolumn_text_with_line_height("one\ntwo", &font, 10.0, region, 2, Pt(14.0), Some(Pt(20.0)));
+    let custom = baselines();
+    assert!((custom[0] - custom[1] - 20.0).abs() < 0.01);
+}
+
+#[test]
+fn test_line_dash_preset() {
+    use PdfDocument;
+
+    let (doc, page, layer) = PdfDocument::new("dashes", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_line_dash_preset(DashPattern::DashDot);
+    layer_ref.set_line_dash_preset(DashPattern::Solid);
+
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    let dash_dot = pdf.find("[6 3 1 3] 0 d").expect("dash-dot pattern");
+    let solid = pdf.find("[] 0 d").expect("solid pattern");
+    assert!(dash_dot < solid);
+}
+
+#[test]
+fn test_change_bar() {
+    use PdfDocument;
+
+    // x of the bar (in mm) and its operators, for a page of the given width
+    let change_bar = |page_width: f64, side: ChangeBarSide| {
+        let (doc, page, layer) = PdfDocument::new("change bars", Mm(page_width), Mm(297.0), "Layer 1");
+        let result = doc.get_page(page).get_layer(layer).add_change_bar(Mm(200.0), Mm(150.0), side);
+        let document = doc.document.borrow();
+        let operations = &document.pages[page.0].layers[layer.0].operations;
+        let operators = operations.iter().map(|op| op.operator.clone()).collect::<Vec<_>>();
+        let start = operations.iter().find(|op| op.operator == "m").unwrap();
+        let end = operations.iter().find(|op| op.operator == "l").unwrap();
+        assert_eq!(start.operands[0].as_f64().ok(), end.operands[0].as_f64().ok());
+        assert!((Mm::from(Pt(end.operands[1].as_f64().unwrap())).0 - 150.0).abs() < 1e-3);
+        (Mm::from(Pt(start.operands[0].as_f64().unwrap())).0, operators, result.bbox)
+    };
+
+    // 4% of the page width, at least 5 mm
+    let (left, operators, bbox) = change_bar(210.0, ChangeBarSide::Left);
+    assert!((left - 8.4).abs() < 1e-3);
+    assert_eq!(operators, vec!["m", "l", "S"]);
+    assert!((bbox.height.0 - 50.0).abs() < 1e-3);
+    let (right, _, _) = change_bar(210.0, ChangeBarSide::Right);
+    assert!((right - 201.6).abs() < 1e-3);
+    let (narrow, _, _) = change_bar(100.0, ChangeBarSide::Left);
+    assert!((narrow - 5.0).abs() < 1e-3);
+}
+
+#[test]
+fn test_add_text_at() {
+    use {PdfDocument, BuiltinFont, Rgb};
+
+    let (doc, page, layer) = PdfDocument::new("text runs", Mm(210.0), Mm(297.0), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+
+    let start = Point { x: Pt(100.0), y: Pt(500.0) };
+    let label_end = layer_ref.add_text_at("Total: ", 10.0, start, &regular);
+    layer_ref.set_fill_color(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None)));
+    let value_end = layer_ref.add_text_at("42", 10.0, label_end, &bold);
+
+    // "Total: " in Helvetica: T 611, o 556, t 278, a 556, l 222, : 278, space 278
+    assert!((label_end.x.0 - (100.0 + 2.779 * 10.0)).abs() < 1e-6);
+    assert_eq!(label_end.y, start.y);
+    // "42" in Helvetica Bold: 556 each
+    assert!((value_end.x.0 - (label_end.x.0 + 11.12)).abs() < 1e-6);
+
+    // the second run starts where the first one ended
+    let document = doc.document.borrow();
+    let starts = document.pages[page.0].layers[layer.0].operations.iter()
+        .filter(|op| op.operator == "Td")
+        .map(|op| op.operands[0].as_f64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(starts.len(), 2);
+    assert!((starts[0] - 100.0).abs() < 1e-3);
+    assert!((starts[1] - label_end.x.0).abs() < 1e-3);
+}
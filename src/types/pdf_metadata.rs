@@ -3,10 +3,24 @@
 use lopdf;
 use crate::OffsetDateTime;
 use {
-	IccProfileType, PdfConformance, XmpMetadata, DocumentInfo, IccProfile
+	ColorSpace, IccProfileType, PdfConformance, XmpMetadata, XmpProperty, DocumentInfo, IccProfile, OutputIntent
 };
 
 use glob_defines::ICC_PROFILE_ECI_V2;
+use utils::random_character_string_32;
+
+/// How `PdfMetadata::merge` combines the metadata of two documents
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MetadataMergeStrategy {
+	/// Keep the values of `self`, only fill in values that are missing
+	/// (empty title / subject / author / creator, no keywords, no color profile / output intents) from `other`
+	PreferSelf,
+	/// Take the values of `other`, except for values that are missing in `other`
+	PreferOther,
+	/// Like `PreferSelf`, but the keywords of both documents are combined
+	/// (without duplicates, in the order they appear)
+	CombineKeywords,
+}
 
 /// This is a wrapper in order to keep shared data between the documents XMP metadata and
 /// the "Info" dictionary in sync
@@ -20,6 +34,18 @@ pub struct PdfMetadata {
 	pub metadata_date: OffsetDateTime,
 	/// PDF document title
 	pub document_title: String,
+	/// Keywords of the document (for search indexing)
+	pub keywords: Vec<String>,
+	/// Subject (description) of the document
+	pub subject: String,
+	/// Author of the document
+	pub author: String,
+	/// Application that created the original document (for example a word processor),
+	/// the `/Creator` of the document info dictionary
+	pub creator: String,
+	/// Application that wrote the PDF (`/Producer` of the document info dictionary,
+	/// `pdf:Producer` in the XMP metadata). Default: `printpdf <version>`
+	pub producer: String,
 	/// Is the document trapped?
 	pub trapping: bool,
 	/// PDF document version
@@ -32,6 +58,9 @@ pub struct PdfMetadata {
 	pub document_info: DocumentInfo,
 	/// Target color profile
 	pub target_icc_profile: Option<IccProfile>,
+	/// Output intents of the document. If empty, an output intent for the target color
+	/// profile is generated when the PDF conformance requires one.
+	pub output_intents: Vec<OutputIntent>,
 }
 
 impl PdfMetadata {
@@ -47,28 +76,158 @@ impl PdfMetadata {
 			modification_date: current_time.clone(),
 			metadata_date: current_time.clone(),
 			document_title: title.into(),
+			keywords: Vec::new(),
+			subject: String::new(),
+			author: String::new(),
+			creator: String::new(),
+			producer: format!("printpdf {}", env!("CARGO_PKG_VERSION")),
 			trapping: trapping,
 			document_version: document_version,
 			conformance: conformance,
 			xmp_metadata: XmpMetadata::new(Some("default".into()), 1),
 			document_info: DocumentInfo::new(),
 			target_icc_profile: None,
+			output_intents: Vec::new(),
+		}
+	}
+
+	/// Adds a (text) property of a custom schema to the XMP metadata, for example
+	/// `add_xmp_property("http://prismstandard.org/namespaces/basic/2.0/", "prism", "issn", "1234-5678")`.
+	/// The namespace is declared with the other namespaces of the XMP packet and the value
+	/// is escaped. A property with the same namespace and key replaces the previous one.
+	/// `prefix` and `key` must be valid XML names without a colon and the namespace must not
+	/// be one of the schemas printpdf writes itself (XMP, Dublin Core, PDF, ...), otherwise
+	/// saving the document fails with `PdfError::InvalidXmpProperty`. The XMP metadata is
+	/// written if there are custom properties, even if the conformance doesn't require it.
+	pub fn add_xmp_property<N, P, K, V>(&mut self, namespace: N, prefix: P, key: K, value: V)
+	where N: Into<String>, P: Into<String>, K: Into<String>, V: Into<String>
+	{
+		self.xmp_metadata.add_property(XmpProperty::new(namespace, prefix, key, value));
+	}
+
+	/// Merges the metadata of another document into this one, for example when the pages
+	/// of two documents are combined. The PDF conformance and the XMP / Info settings of `self`
+	/// are always kept, since they describe the document that is written.
+	pub fn merge(&mut self, other: PdfMetadata, strategy: MetadataMergeStrategy)
+	{
+		let prefer_other = strategy == MetadataMergeStrategy::PreferOther;
+
+		if prefer_other {
+			self.creation_date = other.creation_date;
+			self.modification_date = other.modification_date;
+			self.metadata_date = other.metadata_date;
+			self.trapping = other.trapping;
+		}
+
+		if self.document_title.is_empty() || (prefer_other && !other.document_title.is_empty()) {
+			self.document_title = other.document_title;
+		}
+
+		if self.subject.is_empty() || (prefer_other && !other.subject.is_empty()) {
+			self.subject = other.subject;
+		}
+
+		if self.author.is_empty() || (prefer_other && !other.author.is_empty()) {
+			self.author = other.author;
+		}
+
+		if self.creator.is_empty() || (prefer_other && !other.creator.is_empty()) {
+			self.creator = other.creator;
+		}
+
+		if strategy == MetadataMergeStrategy::CombineKeywords {
+			for keyword in other.keywords {
+				if !self.keywords.contains(&keyword) {
+					self.keywords.push(keyword);
+				}
+			}
+		} else if self.keywords.is_empty() || (prefer_other && !other.keywords.is_empty()) {
+			self.keywords = other.keywords;
+		}
+
+		if self.target_icc_profile.is_none() || (prefer_other && other.target_icc_profile.is_some()) {
+			self.target_icc_profile = other.target_icc_profile.or(self.target_icc_profile.take());
+		}
+
+		if self.output_intents.is_empty() || (prefer_other && !other.output_intents.is_empty()) {
+			self.output_intents = other.output_intents;
 		}
 	}
 
+	/// Returns the target ICC profile, or the default profile (Coated FOGRA39) if none was set
+	pub(crate) fn target_icc_profile_or_default(&self)
+	-> IccProfile
+	{
+		match self.target_icc_profile {
+			Some(ref icc) => icc.clone(),
+			None =>          IccProfile::new(ICC_PROFILE_ECI_V2.to_vec(), IccProfileType::Cmyk)
+								 .with_alternate_profile(false)
+								 .with_range(true),
+		}
+	}
+
+	/// Returns the device color space the content has to use under the conformance, besides
+	/// DeviceGray: CMYK for PDF/X, the color space of the output intent for PDF/A (the first
+	/// output intent with an ICC profile, otherwise the target ICC profile, CMYK by default).
+	/// `None` if the conformance doesn't restrict device colors.
+	pub(crate) fn output_intent_color_space(&self, conformance: &PdfConformance)
+	-> Option<ColorSpace>
+	{
+		if !conformance.is_device_rgb_allowed() {
+			return Some(ColorSpace::Cmyk);
+		}
+		conformance.get_pdfa_part_and_conformance()?;
+
+		let icc_type = self.output_intents.iter()
+			.filter_map(|intent| intent.icc_profile.as_ref())
+			.chain(self.target_icc_profile.as_ref())
+			.next()
+			.map_or(IccProfileType::Cmyk, |icc| icc.icc_type());
+
+		Some(match icc_type {
+			IccProfileType::Cmyk => ColorSpace::Cmyk,
+			IccProfileType::Rgb => ColorSpace::Rgb,
+			IccProfileType::Greyscale => ColorSpace::Greyscale,
+		})
+	}
+
 	/// Consumes the metadata, returning the (Option<xmp_metadata>, document_info, icc_profile_stream).
+	#[inline]
 	pub fn into_obj(self)
 	-> (Option<lopdf::Object>, lopdf::Object, Option<IccProfile>)
 	{
+		self.into_obj_with_instance_id(&random_character_string_32())
+	}
+
+	/// Same as `into_obj`, but with a fixed instance ID in the XMP metadata
+	pub(crate) fn into_obj_with_instance_id(self, instance_id: &str)
+	-> (Option<lopdf::Object>, lopdf::Object, Option<IccProfile>)
+	{
+		// add icc profile if necessary
+		let icc_profile = {
+		    if self.conformance.must_have_icc_profile() {
+		        Some(self.target_icc_profile_or_default())
+		    } else {
+		        None
+		    }
+		};
+
 		let xmp_obj = {
-			if self.conformance.must_have_xmp_metadata() {
+			// custom properties can only be stored in the XMP metadata
+			if self.conformance.must_have_xmp_metadata() || !self.xmp_metadata.custom_properties.is_empty() {
 				Some(self.xmp_metadata.into_obj(
 					 	self.conformance.clone(),
 						self.trapping,
 						self.creation_date.clone(),
 						self.modification_date.clone(),
 						self.metadata_date,
-						self.document_title.clone()))
+						self.document_title.clone(),
+						instance_id,
+						&self.keywords,
+						&self.subject,
+						&self.author,
+						&self.creator,
+						&self.producer))
 			} else {
 				None
 			}
@@ -78,21 +237,38 @@ impl PdfMetadata {
 													   self.trapping,
 													   self.conformance.clone(),
 													   self.creation_date.clone(),
-													   self.modification_date.clone());
-		// add icc profile if necessary
-		let icc_profile = {
-		    if self.conformance.must_have_icc_profile() {
-		        match self.target_icc_profile {
-		            Some(icc) => Some(icc),
-		            None =>      Some(IccProfile::new(ICC_PROFILE_ECI_V2.to_vec(), IccProfileType::Cmyk)
-		            			      .with_alternate_profile(false)
-		            			      .with_range(true)),
-		        }
-		    } else {
-		        None
-		    }
-		};
+													   self.modification_date.clone(),
+													   &self.keywords,
+													   &self.subject,
+													   &self.author,
+													   &self.creator,
+													   &self.producer);
 
 		(xmp_obj, doc_info_obj, icc_profile)
 	}
 }
+
+#[test]
+fn test_merge_metadata() {
+	let mut a = PdfMetadata::new("A", 1, false, PdfConformance::X3_2002_PDF_1_3);
+	a.keywords = vec!["cosmology".into(), "infrared".into()];
+	let mut b = PdfMetadata::new("B", 1, true, PdfConformance::X3_2002_PDF_1_3);
+	b.keywords = vec!["infrared".into(), "starlight".into()];
+
+	let mut merged = a.clone();
+	merged.merge(b.clone(), MetadataMergeStrategy::CombineKeywords);
+	assert_eq!(merged.document_title, "A");
+	assert!(!merged.trapping);
+	assert_eq!(merged.keywords, vec!["cosmology", "infrared", "starlight"]);
+
+	let mut merged = a.clone();
+	merged.merge(b, MetadataMergeStrategy::PreferOther);
+	assert_eq!(merged.document_title, "B");
+	assert!(merged.trapping);
+	assert_eq!(merged.keywords, vec!["infrared", "starlight"]);
+
+	let mut empty = PdfMetadata::new("", 1, false, PdfConformance::X3_2002_PDF_1_3);
+	empty.merge(a, MetadataMergeStrategy::PreferSelf);
+	assert_eq!(empty.document_title, "A");
+	assert_eq!(empty.keywords, vec!["cosmology", "infrared"]);
+}
@@ -3,13 +3,30 @@
 use lopdf;
 use std::rc::Weak;
 use std::cell::RefCell;
+use std::collections::HashSet;
 
 use indices::{PdfPageIndex, PdfLayerIndex};
+use types::plugins::interactive::link::LinkAnnotation;
 use {
     PdfResources, PdfLayer, PdfDocument, ExtendedGraphicsState, ExtendedGraphicsStateRef, Pattern, XObject, XObjectRef,
-    PdfLayerReference, PatternRef, Mm, Pt
+    PdfLayerReference, PatternRef, Pt, StructureRole, ImageXObject, ColorSpace, Error, PdfError,
+    PdfDocumentReference, BuiltinFont, Color, Rgb, Line, Point, Rect, PageLabel
 };
 
+/// Maximum width / height (in pixels) of page thumbnails, larger thumbnails are downsampled
+pub const MAX_THUMBNAIL_DIMENSION: usize = 256;
+
+/// Name of the form XObject with the content of an imported page in the page resources
+const IMPORTED_CONTENT_NAME: &str = "Imported";
+
+/// Colors of the box guides (see `add_box_guides`), by the name of the box
+const BOX_GUIDE_COLORS: [(&str, (f64, f64, f64)); 4] = [
+    ("MediaBox", (0.0, 0.0, 1.0)),     // blue
+    ("BleedBox", (0.0, 0.6, 0.0)),     // green
+    ("TrimBox", (1.0, 0.0, 0.0)),      // red
+    ("ArtBox", (1.0, 0.0, 1.0)),       // magenta
+];
+
 /// PDF page
 #[derive(Debug, Clone)]
 pub struct PdfPage {
@@ -23,6 +40,34 @@ pub struct PdfPage {
     pub layers: Vec<PdfLayer>,
     /// Resources used in this page
     pub(crate) resources: PdfResources,
+    /// Roles of the tagged content on this page, the index is the MCID of the content
+    pub(crate) structure_roles: Vec<StructureRole>,
+    /// Should the content be clipped to the trim box on save (see `set_clip_to_trimbox`)?
+    pub(crate) clip_to_trimbox: bool,
+    /// Trim box as (lower left x, lower left y, upper right x, upper right y), the media box
+    /// if `None` (see `set_trim_box`)
+    pub(crate) trimbox: Option<(Pt, Pt, Pt, Pt)>,
+    /// Bleed box, only written if set (see `set_bleed_box`)
+    pub(crate) bleedbox: Option<(Pt, Pt, Pt, Pt)>,
+    /// Art box, only written if set (see `set_art_box`)
+    pub(crate) artbox: Option<(Pt, Pt, Pt, Pt)>,
+    /// Thumbnail image of the page (`/Thumb`), see `set_thumbnail`
+    pub(crate) thumbnail: Option<ImageXObject>,
+    /// Display duration in seconds for presentations (`/Dur`), see `set_duration`
+    pub(crate) duration: Option<f64>,
+    /// Clockwise rotation of the page in degrees when it is displayed (`/Rotate`),
+    /// a multiple of 90 between 0 and 270, see `set_rotation`
+    pub(crate) rotation: i64,
+    /// Link annotations of the page (`/Annots`), see `PdfLayerReference::add_link`
+    pub(crate) links: Vec<LinkAnnotation>,
+    /// Operations that are written to the content stream as they are, before the layers
+    /// (see `with_operations`)
+    pub(crate) operations: Vec<lopdf::content::Operation>,
+    /// Form XObject with the content of a page of an existing document, drawn before
+    /// everything else (see `impl From<lopdf::Document> for PdfDocumentReference`)
+    pub(crate) imported_content: Option<lopdf::ObjectId>,
+    /// Label of the page range starting at this page (see `PdfDocumentReference::set_page_label`)
+    pub(crate) label: Option<PageLabel>,
 }
 
 /// A "reference" to the current page, allows for inner mutability
@@ -36,14 +81,14 @@ pub struct PdfPageReference {
 
 impl PdfPage {
 
-    /// Create a new page, notice that width / height are in millimeter.
+    /// Create a new page, the width / height can be in any unit (`Mm`, `Pt` or `In`).
     /// Page must contain at least one layer
     #[inline]
-    pub fn new<S>(width: Mm,
-                  height: Mm,
-                  layer_name: S,
-                  page_index: usize)
-    -> (Self, PdfLayerIndex) where S: Into<String>
+    pub fn new<W, H, S>(width: W,
+                        height: H,
+                        layer_name: S,
+                        page_index: usize)
+    -> (Self, PdfLayerIndex) where W: Into<Pt>, H: Into<Pt>, S: Into<String>
     {
         let mut page = Self {
             index: page_index,
@@ -51,6 +96,18 @@ impl PdfPage {
             height: height.into(),
             layers: Vec::new(),
             resources: PdfResources::new(),
+            structure_roles: Vec::new(),
+            clip_to_trimbox: false,
+            trimbox: None,
+            bleedbox: None,
+            artbox: None,
+            thumbnail: None,
+            duration: None,
+            rotation: 0,
+            links: Vec::new(),
+            imported_content: None,
+            label: None,
+            operations: Vec::new(),
         };
 
         let initial_layer = PdfLayer::new(layer_name);
@@ -61,6 +118,36 @@ impl PdfPage {
         (page, PdfLayerIndex(layer_index))
     }
 
+    /// Creates a page (without any layers) whose content stream consists of exactly
+    /// the given operations. Width / height can be in any unit (`Mm`, `Pt` or `In`).
+    ///
+    /// This bypasses the layers, for porting existing `lopdf` code. The operations may
+    /// use the fonts of the document (by their resource name) and the resources of the page.
+    /// Add the page to a document with `PdfDocumentReference::add_pdf_page`.
+    pub fn with_operations<W, H>(width: W, height: H, operations: Vec<lopdf::content::Operation>)
+    -> Self where W: Into<Pt>, H: Into<Pt>
+    {
+        Self {
+            index: 0,
+            width: width.into(),
+            height: height.into(),
+            layers: Vec::new(),
+            resources: PdfResources::new(),
+            structure_roles: Vec::new(),
+            clip_to_trimbox: false,
+            trimbox: None,
+            bleedbox: None,
+            artbox: None,
+            thumbnail: None,
+            duration: None,
+            rotation: 0,
+            links: Vec::new(),
+            imported_content: None,
+            label: None,
+            operations,
+        }
+    }
+
     /// Iterates through the layers attached to this page and gathers all resources,
     /// which the layers need. Then returns a dictonary with all the resources
     /// (fonts, image XObjects, etc.)
@@ -72,18 +159,54 @@ impl PdfPage {
     /// `layers` should be a Vec with all layers (optional content groups) that were added
     /// to the document on a document level, it should contain the indices of the layers
     /// (they will be ignored, todo) and references to the actual OCG dictionaries
+    ///
+    /// If `split_threshold` is set, layers are split into several streams of about
+    /// `split_threshold` bytes each (see `PdfDocumentReference::with_content_split_threshold`)
     #[inline]
-    pub(crate) fn collect_resources_and_streams(self, doc: &mut lopdf::Document, layers: &[(usize, lopdf::Object)])
+    pub(crate) fn collect_resources_and_streams(self, doc: &mut lopdf::Document, layers: &[(usize, lopdf::Object)],
+                                                split_threshold: Option<usize>)
     -> (lopdf::Dictionary, Vec<lopdf::Stream>)
     {
+        let trim_box = self.trim_box();
         let cur_layers = layers.iter().map(|l| l.1.clone()).collect();
-        let (resource_dictionary, ocg_refs) = self.resources.into_with_document_and_layers(doc, cur_layers);
+        let (mut resource_dictionary, ocg_refs) = self.resources.into_with_document_and_layers(doc, cur_layers);
 
         // set contents
         let mut layer_streams = Vec::<lopdf::Stream>::new();
         use lopdf::content::Operation;
         use lopdf::Object::*;
 
+        // clip all layers to the trim box: q llx lly w h re W n ... Q
+        let clip_to_trimbox = self.clip_to_trimbox;
+        if clip_to_trimbox {
+            let (llx, lly, urx, ury) = trim_box;
+            layer_streams.push(operations_to_stream(vec![
+                Operation::new("q", vec![]),
+                Operation::new("re", vec![llx.into(), lly.into(), (urx - llx).into(), (ury - lly).into()]),
+                Operation::new("W", vec![]),
+                Operation::new("n", vec![]),
+            ]));
+        }
+
+        if let Some(form_id) = self.imported_content {
+            let mut xobjects = match resource_dictionary.get(b"XObject").and_then(lopdf::Object::as_dict) {
+                Ok(xobjects) => xobjects.clone(),
+                Err(_) => lopdf::Dictionary::new(),
+            };
+            xobjects.set(IMPORTED_CONTENT_NAME, Reference(form_id));
+            resource_dictionary.set("XObject", Dictionary(xobjects));
+
+            layer_streams.push(operations_to_stream(vec![
+                Operation::new("q", vec![]),
+                Operation::new("Do", vec![Name(IMPORTED_CONTENT_NAME.into())]),
+                Operation::new("Q", vec![]),
+            ]));
+        }
+
+        if !self.operations.is_empty() {
+            layer_streams.extend(operations_to_streams(self.operations, split_threshold));
+        }
+
         for (idx, mut layer) in self.layers.into_iter().enumerate() {
 
             // push OCG and q to the beginning of the layer
@@ -105,13 +228,88 @@ impl PdfPage {
             // Q
             // EMC
 
-            let layer_stream = layer.into();
-            layer_streams.push(layer_stream);
+            match split_threshold {
+                Some(_) => layer_streams.extend(operations_to_streams(layer.operations, split_threshold)),
+                None => layer_streams.push(layer.into()),
+            }
+        }
+
+        if clip_to_trimbox {
+            layer_streams.push(operations_to_stream(vec![Operation::new("Q", vec![])]));
         }
 
         (resource_dictionary, layer_streams)
     }
 
+    /// Returns the trim box of the page as (lower left x, lower left y, upper right x, upper right y)
+    #[inline]
+    pub(crate) fn trim_box(&self)
+    -> (Pt, Pt, Pt, Pt)
+    {
+        self.trimbox.unwrap_or((Pt(0.0), Pt(0.0), self.width, self.height))
+    }
+
+    /// Returns the boxes of the page, with the name of the box in the page dictionary.
+    /// The bleed box and the art box are only returned if they were set.
+    pub(crate) fn page_boxes(&self)
+    -> Vec<(&'static str, Rect)>
+    {
+        let (llx, lly, urx, ury) = self.trim_box();
+        let mut boxes = vec![("MediaBox", Rect::from_extents(Pt(0.0), Pt(0.0), self.width, self.height))];
+        if let Some((llx, lly, urx, ury)) = self.bleedbox {
+            boxes.push(("BleedBox", Rect::from_extents(llx, lly, urx, ury)));
+        }
+        boxes.push(("TrimBox", Rect::from_extents(llx, lly, urx, ury)));
+        if let Some((llx, lly, urx, ury)) = self.artbox {
+            boxes.push(("ArtBox", Rect::from_extents(llx, lly, urx, ury)));
+        }
+        boxes
+    }
+
+    /// Removes the content of the layer, together with the structure tree entries of the
+    /// tagged content on it. The MCIDs of the tagged content on the other layers are
+    /// renumbered, so that they keep pointing at their entries.
+    pub(crate) fn clear_layer(&mut self, layer: usize)
+    {
+        let removed = self.layers[layer].operations.iter()
+            .filter_map(marked_content_id)
+            .collect::<HashSet<i64>>();
+        self.layers[layer].clear();
+        if removed.is_empty() {
+            return;
+        }
+
+        let roles = ::std::mem::take(&mut self.structure_roles);
+        let mut new_mcids = vec![None; roles.len()];
+        for (mcid, role) in roles.into_iter().enumerate() {
+            if !removed.contains(&(mcid as i64)) {
+                new_mcids[mcid] = Some(self.structure_roles.len() as i64);
+                self.structure_roles.push(role);
+            }
+        }
+
+        for op in self.layers.iter_mut().flat_map(|layer| layer.operations.iter_mut()) {
+            let new_mcid = marked_content_id(op).and_then(|mcid| new_mcids.get(mcid as usize).cloned().flatten());
+            if let (Some(new_mcid), Some(lopdf::Object::Dictionary(properties))) = (new_mcid, op.operands.get_mut(1)) {
+                properties.set("MCID", lopdf::Object::Integer(new_mcid));
+            }
+        }
+    }
+
+    /// Removes the XObjects and graphics states from the page resources that are not
+    /// used by any layer on this page anymore. Document-level resources (fonts) are not affected.
+    pub(crate) fn remove_unused_resources(&mut self)
+    {
+        let used_names = self.layers.iter()
+            .flat_map(|layer| layer.operations.iter())
+            .filter(|op| op.operator == "Do" || op.operator == "gs")
+            .filter_map(|op| op.operands.first().and_then(|name| name.as_name_str().ok()))
+            .map(|name| name.to_string())
+            .collect::<HashSet<String>>();
+
+        self.resources.retain_used(&used_names);
+    }
+
     /// Change the graphics state. Before this operation is done, you should save
     /// the graphics state using the `save_graphics_state()` function. This will change the
     /// current graphics state until the end of the page or until the page is reset to the
@@ -125,7 +323,7 @@ impl PdfPage {
         self.resources.add_graphics_state(added_state)
     }
 
-    /// __STUB__: Adds a pattern to the pages resources
+    /// Adds a pattern to the pages resources
     #[inline]
     pub fn add_pattern(&mut self, pattern: Pattern)
     -> PatternRef
@@ -143,8 +341,206 @@ impl PdfPage {
     }
 }
 
+/// Returns the MCID of a `BDC` operation that begins tagged content
+fn marked_content_id(op: &lopdf::content::Operation)
+-> Option<i64>
+{
+    if op.operator != "BDC" {
+        return None;
+    }
+    op.operands.get(1)
+        .and_then(|properties| properties.as_dict().ok())
+        .and_then(|properties| properties.get(b"MCID").and_then(|mcid| mcid.as_i64()).ok())
+}
+
+/// Encodes the operations into an (uncompressed) content stream
+fn operations_to_stream(operations: Vec<lopdf::content::Operation>)
+-> lopdf::Stream
+{
+    let content = lopdf::content::Content { operations };
+    lopdf::Stream::new(lopdf::Dictionary::new(), content.encode().unwrap()).with_compression(false)
+}
+
+/// Encodes the operations into (uncompressed) content streams. If `max_bytes` is set, a new
+/// stream is started once a stream exceeds `max_bytes`, so that streams are only split
+/// between two operations (a stream can't be split in the middle of an operation).
+fn operations_to_streams(operations: Vec<lopdf::content::Operation>, max_bytes: Option<usize>)
+-> Vec<lopdf::Stream>
+{
+    let max_bytes = match max_bytes {
+        Some(max_bytes) => max_bytes,
+        None => return vec![operations_to_stream(operations)],
+    };
+
+    let mut contents = vec![Vec::<u8>::new()];
+    for operation in operations {
+        if contents.last().unwrap().len() >= max_bytes {
+            contents.push(Vec::new());
+        }
+        let encoded = lopdf::content::Content { operations: vec![operation] }.encode().unwrap();
+        contents.last_mut().unwrap().extend(encoded);
+    }
+
+    contents.into_iter()
+        .map(|content| lopdf::Stream::new(lopdf::Dictionary::new(), content).with_compression(false))
+        .collect()
+}
+
 impl PdfPageReference {
 
+    /// Sets the thumbnail image of the page (`/Thumb`), which viewers show as page preview.
+    /// The image has to be an RGB, greyscale or indexed image without alpha channel.
+    /// Images larger than `MAX_THUMBNAIL_DIMENSION` pixels are downsampled.
+    pub fn set_thumbnail(&self, image: ImageXObject)
+    -> Result<(), Error>
+    {
+        match image.color_space {
+            ColorSpace::Rgb | ColorSpace::Greyscale | ColorSpace::Palette => { },
+            _ => return Err(PdfError::InvalidThumbnail.into()),
+        }
+
+        let image = if image.width.0.max(image.height.0) > MAX_THUMBNAIL_DIMENSION {
+            image.downsample(MAX_THUMBNAIL_DIMENSION).ok_or(PdfError::ImageTooLarge)?
+        } else {
+            image
+        };
+
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].thumbnail = Some(image);
+        Ok(())
+    }
+
+    /// Sets how long (in seconds) the page is displayed in presentation mode, before the viewer
+    /// advances to the next page automatically (`/Dur`). Overrides the duration set with
+    /// `PdfDocumentReference::with_auto_advance`; a duration of zero disables auto-advance
+    /// for this page.
+    #[inline]
+    pub fn set_duration(&self, seconds: f64)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].duration = Some(seconds);
+    }
+
+    /// Rotates the page clockwise by `degrees` when it is displayed or printed (`/Rotate`),
+    /// without changing the content, e.g. to show landscape scans upright. PDF only allows
+    /// multiples of 90 degrees, other values return `PdfError::InvalidRotation`.
+    pub fn set_rotation(&self, degrees: i64)
+    -> Result<(), Error>
+    {
+        if degrees % 90 != 0 {
+            return Err(PdfError::InvalidRotation.into());
+        }
+
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].rotation = degrees.rem_euclid(360);
+        Ok(())
+    }
+
+    /// Clips the visible content of the page to its trim box when the document is saved.
+    /// Useful to preview how the page looks after trimming and to check that nothing important
+    /// sits in the trim margin. Off by default, since it hides the bleed.
+    #[inline]
+    pub fn set_clip_to_trimbox(&self, clip_to_trimbox: bool)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].clip_to_trimbox = clip_to_trimbox;
+    }
+
+    /// Sets the trim box of the page (`/TrimBox`): the size of the finished page after trimming,
+    /// from the lower left corner and the size. Without a trim box, the media box is used.
+    pub fn set_trim_box<P: Into<Pt>>(&self, x: P, y: P, width: P, height: P)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].trimbox = Some(box_extents(x, y, width, height));
+    }
+
+    /// Sets the bleed box of the page (`/BleedBox`): the area the content extends to, so that
+    /// nothing white is left at the edges after trimming. Usually the trim box plus 3 mm on
+    /// each side. Not written if it isn't set.
+    pub fn set_bleed_box<P: Into<Pt>>(&self, x: P, y: P, width: P, height: P)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].bleedbox = Some(box_extents(x, y, width, height));
+    }
+
+    /// Sets the art box of the page (`/ArtBox`): the extent of the meaningful content of the
+    /// page, e.g. for placing the page in another document. Not written if it isn't set.
+    pub fn set_art_box<P: Into<Pt>>(&self, x: P, y: P, width: P, height: P)
+    {
+        let doc = self.document.upgrade().unwrap();
+        let mut doc = doc.borrow_mut();
+        doc.pages[self.page.0].artbox = Some(box_extents(x, y, width, height));
+    }
+
+    /// Draws the outlines of the page boxes (media box, bleed box, trim box, art box) as thin colored rectangles
+    /// on a new layer "Box guides", with a legend in the lower left corner of the trim box.
+    /// Intended for proofing only, to check that the boxes are set up correctly: the layer
+    /// can be hidden in the viewer, but it is printed. Returns the new layer.
+    ///
+    /// See also `PdfDocumentReference::with_box_guides` to add the guides to every page.
+    pub fn add_box_guides(&self)
+    -> PdfLayerReference
+    {
+        const LEGEND_FONT_SIZE: f64 = 6.0;
+        const LEGEND_MARGIN: f64 = 4.0;
+
+        let boxes = {
+            let doc = self.document.upgrade().unwrap();
+            let doc = doc.borrow();
+            doc.pages[self.page.0].page_boxes()
+        };
+
+        // adding a builtin font only fails if the font name is invalid
+        let document = PdfDocumentReference { document: self.document.upgrade().unwrap() };
+        let font = document.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+
+        let layer = self.add_layer("Box guides");
+        layer.set_outline_thickness(0.5);
+
+        let (legend_x, legend_y) = match boxes.iter().find(|&&(name, _)| name == "TrimBox") {
+            Some(&(_, trim_box)) => (Pt::from(trim_box.x).0 + LEGEND_MARGIN, Pt::from(trim_box.y).0 + LEGEND_MARGIN),
+            None => (LEGEND_MARGIN, LEGEND_MARGIN),
+        };
+
+        for (index, &(name, page_box)) in boxes.iter().enumerate() {
+            let (r, g, b) = BOX_GUIDE_COLORS.iter().find(|&&(box_name, _)| box_name == name).map_or((0.0, 0.0, 0.0), |&(_, color)| color);
+            let (llx, lly): (Pt, Pt) = (page_box.x.into(), page_box.y.into());
+            let (urx, ury): (Pt, Pt) = ((page_box.x + page_box.width).into(), (page_box.y + page_box.height).into());
+            let color = Color::Rgb(Rgb::new(r, g, b, None));
+            layer.set_outline_color(color.clone());
+            layer.set_fill_color(color);
+
+            layer.add_shape(Line {
+                points: vec![(llx, lly), (urx, lly), (urx, ury), (llx, ury)].into_iter()
+                    .map(|(x, y)| (Point { x, y }, false)).collect(),
+                is_closed: true,
+                has_fill: false,
+                has_stroke: true,
+                is_clipping_path: false,
+            });
+
+            // legend entry: colored square + name of the box
+            let y = legend_y + index as f64 * LEGEND_FONT_SIZE * 1.5;
+            layer.add_shape(Line {
+                points: vec![(0.0, 0.0), (LEGEND_FONT_SIZE, 0.0), (LEGEND_FONT_SIZE, LEGEND_FONT_SIZE), (0.0, LEGEND_FONT_SIZE)]
+                    .into_iter().map(|(x, dy)| (Point { x: Pt(legend_x + x), y: Pt(y + dy) }, false)).collect(),
+                is_closed: true,
+                has_fill: true,
+                has_stroke: false,
+                is_clipping_path: false,
+            });
+            layer.use_text(name, LEGEND_FONT_SIZE, Pt(legend_x + LEGEND_FONT_SIZE * 1.5).into(), Pt(y).into(), &font);
+        }
+
+        layer
+    }
+
     /// Adds a page and returns the index of the currently added page
     #[inline]
     pub fn add_layer<S>(&self, layer_name: S)
@@ -184,3 +580,174 @@ impl PdfPageReference {
         }
     }
 }
+
+/// Returns a page box from the lower left corner and the size as
+/// (lower left x, lower left y, upper right x, upper right y)
+fn box_extents<P: Into<Pt>>(x: P, y: P, width: P, height: P)
+-> (Pt, Pt, Pt, Pt)
+{
+    let (x, y): (Pt, Pt) = (x.into(), y.into());
+    (x, y, x + width.into(), y + height.into())
+}
+
+#[test]
+fn test_operations_to_streams() {
+    use lopdf::content::Operation;
+    let operations = vec![Operation::new("q", vec![]); 10];
+    assert_eq!(operations_to_streams(operations.clone(), None).len(), 1);
+    // every operation is 2 bytes ("q\n"), the streams are split after 3 operations
+    let streams = operations_to_streams(operations, Some(5));
+    let lengths = streams.iter().map(|s| s.content.len()).collect::<Vec<_>>();
+    assert_eq!(lengths, vec![6, 6, 6, 2]);
+}
+
+#[test]
+fn test_set_rotation() {
+    use {PdfDocument, Mm};
+    let (doc, page, _) = PdfDocument::new("rotation", Mm(210.0), Mm(297.0), "Layer 1");
+    let page = doc.get_page(page);
+    assert!(page.set_rotation(45).is_err());
+    page.set_rotation(-90).unwrap();
+    assert_eq!(doc.document.borrow().pages[0].rotation, 270);
+}
+
+#[test]
+fn test_page_boxes() {
+    use {PdfDocument, Mm};
+    let (doc, page, _) = PdfDocument::new("boxes", Mm(216.0), Mm(303.0), "Layer 1");
+    doc.get_page(page).set_trim_box(Mm(3.0), Mm(3.0), Mm(210.0), Mm(297.0));
+    assert_eq!(doc.document.borrow().pages[0].page_boxes().iter().map(|b| b.0).collect::<Vec<_>>(), vec!["MediaBox", "TrimBox"]);
+
+    doc.get_page(page).set_bleed_box(Mm(0.0), Mm(0.0), Mm(216.0), Mm(303.0));
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/BleedBox"));
+    assert!(!pdf.contains("/ArtBox"));
+    assert!(pdf.contains("/TrimBox[8.50"));
+
+    // the boxes can be given in points as well
+    let (doc, page, _) = PdfDocument::new("boxes", Mm(216.0), Mm(303.0), "Layer 1");
+    doc.get_page(page).set_art_box(Pt(10.0), Pt(20.0), Pt(100.0), Pt(200.0));
+    assert_eq!(doc.document.borrow().pages[0].artbox, Some((Pt(10.0), Pt(20.0), Pt(110.0), Pt(220.0))));
+}
+
+#[test]
+fn test_clip_to_trimbox() {
+    use {PdfDocument, Mm};
+    let (doc, page, _) = PdfDocument::new("clip", Mm(216.0), Mm(303.0), "Layer 1");
+    doc.get_page(page).set_trim_box(Mm(3.0), Mm(3.0), Mm(210.0), Mm(297.0));
+    doc.get_page(page).set_clip_to_trimbox(true);
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = String::from_utf8_lossy(&bytes);
+
+    // the clip path is the trim box, not the media box (reals are written with two decimals)
+    let clip = pdf.find(" re\nW\nn").map(|end| &pdf[pdf[..end].rfind("q\n").unwrap() + 2..end]).unwrap();
+    let values = clip.split_whitespace().map(|value| value.parse::<f64>().unwrap()).collect::<Vec<_>>();
+    let expected = [Mm(3.0), Mm(3.0), Mm(210.0), Mm(297.0)].iter().map(|mm| Pt::from(*mm).0).collect::<Vec<_>>();
+    assert_eq!(values.len(), 4);
+    for (value, expected) in values.iter().zip(expected.iter()) {
+        assert!((value - expected).abs() < 0.01);
+    }
+}
+
+#[test]
+fn test_set_thumbnail() {
+    use {PdfDocument, Mm, Px, ColorBits};
+
+    let (doc, page, _) = PdfDocument::new("thumbnail", Mm(210.0), Mm(297.0), "Layer 1");
+    let thumbnail = |width: usize, height: usize, color_space: ColorSpace, components: usize| {
+        ImageXObject::new(Px(width), Px(height), color_space, ColorBits::Bit8, false, None, None,
+                          vec![200; width * height * components])
+    };
+
+    match doc.get_page(page).set_thumbnail(thumbnail(16, 16, ColorSpace::Rgba, 4)) {
+        Err(Error::Pdf(PdfError::InvalidThumbnail)) => { },
+        other => panic!("expected InvalidThumbnail, got {:?}", other),
+    }
+
+    // too large thumbnails are downsampled to the maximum dimension
+    doc.get_page(page).set_thumbnail(thumbnail(512, 128, ColorSpace::Greyscale, 1)).unwrap();
+    {
+        let document = doc.document.borrow();
+        let image = document.pages[page.0].thumbnail.as_ref().unwrap();
+        assert_eq!((image.width.0, image.height.0), (MAX_THUMBNAIL_DIMENSION, 64));
+    }
+
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/Thumb "));
+    assert!(pdf.contains("/Width 256"));
+}
+
+#[test]
+fn test_page_with_operations() {
+    use lopdf::content::Operation;
+    use lopdf::Object::Real;
+    use {PdfDocument, Mm};
+
+    let (doc, _, _) = PdfDocument::new("operations", Mm(210.0), Mm(297.0), "Layer 1");
+    let operations = vec![
+        Operation::new("re", vec![Real(10.0), Real(20.0), Real(30.0), Real(40.0)]),
+        Operation::new("f", vec![]),
+    ];
+    let page = doc.add_pdf_page(PdfPage::with_operations(Pt(200.0), Pt(100.0), operations));
+    assert_eq!(page.0, 1);
+    assert!(doc.document.borrow().pages[1].layers.is_empty());
+
+    // the content stream of the page is exactly the operations, without any layer
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/MediaBox[0 0 200.00 100.00]"));
+    assert!(pdf.contains("stream\n10.00 20.00 30.00 40.00 re\nf\n"));
+}
+
+#[test]
+fn test_box_guides() {
+    use {PdfDocument, Mm};
+
+    let (doc, page, _) = PdfDocument::new("guides", Mm(216.0), Mm(303.0), "Layer 1");
+    let page_ref = doc.get_page(page);
+    page_ref.set_bleed_box(Mm(1.0), Mm(1.0), Mm(214.0), Mm(301.0));
+    page_ref.set_trim_box(Mm(3.0), Mm(3.0), Mm(210.0), Mm(297.0));
+    page_ref.add_box_guides();
+
+    let document = doc.document.borrow();
+    let layer = &document.pages[page.0].layers[1];
+    assert_eq!(layer.name, "Box guides");
+
+    // one outline (closed and stroked) and one legend entry per box, in the color of the box
+    let operators = |operator: &str| layer.operations.iter().filter(|op| op.operator == operator).count();
+    assert_eq!(operators("s"), 3);
+    assert_eq!(operators("Tj"), 3);
+    let outline_colors = layer.operations.iter()
+        .filter(|op| op.operator == "RG")
+        .map(|op| op.operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(outline_colors, vec![vec![0.0, 0.0, 1.0], vec![0.0, 0.6, 0.0], vec![1.0, 0.0, 0.0]]);
+
+    // the trim box outline starts at its lower left corner
+    let trim_box = layer.operations.iter().filter(|op| op.operator == "m").nth(4).unwrap();
+    let corner = trim_box.operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>();
+    let expected = Pt::from(Mm(3.0)).0;
+    assert!(corner.iter().all(|value| (value - expected).abs() < 1e-3));
+}
+
+#[test]
+fn test_page_size_boxes() {
+    use {PdfDocument, Mm};
+
+    let (doc, page, _) = PdfDocument::new("size", Mm(210.0), Mm(297.0), "Layer 1");
+    {
+        let document = doc.document.borrow();
+        let pdf_page = &document.pages[page.0];
+        assert!((pdf_page.width.0 - 595.28).abs() < 0.01);
+        assert!((pdf_page.height.0 - 841.89).abs() < 0.01);
+    }
+
+    // the width and height of the page are used for all page boxes
+    let bytes = doc.save_to_bytes().unwrap();
+    let pdf = ::std::string::String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("/MediaBox[0 0 595.28 841.89]"));
+    assert!(pdf.contains("/TrimBox[0.00 0.00 595.28 841.89]"));
+    assert!(pdf.contains("/CropBox[0 0 595.28 841.89]"));
+}
@@ -8,7 +8,7 @@ use glob_defines::{
     OP_COLOR_SET_FILL_CS_DEVICERGB, OP_COLOR_SET_FILL_CS_DEVICECMYK, OP_COLOR_SET_FILL_CS_DEVICEGRAY,
     OP_COLOR_SET_STROKE_CS_DEVICERGB, OP_COLOR_SET_STROKE_CS_DEVICECMYK, OP_COLOR_SET_STROKE_CS_DEVICEGRAY,
 };
-use IccProfileRef;
+use {IccProfileRef, ImageXObject, PdfPage, XObject};
 
 /// Tuple for differentiating outline and fill colors
 #[derive(Debug, Clone, PartialEq)]
@@ -54,8 +54,193 @@ impl Into<Operation> for PdfColor {
     }
 }
 
+/// Converts the color of a device color operator (`rg` / `RG`, `k` / `K`, `g` / `G`) into
+/// the given color space, see `Color::convert_to`. Returns `None` for other operators.
+pub(crate) fn convert_color_operation(op: &Operation, color_space: ColorSpace)
+-> Option<Operation>
+{
+    let source = device_operator_color_space(&op.operator)?;
+    let color = device_color(source, &operand_values(op)?)?.convert_to(color_space);
+
+    let is_fill = op.operator.chars().all(|c| c.is_ascii_lowercase());
+    Some(if is_fill { PdfColor::FillColor(color) } else { PdfColor::OutlineColor(color) }.into())
+}
+
+/// Converts all device colors of the operations into the given color space: the device color
+/// operators (see `convert_color_operation`) and the colors set with `sc` / `scn` after a device
+/// color space was selected with `cs` (the `cs` operands are changed as well). The current
+/// color space is saved and restored with `q` / `Q`. Colors in other color spaces (ICC based,
+/// patterns, separations) are left as they are.
+pub(crate) fn convert_color_operations(operations: &mut [Operation], color_space: ColorSpace)
+{
+    use lopdf::Object::{Name, Real};
+
+    let target_name: &'static str = color_space.into();
+    // (fill, stroke) color space of the original content, `None` if it is not a device color space
+    let mut current = (Some(ColorSpace::Greyscale), Some(ColorSpace::Greyscale));
+    let mut saved = Vec::new();
+
+    for op in operations.iter_mut() {
+        let is_fill = op.operator.chars().all(|c| c.is_ascii_lowercase());
+        match op.operator.as_str() {
+            "q" => saved.push(current),
+            "Q" => if let Some(state) = saved.pop() { current = state; },
+            "cs" | "CS" => {
+                let source = op.operands.first()
+                    .and_then(|operand| operand.as_name_str().ok())
+                    .and_then(device_color_space);
+                if source.is_some() {
+                    op.operands = vec![Name(target_name.into())];
+                }
+                if is_fill { current.0 = source; } else { current.1 = source; }
+            },
+            "sc" | "scn" | "SC" | "SCN" => {
+                let source = if is_fill { current.0 } else { current.1 };
+                let color = source.and_then(|source| device_color(source, &operand_values(op)?));
+                if let Some(color) = color {
+                    op.operands = color.convert_to(color_space).into_vec().into_iter().map(Real).collect();
+                }
+            },
+            _ => if let Some(converted) = convert_color_operation(op, color_space) {
+                let source = device_operator_color_space(&op.operator);
+                if is_fill { current.0 = source; } else { current.1 = source; }
+                *op = converted;
+            },
+        }
+    }
+}
+
+/// Converts the device colors of the layers and of the shading patterns (gradients) of the
+/// page into the given color space. Images and shadings that can't be converted are left
+/// as they are, see `page_has_unconvertible_colors`.
+pub(crate) fn convert_page_colors(page: &mut PdfPage, color_space: ColorSpace)
+{
+    for layer in page.layers.iter_mut() {
+        convert_color_operations(&mut layer.operations, color_space);
+    }
+    for pattern in page.resources.patterns.patterns_mut() {
+        pattern.convert_device_colors(color_space);
+    }
+}
+
+/// Returns if the images or shading patterns of the page use a device color space other
+/// than `allowed` and DeviceGray that `convert_page_colors` can't convert
+pub(crate) fn page_has_unconvertible_colors(page: &PdfPage, allowed: ColorSpace)
+-> bool
+{
+    let is_other = |color_space: ColorSpace| color_space != ColorSpace::Greyscale && color_space != allowed;
+
+    let images = page.resources.xobjects.objects.values().any(|xobject| match *xobject {
+        XObject::Image(ref image) => is_other(image_color_space(image)),
+        _ => false,
+    });
+    let shadings = page.resources.patterns.patterns().any(|pattern| {
+        pattern.device_color_space().map_or(false, is_other) && !pattern.clone().convert_device_colors(allowed)
+    });
+
+    images || shadings
+}
+
+/// Returns if the layers, images or shading patterns of the page use a device color space
+/// other than `allowed` and DeviceGray
+pub(crate) fn page_has_other_device_colors(page: &PdfPage, allowed: ColorSpace)
+-> bool
+{
+    let is_other = |color_space: ColorSpace| color_space != ColorSpace::Greyscale && color_space != allowed;
+
+    page.layers.iter().any(|layer| !other_device_color_spaces(&layer.operations, allowed).is_empty())
+        || page.resources.xobjects.objects.values().any(|xobject| match *xobject {
+            XObject::Image(ref image) => is_other(image_color_space(image)),
+            _ => false,
+        })
+        || page.resources.patterns.patterns().any(|pattern| pattern.device_color_space().map_or(false, is_other))
+}
+
+/// Returns the device color space of the image, the base color space for indexed images
+fn image_color_space(image: &ImageXObject)
+-> ColorSpace
+{
+    match image.color_space {
+        ColorSpace::Rgb | ColorSpace::Rgba | ColorSpace::Palette => ColorSpace::Rgb,
+        ColorSpace::Cmyk => ColorSpace::Cmyk,
+        ColorSpace::Greyscale | ColorSpace::GreyscaleAlpha => ColorSpace::Greyscale,
+    }
+}
+
+/// Returns the device color spaces (other than `allowed` and DeviceGray) the operations
+/// use, through device color operators or `cs` / `CS`
+pub(crate) fn other_device_color_spaces(operations: &[Operation], allowed: ColorSpace)
+-> Vec<ColorSpace>
+{
+    let mut found = Vec::new();
+    for op in operations {
+        let color_space = match op.operator.as_str() {
+            "cs" | "CS" => op.operands.first()
+                .and_then(|operand| operand.as_name_str().ok())
+                .and_then(device_color_space),
+            operator => device_operator_color_space(operator),
+        };
+        match color_space {
+            Some(ColorSpace::Greyscale) | None => { },
+            Some(color_space) if color_space == allowed || found.contains(&color_space) => { },
+            Some(color_space) => found.push(color_space),
+        }
+    }
+    found
+}
+
+/// Returns the color space of a device color operator (`rg`, `k`, `g` and the stroking variants)
+fn device_operator_color_space(operator: &str)
+-> Option<ColorSpace>
+{
+    match operator {
+        "rg" | "RG" => Some(ColorSpace::Rgb),
+        "k" | "K" => Some(ColorSpace::Cmyk),
+        "g" | "G" => Some(ColorSpace::Greyscale),
+        _ => None,
+    }
+}
+
+/// Returns the color space for the name of a device color space (`/DeviceRGB`, ...)
+pub(crate) fn device_color_space(name: &str)
+-> Option<ColorSpace>
+{
+    match name {
+        "DeviceRGB" => Some(ColorSpace::Rgb),
+        "DeviceCMYK" => Some(ColorSpace::Cmyk),
+        "DeviceGray" => Some(ColorSpace::Greyscale),
+        _ => None,
+    }
+}
+
+/// Returns the color for the components in a device color space, `None` if the number
+/// of components doesn't match the color space
+pub(crate) fn device_color(color_space: ColorSpace, values: &[f64])
+-> Option<Color>
+{
+    match (color_space, values) {
+        (ColorSpace::Rgb, &[r, g, b]) => Some(Color::Rgb(Rgb::new(r, g, b, None))),
+        (ColorSpace::Cmyk, &[c, m, y, k]) => Some(Color::Cmyk(Cmyk::new(c, m, y, k, None))),
+        (ColorSpace::Greyscale, &[gray]) => Some(Color::Greyscale(Greyscale::new(gray, None))),
+        _ => None,
+    }
+}
+
+/// Returns the operands as numbers, `None` if an operand is not a number
+fn operand_values(op: &Operation)
+-> Option<Vec<f64>>
+{
+    use lopdf::Object::{Integer, Real};
+
+    op.operands.iter().map(|operand| match *operand {
+        Integer(i) => Some(i as f64),
+        Real(r) => Some(r),
+        _ => None,
+    }).collect()
+}
+
 /// Color space (enum for marking the number of bits a color has)
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ColorSpace {
     Rgb,
     Rgba,
@@ -144,16 +329,19 @@ pub enum Color {
 
 impl Color {
 
-    /// Consumes the color and converts into into a vector of numbers
+    /// Consumes the color and converts it into a vector of numbers.
+    /// The components are clamped to the valid range of 0.0 - 1.0 (`NaN` becomes 0.0).
     pub fn into_vec(self)
     -> Vec<f64>
     {
-        match self {
+        let components = match self {
             Color::Rgb(rgb) => { vec![rgb.r, rgb.g, rgb.b ]},
             Color::Cmyk(cmyk) => { vec![cmyk.c, cmyk.m, cmyk.y, cmyk.k ]},
             Color::Greyscale(gs) => { vec![gs.percent]},
             Color::SpotColor(spot) => { vec![spot.c, spot.m, spot.y, spot.k ]},
-        }
+        };
+
+        components.into_iter().map(|c| if c.is_nan() { 0.0 } else { c.clamp(0.0, 1.0) }).collect()
     }
 
     /// Returns if the color has an icc profile attached
@@ -167,6 +355,49 @@ impl Color {
             Color::SpotColor(_) => None,
         }
     }
+
+    /// Converts the color into the given color space (`Rgb`, `Cmyk` or `Greyscale`, the alpha
+    /// variants are treated as their base color space), using the naive device conversion
+    /// formulas. Colors with an ICC profile and spot colors are in an explicit color space
+    /// and are returned unchanged, as well as colors that are already in the target color space.
+    pub fn convert_to(self, color_space: ColorSpace)
+    -> Color
+    {
+        if self.get_icc_profile().map_or(true, |icc| icc.is_some()) {
+            return self;
+        }
+
+        // normalize to rgb first, then convert into the target color space
+        let (r, g, b) = match self {
+            Color::Rgb(ref rgb) => (rgb.r, rgb.g, rgb.b),
+            Color::Cmyk(ref cmyk) => (
+                (1.0 - cmyk.c) * (1.0 - cmyk.k),
+                (1.0 - cmyk.m) * (1.0 - cmyk.k),
+                (1.0 - cmyk.y) * (1.0 - cmyk.k),
+            ),
+            Color::Greyscale(ref gs) => (gs.percent, gs.percent, gs.percent),
+            Color::SpotColor(_) => return self,
+        };
+
+        match (color_space, self) {
+            (ColorSpace::Rgb, c @ Color::Rgb(_)) | (ColorSpace::Rgba, c @ Color::Rgb(_)) |
+            (ColorSpace::Cmyk, c @ Color::Cmyk(_)) |
+            (ColorSpace::Greyscale, c @ Color::Greyscale(_)) | (ColorSpace::GreyscaleAlpha, c @ Color::Greyscale(_)) => c,
+            (ColorSpace::Rgb, _) | (ColorSpace::Rgba, _) => Color::Rgb(Rgb::new(r, g, b, None)),
+            (ColorSpace::Cmyk, _) => {
+                let k = 1.0 - r.max(g).max(b);
+                if k >= 1.0 {
+                    Color::Cmyk(Cmyk::new(0.0, 0.0, 0.0, 1.0, None))
+                } else {
+                    Color::Cmyk(Cmyk::new((1.0 - r - k) / (1.0 - k), (1.0 - g - k) / (1.0 - k), (1.0 - b - k) / (1.0 - k), k, None))
+                }
+            },
+            (ColorSpace::Greyscale, _) | (ColorSpace::GreyscaleAlpha, _) => {
+                Color::Greyscale(Greyscale::new(0.299 * r + 0.587 * g + 0.114 * b, None))
+            },
+            (ColorSpace::Palette, c) => c,
+        }
+    }
 }
 
 /// RGB color
@@ -242,3 +473,67 @@ impl SpotColor {
         Self { c, m, y, k }
     }
 }
+
+#[test]
+fn test_color_convert_to() {
+    let red = Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None));
+    assert_eq!(red.clone().convert_to(ColorSpace::Cmyk), Color::Cmyk(Cmyk::new(0.0, 1.0, 1.0, 0.0, None)));
+    let black = Color::Greyscale(Greyscale::new(0.0, None));
+    assert_eq!(black.convert_to(ColorSpace::Cmyk), Color::Cmyk(Cmyk::new(0.0, 0.0, 0.0, 1.0, None)));
+    let cmyk = Color::Cmyk(Cmyk::new(0.0, 1.0, 1.0, 0.0, None));
+    assert_eq!(cmyk.convert_to(ColorSpace::Rgb), red);
+}
+
+#[test]
+fn test_color_operators() {
+    let fill: Operation = PdfColor::FillColor(Color::Cmyk(Cmyk::new(0.0, 1.5, -0.5, 0.2, None))).into();
+    assert_eq!(fill.operator, "k");
+    let values = fill.operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>();
+    assert_eq!(values, vec![0.0, 1.0, 0.0, 0.2]);
+
+    let outline: Operation = PdfColor::OutlineColor(Color::Greyscale(Greyscale::new(0.5, None))).into();
+    assert_eq!(outline.operator, "G");
+    let outline: Operation = PdfColor::OutlineColor(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None))).into();
+    assert_eq!(outline.operator, "RG");
+}
+
+#[test]
+fn test_convert_color_operation() {
+    use lopdf::Object::{Integer, Real};
+
+    let stroke = Operation::new("RG", vec![Integer(1), Real(0.0), Real(0.0)]);
+    let converted = convert_color_operation(&stroke, ColorSpace::Cmyk).unwrap();
+    assert_eq!(converted.operator, "K");
+    let values = converted.operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>();
+    assert_eq!(values, vec![0.0, 1.0, 1.0, 0.0]);
+
+    assert!(convert_color_operation(&Operation::new("re", vec![Integer(0); 4]), ColorSpace::Cmyk).is_none());
+}
+
+#[test]
+fn test_convert_color_operations() {
+    use lopdf::Object::{Name, Real};
+
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("cs", vec![Name("DeviceRGB".into())]),
+        Operation::new("sc", vec![Real(1.0), Real(0.0), Real(0.0)]),
+        Operation::new("Q", vec![]),
+        // the color space is DeviceGray again after the restore
+        Operation::new("sc", vec![Real(0.5)]),
+        Operation::new("CS", vec![Name("CS0".into())]),
+        Operation::new("SCN", vec![Real(0.2)]),
+    ];
+    assert_eq!(other_device_color_spaces(&operations, ColorSpace::Cmyk), vec![ColorSpace::Rgb]);
+    convert_color_operations(&mut operations, ColorSpace::Cmyk);
+
+    assert_eq!(operations[1].operands[0].as_name_str().ok(), Some("DeviceCMYK"));
+    let values = operations[2].operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>();
+    assert_eq!(values, vec![0.0, 1.0, 1.0, 0.0]);
+    let values = operations[4].operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>();
+    assert_eq!(values.len(), 4);
+    // colors in other color spaces are not touched
+    assert_eq!(operations[5].operands[0].as_name_str().ok(), Some("CS0"));
+    assert_eq!(operations[6].operands.len(), 1);
+    assert!(other_device_color_spaces(&operations, ColorSpace::Cmyk).is_empty());
+}
@@ -0,0 +1,151 @@
+//! Validation of the operator sequence of a layer, catches malformed content streams
+//! (e.g. a `begin_text_section` without `end_text_section`) before the document is saved.
+
+use lopdf::content::Operation;
+use std::fmt;
+use indices::{PdfLayerIndex, PdfPageIndex};
+
+/// What is wrong with an operation in a content stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentErrorKind {
+    /// `BT` inside of a text object (text objects can't be nested)
+    NestedTextObject,
+    /// `ET` without a matching `BT`
+    UnmatchedEndText,
+    /// `BT` without a matching `ET` at the end of the layer
+    UnclosedTextObject,
+    /// `Q` without a matching `q`
+    UnmatchedRestoreGraphicsState,
+    /// `q` without a matching `Q` at the end of the layer
+    UnclosedGraphicsState,
+    /// Text showing or positioning operator (`Tj`, `Td`, ...) outside of `BT` / `ET`
+    TextOutsideTextObject(String),
+    /// Color operator with the wrong number of operands
+    WrongOperandCount {
+        /// The color operator (`g`, `rg`, `k`, ...)
+        operator: String,
+        /// Number of operands the operator needs
+        expected: usize,
+        /// Number of operands the operator has
+        found: usize,
+    },
+}
+
+impl fmt::Display for ContentErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ContentErrorKind::*;
+        match *self {
+            NestedTextObject => write!(f, "BT inside of a text object"),
+            UnmatchedEndText => write!(f, "ET without a matching BT"),
+            UnclosedTextObject => write!(f, "BT without a matching ET"),
+            UnmatchedRestoreGraphicsState => write!(f, "Q without a matching q"),
+            UnclosedGraphicsState => write!(f, "q without a matching Q"),
+            TextOutsideTextObject(ref op) => write!(f, "text operator {} outside of BT / ET", op),
+            WrongOperandCount { ref operator, expected, found } =>
+                write!(f, "operator {} needs {} operands, but has {}", operator, expected, found),
+        }
+    }
+}
+
+/// Malformed operation in the content stream of a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentError {
+    /// Page of the layer
+    pub page: PdfPageIndex,
+    /// Layer containing the operation
+    pub layer: PdfLayerIndex,
+    /// Index of the offending operation in the layer. For unclosed `BT` / `q`,
+    /// this is the number of operations in the layer (the end of the layer).
+    pub operation_index: usize,
+    /// What is wrong
+    pub kind: ContentErrorKind,
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "page {}, layer {}, operation {}: {}", self.page.0, self.layer.0, self.operation_index, self.kind)
+    }
+}
+
+impl ::std::error::Error for ContentError {}
+
+/// Checks that the operations are well-formed: balanced `BT` / `ET` and `q` / `Q`,
+/// no text operators outside of text objects and the right operand count for color operators.
+/// Returns the index of the offending operation together with the error.
+pub(crate) fn validate_operations(operations: &[Operation])
+-> Vec<(usize, ContentErrorKind)>
+{
+    use self::ContentErrorKind::*;
+
+    let mut errors = Vec::new();
+    let mut in_text_object = false;
+    let mut graphics_state_depth = 0;
+
+    for (index, op) in operations.iter().enumerate() {
+        match op.operator.as_str() {
+            "BT" => {
+                if in_text_object { errors.push((index, NestedTextObject)); }
+                in_text_object = true;
+            },
+            "ET" => {
+                if !in_text_object { errors.push((index, UnmatchedEndText)); }
+                in_text_object = false;
+            },
+            "q" => graphics_state_depth += 1,
+            "Q" => {
+                if graphics_state_depth == 0 {
+                    errors.push((index, UnmatchedRestoreGraphicsState));
+                } else {
+                    graphics_state_depth -= 1;
+                }
+            },
+            "Tj" | "TJ" | "'" | "\"" | "Td" | "TD" | "Tm" | "T*" if !in_text_object => {
+                errors.push((index, TextOutsideTextObject(op.operator.clone())));
+            },
+            _ => { },
+        }
+
+        let expected_operands = match op.operator.as_str() {
+            "g" | "G" => Some(1),
+            "rg" | "RG" => Some(3),
+            "k" | "K" => Some(4),
+            _ => None,
+        };
+
+        if let Some(expected) = expected_operands {
+            if op.operands.len() != expected {
+                errors.push((index, WrongOperandCount {
+                    operator: op.operator.clone(),
+                    expected,
+                    found: op.operands.len(),
+                }));
+            }
+        }
+    }
+
+    if in_text_object {
+        errors.push((operations.len(), UnclosedTextObject));
+    }
+
+    if graphics_state_depth > 0 {
+        errors.push((operations.len(), UnclosedGraphicsState));
+    }
+
+    errors
+}
+
+#[test]
+fn test_validate_operations() {
+    let ops = vec![
+        Operation::new("Tj", vec![]),
+        Operation::new("q", vec![]),
+        Operation::new("BT", vec![]),
+        Operation::new("rg", vec![0.into(), 0.into()]),
+    ];
+    assert_eq!(validate_operations(&ops), vec![
+        (0, ContentErrorKind::TextOutsideTextObject("Tj".into())),
+        (3, ContentErrorKind::WrongOperandCount { operator: "rg".into(), expected: 3, found: 2 }),
+        (4, ContentErrorKind::UnclosedTextObject),
+        (4, ContentErrorKind::UnclosedGraphicsState),
+    ]);
+}
@@ -19,6 +19,34 @@ pub enum CurTransMat {
     Scale(f64, f64),
     /// Identity matrix
     Identity,
+    /// Any other matrix (`a b c d e f`), e.g. several transformations combined with `then`
+    Raw([f64; 6]),
+}
+
+impl CurTransMat {
+
+    /// Combines this transformation with `next` into one matrix. The result transforms
+    /// content the same way as calling `layer.set_ctm()` with this matrix and then with `next`:
+    /// `Translate(x, y).then(Rotate(45.0))` rotates the content around the point (x, y).
+    pub fn then(self, next: CurTransMat)
+    -> CurTransMat
+    {
+        CurTransMat::Raw(multiply(next.into(), self.into()))
+    }
+}
+
+/// Multiplies two transformation matrices (`a` is applied first)
+pub(crate) fn multiply(a: [f64; 6], b: [f64; 6])
+-> [f64; 6]
+{
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
 }
 
 /// Text matrix. Text placement is a bit different, but uses the same
@@ -77,6 +105,7 @@ impl Into<[f64; 6]> for CurTransMat {
             Identity => { 
                 [ 1.0, 0.0, 0.0, 1.0, 0.0, 0.0 ] 
             }
+            Raw(matrix) => matrix,
         }
     }
 }
@@ -131,3 +160,24 @@ fn test_ctm_translate()
     let ctm_rot_arr: [f64; 6] = ctm_rot.into();
     assert_eq!([0.8660254037844384, 0.5000000000000004, -0.5000000000000004, 0.8660254037844384, 0.0, 0.0], ctm_rot_arr);
 }
+
+#[test]
+fn test_multiply() {
+    // scale, then translate
+    let ctm = multiply([2.0, 0.0, 0.0, 3.0, 0.0, 0.0], [1.0, 0.0, 0.0, 1.0, 10.0, 20.0]);
+    assert_eq!(ctm, [2.0, 0.0, 0.0, 3.0, 10.0, 20.0]);
+    // translate, then scale
+    let ctm = multiply([1.0, 0.0, 0.0, 1.0, 10.0, 20.0], [2.0, 0.0, 0.0, 3.0, 0.0, 0.0]);
+    assert_eq!(ctm, [2.0, 0.0, 0.0, 3.0, 20.0, 60.0]);
+}
+
+#[test]
+fn test_ctm_then() {
+    // translate, then rotate: the origin of the rotated content is the translated point
+    let ctm: [f64; 6] = CurTransMat::Translate(Mm(10.0), Mm(0.0)).then(CurTransMat::Rotate(90.0)).into();
+    let x: Pt = Mm(10.0).into();
+    assert!((ctm[4] - x.0).abs() < 1e-9 && ctm[5].abs() < 1e-9);
+    // same result as two separate `cm` operators
+    let separate = multiply(CurTransMat::Rotate(90.0).into(), CurTransMat::Translate(Mm(10.0), Mm(0.0)).into());
+    assert_eq!(ctm, separate);
+}
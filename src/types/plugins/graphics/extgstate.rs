@@ -19,7 +19,7 @@
 //! let mut new_state = ExtendedGraphicsState::default();
 //! new_state.overprint_stroke = true;
 //!
-//! // it is best to put the next lines in a seperate function
+//! // it is best to put the next lines in a separate function
 //! // A PdfLayerReferences contains the indices of the page and the layer
 //! // as well as a `std::sync::Weak` reference to the document.
 //! // This is why you need the braces, otherwise, you'll trigger a deadlock
@@ -103,11 +103,28 @@ impl ExtendedGraphicsStateList {
     pub fn add_graphics_state(&mut self, added_state: ExtendedGraphicsState)
     -> ExtendedGraphicsStateRef
     {
-        let gs_ref = ExtendedGraphicsStateRef::new(self.all_graphics_states.len());
+        // names of removed graphics states leave gaps, so the length may already be taken
+        let mut index = self.all_graphics_states.len();
+        while self.all_graphics_states.contains_key(&format!("GS{:?}", index)) { index += 1; }
+        self.add_graphics_state_with_index(index, added_state)
+    }
+
+    /// Adds a graphics state under the name for the given index (see `ExtendedGraphicsStateRef::new`).
+    /// The caller has to make sure that the name is not taken yet.
+    pub(crate) fn add_graphics_state_with_index(&mut self, index: usize, added_state: ExtendedGraphicsState)
+    -> ExtendedGraphicsStateRef
+    {
+        let gs_ref = ExtendedGraphicsStateRef::new(index);
         self.all_graphics_states.insert(gs_ref.gs_name.clone(), (self.latest_graphics_state.0, added_state.clone()));
         self.latest_graphics_state = (self.latest_graphics_state.0, added_state);
         gs_ref
     }
+
+    /// Removes all graphics states whose names are not in `used_names`
+    pub(crate) fn retain_used(&mut self, used_names: &HashSet<String>)
+    {
+        self.all_graphics_states.retain(|name, _| used_names.contains(name));
+    }
 }
 
 impl Into<lopdf::Dictionary> for ExtendedGraphicsStateList {
@@ -671,11 +688,11 @@ impl Into<lopdf::Object> for ExtendedGraphicsState {
         }
 
         if self.changed_fields.contains(CURRENT_FILL_ALPHA) {
-            gs_operations.push(("CA".to_string(), self.current_fill_alpha.into()));
+            gs_operations.push(("ca".to_string(), self.current_fill_alpha.into()));
         }
 
         if self.changed_fields.contains(CURRENT_STROKE_ALPHA) {
-            gs_operations.push(("ca".to_string(), self.current_stroke_alpha.into()));
+            gs_operations.push(("CA".to_string(), self.current_stroke_alpha.into()));
         }
 
         if self.changed_fields.contains(BLEND_MODE) {
@@ -1396,10 +1413,14 @@ pub enum LineJoinStyle {
     /// Bevel join. The two segments are finished with butt caps (see “Line Cap Style”
     /// on page 216) and the resulting notch beyond the ends of the segments is filled
     /// with a triangle.
+    Bevel,
+    /// Bevel join (old name of `Bevel`)
+    #[deprecated(note = "use `LineJoinStyle::Bevel`")]
     Limit,
 }
 
 impl Into<i64> for LineJoinStyle {
+    #[allow(deprecated)]
     fn into(self)
     -> i64
     {
@@ -1407,7 +1428,7 @@ impl Into<i64> for LineJoinStyle {
         match self {
             Miter => 0,
             Round => 1,
-            Limit => 2,
+            Bevel | Limit => 2,
         }
     }
 }
@@ -1509,6 +1530,33 @@ impl LineDashPattern {
     }
 }
 
+/// Common line dash patterns, so that the dash array doesn't have to be constructed by hand.
+/// Use `LineDashPattern` directly for custom patterns.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DashPattern {
+    /// Solid line (resets the dash pattern)
+    Solid,
+    /// Dotted line: `[1 2] 0`
+    Dotted,
+    /// Dashed line: `[6 3] 0`
+    Dashed,
+    /// Alternating dashes and dots: `[6 3 1 3] 0`
+    DashDot,
+}
+
+impl From<DashPattern> for LineDashPattern {
+    fn from(preset: DashPattern)
+    -> Self
+    {
+        match preset {
+            DashPattern::Solid => LineDashPattern::default(),
+            DashPattern::Dotted => LineDashPattern::new(0, Some(1), Some(2), None, None, None, None),
+            DashPattern::Dashed => LineDashPattern::new(0, Some(6), Some(3), None, None, None, None),
+            DashPattern::DashDot => LineDashPattern::new(0, Some(6), Some(3), Some(1), Some(3), None, None),
+        }
+    }
+}
+
 // conversion into a dash array for reuse in operation / gs dictionary
 impl Into<(Vec<i64>, i64)> for LineDashPattern {
     #[cfg_attr(feature = "cargo-clippy", allow(never_loop))]
@@ -1572,9 +1620,29 @@ impl Into<lopdf::Object> for LineDashPattern {
     -> lopdf::Object
     {
         use lopdf::Object::*;
+        // `/D` entry of a graphics state: `[[dash array] phase]`
         let (dash_array, offset) = self.into();
-        let mut dash_array_ints: Vec<lopdf::Object> = dash_array.into_iter().map(Integer).collect();
-        dash_array_ints.push(Integer(offset));
-        Array(dash_array_ints)
+        let dash_array_ints: Vec<lopdf::Object> = dash_array.into_iter().map(Integer).collect();
+        Array(vec![Array(dash_array_ints), Integer(offset)])
+    }
+}
+
+#[test]
+fn test_line_dash_pattern() {
+    let dotted: Operation = LineDashPattern::from(DashPattern::Dotted).into();
+    assert_eq!(dotted.operator, "d");
+    match dotted.operands[..] {
+        [Array(ref dashes), Integer(0)] => assert!(matches!(dashes[..], [Integer(1), Integer(2)])),
+        ref other => panic!("unexpected operands {:?}", other),
+    }
+
+    // the solid pattern resets the dashes: `[] 0 d`
+    let solid: Operation = LineDashPattern::default().into();
+    assert!(matches!(solid.operands[..], [Array(ref dashes), Integer(0)] if dashes.is_empty()));
+
+    let graphics_state: lopdf::Object = LineDashPattern::from(DashPattern::Dashed).into();
+    match graphics_state {
+        Array(ref entry) => assert!(matches!(entry[..], [Array(ref dashes), Integer(0)] if dashes.len() == 2)),
+        ref other => panic!("unexpected /D entry {:?}", other),
     }
 }
@@ -0,0 +1,257 @@
+//! Gradients (smooth shadings), used as fill color via a shading pattern,
+//! see `PdfLayerReference::set_fill_gradient`
+
+use lopdf;
+use lopdf::Object::{Array, Boolean, Dictionary, Integer, Name, Real};
+use std::iter::FromIterator;
+use {Cmyk, Color, ColorSpace, Greyscale, Point, Pt, Rgb};
+
+/// One color of a gradient
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradientStop {
+    /// Position of the color on the gradient, from 0.0 (start) to 1.0 (end)
+    pub offset: f64,
+    /// Color at the position
+    pub color: Color,
+}
+
+impl GradientStop {
+
+    /// Creates a new gradient stop
+    pub fn new(offset: f64, color: Color)
+    -> Self
+    {
+        Self { offset, color }
+    }
+}
+
+/// Geometry of a gradient, in page coordinates
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GradientShape {
+    /// Linear gradient: the colors change along the line from `start` to `end`
+    /// and are constant perpendicular to it (shading type 2)
+    Axial {
+        /// Point of the first color (offset 0.0)
+        start: Point,
+        /// Point of the last color (offset 1.0)
+        end: Point,
+    },
+    /// Radial gradient: the colors change from the start circle to the end circle
+    /// (shading type 3). For a fade from a center point to the edge of a circle, use
+    /// a start radius of 0 with the same center for both circles.
+    Radial {
+        /// Center of the start circle (offset 0.0)
+        start_center: Point,
+        /// Radius of the start circle
+        start_radius: Pt,
+        /// Center of the end circle (offset 1.0)
+        end_center: Point,
+        /// Radius of the end circle
+        end_radius: Pt,
+    },
+}
+
+/// Smooth transition between two or more colors
+///
+/// All colors are converted into the color space of the first stop (or the default color
+/// space of the document), ICC profiles of the colors are not used. Spot colors are
+/// drawn with their CMYK values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    /// Geometry of the gradient
+    pub shape: GradientShape,
+    /// Colors of the gradient, ordered by their offset
+    pub stops: Vec<GradientStop>,
+    /// Should the first / last color be continued beyond the start / end of the gradient?
+    /// Default: true for both ends
+    pub extend: (bool, bool),
+}
+
+impl Gradient {
+
+    /// Creates a linear gradient from `start` to `end`. For example, a gradient from the
+    /// top to the bottom of a rectangle fills it from the first to the last color.
+    pub fn axial(start: Point, end: Point, stops: Vec<GradientStop>)
+    -> Self
+    {
+        Self { shape: GradientShape::Axial { start, end }, stops, extend: (true, true) }
+    }
+
+    /// Creates a radial gradient from the start circle to the end circle
+    pub fn radial(start_center: Point, start_radius: Pt, end_center: Point, end_radius: Pt, stops: Vec<GradientStop>)
+    -> Self
+    {
+        let shape = GradientShape::Radial { start_center, start_radius, end_center, end_radius };
+        Self { shape, stops, extend: (true, true) }
+    }
+
+    /// Sets if the first / last color is continued beyond the start / end of the gradient
+    #[inline]
+    pub fn with_extend(mut self, extend_start: bool, extend_end: bool)
+    -> Self
+    {
+        self.extend = (extend_start, extend_end);
+        self
+    }
+
+    /// Returns the shading pattern dictionary of the gradient, with the colors in the
+    /// given color space (or the color space of the first stop, if `None`)
+    pub(crate) fn into_pattern(self, color_space: Option<ColorSpace>)
+    -> lopdf::Dictionary
+    {
+        let color_space = match color_space {
+            Some(ColorSpace::Rgb) | Some(ColorSpace::Rgba) => ColorSpace::Rgb,
+            Some(ColorSpace::Cmyk) => ColorSpace::Cmyk,
+            Some(ColorSpace::Greyscale) | Some(ColorSpace::GreyscaleAlpha) => ColorSpace::Greyscale,
+            _ => match self.stops.first().map(|stop| &stop.color) {
+                Some(Color::Cmyk(_)) | Some(Color::SpotColor(_)) => ColorSpace::Cmyk,
+                Some(Color::Greyscale(_)) => ColorSpace::Greyscale,
+                _ => ColorSpace::Rgb,
+            },
+        };
+        let device_name = match color_space {
+            ColorSpace::Cmyk => "DeviceCMYK",
+            ColorSpace::Greyscale => "DeviceGray",
+            _ => "DeviceRGB",
+        };
+
+        let mut stops = self.stops.into_iter()
+            .map(|stop| (stop.offset.clamp(0.0, 1.0), device_components(stop.color, color_space)))
+            .collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+        let (shading_type, coords) = match self.shape {
+            GradientShape::Axial { start, end } => (2, vec![start.x.0, start.y.0, end.x.0, end.y.0]),
+            GradientShape::Radial { start_center, start_radius, end_center, end_radius } => (3, vec![
+                start_center.x.0, start_center.y.0, start_radius.0.max(0.0),
+                end_center.x.0, end_center.y.0, end_radius.0.max(0.0),
+            ]),
+        };
+
+        let shading = lopdf::Dictionary::from_iter(vec![
+            ("ShadingType", Integer(shading_type)),
+            ("ColorSpace", Name(device_name.into())),
+            ("Coords", Array(coords.into_iter().map(Real).collect())),
+            ("Function", Dictionary(color_function(stops))),
+            ("Extend", Array(vec![Boolean(self.extend.0), Boolean(self.extend.1)])),
+        ]);
+
+        lopdf::Dictionary::from_iter(vec![
+            ("Type", Name("Pattern".into())),
+            ("PatternType", Integer(2)),
+            ("Shading", Dictionary(shading)),
+        ])
+    }
+}
+
+/// Returns the components of the color in the (device) color space, ignoring ICC profiles
+fn device_components(color: Color, color_space: ColorSpace)
+-> Vec<f64>
+{
+    let device_color = match color {
+        Color::Rgb(rgb) => Color::Rgb(Rgb::new(rgb.r, rgb.g, rgb.b, None)),
+        Color::Cmyk(cmyk) => Color::Cmyk(Cmyk::new(cmyk.c, cmyk.m, cmyk.y, cmyk.k, None)),
+        Color::Greyscale(gs) => Color::Greyscale(Greyscale::new(gs.percent, None)),
+        Color::SpotColor(spot) => Color::Cmyk(Cmyk::new(spot.c, spot.m, spot.y, spot.k, None)),
+    };
+    device_color.convert_to(color_space).into_vec()
+}
+
+/// Returns the function that maps the position on the gradient (0.0 - 1.0) to the color:
+/// an exponential interpolation function (type 2) between two colors, or a stitching function
+/// (type 3) made of one interpolation function for each pair of neighbouring stops
+fn color_function(mut stops: Vec<(f64, Vec<f64>)>)
+-> lopdf::Dictionary
+{
+    // the first / last color is used up to the start / end of the gradient
+    match (stops.first().cloned(), stops.last().cloned()) {
+        (Some(first), Some(last)) => {
+            if first.0 > 0.0 { stops.insert(0, (0.0, first.1)); }
+            if last.0 < 1.0 { stops.push((1.0, last.1)); }
+        },
+        _ => stops = vec![(0.0, vec![0.0]), (1.0, vec![0.0])],
+    }
+    if stops.len() == 1 {
+        let only = stops[0].1.clone();
+        stops = vec![(0.0, only.clone()), (1.0, only)];
+    }
+
+    let interpolation = |from: &[f64], to: &[f64]| lopdf::Dictionary::from_iter(vec![
+        ("FunctionType", Integer(2)),
+        ("Domain", Array(vec![Real(0.0), Real(1.0)])),
+        ("C0", Array(from.iter().cloned().map(Real).collect())),
+        ("C1", Array(to.iter().cloned().map(Real).collect())),
+        ("N", Integer(1)),
+    ]);
+
+    if stops.len() == 2 {
+        return interpolation(&stops[0].1, &stops[1].1);
+    }
+
+    let functions = stops.windows(2).map(|pair| Dictionary(interpolation(&pair[0].1, &pair[1].1))).collect();
+    let bounds = stops[1..stops.len() - 1].iter().map(|stop| Real(stop.0)).collect();
+    let encode = stops.windows(2).flat_map(|_| vec![Real(0.0), Real(1.0)]).collect();
+
+    lopdf::Dictionary::from_iter(vec![
+        ("FunctionType", Integer(3)),
+        ("Domain", Array(vec![Real(0.0), Real(1.0)])),
+        ("Functions", Array(functions)),
+        ("Bounds", Array(bounds)),
+        ("Encode", Array(encode)),
+    ])
+}
+
+#[test]
+fn test_gradient_pattern() {
+    use Mm;
+
+    let blue = Color::Rgb(Rgb::new(0.0, 0.0, 1.0, None));
+    let white = Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None));
+    let gradient = Gradient::axial(Point::new(Mm(0.0), Mm(297.0)), Point::new(Mm(0.0), Mm(0.0)), vec![
+        GradientStop::new(0.0, blue.clone()),
+        GradientStop::new(1.0, white.clone()),
+    ]);
+
+    let pattern = gradient.into_pattern(None);
+    let shading = pattern.get(b"Shading").and_then(|shading| shading.as_dict()).unwrap();
+    assert_eq!(shading.get(b"ShadingType").and_then(|t| t.as_i64()).ok(), Some(2));
+    assert_eq!(shading.get(b"ColorSpace").and_then(|cs| cs.as_name_str()).ok(), Some("DeviceRGB"));
+    let function = shading.get(b"Function").and_then(|f| f.as_dict()).unwrap();
+    assert_eq!(function.get(b"FunctionType").and_then(|t| t.as_i64()).ok(), Some(2));
+
+    // three stops are stitched together from two functions, converted into CMYK
+    let black = Color::Greyscale(Greyscale::new(0.0, None));
+    let gradient = Gradient::axial(Point::new(Mm(0.0), Mm(0.0)), Point::new(Mm(100.0), Mm(0.0)), vec![
+        GradientStop::new(0.0, blue),
+        GradientStop::new(0.5, white),
+        GradientStop::new(1.0, black),
+    ]);
+    let pattern = gradient.into_pattern(Some(ColorSpace::Cmyk));
+    let shading = pattern.get(b"Shading").and_then(|shading| shading.as_dict()).unwrap();
+    assert_eq!(shading.get(b"ColorSpace").and_then(|cs| cs.as_name_str()).ok(), Some("DeviceCMYK"));
+    let function = shading.get(b"Function").and_then(|f| f.as_dict()).unwrap();
+    assert_eq!(function.get(b"FunctionType").and_then(|t| t.as_i64()).ok(), Some(3));
+    assert_eq!(function.get(b"Functions").and_then(|f| f.as_array()).map(|f| f.len()).ok(), Some(2));
+}
+
+#[test]
+fn test_radial_gradient_pattern() {
+    use Mm;
+
+    let center = Point::new(Mm(50.0), Mm(50.0));
+    let gradient = Gradient::radial(center, Pt(0.0), center, Mm(20.0).into(), vec![
+        GradientStop::new(0.0, Color::Greyscale(Greyscale::new(1.0, None))),
+        GradientStop::new(1.0, Color::Greyscale(Greyscale::new(0.0, None))),
+    ]).with_extend(true, false);
+
+    let pattern = gradient.into_pattern(None);
+    let shading = pattern.get(b"Shading").and_then(|shading| shading.as_dict()).unwrap();
+    assert_eq!(shading.get(b"ShadingType").and_then(|t| t.as_i64()).ok(), Some(3));
+    assert_eq!(shading.get(b"ColorSpace").and_then(|cs| cs.as_name_str()).ok(), Some("DeviceGray"));
+    assert_eq!(shading.get(b"Coords").and_then(|c| c.as_array()).map(|c| c.len()).ok(), Some(6));
+    let extend = shading.get(b"Extend").and_then(|e| e.as_array()).unwrap();
+    match extend[1] {
+        Boolean(extend_end) => assert!(!extend_end),
+        ref other => panic!("expected a boolean, got {:?}", other),
+    }
+}
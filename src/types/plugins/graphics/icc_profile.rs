@@ -22,6 +22,13 @@ pub struct IccProfile {
     /// Does the ICC profile have an "Range" dictionary
     /// Really not sure why this is needed, but this is needed on the documents Info dictionary
     pub has_range: bool,
+    /// Output condition identifier (e.g. `"CGATS TR 006"` for GRACoL), used for the output intent
+    /// if the profile is the target profile of the document
+    pub(crate) identifier: Option<String>,
+    /// Human-readable description of the output condition of the profile
+    pub(crate) description: Option<String>,
+    /// Registry in which the identifier is defined, e.g. `"http://www.color.org"`
+    pub(crate) registry_name: Option<String>,
 }
 
 impl IccProfile {
@@ -34,9 +41,20 @@ impl IccProfile {
             icc_type: icc_type,
             has_alternate: true,
             has_range: false,
+            identifier: None,
+            description: None,
+            registry_name: None,
         }
     }
 
+    /// Returns the color space of the profile
+    #[inline]
+    pub(crate) fn icc_type(&self)
+    -> IccProfileType
+    {
+        self.icc_type
+    }
+
     /// Does the ICC profile have an alternate version (such as "DeviceCMYk")?
     #[inline]
     pub fn with_alternate_profile(mut self, has_alternate: bool)
@@ -55,6 +73,33 @@ impl IccProfile {
         self
     }
 
+    /// Sets the output condition identifier of the profile
+    #[inline]
+    pub fn with_identifier<S>(mut self, identifier: S)
+    -> Self where S: Into<String>
+    {
+        self.identifier = Some(identifier.into());
+        self
+    }
+
+    /// Sets the human-readable description of the output condition of the profile
+    #[inline]
+    pub fn with_description<S>(mut self, description: S)
+    -> Self where S: Into<String>
+    {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the registry in which the identifier of the profile is defined
+    #[inline]
+    pub fn with_registry_name<S>(mut self, registry_name: S)
+    -> Self where S: Into<String>
+    {
+        self.registry_name = Some(registry_name.into());
+        self
+    }
+
 }
 
 impl Into<lopdf::Stream> for IccProfile {
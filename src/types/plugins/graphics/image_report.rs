@@ -0,0 +1,167 @@
+//! Preflight report of the effective resolution of all placed images
+
+use lopdf::Object;
+use lopdf::content::Operation;
+use indices::{PdfLayerIndex, PdfPageIndex};
+use types::plugins::graphics::ctm::multiply;
+use {Mm, PdfPage, Pt, Px, XObject};
+
+/// Placement of an image on a page, see `PdfDocumentReference::image_report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageReport {
+    /// Page the image is placed on
+    pub page: PdfPageIndex,
+    /// Layer the image is placed on
+    pub layer: PdfLayerIndex,
+    /// Name of the image XObject in the page resources
+    pub name: String,
+    /// Width of the image data in pixels
+    pub width_px: Px,
+    /// Height of the image data in pixels
+    pub height_px: Px,
+    /// Width of the image on the page
+    pub placed_width: Mm,
+    /// Height of the image on the page
+    pub placed_height: Mm,
+    /// Effective horizontal resolution at the placed size
+    pub dpi_x: f64,
+    /// Effective vertical resolution at the placed size
+    pub dpi_y: f64,
+}
+
+impl ImageReport {
+
+    /// Returns the lower of the horizontal and vertical resolution
+    #[inline]
+    pub fn effective_dpi(&self)
+    -> f64
+    {
+        self.dpi_x.min(self.dpi_y)
+    }
+
+    /// Returns true if the image resolution is below `min_dpi`
+    /// (for example 300 dpi for offset print)
+    #[inline]
+    pub fn is_below_dpi(&self, min_dpi: f64)
+    -> bool
+    {
+        self.effective_dpi() < min_dpi
+    }
+}
+
+/// Reads the operands of a `cm` operator
+fn read_matrix(op: &Operation)
+-> Option<[f64; 6]>
+{
+    let mut matrix = [0.0; 6];
+    if op.operands.len() != 6 { return None; }
+    for (value, operand) in matrix.iter_mut().zip(op.operands.iter()) {
+        *value = match *operand {
+            Object::Integer(i) => i as f64,
+            Object::Real(r) => r,
+            _ => return None,
+        };
+    }
+    Some(matrix)
+}
+
+/// Finds all images drawn on the page and computes their size on the page
+/// from the current transformation matrix at the time the image is drawn
+pub(crate) fn page_image_report(page: &PdfPage, page_index: PdfPageIndex)
+-> Vec<ImageReport>
+{
+    let mut reports = Vec::new();
+
+    for (layer_index, layer) in page.layers.iter().enumerate() {
+        let mut stack = vec![[1.0, 0.0, 0.0, 1.0, 0.0, 0.0]];
+
+        for op in &layer.operations {
+            match op.operator.as_str() {
+                "q" => { let ctm = *stack.last().unwrap(); stack.push(ctm); },
+                "Q" if stack.len() > 1 => { stack.pop(); },
+                "cm" => if let Some(matrix) = read_matrix(op) {
+                    let ctm = stack.last_mut().unwrap();
+                    *ctm = multiply(matrix, *ctm);
+                },
+                "Do" => {
+                    let name = match op.operands.first().and_then(|n| n.as_name_str().ok()) {
+                        Some(name) => name,
+                        None => continue,
+                    };
+
+                    let image = match page.resources.xobjects.objects.get(name) {
+                        Some(XObject::Image(image)) => image,
+                        _ => continue,
+                    };
+
+                    // images are drawn into the unit square, so the CTM determines the size
+                    let ctm = stack.last().unwrap();
+                    let placed_width = Pt(ctm[0].hypot(ctm[1]));
+                    let placed_height = Pt(ctm[2].hypot(ctm[3]));
+                    let dpi = |px: Px, size: Pt| if size.0 > 0.0 { px.0 as f64 * 72.0 / size.0 } else { 0.0 };
+
+                    reports.push(ImageReport {
+                        page: page_index,
+                        layer: PdfLayerIndex(layer_index),
+                        name: name.to_string(),
+                        width_px: image.width,
+                        height_px: image.height,
+                        placed_width: placed_width.into(),
+                        placed_height: placed_height.into(),
+                        dpi_x: dpi(image.width, placed_width),
+                        dpi_y: dpi(image.height, placed_height),
+                    });
+                },
+                _ => { },
+            }
+        }
+    }
+
+    reports
+}
+
+#[test]
+fn test_image_report() {
+    use {PdfDocument, Image, ImageXObject, ImageMaskXObject, ColorSpace, ColorBits, CurTransMat, Color, Greyscale, Point};
+
+    let image = || Image::from(ImageXObject::new(Px(300), Px(150), ColorSpace::Greyscale, ColorBits::Bit8,
+                                                 false, None, None, vec![0; 300 * 150]));
+    let (doc, page, layer) = PdfDocument::new("report", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+
+    // 300 dpi, rotated (the rotation doesn't change the size) and at 150 dpi
+    image().add_to_layer(layer_ref.clone(), Some(Mm(10.0)), Some(Mm(10.0)), None, None, None, None);
+    image().add_to_layer(layer_ref.clone(), Some(Mm(50.0)), Some(Mm(10.0)), Some(90.0), None, None, Some(150.0));
+
+    // the scale of the enclosing graphics state enlarges the image, until the state is restored
+    layer_ref.save_graphics_state();
+    layer_ref.set_ctm(CurTransMat::Scale(2.0, 4.0));
+    image().add_to_layer(layer_ref.clone(), None, None, None, None, None, None);
+    layer_ref.restore_graphics_state();
+    image().add_to_layer(layer_ref.clone(), None, None, None, Some(0.5), Some(0.5), None);
+
+    // stencil masks are not images with a resolution to check
+    let mask = ImageMaskXObject::new(Px(8), Px(1), vec![0]);
+    layer_ref.add_image_mask(mask, Point::new(Mm(0.0), Mm(0.0)), Mm(10.0), Mm(1.0), Color::Greyscale(Greyscale::new(0.0, None))).unwrap();
+
+    let report = doc.image_report();
+    let dpi = report.iter().map(|image| (image.dpi_x.round(), image.dpi_y.round())).collect::<Vec<_>>();
+    assert_eq!(dpi, vec![(300.0, 300.0), (150.0, 150.0), (150.0, 75.0), (600.0, 600.0)]);
+
+    assert!(report.iter().all(|image| image.page == page && image.layer == layer));
+    assert_eq!((report[0].width_px, report[0].height_px), (Px(300), Px(150)));
+    assert!((report[0].placed_width.0 - 25.4).abs() < 1e-3);
+    assert!((report[0].placed_height.0 - 12.7).abs() < 1e-3);
+
+    assert_eq!(report[2].effective_dpi().round(), 75.0);
+    let low_resolution = report.iter().filter(|image| image.is_below_dpi(200.0)).count();
+    assert_eq!(low_resolution, 2);
+}
+
+#[test]
+fn test_read_matrix() {
+    let cm = Operation::new("cm", vec![Object::Integer(2), Object::Real(0.5), Object::Integer(0), Object::Integer(1), Object::Real(10.0), Object::Integer(20)]);
+    assert_eq!(read_matrix(&cm), Some([2.0, 0.5, 0.0, 1.0, 10.0, 20.0]));
+    assert_eq!(read_matrix(&Operation::new("cm", vec![Object::Integer(1); 5])), None);
+    assert_eq!(read_matrix(&Operation::new("cm", vec![Object::Name(b"x".to_vec()); 6])), None);
+}
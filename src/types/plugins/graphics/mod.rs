@@ -13,7 +13,12 @@ pub mod ctm;
 pub mod extgstate;
 pub mod xobject;
 pub mod pattern;
+pub mod gradient;
 pub mod pdf_resources;
+pub mod palette;
+pub mod transparency;
+pub mod content_validation;
+pub mod image_report;
 
 pub use self::ctm::*;
 pub use self::ocg::*;
@@ -22,4 +27,9 @@ pub use self::icc_profile::*;
 pub use self::extgstate::*;
 pub use self::xobject::*;
 pub use self::pattern::*;
+pub use self::gradient::*;
 pub use self::pdf_resources::*;
+pub use self::palette::*;
+pub use self::transparency::*;
+pub use self::content_validation::*;
+pub use self::image_report::*;
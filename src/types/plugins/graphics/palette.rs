@@ -0,0 +1,90 @@
+//! Predefined categorical color palettes that stay distinguishable for people
+//! with color vision deficiencies. Useful for charts and diagrams.
+
+use {Color, Rgb};
+
+/// Color-blind-safe categorical palette. Use `get(n)` to get the color of the n-th
+/// data series - the colors repeat after `len()` colors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Palette {
+    /// "Dark2" palette from [ColorBrewer](https://colorbrewer2.org) by Cynthia Brewer
+    /// (the first three colors are safe for all types of color blindness)
+    ColorBrewer,
+    /// Palette from Okabe & Ito, "Color Universal Design" (2008),
+    /// <https://jfly.uni-koeln.de/color/>. Black is left out.
+    OkabeIto,
+    /// "Bright" qualitative scheme by Paul Tol, <https://personal.sron.nl/~pault/>
+    TolBright,
+}
+
+const COLOR_BREWER: [(u8, u8, u8); 8] = [
+    (27, 158, 119), (217, 95, 2), (117, 112, 179), (231, 41, 138),
+    (102, 166, 30), (230, 171, 2), (166, 118, 29), (102, 102, 102),
+];
+
+const OKABE_ITO: [(u8, u8, u8); 7] = [
+    (230, 159, 0), (86, 180, 233), (0, 158, 115), (240, 228, 66),
+    (0, 114, 178), (213, 94, 0), (204, 121, 167),
+];
+
+const TOL_BRIGHT: [(u8, u8, u8); 7] = [
+    (68, 119, 170), (102, 204, 238), (34, 136, 51), (204, 187, 68),
+    (238, 102, 119), (170, 51, 119), (187, 187, 187),
+];
+
+impl Palette {
+
+    /// Returns the number of distinct colors in this palette
+    #[inline]
+    pub fn len(&self)
+    -> usize
+    {
+        self.colors().len()
+    }
+
+    /// Always false, every palette has at least one color
+    #[inline]
+    pub fn is_empty(&self)
+    -> bool
+    {
+        false
+    }
+
+    /// Returns the n-th color of the palette (as an RGB color), wrapping around
+    /// at the end of the palette
+    pub fn get(&self, n: usize)
+    -> Color
+    {
+        let colors = self.colors();
+        let (r, g, b) = colors[n % colors.len()];
+        Color::Rgb(Rgb::new(f64::from(r) / 255.0, f64::from(g) / 255.0, f64::from(b) / 255.0, None))
+    }
+
+    fn colors(&self)
+    -> &'static [(u8, u8, u8)]
+    {
+        match *self {
+            Palette::ColorBrewer => &COLOR_BREWER,
+            Palette::OkabeIto => &OKABE_ITO,
+            Palette::TolBright => &TOL_BRIGHT,
+        }
+    }
+}
+
+#[test]
+fn test_palette_colors() {
+    use {PdfDocument, Mm};
+
+    assert_eq!(Palette::ColorBrewer.len(), 8);
+    assert_eq!(Palette::OkabeIto.len(), 7);
+    assert_eq!(Palette::TolBright.get(7), Palette::TolBright.get(0));
+    assert_ne!(Palette::OkabeIto.get(1), Palette::OkabeIto.get(0));
+
+    // the colors are written as DeviceRGB fill colors
+    let (doc, page, layer) = PdfDocument::new("palette", Mm(210.0), Mm(297.0), "Layer 1");
+    doc.get_page(page).get_layer(layer).set_fill_color(Palette::OkabeIto.get(4));
+    let document = doc.document.borrow();
+    let rg = document.pages[page.0].layers[layer.0].operations.iter().find(|op| op.operator == "rg").unwrap();
+    let values = rg.operands.iter().map(|o| (o.as_f64().unwrap() * 255.0).round() as u8).collect::<Vec<_>>();
+    assert_eq!(values, vec![0, 114, 178]);
+}
@@ -1,19 +1,102 @@
 use lopdf;
 use std::collections::HashMap;
+use {ColorSpace, Gradient};
+use types::plugins::graphics::color::{device_color, device_color_space};
 
-/// __STUB__
-#[derive(Default, Debug, Copy, Clone)]
+/// Pattern that can be used like a color, currently only shading patterns (gradients)
+#[derive(Default, Debug, Clone)]
 pub struct Pattern {
-
+    /// Pattern dictionary, empty patterns are not written to the PDF
+    pub(crate) dictionary: lopdf::Dictionary,
 }
 
 impl Pattern {
-    /// Creates a new Pattern
+    /// Creates a new (empty) Pattern
     pub fn new()
     -> Self
     {
         Self::default()
     }
+
+    /// Creates a shading pattern from the gradient. The colors of the gradient are converted
+    /// into `color_space` (if `None`, into the color space of the first color).
+    pub fn from_gradient(gradient: Gradient, color_space: Option<ColorSpace>)
+    -> Self
+    {
+        Self { dictionary: gradient.into_pattern(color_space) }
+    }
+
+    /// Returns the device color space of the shading, `None` for other patterns or
+    /// shadings in other color spaces (ICC based, separations, ...)
+    pub(crate) fn device_color_space(&self)
+    -> Option<ColorSpace>
+    {
+        self.dictionary.get(b"Shading").and_then(|shading| shading.as_dict()).ok()
+            .and_then(|shading| shading.get(b"ColorSpace").and_then(|cs| cs.as_name_str()).ok())
+            .and_then(device_color_space)
+    }
+
+    /// Converts the colors of a shading in a device color space into the given device
+    /// color space. Returns `false` (and leaves the pattern unchanged) if the shading colors
+    /// are not given by interpolation functions or stitched interpolation functions, as
+    /// written for gradients.
+    pub(crate) fn convert_device_colors(&mut self, color_space: ColorSpace)
+    -> bool
+    {
+        let source = match self.device_color_space() {
+            Some(source) if source != color_space => source,
+            _ => return true,
+        };
+
+        let mut shading = match self.dictionary.get(b"Shading").and_then(|shading| shading.as_dict()) {
+            Ok(shading) => shading.clone(),
+            Err(_) => return true,
+        };
+        let converted = match shading.get_mut(b"Function").and_then(|function| function.as_dict_mut()) {
+            Ok(function) => convert_function_colors(function, source, color_space),
+            Err(_) => false,
+        };
+        if !converted {
+            return false;
+        }
+
+        let target_name: &'static str = color_space.into();
+        shading.set("ColorSpace", lopdf::Object::Name(target_name.into()));
+        self.dictionary.set("Shading", lopdf::Object::Dictionary(shading));
+        true
+    }
+}
+
+/// Converts the `C0` / `C1` colors of an interpolation function (type 2), or of the
+/// functions of a stitching function (type 3), from one device color space into another
+fn convert_function_colors(function: &mut lopdf::Dictionary, from: ColorSpace, to: ColorSpace)
+-> bool
+{
+    use lopdf::Object::{Array, Real};
+
+    match function.get(b"FunctionType").and_then(|t| t.as_i64()) {
+        Ok(2) => {
+            for (key, default) in &[(&b"C0"[..], 0.0), (&b"C1"[..], 1.0)] {
+                let values = match function.get(key).and_then(|c| c.as_array()) {
+                    Ok(values) => values.iter().map(|v| v.as_f64().or_else(|_| v.as_i64().map(|i| i as f64)).ok()).collect::<Option<Vec<f64>>>(),
+                    Err(_) => Some(vec![*default]),
+                };
+                match values.and_then(|values| device_color(from, &values)) {
+                    Some(color) => function.set(*key, Array(color.convert_to(to).into_vec().into_iter().map(Real).collect())),
+                    None => return false,
+                }
+            }
+            true
+        },
+        Ok(3) => match function.get_mut(b"Functions").and_then(|functions| functions.as_array_mut()) {
+            Ok(functions) => functions.iter_mut().all(|function| match function.as_dict_mut() {
+                Ok(function) => convert_function_colors(function, from, to),
+                Err(_) => false,
+            }),
+            Err(_) => false,
+        },
+        _ => false,
+    }
 }
 
 /// Named reference to a pattern
@@ -51,18 +134,84 @@ impl PatternList {
     pub fn add_pattern(&mut self, pattern: Pattern)
     -> PatternRef
     {
-        let len = self.patterns.len();
-        let pattern_ref = PatternRef::new(len);
+        // patterns added with an allocated index may already use the name for the length
+        let mut index = self.patterns.len();
+        while self.patterns.contains_key(&format!("PT{}", index)) { index += 1; }
+        self.add_pattern_with_index(index, pattern)
+    }
+
+    /// Adds a pattern under the name for the given index (see `PatternRef::new`).
+    /// The caller has to make sure that the name is not taken yet.
+    pub(crate) fn add_pattern_with_index(&mut self, index: usize, pattern: Pattern)
+    -> PatternRef
+    {
+        let pattern_ref = PatternRef::new(index);
         self.patterns.insert(pattern_ref.name.clone(), pattern);
         pattern_ref
     }
+
+    /// Returns the patterns (in no particular order)
+    pub(crate) fn patterns(&self)
+    -> impl Iterator<Item = &Pattern>
+    {
+        self.patterns.values()
+    }
+
+    /// Returns the patterns (in no particular order)
+    pub(crate) fn patterns_mut(&mut self)
+    -> impl Iterator<Item = &mut Pattern>
+    {
+        self.patterns.values_mut()
+    }
+
+    /// Returns if a pattern with this name exists
+    pub(crate) fn contains(&self, name: &str)
+    -> bool
+    {
+        self.patterns.contains_key(name)
+    }
 }
 
-impl Into<lopdf::Dictionary> for PatternList {
-    fn into(self)
+impl PatternList {
+    /// Adds the patterns to the document as indirect objects and returns the
+    /// `/Pattern` resource dictionary
+    pub fn into_with_document(self, doc: &mut lopdf::Document)
     -> lopdf::Dictionary
     {
-        // todo
-        lopdf::Dictionary::new()
+        self.patterns.into_iter()
+            .filter(|(_, pattern)| !pattern.dictionary.is_empty())
+            .map(|(name, pattern)| (name, lopdf::Object::Reference(doc.add_object(pattern.dictionary))))
+            .collect()
+    }
+}
+
+#[test]
+fn test_convert_pattern_colors() {
+    use std::iter::FromIterator;
+    use {Color, GradientStop, Point, Rgb, Mm, Pt};
+
+    let red = Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None));
+    let blue = Color::Rgb(Rgb::new(0.0, 0.0, 1.0, None));
+    let stops = vec![GradientStop::new(0.0, red.clone()), GradientStop::new(0.5, blue), GradientStop::new(1.0, red)];
+    let gradient = Gradient::radial(Point::new(Mm(0.0), Mm(0.0)), Pt(0.0), Point::new(Mm(0.0), Mm(0.0)), Pt(10.0), stops);
+    let mut pattern = Pattern::from_gradient(gradient, None);
+    assert_eq!(pattern.device_color_space(), Some(ColorSpace::Rgb));
+
+    assert!(pattern.convert_device_colors(ColorSpace::Cmyk));
+    assert_eq!(pattern.device_color_space(), Some(ColorSpace::Cmyk));
+    let shading = pattern.dictionary.get(b"Shading").and_then(|s| s.as_dict()).unwrap();
+    let functions = shading.get(b"Function").and_then(|f| f.as_dict()).and_then(|f| f.get(b"Functions")).and_then(|f| f.as_array()).unwrap();
+    let first = functions[0].as_dict().unwrap();
+    let c0 = first.get(b"C0").and_then(|c| c.as_array()).unwrap().iter().map(|c| c.as_f64().unwrap()).collect::<Vec<_>>();
+    let c1 = first.get(b"C1").and_then(|c| c.as_array()).unwrap().iter().map(|c| c.as_f64().unwrap()).collect::<Vec<_>>();
+    assert_eq!(c0, vec![0.0, 1.0, 1.0, 0.0]);
+    assert_eq!(c1, vec![1.0, 1.0, 0.0, 0.0]);
+
+    // shadings with other functions are left as they are
+    let mut sampled = Pattern::from_gradient(Gradient::axial(Point::new(Mm(0.0), Mm(0.0)), Point::new(Mm(1.0), Mm(0.0)), vec![]), Some(ColorSpace::Rgb));
+    if let Ok(shading) = sampled.dictionary.get_mut(b"Shading").and_then(|s| s.as_dict_mut()) {
+        shading.set("Function", lopdf::Object::Dictionary(lopdf::Dictionary::from_iter(vec![("FunctionType", lopdf::Object::Integer(0))])));
     }
+    assert!(!sampled.convert_device_colors(ColorSpace::Cmyk));
+    assert_eq!(sampled.device_color_space(), Some(ColorSpace::Rgb));
 }
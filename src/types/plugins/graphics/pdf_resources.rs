@@ -1,4 +1,5 @@
 use lopdf;
+use std::collections::HashSet;
 use {
     XObject, Pattern, ExtendedGraphicsState, ExtendedGraphicsStateList, 
     PatternRef, OCGRef, XObjectList, XObjectRef, ExtendedGraphicsStateRef,
@@ -10,7 +11,7 @@ use {
 pub struct PdfResources {
     /// External graphics objects
     pub xobjects: XObjectList,
-    /// Patterns used on this page. Do not yet, use, placeholder.
+    /// Patterns (gradients) used on this page
     pub patterns: PatternList,
     /// Graphics states used on this page
     pub graphics_states: ExtendedGraphicsStateList,
@@ -43,7 +44,7 @@ impl PdfResources {
         self.xobjects.add_xobject(xobj)
     }
 
-    /// __STUB__: Adds a pattern to the resources, to be used like a color
+    /// Adds a pattern to the resources, to be used like a color
     #[inline]
     pub fn add_pattern(&mut self, pattern: Pattern)
     -> PatternRef
@@ -51,6 +52,23 @@ impl PdfResources {
         self.patterns.add_pattern(pattern)
     }
 
+    /// Returns if any resource (XObject, pattern or graphics state) on this page has the given name
+    pub(crate) fn contains_name(&self, name: &str)
+    -> bool
+    {
+        self.xobjects.objects.contains_key(name) ||
+        self.patterns.contains(name) ||
+        self.graphics_states.all_graphics_states.contains_key(name)
+    }
+
+    /// Removes all XObjects and graphics states whose names are not in `used_names`
+    /// (the names invoked by the `Do` and `gs` operators of the page)
+    pub(crate) fn retain_used(&mut self, used_names: &HashSet<String>)
+    {
+        self.xobjects.retain_used(used_names);
+        self.graphics_states.retain_used(used_names);
+    }
+
     /// See `XObject::Into_with_document`.
     /// The resources also need access to the layers (the optional content groups), this should be a
     /// `Vec<lopdf::Object::Reference>` (to the actual OCG groups, which are added on the document level)
@@ -64,7 +82,7 @@ impl PdfResources {
             let mut ocg_references = Vec::<OCGRef>::new();
 
             let xobjects_dict: lopdf::Dictionary = self.xobjects.into_with_document(doc);
-            let patterns_dict: lopdf::Dictionary = self.patterns.into();
+            let patterns_dict: lopdf::Dictionary = self.patterns.into_with_document(doc);
             let graphics_state_dict: lopdf::Dictionary = self.graphics_states.into();
 
             if !layers.is_empty() {
@@ -95,3 +113,26 @@ impl PdfResources {
             return (dict, ocg_references);
     }
 }
+
+#[test]
+fn test_retain_used() {
+    use {ImageMaskXObject, Px, ExtendedGraphicsStateBuilder};
+
+    let mut resources = PdfResources::new();
+    let mask = || XObject::ImageMask(ImageMaskXObject::new(Px(8), Px(1), vec![0]));
+    let used_image = resources.add_xobject(mask());
+    let unused_image = resources.add_xobject(mask());
+    let used_state = resources.add_graphics_state(ExtendedGraphicsStateBuilder::new().with_line_width(2.0).build());
+    let unused_state = resources.add_graphics_state(ExtendedGraphicsStateBuilder::new().with_line_width(3.0).build());
+
+    let used_names = vec![used_image.name.clone(), used_state.gs_name.clone()].into_iter().collect();
+    resources.retain_used(&used_names);
+    assert!(resources.contains_name(&used_image.name));
+    assert!(resources.contains_name(&used_state.gs_name));
+    assert!(!resources.contains_name(&unused_image.name));
+    assert!(!resources.contains_name(&unused_state.gs_name));
+
+    // the names of the removed resources can be handed out again without clashing
+    let new_state = resources.add_graphics_state(ExtendedGraphicsStateBuilder::new().build());
+    assert_ne!(new_state.gs_name, used_state.gs_name);
+}
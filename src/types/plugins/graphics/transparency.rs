@@ -0,0 +1,439 @@
+//! Flattening of transparency, for standards that don't allow live transparency (PDF/X-3, PDF/A-1).
+//!
+//! Real flattening would require rasterizing the overlapping regions of a page, which is out of
+//! scope for printpdf. Instead, transparency is approximated by compositing the transparent
+//! content onto white paper: alpha constants are multiplied into the fill / stroke colors,
+//! images with an alpha channel are composited onto white and blend modes are reset to `Normal`.
+//! This is exact for content that doesn't overlap other content.
+
+use lopdf::Object;
+use lopdf::content::Operation;
+use std::collections::HashMap;
+use indices::{PdfLayerIndex, PdfPageIndex};
+use {BlendMode, ColorBits, ColorSpace, ImageXObject, PdfPage, SeperableBlendMode, XObject};
+use super::extgstate::{BLEND_MODE, CURRENT_FILL_ALPHA, CURRENT_STROKE_ALPHA, SOFT_MASK};
+
+/// Transparent content that could not be flattened exactly by
+/// `PdfDocumentReference::flatten_transparency`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnflattenedTransparency {
+    /// Page of the transparent content
+    pub page: PdfPageIndex,
+    /// Layer of the transparent content (`None` if the issue concerns the page resources)
+    pub layer: Option<PdfLayerIndex>,
+    /// Description of what couldn't be flattened
+    pub reason: String,
+}
+
+/// Transparency that a graphics state dictionary introduces
+#[derive(Debug, Copy, Clone)]
+struct GraphicsStateAlpha {
+    fill_alpha: Option<f64>,
+    stroke_alpha: Option<f64>,
+}
+
+/// Current colors and alpha values while walking through the operations
+#[derive(Debug, Clone)]
+struct FlattenState {
+    fill_alpha: f64,
+    stroke_alpha: f64,
+    fill_color: Option<Operation>,
+    stroke_color: Option<Operation>,
+}
+
+impl Default for FlattenState {
+    fn default() -> Self {
+        Self { fill_alpha: 1.0, stroke_alpha: 1.0, fill_color: None, stroke_color: None }
+    }
+}
+
+/// Returns if the graphics states or images of the page use live transparency
+pub(crate) fn page_has_transparency(page: &PdfPage)
+-> bool
+{
+    let graphics_states = page.resources.graphics_states.all_graphics_states.values().any(|(_, gs)| {
+        (gs.changed_fields.contains(CURRENT_FILL_ALPHA) && gs.current_fill_alpha < 1.0)
+            || (gs.changed_fields.contains(CURRENT_STROKE_ALPHA) && gs.current_stroke_alpha < 1.0)
+            || gs.soft_mask.is_some()
+            || gs.blend_mode != BlendMode::Seperable(SeperableBlendMode::Normal)
+    });
+
+    let images = page.resources.xobjects.objects.values().any(|xobject| {
+        matches!(*xobject, XObject::Image(ImageXObject { smask: Some(_), .. }))
+    });
+
+    graphics_states || images
+}
+
+/// Flattens the transparency of one page, returns what could not be flattened
+pub(crate) fn flatten_page_transparency(page: &mut PdfPage, page_index: PdfPageIndex)
+-> Vec<UnflattenedTransparency>
+{
+    let mut issues = Vec::new();
+    let mut gs_alpha = HashMap::<String, GraphicsStateAlpha>::new();
+
+    // remove the transparency from the graphics states, but remember the alpha values
+    for (name, &mut (_, ref mut gs)) in page.resources.graphics_states.all_graphics_states.iter_mut() {
+
+        let alpha = GraphicsStateAlpha {
+            fill_alpha: if gs.changed_fields.remove(CURRENT_FILL_ALPHA) { Some(gs.current_fill_alpha) } else { None },
+            stroke_alpha: if gs.changed_fields.remove(CURRENT_STROKE_ALPHA) { Some(gs.current_stroke_alpha) } else { None },
+        };
+        gs.current_fill_alpha = 1.0;
+        gs.current_stroke_alpha = 1.0;
+        gs_alpha.insert(name.clone(), alpha);
+
+        if gs.changed_fields.remove(BLEND_MODE) && gs.blend_mode != BlendMode::Seperable(SeperableBlendMode::Normal) {
+            issues.push(UnflattenedTransparency {
+                page: page_index,
+                layer: None,
+                reason: format!("blend mode {:?} of graphics state /{} was replaced with Normal", gs.blend_mode, name),
+            });
+        }
+        gs.blend_mode = BlendMode::Seperable(SeperableBlendMode::Normal);
+
+        if gs.changed_fields.remove(SOFT_MASK) || gs.soft_mask.is_some() {
+            issues.push(UnflattenedTransparency {
+                page: page_index,
+                layer: None,
+                reason: format!("soft mask of graphics state /{} was removed", name),
+            });
+        }
+        gs.soft_mask = None;
+    }
+
+    for (layer_index, layer) in page.layers.iter_mut().enumerate() {
+        let mut stack = vec![FlattenState::default()];
+        let mut operations = Vec::with_capacity(layer.operations.len());
+
+        for op in layer.operations.drain(..) {
+            let state = stack.last_mut().unwrap();
+            match op.operator.as_str() {
+                "q" => {
+                    let new_state = state.clone();
+                    stack.push(new_state);
+                    operations.push(op);
+                },
+                "Q" => {
+                    if stack.len() > 1 { stack.pop(); }
+                    operations.push(op);
+                },
+                "rg" | "g" | "k" => {
+                    operations.push(blend_with_white(&op, state.fill_alpha));
+                    state.fill_color = Some(op);
+                },
+                "RG" | "G" | "K" => {
+                    operations.push(blend_with_white(&op, state.stroke_alpha));
+                    state.stroke_color = Some(op);
+                },
+                "gs" => {
+                    let alpha = op.operands.first()
+                        .and_then(|name| name.as_name_str().ok())
+                        .and_then(|name| gs_alpha.get(name).cloned());
+                    operations.push(op);
+
+                    if let Some(alpha) = alpha {
+                        if let Some(fill_alpha) = alpha.fill_alpha {
+                            state.fill_alpha = fill_alpha;
+                            operations.extend(state.fill_color.as_ref().map(|c| blend_with_white(c, fill_alpha)));
+                        }
+                        if let Some(stroke_alpha) = alpha.stroke_alpha {
+                            state.stroke_alpha = stroke_alpha;
+                            operations.extend(state.stroke_color.as_ref().map(|c| blend_with_white(c, stroke_alpha)));
+                        }
+                    }
+                },
+                "Do" if state.fill_alpha < 1.0 => {
+                    issues.push(UnflattenedTransparency {
+                        page: page_index,
+                        layer: Some(PdfLayerIndex(layer_index)),
+                        reason: format!("XObject {:?} is drawn with a fill alpha of {}", op.operands, state.fill_alpha),
+                    });
+                    operations.push(op);
+                },
+                _ => operations.push(op),
+            }
+        }
+
+        layer.operations = operations;
+    }
+
+    for (name, xobject) in page.resources.xobjects.objects.iter_mut() {
+        if let XObject::Image(ref mut image) = *xobject {
+            if !flatten_image_alpha(image) {
+                issues.push(UnflattenedTransparency {
+                    page: page_index,
+                    layer: None,
+                    reason: format!("the alpha channel of the (compressed) image /{} could not be removed", name),
+                });
+            }
+            if !flatten_image_smask(image) {
+                issues.push(UnflattenedTransparency {
+                    page: page_index,
+                    layer: None,
+                    reason: format!("the soft mask of the image /{} could not be removed", name),
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+/// Composites a color (from a `rg`, `g` or `k` operator) with the given alpha onto white paper
+fn blend_with_white(op: &Operation, alpha: f64)
+-> Operation
+{
+    let alpha = alpha.clamp(0.0, 1.0);
+    let is_cmyk = op.operator.eq_ignore_ascii_case("k");
+
+    let operands = op.operands.iter().map(|o| {
+        let value = match *o {
+            Object::Integer(i) => i as f64,
+            Object::Real(r) => r,
+            _ => return o.clone(),
+        };
+
+        // CMYK values are ink coverage (0 = paper white), RGB / grey values are light (1 = white)
+        Object::Real(if is_cmyk { value * alpha } else { 1.0 - alpha * (1.0 - value) })
+    }).collect();
+
+    Operation::new(&op.operator, operands)
+}
+
+/// Composites an image with an alpha channel onto white paper and removes the alpha channel.
+/// Returns false if the image has an alpha channel, but is compressed.
+fn flatten_image_alpha(image: &mut ImageXObject)
+-> bool
+{
+    let (color_space, color_components) = match image.color_space {
+        ColorSpace::Rgba => (ColorSpace::Rgb, 3),
+        ColorSpace::GreyscaleAlpha => (ColorSpace::Greyscale, 1),
+        _ => return true,
+    };
+
+    if image.image_filter.is_some() {
+        return false;
+    }
+
+    let bytes_per_component = match image.bits_per_component {
+        ColorBits::Bit16 => 2,
+        _ => 1,
+    };
+
+    let max = if bytes_per_component == 2 { 65535.0 } else { 255.0 };
+    let read = |bytes: &[u8]| if bytes_per_component == 2 { f64::from(u16::from_be_bytes([bytes[0], bytes[1]])) } else { f64::from(bytes[0]) };
+
+    let pixel_size = (color_components + 1) * bytes_per_component;
+    let mut image_data = Vec::with_capacity(image.image_data.len() / (color_components + 1) * color_components);
+
+    for pixel in image.image_data.chunks(pixel_size).filter(|p| p.len() == pixel_size) {
+        let alpha = read(&pixel[color_components * bytes_per_component..]) / max;
+        for component in pixel.chunks(bytes_per_component).take(color_components) {
+            let value = max - alpha * (max - read(component));
+            if bytes_per_component == 2 {
+                image_data.extend_from_slice(&(value.round() as u16).to_be_bytes());
+            } else {
+                image_data.push(value.round() as u8);
+            }
+        }
+    }
+
+    image.color_space = color_space;
+    image.image_data = image_data;
+    true
+}
+
+/// Composites an image with a soft mask onto white paper and removes the soft mask.
+/// Indexed images (8 bit) are converted to RGB. Returns false if the soft mask can't be
+/// applied: the image or the mask is compressed, they have different sizes or the image
+/// has 1 bit per component.
+fn flatten_image_smask(image: &mut ImageXObject)
+-> bool
+{
+    let bytes_per_component = |bits: ColorBits| match bits {
+        ColorBits::Bit1 => None,
+        ColorBits::Bit8 => Some(1),
+        ColorBits::Bit16 => Some(2),
+    };
+    let read = |bytes: &[u8]| if bytes.len() == 2 { f64::from(u16::from_be_bytes([bytes[0], bytes[1]])) } else { f64::from(bytes[0]) };
+
+    let image_data = {
+        let smask = match image.smask {
+            Some(ref smask) => smask,
+            None => return true,
+        };
+
+        let (color_components, is_cmyk) = match image.color_space {
+            ColorSpace::Rgb | ColorSpace::Palette => (3, false),
+            ColorSpace::Greyscale => (1, false),
+            ColorSpace::Cmyk => (4, true),
+            _ => return false,
+        };
+
+        let (image_bytes, mask_bytes) = match (bytes_per_component(image.bits_per_component), bytes_per_component(smask.bits_per_component)) {
+            (Some(image_bytes), Some(mask_bytes)) => (image_bytes, mask_bytes),
+            _ => return false,
+        };
+
+        let pixels = image.width.0 * image.height.0;
+        let pixel_size = color_components * image_bytes;
+        if image.image_filter.is_some() || smask.image_filter.is_some()
+            || smask.color_space != ColorSpace::Greyscale
+            || (smask.width, smask.height) != (image.width, image.height)
+            || smask.image_data.len() < pixels * mask_bytes {
+            return false;
+        }
+
+        // the colors of an indexed image are looked up in its (RGB) palette
+        let colors = match (image.color_space, image.palette.as_ref()) {
+            (ColorSpace::Palette, Some(palette)) if image_bytes == 1 && image.image_data.len() >= pixels => {
+                image.image_data.iter().take(pixels)
+                    .flat_map(|index| palette.get(*index as usize * 3..*index as usize * 3 + 3).unwrap_or(&[0, 0, 0]).iter().cloned())
+                    .collect()
+            },
+            (ColorSpace::Palette, _) => return false,
+            _ if image.image_data.len() >= pixels * pixel_size => image.image_data.clone(),
+            _ => return false,
+        };
+
+        let max = if image_bytes == 2 { 65535.0 } else { 255.0 };
+        let mask_max = if mask_bytes == 2 { 65535.0 } else { 255.0 };
+        let mut image_data = Vec::with_capacity(pixels * pixel_size);
+
+        for (pixel, alpha) in colors.chunks(pixel_size).zip(smask.image_data.chunks(mask_bytes)).take(pixels) {
+            let alpha = read(alpha) / mask_max;
+            for component in pixel.chunks(image_bytes) {
+                // CMYK values are ink coverage (0 = paper white), RGB / grey values are light (max = white)
+                let value = if is_cmyk { alpha * read(component) } else { max - alpha * (max - read(component)) };
+                if image_bytes == 2 {
+                    image_data.extend_from_slice(&(value.round() as u16).to_be_bytes());
+                } else {
+                    image_data.push(value.round() as u8);
+                }
+            }
+        }
+
+        image_data
+    };
+
+    if image.color_space == ColorSpace::Palette {
+        image.color_space = ColorSpace::Rgb;
+        image.palette = None;
+    }
+    image.image_data = image_data;
+    image.smask = None;
+    true
+}
+
+#[test]
+fn test_blend_with_white() {
+    let reals = |op: Operation| op.operands.iter().map(|o| match *o { Object::Real(r) => r, _ => -1.0 }).collect::<Vec<f64>>();
+    let rgb = Operation::new("rg", vec![Object::Real(1.0), Object::Real(0.0), Object::Integer(0)]);
+    assert_eq!(reals(blend_with_white(&rgb, 0.5)), vec![1.0, 0.5, 0.5]);
+    let cmyk = Operation::new("k", vec![Object::Real(0.0), Object::Real(1.0), Object::Real(0.5), Object::Real(0.0)]);
+    assert_eq!(reals(blend_with_white(&cmyk, 0.5)), vec![0.0, 0.5, 0.25, 0.0]);
+}
+
+#[test]
+fn test_flatten_transparency() {
+    use {PdfDocument, Mm, Color, Rgb, Cmyk, Greyscale, Image, ImageFilter, Px};
+
+    let (doc, page, layer) = PdfDocument::new("flatten", Mm(210.0), Mm(297.0), "Layer 1");
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    layer_ref.set_fill_color(Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None)));
+    layer_ref.save_graphics_state();
+    layer_ref.set_fill_alpha(0.5);
+    layer_ref.set_outline_color(Color::Cmyk(Cmyk::new(0.0, 1.0, 0.0, 0.0, None)));
+    let image = ImageXObject::new(Px(1), Px(1), ColorSpace::Rgba, ColorBits::Bit8, false,
+                                  Some(ImageFilter::DCT), None, vec![0; 4]);
+    Image::from(image).add_to_layer(layer_ref.clone(), None, None, None, None, None, None);
+    layer_ref.restore_graphics_state();
+    let smask = ImageXObject::new(Px(1), Px(1), ColorSpace::Greyscale, ColorBits::Bit8, false, None, None, vec![128]);
+    let masked = ImageXObject::new(Px(1), Px(1), ColorSpace::Rgb, ColorBits::Bit8, false, None, None, vec![0; 3]).with_smask(smask);
+    Image::from(masked).add_to_layer(layer_ref.clone(), None, None, None, None, None, None);
+    layer_ref.set_fill_color(Color::Greyscale(Greyscale::new(0.0, None)));
+    layer_ref.set_blend_mode(BlendMode::Seperable(SeperableBlendMode::Multiply));
+    assert!(page_has_transparency(&doc.document.borrow().pages[page.0]));
+
+    let issues = doc.flatten_transparency();
+    let document = doc.document.borrow();
+
+    // the blend mode, the image drawn with alpha and the compressed image alpha are reported,
+    // the soft mask of the uncompressed image is composited onto white
+    assert_eq!(issues.len(), 3);
+    assert!(issues.iter().any(|issue| issue.layer.is_none() && issue.reason.starts_with("blend mode Seperable(Multiply)")));
+    assert!(issues.iter().any(|issue| issue.layer == Some(layer) && issue.reason.contains("fill alpha of 0.5")));
+    assert!(issues.iter().any(|issue| issue.reason.contains("compressed")));
+    assert!(document.pages[page.0].resources.xobjects.objects.values()
+        .all(|xobject| !matches!(*xobject, XObject::Image(ImageXObject { smask: Some(_), .. }))));
+
+    // the red is re-emitted as pink after the alpha is set, the stroke color is opaque
+    // and the alpha ends with the saved state, so the black stays black
+    let colors = document.pages[page.0].layers[layer.0].operations.iter()
+        .filter(|op| matches!(op.operator.as_str(), "rg" | "K" | "g"))
+        .map(|op| (op.operator.clone(), op.operands.iter().map(|o| o.as_f64().unwrap_or_else(|_| o.as_i64().unwrap() as f64)).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    assert_eq!(colors, vec![
+        ("rg".to_string(), vec![1.0, 0.0, 0.0]),
+        ("rg".to_string(), vec![1.0, 0.5, 0.5]),
+        ("K".to_string(), vec![0.0, 1.0, 0.0, 0.0]),
+        ("g".to_string(), vec![0.0]),
+    ]);
+}
+
+#[test]
+fn test_flatten_image_alpha() {
+    use Px;
+
+    // a half transparent black pixel and an opaque blue pixel
+    let mut rgba = ImageXObject::new(Px(2), Px(1), ColorSpace::Rgba, ColorBits::Bit8, false, None, None,
+                                     vec![0, 0, 0, 128, 0, 0, 255, 255]);
+    assert!(flatten_image_alpha(&mut rgba));
+    assert_eq!(rgba.color_space, ColorSpace::Rgb);
+    assert_eq!(rgba.image_data, vec![127, 127, 127, 0, 0, 255]);
+
+    let mut grey = ImageXObject::new(Px(1), Px(1), ColorSpace::GreyscaleAlpha, ColorBits::Bit16, false, None, None,
+                                     vec![0, 0, 0, 0]);
+    assert!(flatten_image_alpha(&mut grey));
+    assert_eq!(grey.color_space, ColorSpace::Greyscale);
+    assert_eq!(grey.image_data, vec![255, 255]);
+
+    // images without an alpha channel are left alone
+    let mut rgb = ImageXObject::new(Px(1), Px(1), ColorSpace::Rgb, ColorBits::Bit8, false, None, None, vec![1, 2, 3]);
+    assert!(flatten_image_alpha(&mut rgb));
+    assert_eq!(rgb.image_data, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_flatten_image_smask() {
+    use Px;
+
+    // a black pixel that is half transparent and one that is opaque
+    let smask = ImageXObject::new(Px(2), Px(1), ColorSpace::Greyscale, ColorBits::Bit8, false, None, None, vec![128, 255]);
+    let mut rgb = ImageXObject::new(Px(2), Px(1), ColorSpace::Rgb, ColorBits::Bit8, false, None, None, vec![0; 6])
+        .with_smask(smask.clone());
+    assert!(flatten_image_smask(&mut rgb));
+    assert!(rgb.smask.is_none());
+    assert_eq!(rgb.image_data, vec![127, 127, 127, 0, 0, 0]);
+
+    // CMYK values are ink coverage, transparent ink fades to paper white
+    let mut cmyk = ImageXObject::new(Px(2), Px(1), ColorSpace::Cmyk, ColorBits::Bit8, false, None, None, vec![0, 0, 0, 255, 0, 0, 0, 255])
+        .with_smask(smask.clone());
+    assert!(flatten_image_smask(&mut cmyk));
+    assert_eq!(cmyk.image_data, vec![0, 0, 0, 128, 0, 0, 0, 255]);
+
+    // indexed images (e.g. PNGs with transparency) become RGB images
+    let mut indexed = ImageXObject::new(Px(2), Px(1), ColorSpace::Palette, ColorBits::Bit8, false, None, None, vec![1, 0])
+        .with_smask(smask.clone());
+    indexed.palette = Some(vec![0, 0, 0, 0, 0, 255]);
+    assert!(flatten_image_smask(&mut indexed));
+    assert_eq!((indexed.color_space, indexed.palette.is_none()), (ColorSpace::Rgb, true));
+    assert_eq!(indexed.image_data, vec![127, 127, 255, 0, 0, 0]);
+
+    // masks of a different size or compressed images can't be composited
+    let mut wrong_size = ImageXObject::new(Px(1), Px(1), ColorSpace::Rgb, ColorBits::Bit8, false, None, None, vec![0; 3])
+        .with_smask(smask);
+    assert!(!flatten_image_smask(&mut wrong_size));
+    assert!(wrong_size.smask.is_some());
+}
@@ -0,0 +1,399 @@
+//! Simple bar and line charts, drawn with the regular shape and text primitives.
+//!
+//! The y axis is scaled automatically to "nice" tick values (1, 2 or 5 times a power of ten),
+//! the colors of the data series are taken from a (color-blind-safe) `Palette`.
+
+use {
+    Color, Greyscale, IndirectFontRef, Line, Mm, Palette, PdfLayerReference, Point, Pt, CurTransMat,
+};
+use std::iter::FromIterator;
+use utils::calculate_points_for_circle;
+
+/// One data series of a chart (one value per category)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartSeries {
+    /// Name of the series, shown in the legend
+    pub name: String,
+    /// The values, one value per category. Values that are not finite (NaN or infinite)
+    /// are left out, like missing values.
+    pub values: Vec<f64>,
+}
+
+/// Data and settings that are shared between all chart types
+#[derive(Debug, Clone)]
+struct ChartData {
+    series: Vec<ChartSeries>,
+    categories: Vec<String>,
+    x_label: Option<String>,
+    y_label: Option<String>,
+    palette: Palette,
+    font_size: f64,
+}
+
+impl Default for ChartData {
+    fn default() -> Self {
+        Self {
+            series: Vec::new(),
+            categories: Vec::new(),
+            x_label: None,
+            y_label: None,
+            palette: Palette::ColorBrewer,
+            font_size: 8.0,
+        }
+    }
+}
+
+/// Bar chart, the bars of all series are drawn next to each other for every category
+#[derive(Debug, Clone, Default)]
+pub struct BarChart {
+    data: ChartData,
+}
+
+/// Line chart, every series is drawn as a line through the categories
+#[derive(Debug, Clone, Default)]
+pub struct LineChart {
+    data: ChartData,
+}
+
+macro_rules! impl_chart_builder {
+    ($chart:ident) => {
+        impl $chart {
+
+            /// Creates a new, empty chart
+            pub fn new()
+            -> Self
+            {
+                Self::default()
+            }
+
+            /// Adds a data series (one value per category)
+            pub fn with_series<S>(mut self, name: S, values: Vec<f64>)
+            -> Self where S: Into<String>
+            {
+                self.data.series.push(ChartSeries { name: name.into(), values });
+                self
+            }
+
+            /// Sets the names of the categories, shown below the x axis
+            pub fn with_categories<S>(mut self, categories: Vec<S>)
+            -> Self where S: Into<String>
+            {
+                self.data.categories = categories.into_iter().map(|c| c.into()).collect();
+                self
+            }
+
+            /// Sets the label of the x axis
+            pub fn with_x_label<S>(mut self, label: S)
+            -> Self where S: Into<String>
+            {
+                self.data.x_label = Some(label.into());
+                self
+            }
+
+            /// Sets the label of the y axis
+            pub fn with_y_label<S>(mut self, label: S)
+            -> Self where S: Into<String>
+            {
+                self.data.y_label = Some(label.into());
+                self
+            }
+
+            /// Sets the palette for the colors of the series. Default: `Palette::ColorBrewer`
+            pub fn with_palette(mut self, palette: Palette)
+            -> Self
+            {
+                self.data.palette = palette;
+                self
+            }
+
+            /// Sets the font size (in points) for all labels. Default: 8pt
+            pub fn with_font_size(mut self, font_size: f64)
+            -> Self
+            {
+                self.data.font_size = font_size;
+                self
+            }
+        }
+    }
+}
+
+impl_chart_builder!(BarChart);
+impl_chart_builder!(LineChart);
+
+impl BarChart {
+
+    /// Draws the chart (including axes, labels and legend) into the rectangle with the
+    /// lower left corner at `x` / `y` on the layer and consumes the chart.
+    pub fn add_to_layer(self, layer: &PdfLayerReference, font: &IndirectFontRef,
+                        x: Mm, y: Mm, width: Mm, height: Mm)
+    {
+        layer.save_graphics_state();
+        let plot = self.data.draw_frame(layer, font, x.into(), y.into(), width.into(), height.into());
+        let num_series = self.data.series.len().max(1) as f64;
+
+        for (series_idx, series) in self.data.series.iter().enumerate() {
+            layer.set_fill_color(self.data.palette.get(series_idx));
+            for (category_idx, value) in series.values.iter().enumerate().take(plot.num_categories) {
+                if !value.is_finite() {
+                    continue;
+                }
+                let (slot_x, slot_width) = plot.slot(category_idx);
+                let bar_width = slot_width * 0.8 / num_series;
+                let left = slot_x + slot_width * 0.1 + bar_width * series_idx as f64;
+                let (y0, y1) = (plot.y_pos(0.0), plot.y_pos(*value));
+                let mut bar = Line::rectangle(Pt(left).into(), Pt(y0.min(y1)).into(), Pt(bar_width).into(), Pt((y1 - y0).abs()).into());
+                bar.set_fill(true);
+                layer.add_shape(bar);
+            }
+        }
+
+        layer.restore_graphics_state();
+    }
+}
+
+impl LineChart {
+
+    /// Draws the chart (including axes, labels and legend) into the rectangle with the
+    /// lower left corner at `x` / `y` on the layer and consumes the chart.
+    pub fn add_to_layer(self, layer: &PdfLayerReference, font: &IndirectFontRef,
+                        x: Mm, y: Mm, width: Mm, height: Mm)
+    {
+        layer.save_graphics_state();
+        let plot = self.data.draw_frame(layer, font, x.into(), y.into(), width.into(), height.into());
+        layer.set_outline_thickness(1.5);
+
+        for (series_idx, series) in self.data.series.iter().enumerate() {
+            let color = self.data.palette.get(series_idx);
+            layer.set_outline_color(color.clone());
+            layer.set_fill_color(color);
+
+            // values that are left out split the line into several segments
+            let mut segments = vec![Vec::<(f64, f64)>::new()];
+            for (category_idx, value) in series.values.iter().enumerate().take(plot.num_categories) {
+                if value.is_finite() {
+                    let (slot_x, slot_width) = plot.slot(category_idx);
+                    segments.last_mut().unwrap().push((slot_x + slot_width / 2.0, plot.y_pos(*value)));
+                } else if !segments.last().unwrap().is_empty() {
+                    segments.push(Vec::new());
+                }
+            }
+
+            for segment in segments.iter().filter(|segment| segment.len() > 1) {
+                let mut line = Line::from_iter(segment.iter().map(|&(x, y)| (Point { x: Pt(x), y: Pt(y) }, false)));
+                line.set_stroke(true);
+                layer.add_shape(line);
+            }
+
+            for &(x, y) in segments.iter().flat_map(|segment| segment.iter()) {
+                layer.add_shape(Line {
+                    points: calculate_points_for_circle(Pt(self.data.font_size * 0.2), Pt(x), Pt(y)),
+                    is_closed: true,
+                    has_fill: true,
+                    has_stroke: false,
+                    is_clipping_path: false,
+                });
+            }
+        }
+
+        layer.restore_graphics_state();
+    }
+}
+
+/// The area inside the axes, in points
+struct PlotArea {
+    left: f64,
+    bottom: f64,
+    width: f64,
+    height: f64,
+    y_min: f64,
+    y_max: f64,
+    num_categories: usize,
+}
+
+impl PlotArea {
+
+    /// Returns the y position of a value
+    fn y_pos(&self, value: f64)
+    -> f64
+    {
+        self.bottom + (value - self.y_min) / (self.y_max - self.y_min) * self.height
+    }
+
+    /// Returns the left edge and the width of the space for one category
+    fn slot(&self, category: usize)
+    -> (f64, f64)
+    {
+        let width = self.width / self.num_categories.max(1) as f64;
+        (self.left + width * category as f64, width)
+    }
+}
+
+impl ChartData {
+
+    /// Draws the legend, axes, tick labels and axis labels and returns the remaining plot area
+    fn draw_frame(&self, layer: &PdfLayerReference, font: &IndirectFontRef, x: Pt, y: Pt, width: Pt, height: Pt)
+    -> PlotArea
+    {
+        let fs = self.font_size;
+        let num_categories = self.series.iter().map(|s| s.values.len())
+            .chain(Some(self.categories.len()))
+            .max().unwrap_or(0);
+
+        let (data_min, data_max) = self.series.iter().flat_map(|s| s.values.iter())
+            .filter(|v| v.is_finite())
+            .fold((0.0_f64, 0.0_f64), |(min, max), v| (min.min(*v), max.max(*v)));
+        let (y_min, y_max, step) = nice_axis(data_min, data_max, 5);
+        let ticks = ((y_max - y_min) / step).round() as usize;
+        let tick_labels = (0..=ticks).map(|i| format_tick(y_min + step * i as f64, step)).collect::<Vec<String>>();
+
+        let tick_label_width = tick_labels.iter().map(|l| layer.text_width(l, font, fs).0).fold(0.0, f64::max);
+        let left = x.0 + tick_label_width + fs * 0.5 + if self.y_label.is_some() { fs * 1.5 } else { 0.0 };
+        let bottom = y.0 + fs * 1.8 + if self.x_label.is_some() { fs * 1.5 } else { 0.0 };
+        let top = y.0 + height.0 - if self.series.is_empty() { fs * 0.5 } else { fs * 2.0 };
+        let right = x.0 + width.0 - fs * 0.5;
+
+        let plot = PlotArea {
+            left,
+            bottom,
+            width: (right - left).max(0.0),
+            height: (top - bottom).max(0.0),
+            y_min,
+            y_max,
+            num_categories,
+        };
+
+        let black = Color::Greyscale(Greyscale::new(0.0, None));
+        let rule = |x1: f64, y1: f64, x2: f64, y2: f64| {
+            let mut rule = Line::from_iter(vec![(Point::new(Pt(x1), Pt(y1)), false), (Point::new(Pt(x2), Pt(y2)), false)]);
+            rule.set_stroke(true);
+            rule
+        };
+
+        // grid lines and tick labels
+        layer.set_outline_thickness(0.5);
+        layer.set_outline_color(Color::Greyscale(Greyscale::new(0.85, None)));
+        layer.set_fill_color(black.clone());
+        for (i, label) in tick_labels.iter().enumerate() {
+            let tick_y = plot.y_pos(y_min + step * i as f64);
+            layer.add_shape(rule(left, tick_y, right, tick_y));
+            let label_x = left - fs * 0.5 - layer.text_width(label, font, fs).0;
+            layer.use_text(label.clone(), fs, Pt(label_x).into(), Pt(tick_y - fs * 0.35).into(), font);
+        }
+
+        // axes
+        layer.set_outline_color(black.clone());
+        layer.add_shape(rule(left, bottom, left, top));
+        let zero_y = plot.y_pos(0.0);
+        layer.add_shape(rule(left, zero_y, right, zero_y));
+
+        // category labels
+        for (i, category) in self.categories.iter().enumerate().take(num_categories) {
+            let (slot_x, slot_width) = plot.slot(i);
+            let label_x = slot_x + (slot_width - layer.text_width(category, font, fs).0) / 2.0;
+            layer.use_text(category.clone(), fs, Pt(label_x).into(), Pt(bottom - fs * 1.3).into(), font);
+        }
+
+        // axis labels
+        if let Some(ref label) = self.x_label {
+            let label_x = left + (plot.width - layer.text_width(label, font, fs).0) / 2.0;
+            layer.use_text(label.clone(), fs, Pt(label_x).into(), Pt(y.0 + fs * 0.3).into(), font);
+        }
+
+        if let Some(ref label) = self.y_label {
+            let label_y = bottom + (plot.height - layer.text_width(label, font, fs).0) / 2.0;
+            layer.save_graphics_state();
+            layer.set_ctm(CurTransMat::Translate(Pt(x.0 + fs).into(), Pt(label_y).into()));
+            layer.set_ctm(CurTransMat::Rotate(270.0));
+            layer.use_text(label.clone(), fs, Mm(0.0), Mm(0.0), font);
+            layer.restore_graphics_state();
+        }
+
+        // legend
+        let mut legend_x = left;
+        let legend_y = y.0 + height.0 - fs * 1.2;
+        for (i, series) in self.series.iter().enumerate() {
+            layer.set_fill_color(self.palette.get(i));
+            let mut swatch = Line::rectangle(Pt(legend_x).into(), Pt(legend_y).into(), Pt(fs * 0.8).into(), Pt(fs * 0.8).into());
+            swatch.set_fill(true);
+            layer.add_shape(swatch);
+            layer.set_fill_color(black.clone());
+            layer.use_text(series.name.clone(), fs, Pt(legend_x + fs * 1.2).into(), Pt(legend_y).into(), font);
+            legend_x += fs * 2.4 + layer.text_width(&series.name, font, fs).0;
+        }
+
+        plot
+    }
+}
+
+/// Calculates an axis range that includes `min` and `max`, with at most
+/// (about) `max_ticks` steps of 1, 2 or 5 times a power of ten. Returns (min, max, step)
+fn nice_axis(min: f64, max: f64, max_ticks: usize)
+-> (f64, f64, f64)
+{
+    let range = if max - min > 0.0 { max - min } else { max.abs().max(1.0) };
+    let raw_step = range / max_ticks as f64;
+    let magnitude = 10.0_f64.powf(raw_step.log10().floor());
+    let step = magnitude * match raw_step / magnitude {
+        n if n <= 1.0 => 1.0,
+        n if n <= 2.0 => 2.0,
+        n if n <= 5.0 => 5.0,
+        _ => 10.0,
+    };
+
+    let axis_min = (min / step).floor() * step;
+    let axis_max = ((max / step).ceil() * step).max(axis_min + step);
+    (axis_min, axis_max, step)
+}
+
+/// Formats a tick value with as many decimal places as the step needs
+fn format_tick(value: f64, step: f64)
+-> String
+{
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+#[test]
+fn test_nice_axis() {
+    assert_eq!(nice_axis(0.0, 87.0, 5), (0.0, 100.0, 20.0));
+    assert_eq!(nice_axis(-3.0, 7.0, 5), (-4.0, 8.0, 2.0));
+    assert_eq!(nice_axis(0.0, 0.0, 5), (0.0, 0.2, 0.2));
+    assert_eq!(format_tick(0.4, 0.2), "0.4");
+}
+
+#[test]
+fn test_chart_skips_non_finite_values() {
+    use {BuiltinFont, PdfDocument};
+
+    let (doc, page, layer) = PdfDocument::new("chart", Mm(210.0), Mm(297.0), "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    // (operator, number of operands that are not finite) of all operations of the layer
+    let operations = || {
+        let document = doc.document.borrow();
+        document.pages[page.0].layers[layer.0].operations.iter()
+            .map(|op| (op.operator.clone(), op.operands.iter().filter(|o| matches!(o.as_f64(), Ok(v) if !v.is_finite())).count()))
+            .collect::<Vec<_>>()
+    };
+
+    BarChart::new()
+        .with_series("sales", vec![2.0, f64::NAN, 4.0])
+        .with_categories(vec!["a", "b", "c"])
+        .add_to_layer(&layer_ref, &font, Mm(10.0), Mm(10.0), Mm(100.0), Mm(60.0));
+    let bar_chart = operations();
+    assert!(bar_chart.iter().all(|&(_, non_finite)| non_finite == 0));
+    // two bars and the legend swatch
+    assert_eq!(bar_chart.iter().filter(|(op, _)| op == "f").count(), 3);
+
+    layer_ref.clear();
+    LineChart::new()
+        .with_series("visitors", vec![1.0, 2.0, f64::INFINITY, 3.0, 5.0])
+        .add_to_layer(&layer_ref, &font, Mm(10.0), Mm(10.0), Mm(100.0), Mm(60.0));
+    let line_chart = operations();
+    assert!(line_chart.iter().all(|&(_, non_finite)| non_finite == 0));
+    // the line is split in two at the missing value, four markers and the legend swatch
+    let count = |operator: &str| line_chart.iter().filter(|(op, _)| op == operator).count();
+    assert_eq!(count("f"), 5);
+    // 6 grid lines (0 - 5), the two axes and the two segments of the line
+    assert_eq!(count("S"), 10);
+}
@@ -4,9 +4,11 @@
 use lopdf;
 use lopdf::{Stream as LoStream, Dictionary as LoDictionary};
 use lopdf::StringFormat;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::iter::FromIterator;
-use Error;
+use {Error, Pt, Type3Font};
+use super::builtin_font_metrics::builtin_font_metrics;
+use super::font_subset::subset_font;
 
 use rusttype::FontCollection;
 
@@ -17,6 +19,8 @@ pub enum Font {
     BuiltinFont(BuiltinFont),
     /// Represents a font loaded from an external file
     ExternalFont(ExternalFont),
+    /// Represents a font made of custom glyphs
+    Type3Font(Type3Font),
 }
 
 /// Standard built-in PDF fonts
@@ -60,6 +64,53 @@ impl Into<&'static str> for BuiltinFont {
     }
 }
 
+impl BuiltinFont {
+
+    /// Returns true for the fonts that contain symbols instead of latin text
+    /// (`Symbol` and `ZapfDingbats`). These fonts use their built-in encoding instead of
+    /// WinAnsiEncoding: each character from U+0020 to U+00FF selects the glyph with that code
+    /// (for example, `a` is the greek alpha in `Symbol`).
+    pub fn is_symbolic(&self)
+    -> bool
+    {
+        matches!(*self, BuiltinFont::Symbol | BuiltinFont::ZapfDingbats)
+    }
+
+    /// Encodes the text for a `Tj` operator, characters that the encoding of
+    /// the font doesn't support are left out
+    pub(crate) fn encode_text(&self, text: &str)
+    -> Vec<u8>
+    {
+        if self.is_symbolic() {
+            text.chars()
+                .map(|ch| ch as u32)
+                .filter(|code| (0x20..=0xFF).contains(code))
+                .map(|code| code as u8)
+                .collect()
+        } else {
+            // see the WinAnsiEncoding in the Into<LoDictionary> implementation
+            lopdf::Document::encode_text(Some("WinAnsiEncoding"), text)
+        }
+    }
+
+    /// Returns the font dictionary, with a `ToUnicode` CMap for the WinAnsiEncoding.
+    /// The symbolic fonts have no `ToUnicode` CMap, their glyphs aren't unicode characters.
+    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document)
+    -> LoDictionary
+    {
+        let mut dict: LoDictionary = self.into();
+        if !self.is_symbolic() {
+            let font_id: &'static str = self.into();
+            let codes = (0x20..=0xFF_u8)
+                .filter_map(|code| lopdf::Document::decode_text(Some("WinAnsiEncoding"), &[code]).chars().next().map(|ch| (code, ch)))
+                .collect();
+            let to_unicode = simple_to_unicode_stream(font_id, codes);
+            dict.set("ToUnicode", lopdf::Object::Reference(doc.add_object(to_unicode)));
+        }
+        dict
+    }
+}
+
 impl Into<LoDictionary> for BuiltinFont {
     fn into(self) -> LoDictionary {
         use lopdf::Object;
@@ -72,11 +123,14 @@ impl Into<LoDictionary> for BuiltinFont {
             ("Type".into(), Name("Font".into())),
             ("Subtype".into(), Name("Type1".into())),
             ("BaseFont".into(), Name(font_id.into())),
-            ("Encoding".into(), Name("WinAnsiEncoding".into())),
-            // Missing DescendantFonts and ToUnicode
         ];
 
-        LoDictionary::from_iter(font_vec)
+        let mut dict = LoDictionary::from_iter(font_vec);
+        // the glyph names of WinAnsiEncoding don't exist in the symbolic fonts
+        if !self.is_symbolic() {
+            dict.set("Encoding", Name("WinAnsiEncoding".into()));
+        }
+        dict
     }
 }
 
@@ -97,19 +151,70 @@ pub struct ExternalFont {
 /// fill / stroke is determine by the current pages outline /
 /// fill color.
 ///
+/// The clipping modes (`FillClip`, `StrokeClip`, `FillStrokeClip` and `Clip`) additionally
+/// add the glyph outlines to the clipping path, once the text section is ended. Everything
+/// that is drawn afterwards (for example an image or a gradient) only shows through the
+/// letters. Since the clipping path can only be reset by restoring the graphics state,
+/// wrap the text and the clipped content in `save_graphics_state` / `restore_graphics_state`.
+///
 /// See PDF Reference 1.7 Page 402
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TextRenderingMode {
+    /// Fill the text (0)
     Fill,
+    /// Stroke the outlines of the text (1)
     Stroke,
+    /// Fill, then stroke the text (2)
     FillStroke,
+    /// Neither fill nor stroke the text (3), for example for an OCR text layer over a scan
     Invisible,
+    /// Fill the text and add it to the clipping path (4)
     FillClip,
+    /// Stroke the text and add it to the clipping path (5)
     StrokeClip,
+    /// Fill, then stroke the text and add it to the clipping path (6)
     FillStrokeClip,
+    /// Only add the text to the clipping path (7)
     Clip,
 }
 
+impl TextRenderingMode {
+
+    /// Returns the rendering mode for the value of the `Tr` operator,
+    /// or `None` if the value is not a valid mode (0 - 7)
+    pub fn from_i64(mode: i64)
+    -> Option<Self>
+    {
+        use TextRenderingMode::*;
+        match mode {
+            0 => Some(Fill),
+            1 => Some(Stroke),
+            2 => Some(FillStroke),
+            3 => Some(Invisible),
+            4 => Some(FillClip),
+            5 => Some(StrokeClip),
+            6 => Some(FillStrokeClip),
+            7 => Some(Clip),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the text is painted (filled and / or stroked) in this mode
+    pub fn is_visible(&self)
+    -> bool
+    {
+        !matches!(*self, TextRenderingMode::Invisible | TextRenderingMode::Clip)
+    }
+
+    /// Returns true if the text is added to the clipping path in this mode
+    pub fn adds_to_clip(&self)
+    -> bool
+    {
+        matches!(*self, TextRenderingMode::FillClip | TextRenderingMode::StrokeClip |
+                        TextRenderingMode::FillStrokeClip | TextRenderingMode::Clip)
+    }
+}
+
 impl Into<i64> for TextRenderingMode {
     fn into(self)
     -> i64
@@ -161,23 +266,44 @@ impl ExternalFont {
         }
     }
 
-    /// Takes the font and adds it to the document and consumes the font
-    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document)
+    /// Takes the font and adds it to the document and consumes the font. If `used_glyphs` is
+    /// set, only these glyphs are embedded (see `SaveOptions::subset_fonts`), fonts that can't
+    /// be subset are embedded completely.
+    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document, used_glyphs: Option<&BTreeSet<u16>>)
     -> LoDictionary
     {
         use lopdf::Object;
         use lopdf::Object::*;
 
-        let face_name = self.face_name.clone();
+        let glyph_ids = self.font_data.glyph_ids();
+        let subset = used_glyphs.and_then(|glyphs| {
+            let subset = subset_font(&self.font_bytes, glyphs, &glyph_ids);
+            #[cfg(feature = "logging")] {
+                if subset.is_none() {
+                    warn!("Font {} can't be subset, the whole font is embedded", self.face_name);
+                }
+            }
+            subset
+        });
+
+        // subset fonts are marked by a tag in front of the name
+        let face_name = match subset {
+            Some(ref subset) => format!("{}+{}", subset.tag(), self.face_name),
+            None => self.face_name.clone(),
+        };
 
         // Extract basic font information
         let face_metrics = self.font_data.font_metrics();
 
+        let font_bytes = match subset {
+            Some(ref subset) => subset.bytes.clone(),
+            None => self.font_bytes,
+        };
         let font_stream = LoStream::new(
             LoDictionary::from_iter(vec![
-                ("Length1", Integer(self.font_bytes.len() as i64)),
+                ("Length1", Integer(font_bytes.len() as i64)),
                 ]),
-            self.font_bytes)
+            font_bytes)
         .with_compression(false); /* important! font stream must not be compressed! */
 
         // Begin setting required font attributes
@@ -215,7 +341,11 @@ impl ExternalFont {
         let mut cmap = BTreeMap::<u32, (u32, u32, u32)>::new();
         cmap.insert(0, (0, 1000, 1000));
 
-        for (glyph_id, c) in self.font_data.glyph_ids() {
+        // widths and unicode values are only needed for the glyphs in the subset
+        let glyph_ids = glyph_ids.into_iter()
+            .filter(|(glyph_id, _)| subset.as_ref().map_or(true, |subset| subset.glyphs.contains(glyph_id)));
+
+        for (glyph_id, c) in glyph_ids {
             if let Some(glyph_metrics) = self.font_data.glyph_metrics(glyph_id) {
                 if glyph_metrics.height > max_height {
                     max_height = glyph_metrics.height;
@@ -320,6 +450,12 @@ impl ExternalFont {
             w, dw,
         ]);
 
+        // the content streams use the original glyph IDs as CIDs
+        if let Some(ref subset) = subset {
+            let cid_to_gid_map = LoStream::new(LoDictionary::new(), subset.cid_to_gid_map());
+            desc_fonts.set("CIDToGIDMap", Reference(doc.add_object(cid_to_gid_map)));
+        }
+
         let font_bbox = vec![ Integer(0), Integer(max_height as i64), Integer(total_width as i64), Integer(max_height as i64) ];
         font_descriptor_vec.push(("FontFile2".into(), Reference(doc.add_object(font_stream))));
 
@@ -341,15 +477,20 @@ type GlyphId = u32;
 type UnicodeCodePoint = u32;
 type CmapBlock = Vec<(GlyphId, UnicodeCodePoint)>;
 
-/// Generates a CMAP (character map) from valid cmap blocks
+/// Generates a CMAP (character map) from valid cmap blocks. The `.notdef` glyph (which has
+/// no character) is left out, characters outside of the BMP are written as UTF-16 surrogate pairs.
 fn generate_cid_to_unicode_map(face_name: String, all_cmap_blocks: Vec<CmapBlock>) -> String {
 
-    let mut cid_to_unicode_map = format!(include_str!("../../../../templates/gid_to_unicode_beg.txt"), face_name);
+    let mut cid_to_unicode_map = format!(include_str!("../../../../templates/gid_to_unicode_beg.txt"), face_name, "0000", "FFFF");
+
+    let all_cmap_blocks = all_cmap_blocks.into_iter()
+        .map(|block| block.into_iter().filter(|(_, unicode)| *unicode != 0).collect::<CmapBlock>())
+        .filter(|block| !block.is_empty());
 
-    for cmap_block in all_cmap_blocks.into_iter().filter(|block| !block.is_empty() || block.len() < 100) {
+    for cmap_block in all_cmap_blocks {
         cid_to_unicode_map.push_str(format!("{} beginbfchar\r\n", cmap_block.len()).as_str());
         for (glyph_id, unicode) in cmap_block {
-            cid_to_unicode_map.push_str(format!("<{:04x}> <{:04x}>\n", glyph_id, unicode).as_str());
+            cid_to_unicode_map.push_str(format!("<{:04x}> <{}>\n", glyph_id, utf16_hex(unicode)).as_str());
         }
         cid_to_unicode_map.push_str("endbfchar\r\n");
     }
@@ -358,6 +499,33 @@ fn generate_cid_to_unicode_map(face_name: String, all_cmap_blocks: Vec<CmapBlock
     cid_to_unicode_map
 }
 
+/// Generates the `ToUnicode` CMap stream of a font with single-byte character codes
+/// (built-in and Type3 fonts), so that the text can be copied and searched
+pub(crate) fn simple_to_unicode_stream(face_name: &str, codes: Vec<(u8, char)>)
+-> LoStream
+{
+    let mut to_unicode_map = format!(include_str!("../../../../templates/gid_to_unicode_beg.txt"), face_name, "00", "FF");
+
+    for block in codes.chunks(100) {
+        to_unicode_map.push_str(format!("{} beginbfchar\r\n", block.len()).as_str());
+        for (code, ch) in block {
+            to_unicode_map.push_str(format!("<{:02x}> <{}>\n", code, utf16_hex(*ch as u32)).as_str());
+        }
+        to_unicode_map.push_str("endbfchar\r\n");
+    }
+
+    to_unicode_map.push_str(include_str!("../../../../templates/gid_to_unicode_end.txt"));
+    LoStream::new(LoDictionary::new(), to_unicode_map.into_bytes())
+}
+
+/// Returns the UTF-16BE code units of the code point in hex, as used in `ToUnicode` CMaps
+fn utf16_hex(unicode: UnicodeCodePoint)
+-> String
+{
+    let c = ::std::char::from_u32(unicode).unwrap_or('\u{FFFD}');
+    c.encode_utf16(&mut [0; 2]).iter().map(|unit| format!("{:04x}", unit)).collect()
+}
+
 impl PartialEq for ExternalFont {
     /// Two fonts are equal if their names are equal, the contents aren't checked
     fn eq(&self, other: &ExternalFont) -> bool {
@@ -365,6 +533,334 @@ impl PartialEq for ExternalFont {
     }
 }
 
+/// Decorations that are drawn together with a line of text
+/// (see `PdfLayerReference::use_text_with_options`)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Draws a line below the text
+    pub underline: bool,
+    /// Draws a line through the text
+    pub strikethrough: bool,
+    /// Emboldens the text by additionally stroking the glyph outlines with the fill color.
+    /// This is only an approximation of a bold font, for when no bold face is available.
+    pub synthetic_bold: bool,
+    /// Slants the text by 12 degrees. This is only an approximation of an italic font
+    /// (the glyph shapes stay the same), for when no italic face is available.
+    pub synthetic_italic: bool,
+    /// Rendering mode of the text (see `TextRenderingMode`). The mode only applies to this
+    /// text, afterwards it is reset to `Fill`. If not set, the current rendering mode is used.
+    /// The mode is ignored for synthetic bold / italic text, which is always filled
+    /// (and stroked for bold).
+    pub rendering_mode: Option<TextRenderingMode>,
+}
+
+/// The faces of a font family that were added to the document, for selecting a face
+/// by weight and style (see `FontFamily::select`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFamily {
+    /// Regular face, always available
+    pub regular: IndirectFontRef,
+    /// Bold face
+    pub bold: Option<IndirectFontRef>,
+    /// Italic face
+    pub italic: Option<IndirectFontRef>,
+    /// Bold italic face
+    pub bold_italic: Option<IndirectFontRef>,
+}
+
+impl FontFamily {
+
+    /// Creates a font family that only has a regular face
+    pub fn new(regular: IndirectFontRef)
+    -> Self
+    {
+        Self { regular, bold: None, italic: None, bold_italic: None }
+    }
+
+    /// Sets the bold face of the family
+    #[inline]
+    pub fn with_bold(mut self, bold: IndirectFontRef)
+    -> Self
+    {
+        self.bold = Some(bold);
+        self
+    }
+
+    /// Sets the italic face of the family
+    #[inline]
+    pub fn with_italic(mut self, italic: IndirectFontRef)
+    -> Self
+    {
+        self.italic = Some(italic);
+        self
+    }
+
+    /// Sets the bold italic face of the family
+    #[inline]
+    pub fn with_bold_italic(mut self, bold_italic: IndirectFontRef)
+    -> Self
+    {
+        self.bold_italic = Some(bold_italic);
+        self
+    }
+
+    /// Returns the face for the weight and style, together with the text options to pass
+    /// to `PdfLayerReference::use_text_with_options`. If the family has no such face, the
+    /// closest face is returned and the missing weight / style is approximated with synthetic
+    /// bold / italic text (see `TextOptions::synthetic_bold`), e.g. a bold italic text
+    /// without a bold italic face uses the bold face with `synthetic_italic`.
+    pub fn select(&self, bold: bool, italic: bool)
+    -> (IndirectFontRef, TextOptions)
+    {
+        let candidates = match (bold, italic) {
+            (true, true) => vec![(&self.bold_italic, false, false), (&self.bold, false, true), (&self.italic, true, false)],
+            (true, false) => vec![(&self.bold, false, false)],
+            (false, true) => vec![(&self.italic, false, false)],
+            (false, false) => vec![],
+        };
+
+        let (font, synthetic_bold, synthetic_italic) = candidates.into_iter()
+            .filter_map(|(face, synthetic_bold, synthetic_italic)| face.as_ref().map(|face| (face.clone(), synthetic_bold, synthetic_italic)))
+            .next()
+            .unwrap_or_else(|| (self.regular.clone(), bold, italic));
+
+        (font, TextOptions { synthetic_bold, synthetic_italic, .. Default::default() })
+    }
+}
+
+/// Horizontal alignment of the lines of a paragraph (see `PdfLayerReference::add_aligned_paragraph`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextAlignment {
+    /// Lines start at the left edge of the paragraph (default)
+    Left,
+    /// Lines end at the right edge of the paragraph
+    Right,
+    /// Lines are centered between the edges of the paragraph
+    Center,
+    /// The space between words is stretched so that the lines fill the whole width.
+    /// The last line of a paragraph and lines with a single word are left-aligned.
+    Justify,
+}
+
+impl Default for TextAlignment {
+    fn default() -> Self {
+        TextAlignment::Left
+    }
+}
+
+/// What happens to characters that the font has no glyph for when text is written
+/// (see `PdfDocumentReference::with_missing_glyph_policy`). A warning is logged for
+/// every character that is substituted or skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissingGlyphPolicy {
+    /// The character is left out (default)
+    Skip,
+    /// The `.notdef` glyph (glyph 0) of the font is drawn instead, which usually shows up
+    /// as an empty box. Built-in and Type3 fonts have no addressable `.notdef` glyph,
+    /// so the character is left out for these fonts.
+    Notdef,
+    /// The character is written with the given font instead. If the fallback font can't
+    /// render the character either, it is left out.
+    Fallback(IndirectFontRef),
+    /// Strict mode: the character is left out and recorded, `check_for_errors` and
+    /// `save` then fail with `PdfError::MissingGlyph` (see `PdfDocumentReference::missing_glyphs`
+    /// and `clear_missing_glyphs`)
+    Error,
+}
+
+impl Default for MissingGlyphPolicy {
+    fn default() -> Self {
+        MissingGlyphPolicy::Skip
+    }
+}
+
+/// Stroke width of synthetic bold text, in fractions of the font size
+pub(crate) const SYNTHETIC_BOLD_STROKE_WIDTH: f64 = 0.03;
+
+/// Slant of synthetic italic text, in degrees
+pub(crate) const SYNTHETIC_ITALIC_ANGLE: f64 = 12.0;
+
+/// Line gap (in fractions of the font size) for fonts that don't specify one
+/// (builtin fonts, Type3 fonts and fonts without a `hhea` table)
+const DEFAULT_LINE_GAP: f64 = 0.2;
+
+/// Position and thickness of the underline and strikeout lines,
+/// in fractions of the font size (relative to the baseline)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct TextDecorationMetrics {
+    pub(crate) underline_position: f64,
+    pub(crate) underline_thickness: f64,
+    pub(crate) strikeout_position: f64,
+    pub(crate) strikeout_thickness: f64,
+}
+
+impl Font {
+
+    /// Renames an external or Type3 font (the resource name and the PostScript name of
+    /// external fonts), built-in fonts keep their name
+    pub(crate) fn set_face_name(&mut self, name: String) {
+        match *self {
+            Font::BuiltinFont(_) => { },
+            Font::ExternalFont(ref mut font) => font.face_name = name,
+            Font::Type3Font(ref mut font) => font.face_name = name,
+        }
+    }
+
+    /// Returns the bytes that select the glyph of the character in a text string
+    /// (`Tj` operand), or `None` if the font has no glyph for the character
+    pub(crate) fn encode_char(&self, ch: char)
+    -> Option<Vec<u8>>
+    {
+        match *self {
+            Font::ExternalFont(ref font) => font.font_data.glyph_id(ch).map(|gid| vec![(gid >> 8) as u8, (gid & 255) as u8]),
+            Font::Type3Font(ref font) => Some(font.encode_text(ch.encode_utf8(&mut [0; 4]))).filter(|bytes| !bytes.is_empty()),
+            Font::BuiltinFont(ref font) => Some(font.encode_text(ch.encode_utf8(&mut [0; 4]))).filter(|bytes| !bytes.is_empty()),
+        }
+    }
+
+    /// Returns the bytes that select the `.notdef` glyph, if the font has an addressable one
+    pub(crate) fn encode_notdef(&self)
+    -> Option<Vec<u8>>
+    {
+        match *self {
+            Font::ExternalFont(_) => Some(vec![0, 0]),
+            Font::Type3Font(_) | Font::BuiltinFont(_) => None,
+        }
+    }
+
+    /// Returns the width of the text in points, if it was written with this font in the given size,
+    /// from the advance widths of the glyphs (the `hmtx` table for external fonts, the AFM widths
+    /// for built-in fonts). Kerning is not applied. Characters that can't be rendered with this
+    /// font are ignored (same as in `write_text`).
+    pub fn text_width(&self, text: &str, font_size: f64)
+    -> Pt
+    {
+        let width_per_mille = match *self {
+            Font::BuiltinFont(ref font) => {
+                let metrics = builtin_font_metrics(*font);
+                font.encode_text(text).into_iter()
+                    .map(|code| f64::from(metrics.char_width(code)))
+                    .sum::<f64>()
+            },
+            Font::ExternalFont(ref font) => {
+                let units_per_em = f64::from(font.font_data.font_metrics().units_per_em.max(1));
+                let width = text.chars()
+                    .filter_map(|ch| font.font_data.glyph_id(ch))
+                    .filter_map(|gid| font.font_data.glyph_metrics(gid))
+                    .map(|metrics| f64::from(metrics.width))
+                    .sum::<f64>();
+                width * 1000.0 / units_per_em
+            },
+            Font::Type3Font(ref font) => return font.text_width(text, font_size),
+        };
+
+        Pt(width_per_mille * font_size / 1000.0)
+    }
+
+    /// Returns the ascent and the (negative) descent of the font, in multiples of the font size
+    pub(crate) fn vertical_metrics(&self)
+    -> (f64, f64)
+    {
+        match *self {
+            Font::BuiltinFont(ref font) => {
+                let metrics = builtin_font_metrics(*font);
+                (f64::from(metrics.ascent) / 1000.0, f64::from(metrics.descent) / 1000.0)
+            },
+            Font::ExternalFont(ref font) => {
+                let metrics = font.font_data.font_metrics();
+                let units_per_em = f64::from(metrics.units_per_em.max(1));
+                (f64::from(metrics.ascent) / units_per_em, f64::from(metrics.descent) / units_per_em)
+            },
+            Font::Type3Font(ref font) => {
+                let (_, lly, _, ury) = font.bbox();
+                (ury / 1000.0, lly / 1000.0)
+            },
+        }
+    }
+
+    /// Returns the recommended distance between two baselines (the leading) for text
+    /// in the given size: the ascent minus the descent plus the line gap of the font.
+    /// For external fonts, the line gap is read from the `hhea` table.
+    pub fn recommended_leading(&self, font_size: f64)
+    -> Pt
+    {
+        let (ascent, descent) = self.vertical_metrics();
+        let line_gap = match *self {
+            Font::ExternalFont(ref font) => {
+                let units_per_em = f64::from(font.font_data.font_metrics().units_per_em.max(1));
+                find_font_table(&font.font_bytes, b"hhea")
+                    .and_then(|hhea| hhea.get(8..10))
+                    .map(|b| f64::from(i16::from_be_bytes([b[0], b[1]])).max(0.0) / units_per_em)
+                    .unwrap_or(DEFAULT_LINE_GAP)
+            },
+            Font::BuiltinFont(_) | Font::Type3Font(_) => DEFAULT_LINE_GAP,
+        };
+
+        Pt((ascent - descent + line_gap) * font_size)
+    }
+
+    /// Returns the underline and strikeout metrics of the font. For external fonts, these are
+    /// read from the `post` and `OS/2` tables. If the tables are missing, common defaults are used.
+    pub(crate) fn text_decoration_metrics(&self)
+    -> TextDecorationMetrics
+    {
+        match *self {
+            Font::BuiltinFont(ref font) => {
+                let metrics = builtin_font_metrics(*font);
+                TextDecorationMetrics {
+                    underline_position: f64::from(metrics.underline_position) / 1000.0,
+                    underline_thickness: f64::from(metrics.underline_thickness) / 1000.0,
+                    strikeout_position: f64::from(metrics.x_height) / 2000.0,
+                    strikeout_thickness: f64::from(metrics.underline_thickness) / 1000.0,
+                }
+            },
+            Font::ExternalFont(ref font) => {
+                let units_per_em = f64::from(font.font_data.font_metrics().units_per_em.max(1));
+                let read_i16 = |table: &[u8], offset: usize| {
+                    table.get(offset..offset + 2).map(|b| f64::from(i16::from_be_bytes([b[0], b[1]])) / units_per_em)
+                };
+
+                let post = find_font_table(&font.font_bytes, b"post");
+                let os2 = find_font_table(&font.font_bytes, b"OS/2");
+
+                TextDecorationMetrics {
+                    underline_position: post.and_then(|t| read_i16(t, 8)).unwrap_or(-0.1),
+                    underline_thickness: post.and_then(|t| read_i16(t, 10)).filter(|t| *t > 0.0).unwrap_or(0.05),
+                    strikeout_position: os2.and_then(|t| read_i16(t, 28)).unwrap_or(0.25),
+                    strikeout_thickness: os2.and_then(|t| read_i16(t, 26)).filter(|t| *t > 0.0).unwrap_or(0.05),
+                }
+            },
+            Font::Type3Font(_) => TextDecorationMetrics {
+                underline_position: -0.1,
+                underline_thickness: 0.05,
+                strikeout_position: 0.25,
+                strikeout_thickness: 0.05,
+            },
+        }
+    }
+}
+
+/// Looks up a table (such as `post` or `OS/2`) in the raw bytes of a TrueType / OpenType font.
+/// For font collections, the table of the first font is returned.
+pub(crate) fn find_font_table<'a>(font_bytes: &'a [u8], tag: &[u8; 4])
+-> Option<&'a [u8]>
+{
+    let read_u32 = |offset: usize| {
+        font_bytes.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+
+    // font collections start with a list of offsets to the table directories
+    let directory = if font_bytes.get(0..4) == Some(b"ttcf") { read_u32(12)? } else { 0 };
+    let num_tables = font_bytes.get(directory + 4..directory + 6).map(|b| u16::from_be_bytes([b[0], b[1]]))?;
+
+    (0..usize::from(num_tables)).map(|i| directory + 12 + i * 16).find_map(|record| {
+        if font_bytes.get(record..record + 4)? != tag { return None; }
+        let offset = read_u32(record + 8)?;
+        let length = read_u32(record + 12)?;
+        font_bytes.get(offset..offset + length)
+    })
+}
+
 /// Indexed reference to a font that was added to the document
 /// This is a "reference by postscript name"
 #[derive(Debug, Hash, Eq, Ord, Clone, PartialEq, PartialOrd)]
@@ -431,6 +927,13 @@ impl FontList {
         }
     }
 
+    /// Returns if a font with this name was added to the list
+    pub(crate) fn contains_name(&self, name: &str)
+    -> bool
+    {
+        self.fonts.contains_key(&IndirectFontRef::new(name))
+    }
+
     /// Returns the number of fonts currenly in use
     #[inline]
     pub fn len(&self)
@@ -447,16 +950,34 @@ impl FontList {
         self.fonts.is_empty()
     }
 
-    /// Converts the fonts into a dictionary
-    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document)
+    /// Returns if any of the fonts is a builtin (not embedded) font
+    pub(crate) fn has_builtin_fonts(&self)
+    -> bool
+    {
+        self.fonts.values().any(|font| matches!(font.data, Font::BuiltinFont(_)))
+    }
+
+    /// Returns all fonts of the list, for moving them into another document
+    pub(crate) fn into_fonts(self)
+    -> Vec<(IndirectFontRef, DirectFontRef)>
+    {
+        self.fonts.into_iter().collect()
+    }
+
+    /// Converts the fonts into a dictionary. If `used_glyphs` (glyph IDs by font name) is set,
+    /// the external fonts are subset to these glyphs.
+    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document, used_glyphs: Option<&HashMap<String, BTreeSet<u16>>>)
     ->lopdf::Dictionary
     {
         let mut font_dict = lopdf::Dictionary::new();
+        let no_glyphs = BTreeSet::new();
 
         for (indirect_ref, direct_font_ref) in self.fonts {
+            let font_glyphs = used_glyphs.map(|used| used.get(&indirect_ref.name).unwrap_or(&no_glyphs));
             let font_dict_collected = match direct_font_ref.data {
-                Font::ExternalFont(font) => font.into_with_document(doc),
-                Font::BuiltinFont(font)  => font.into(),
+                Font::ExternalFont(font) => font.into_with_document(doc, font_glyphs),
+                Font::BuiltinFont(font)  => font.into_with_document(doc),
+                Font::Type3Font(font)    => font.into_with_document(doc),
             };
 
             doc.objects.insert(direct_font_ref.inner_obj, lopdf::Object::Dictionary(font_dict_collected));
@@ -502,7 +1023,7 @@ pub trait FontData: FontDataClone + std::fmt::Debug {
     fn glyph_id(&self, c: char) -> Option<u16>;
 
     /// Returns a mapping from glyph IDs to Unicode characters for all supported characters.
-    fn glyph_ids(&self) -> std::collections::HashMap<u16, char>;
+    fn glyph_ids(&self) -> HashMap<u16, char>;
 
     /// Returns the glyph metrics for a glyph of this font, if available.
     fn glyph_metrics(&self, glyph_id: u16) -> Option<GlyphMetrics>;
@@ -527,8 +1048,8 @@ impl FontData for rusttype::Font<'static> {
         }
     }
 
-    fn glyph_ids(&self) -> std::collections::HashMap<u16, char> {
-        let mut map = std::collections::HashMap::with_capacity(self.glyph_count());
+    fn glyph_ids(&self) -> HashMap<u16, char> {
+        let mut map = HashMap::with_capacity(self.glyph_count());
         for c in char::default()..std::char::MAX {
             if let Some(glyph_id) = self.glyph_id(c) {
                 map.insert(glyph_id, c);
@@ -569,3 +1090,94 @@ impl Clone for Box<dyn FontData> {
         self.clone_font_data()
     }
 }
+
+#[test]
+fn test_text_rendering_mode_from_i64() {
+    for mode in 0..8 {
+        let parsed = TextRenderingMode::from_i64(mode).unwrap();
+        assert_eq!(Into::<i64>::into(parsed), mode);
+    }
+    assert_eq!(TextRenderingMode::from_i64(8), None);
+    assert_eq!(TextRenderingMode::from_i64(-1), None);
+    assert!(TextRenderingMode::FillClip.adds_to_clip());
+    assert!(!TextRenderingMode::Clip.is_visible());
+}
+
+#[test]
+fn test_recommended_leading() {
+    let font = Font::BuiltinFont(BuiltinFont::Helvetica);
+    let (ascent, descent) = font.vertical_metrics();
+    let leading = font.recommended_leading(10.0);
+    assert!((leading.0 - (ascent - descent + DEFAULT_LINE_GAP) * 10.0).abs() < 1e-9);
+    assert!(leading.0 > 10.0);
+}
+
+#[test]
+fn test_encode_char() {
+    let font = Font::BuiltinFont(BuiltinFont::Helvetica);
+    assert_eq!(font.encode_char('a'), Some(vec![b'a']));
+    assert_eq!(font.encode_char('€'), Some(vec![0x80]));
+    assert_eq!(font.encode_char('中'), None);
+    assert_eq!(font.encode_notdef(), None);
+}
+
+#[test]
+fn test_builtin_font_encoding() {
+    let helvetica: LoDictionary = BuiltinFont::Helvetica.into();
+    assert_eq!(helvetica.get(b"Encoding").and_then(|e| e.as_name_str()).ok(), Some("WinAnsiEncoding"));
+    let symbol: LoDictionary = BuiltinFont::Symbol.into();
+    assert!(symbol.get(b"Encoding").is_err());
+    assert_eq!(BuiltinFont::Symbol.encode_text("ab\u{3b1}"), b"ab".to_vec());
+    assert_eq!(BuiltinFont::Helvetica.encode_text("\u{20ac}"), vec![0x80]);
+}
+
+#[test]
+fn test_text_width() {
+    // "Hello" in Helvetica: 722 + 556 + 222 + 222 + 556 = 2278 per mille
+    let width = Font::BuiltinFont(BuiltinFont::Helvetica).text_width("Hello", 10.0);
+    assert!((width.0 - 22.78).abs() < 1e-9);
+}
+
+#[test]
+fn test_cid_to_unicode_map() {
+    let blocks = vec![vec![(0, 0), (3, 'Ω' as u32), (4, 'Ж' as u32)], vec![], vec![(300, 0x1F600)]];
+    let map = generate_cid_to_unicode_map("F1".into(), blocks);
+    assert!(map.contains("2 beginbfchar\r\n<0003> <03a9>\n<0004> <0416>\nendbfchar"));
+    assert!(map.contains("1 beginbfchar\r\n<012c> <d83dde00>\nendbfchar"));
+    assert!(!map.contains("<0000> <0000>"));
+    assert!(!map.contains("0 beginbfchar"));
+}
+
+#[test]
+fn test_builtin_font_to_unicode() {
+    let mut doc = lopdf::Document::new();
+    let helvetica = BuiltinFont::Helvetica.into_with_document(&mut doc);
+    let to_unicode = helvetica.get(b"ToUnicode").and_then(|id| id.as_reference()).unwrap();
+    let to_unicode = doc.get_object(to_unicode).and_then(|stream| stream.as_stream()).unwrap();
+    let cmap = ::std::string::String::from_utf8_lossy(&to_unicode.content).into_owned();
+    assert!(cmap.contains("<00> <FF>"));
+    assert!(cmap.contains("<41> <0041>"));
+    // the euro sign is 0x80 in WinAnsiEncoding
+    assert!(cmap.contains("<80> <20ac>"));
+
+    let symbol = BuiltinFont::Symbol.into_with_document(&mut doc);
+    assert!(symbol.get(b"ToUnicode").is_err());
+}
+
+#[test]
+fn test_font_family_select() {
+    let family = FontFamily::new(IndirectFontRef::new("F0")).with_bold(IndirectFontRef::new("F1"));
+
+    let (font, options) = family.select(false, false);
+    assert_eq!((font.name.as_str(), options.synthetic_bold, options.synthetic_italic), ("F0", false, false));
+    let (font, options) = family.select(true, false);
+    assert_eq!((font.name.as_str(), options.synthetic_bold, options.synthetic_italic), ("F1", false, false));
+    // no italic faces: the bold face is slanted
+    let (font, options) = family.select(true, true);
+    assert_eq!((font.name.as_str(), options.synthetic_bold, options.synthetic_italic), ("F1", false, true));
+    let (font, options) = family.select(false, true);
+    assert_eq!((font.name.as_str(), options.synthetic_bold, options.synthetic_italic), ("F0", false, true));
+
+    let family = family.with_bold_italic(IndirectFontRef::new("F2"));
+    assert_eq!(family.select(true, true).0.name, "F2");
+}
@@ -0,0 +1,426 @@
+//! Subsetting of TrueType fonts, see `SaveOptions::subset_fonts`
+//!
+//! The subset only contains the glyphs that are used in the document (and the glyphs these
+//! are composed of). The glyphs are renumbered, so the font has to be embedded together with
+//! a `/CIDToGIDMap` that maps the original glyph IDs (which are used as CIDs in the content
+//! streams) to the new glyph IDs.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use lopdf::Object;
+use lopdf::content::Operation;
+use types::plugins::graphics::two_dimensional::font::find_font_table;
+use PdfPage;
+
+/// Font program that only contains some of the glyphs of a font
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FontSubset {
+    /// The new font program
+    pub(crate) bytes: Vec<u8>,
+    /// Original glyph ID of each glyph of the subset, indexed by the new glyph ID
+    pub(crate) glyphs: Vec<u16>,
+}
+
+impl FontSubset {
+
+    /// Returns the `/CIDToGIDMap` stream data: the new glyph ID (2 bytes, big endian)
+    /// for every CID (original glyph ID) up to the highest glyph ID in the subset
+    pub(crate) fn cid_to_gid_map(&self)
+    -> Vec<u8>
+    {
+        let max_cid = self.glyphs.iter().cloned().max().unwrap_or(0);
+        let mut map = vec![0; (usize::from(max_cid) + 1) * 2];
+        for (new_gid, old_gid) in self.glyphs.iter().enumerate() {
+            let cid = usize::from(*old_gid);
+            map[cid * 2..cid * 2 + 2].copy_from_slice(&(new_gid as u16).to_be_bytes());
+        }
+        map
+    }
+
+    /// Returns the six letter tag that is prepended to the font name of the subset
+    /// (`ABCDEF+Name`), derived from the glyphs, so that saving the same document twice
+    /// gives the same name
+    pub(crate) fn tag(&self)
+    -> String
+    {
+        let mut hash = self.glyphs.iter().fold(17_u32, |hash, gid| hash.wrapping_mul(31).wrapping_add(u32::from(*gid)));
+        (0..6).map(|_| {
+            let letter = (b'A' + (hash % 26) as u8) as char;
+            hash /= 26;
+            letter
+        }).collect()
+    }
+}
+
+/// Collects the glyph IDs that are drawn with each font (by resource name) on the pages.
+/// Text strings are read as 2-byte glyph IDs, which is how text is encoded for external fonts,
+/// the entries of other fonts should be ignored.
+pub(crate) fn collect_used_glyphs(pages: &[PdfPage])
+-> HashMap<String, BTreeSet<u16>>
+{
+    let mut used = HashMap::<String, BTreeSet<u16>>::new();
+
+    for page in pages {
+        let operations = page.layers.iter().flat_map(|layer| layer.operations.iter())
+            .chain(page.operations.iter());
+
+        // the font is part of the graphics state, so it is restored by `Q`
+        let mut font = None::<String>;
+        let mut stack = Vec::new();
+
+        for op in operations {
+            let strings: Vec<&Object> = match op.operator.as_str() {
+                "q" => { stack.push(font.clone()); continue; },
+                "Q" => { font = stack.pop().unwrap_or(None); continue; },
+                "Tf" => {
+                    font = op.operands.first().and_then(|name| name.as_name_str().ok()).map(|name| name.to_string());
+                    continue;
+                },
+                "Tj" | "'" => op.operands.iter().take(1).collect(),
+                "\"" => op.operands.iter().skip(2).take(1).collect(),
+                "TJ" => text_array(op),
+                _ => continue,
+            };
+
+            if let Some(ref font) = font {
+                let glyphs = used.entry(font.clone()).or_default();
+                for string in strings {
+                    if let Object::String(ref bytes, _) = *string {
+                        glyphs.extend(bytes.chunks(2).filter(|pair| pair.len() == 2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])));
+                    }
+                }
+            }
+        }
+    }
+
+    used
+}
+
+/// Returns the strings of a `TJ` operation (the numbers in between are position adjustments)
+fn text_array(op: &Operation)
+-> Vec<&Object>
+{
+    match op.operands.first() {
+        Some(Object::Array(ref items)) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Builds a subset of the TrueType font that only contains the `.notdef` glyph, the `glyphs`
+/// and the glyphs they are composed of. The `unicode` characters of the glyphs are written
+/// to the `cmap` of the subset.
+///
+/// Returns `None` if the font can't be subset (e.g. OpenType fonts with CFF outlines),
+/// the whole font should be embedded in that case.
+pub(crate) fn subset_font(font_bytes: &[u8], glyphs: &BTreeSet<u16>, unicode: &HashMap<u16, char>)
+-> Option<FontSubset>
+{
+    let head = find_font_table(font_bytes, b"head")?;
+    let maxp = find_font_table(font_bytes, b"maxp")?;
+    let hhea = find_font_table(font_bytes, b"hhea")?;
+    let hmtx = find_font_table(font_bytes, b"hmtx")?;
+    let loca = find_font_table(font_bytes, b"loca")?;
+    let glyf = find_font_table(font_bytes, b"glyf")?;
+
+    let num_glyphs = read_u16(maxp, 4)?;
+    let num_h_metrics = usize::from(read_u16(hhea, 34)?);
+    let long_loca = read_u16(head, 50)? == 1;
+
+    // glyph data of the original font
+    let glyph_data = |gid: u16| -> Option<&[u8]> {
+        let gid = usize::from(gid);
+        let (start, end) = if long_loca {
+            (read_u32(loca, gid * 4)? as usize, read_u32(loca, gid * 4 + 4)? as usize)
+        } else {
+            (usize::from(read_u16(loca, gid * 2)?) * 2, usize::from(read_u16(loca, gid * 2 + 2)?) * 2)
+        };
+        glyf.get(start..end.max(start))
+    };
+
+    // glyphs to keep: .notdef, the used glyphs and the components of composite glyphs
+    let mut keep = BTreeSet::new();
+    let mut queue = vec![0];
+    queue.extend(glyphs.iter().cloned().filter(|gid| *gid < num_glyphs));
+    while let Some(gid) = queue.pop() {
+        if keep.insert(gid) {
+            let components = composite_components(glyph_data(gid)?)?;
+            queue.extend(components.into_iter().map(|(_, component)| component).filter(|gid| *gid < num_glyphs));
+        }
+    }
+
+    let new_glyphs: Vec<u16> = keep.into_iter().collect();
+    let new_gid = new_glyphs.iter().enumerate().map(|(new, old)| (*old, new as u16)).collect::<HashMap<u16, u16>>();
+
+    // glyf + loca (always in the long format)
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::new();
+    for gid in &new_glyphs {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        let mut data = glyph_data(*gid)?.to_vec();
+        for (offset, component) in composite_components(&data)? {
+            let component = new_gid.get(&component).cloned().unwrap_or(0);
+            data[offset..offset + 2].copy_from_slice(&component.to_be_bytes());
+        }
+        new_glyf.extend(data);
+        while new_glyf.len() % 4 != 0 { new_glyf.push(0); }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    // hmtx, with a full metric for every glyph
+    let last_advance = read_u16(hmtx, num_h_metrics.checked_sub(1)? * 4)?;
+    let mut new_hmtx = Vec::new();
+    for gid in &new_glyphs {
+        let gid = usize::from(*gid);
+        let (advance, lsb) = if gid < num_h_metrics {
+            (read_u16(hmtx, gid * 4)?, read_u16(hmtx, gid * 4 + 2)?)
+        } else {
+            (last_advance, read_u16(hmtx, num_h_metrics * 4 + (gid - num_h_metrics) * 2).unwrap_or(0))
+        };
+        new_hmtx.extend_from_slice(&advance.to_be_bytes());
+        new_hmtx.extend_from_slice(&lsb.to_be_bytes());
+    }
+
+    let mut new_head = head.to_vec();
+    set_u32(&mut new_head, 8, 0); // checkSumAdjustment, set below
+    set_u16(&mut new_head, 50, 1);
+
+    let mut new_maxp = maxp.to_vec();
+    set_u16(&mut new_maxp, 4, new_glyphs.len() as u16);
+
+    let mut new_hhea = hhea.to_vec();
+    set_u16(&mut new_hhea, 34, new_glyphs.len() as u16);
+
+    let chars = new_glyphs.iter().enumerate()
+        .filter_map(|(new, old)| unicode.get(old).map(|c| (*c as u32, new as u16)))
+        .filter(|(code, _)| *code < 0xFFFF)
+        .collect::<BTreeMap<u32, u16>>();
+
+    let mut tables: Vec<([u8; 4], Vec<u8>)> = vec![
+        (*b"cmap", cmap_table(&chars)?),
+        (*b"glyf", new_glyf),
+        (*b"head", new_head),
+        (*b"hhea", new_hhea),
+        (*b"hmtx", new_hmtx),
+        (*b"loca", new_loca),
+        (*b"maxp", new_maxp),
+    ];
+
+    // glyph names are dropped, they refer to the original glyph IDs
+    if let Some(post) = find_font_table(font_bytes, b"post").and_then(|post| post.get(0..32)) {
+        let mut new_post = post.to_vec();
+        set_u32(&mut new_post, 0, 0x0003_0000);
+        tables.push((*b"post", new_post));
+    }
+
+    // tables that don't depend on the glyph IDs are copied
+    for tag in &[b"OS/2", b"cvt ", b"fpgm", b"gasp", b"name", b"prep"] {
+        if let Some(table) = find_font_table(font_bytes, tag) {
+            tables.push((**tag, table.to_vec()));
+        }
+    }
+
+    Some(FontSubset { bytes: write_font(tables), glyphs: new_glyphs })
+}
+
+/// Returns the components of a composite glyph: the offset of the glyph ID in the
+/// glyph data and the glyph ID. Simple glyphs have no components.
+fn composite_components(data: &[u8])
+-> Option<Vec<(usize, u16)>>
+{
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    let mut components = Vec::new();
+
+    // empty glyphs (e.g. the space) and glyphs with contours are not composite
+    if data.is_empty() || (read_u16(data, 0)? as i16) >= 0 {
+        return Some(components);
+    }
+
+    let mut offset = 10;
+    loop {
+        let flags = read_u16(data, offset)?;
+        components.push((offset + 2, read_u16(data, offset + 2)?));
+        offset += 4;
+        offset += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & WE_HAVE_A_SCALE != 0 {
+            offset += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            offset += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(components);
+        }
+    }
+}
+
+/// Builds a `cmap` table with a format 4 subtable (Windows, Unicode BMP) for the characters
+/// (mapped to the glyph IDs of the subset). Returns `None` if there are too many characters
+/// for the format.
+fn cmap_table(chars: &BTreeMap<u32, u16>)
+-> Option<Vec<u8>>
+{
+    // segments of consecutive characters with consecutive glyph IDs: (start, end, glyph of start)
+    let mut segments = Vec::<(u32, u32, u16)>::new();
+    for (code, gid) in chars {
+        match segments.last_mut() {
+            Some(segment) if segment.1 + 1 == *code && u32::from(segment.2) + (*code - segment.0) == u32::from(*gid) => segment.1 = *code,
+            _ => segments.push((*code, *code, *gid)),
+        }
+    }
+    // the last segment must map 0xFFFF
+    segments.push((0xFFFF, 0xFFFF, 0));
+
+    let seg_count = segments.len();
+    let length = 16 + seg_count * 8;
+    if length > usize::from(u16::MAX) {
+        return None;
+    }
+    let search_range = 2 * largest_power_of_two(seg_count);
+    let entry_selector = search_range.trailing_zeros() - 1;
+
+    let mut subtable = Vec::with_capacity(length);
+    for value in &[4, length, 0, seg_count * 2, search_range, entry_selector as usize, seg_count * 2 - search_range] {
+        subtable.extend_from_slice(&(*value as u16).to_be_bytes());
+    }
+    segments.iter().for_each(|segment| subtable.extend_from_slice(&(segment.1 as u16).to_be_bytes()));
+    subtable.extend_from_slice(&[0, 0]);
+    segments.iter().for_each(|segment| subtable.extend_from_slice(&(segment.0 as u16).to_be_bytes()));
+    segments.iter().for_each(|segment| {
+        let delta = if segment.0 == 0xFFFF { 1 } else { u32::from(segment.2).wrapping_sub(segment.0) as u16 };
+        subtable.extend_from_slice(&delta.to_be_bytes());
+    });
+    segments.iter().for_each(|_| subtable.extend_from_slice(&[0, 0]));
+
+    // version 0, one subtable (platform 3, encoding 1) at offset 12
+    let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12];
+    cmap.extend(subtable);
+    Some(cmap)
+}
+
+/// Returns the largest power of two that is not larger than `n` (for the binary search
+/// fields of the font headers)
+fn largest_power_of_two(n: usize)
+-> usize
+{
+    let mut power = 1;
+    while power * 2 <= n {
+        power *= 2;
+    }
+    power
+}
+
+/// Writes the tables into a TrueType font file and sets the checksum adjustment of `head`
+fn write_font(mut tables: Vec<([u8; 4], Vec<u8>)>)
+-> Vec<u8>
+{
+    tables.sort_by_key(|table| table.0);
+
+    let num_tables = tables.len();
+    let search_range = 16 * largest_power_of_two(num_tables);
+    let entry_selector = (search_range / 16).trailing_zeros() as usize;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x0001_0000_u32.to_be_bytes());
+    for value in &[num_tables, search_range, entry_selector, num_tables * 16 - search_range] {
+        font.extend_from_slice(&(*value as u16).to_be_bytes());
+    }
+
+    let mut offset = 12 + num_tables * 16;
+    let mut head_offset = None;
+    for (tag, data) in &tables {
+        if tag == b"head" { head_offset = Some(offset); }
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += (data.len() + 3) / 4 * 4;
+    }
+
+    for (_, data) in tables {
+        font.extend(data);
+        while font.len() % 4 != 0 { font.push(0); }
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = 0xB1B0_AFBA_u32.wrapping_sub(checksum(&font));
+        set_u32(&mut font, head_offset + 8, adjustment);
+    }
+
+    font
+}
+
+/// Sum of the data as big endian u32 values (the data is padded with zeros)
+fn checksum(data: &[u8])
+-> u32
+{
+    data.chunks(4).fold(0_u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize)
+-> Option<u16>
+{
+    data.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize)
+-> Option<u32>
+{
+    data.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn set_u16(data: &mut [u8], offset: usize, value: u16) {
+    if let Some(bytes) = data.get_mut(offset..offset + 2) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    if let Some(bytes) = data.get_mut(offset..offset + 4) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+#[test]
+fn test_subset_font() {
+    use rusttype::Font;
+    use FontData;
+
+    let font_bytes = ::std::fs::read("assets/fonts/RobotoMedium.ttf").unwrap();
+    let font = Font::from_bytes(font_bytes.clone()).unwrap();
+    let unicode = font.glyph_ids();
+    let glyphs = "Hello".chars().filter_map(|c| font.glyph_id(c)).collect::<BTreeSet<u16>>();
+
+    let subset = subset_font(&font_bytes, &glyphs, &unicode).unwrap();
+    assert!(subset.bytes.len() * 10 < font_bytes.len());
+    // .notdef + H, e, l, o
+    assert_eq!(subset.glyphs.len(), 5);
+    assert_eq!(subset.glyphs[0], 0);
+
+    // the subset is a valid font with the same metrics for the used glyphs
+    let subset_font = Font::from_bytes(subset.bytes.clone()).unwrap();
+    assert_eq!(subset_font.glyph_count(), 5);
+    let new_gid = subset_font.glyph_id('e').unwrap();
+    assert_eq!(subset.glyphs[usize::from(new_gid)], font.glyph_id('e').unwrap());
+    assert_eq!(subset_font.glyph_metrics(new_gid).unwrap().width, font.glyph_metrics(font.glyph_id('e').unwrap()).unwrap().width);
+
+    let map = subset.cid_to_gid_map();
+    let cid = usize::from(font.glyph_id('e').unwrap());
+    assert_eq!(u16::from_be_bytes([map[cid * 2], map[cid * 2 + 1]]), new_gid);
+}
+
+#[test]
+fn test_largest_power_of_two() {
+    assert_eq!(largest_power_of_two(1), 1);
+    assert_eq!(largest_power_of_two(7), 4);
+    assert_eq!(largest_power_of_two(16), 16);
+    assert_eq!(largest_power_of_two(17), 16);
+}
@@ -3,8 +3,29 @@
 
 #[cfg(feature = "embedded_images")]
 use image::{self, ImageDecoder, DynamicImage};
+use std::io::Read;
 use Mm;
-use {ImageXObject, PdfLayerReference};
+use {Error, ImageXObject, PdfLayerReference, StructureRole};
+
+/// Default for the maximum width / height of an image (in pixels) that can be added to a document
+pub const DEFAULT_MAX_IMAGE_DIMENSION: usize = 10_000;
+
+/// What to do when an image is larger than the maximum image dimension of the document
+/// (see `PdfDocumentReference::with_max_image_dimension`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OversizedImagePolicy {
+    /// Adding the image returns an error (default)
+    Error,
+    /// The image is downsampled to fit the maximum dimension. Compressed images
+    /// (for example JPEG images) can't be downsampled and still return an error.
+    Downsample,
+}
+
+impl Default for OversizedImagePolicy {
+    fn default() -> Self {
+        OversizedImagePolicy::Error
+    }
+}
 
 /// Image - wrapper around an `ImageXObject` to allow for more control
 /// within the library
@@ -43,10 +64,30 @@ impl<'a> Image {
             image: ImageXObject::from_dynamic_image(image),
         }
     }
+
+    /// Creates an image from a PNG file, keeping the alpha channel as a soft mask
+    /// (see `ImageXObject::try_from_png`)
+    pub fn try_from_png<R: Read>(png: R)
+    -> Result<Self, Error>
+    {
+        Ok(Self {
+            image: ImageXObject::try_from_png(png)?,
+        })
+    }
 }
 
 impl Image {
 
+    /// Creates an image from a JPEG file, without decoding and re-encoding the JPEG data
+    /// (see `ImageXObject::try_from_jpeg`)
+    pub fn try_from_jpeg<R: Read>(jpeg: R)
+    -> Result<Self, Error>
+    {
+        Ok(Self {
+            image: ImageXObject::try_from_jpeg(jpeg)?,
+        })
+    }
+
     /// Adds the image to a specific layer and consumes it
     /// This is due to a PDF weirdness - images are basically just "names"
     /// and you have to make sure that they are added to the same page
@@ -54,12 +95,40 @@ impl Image {
     ///
     /// You can use the "dpi" parameter to specify a scaling - the default is 300dpi
     ///
+    /// Images that are larger than the maximum image dimension of the document are downsampled
+    /// if the `OversizedImagePolicy` says so (keeping the size they would have had on the page),
+    /// otherwise they are added as they are. Use `try_add_to_layer` to enforce the limit.
     #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     pub fn add_to_layer(self, layer: PdfLayerReference,
                         translate_x: Option<Mm>, translate_y: Option<Mm>,
                         rotate_cw: Option<f64>,
                         scale_x: Option<f64>, scale_y: Option<f64>,
                         dpi: Option<f64>)
+    {
+        // without the limit, adding the image can't fail
+        let _ = self.add_to_layer_with_limit(layer, translate_x, translate_y, rotate_cw, scale_x, scale_y, dpi, false);
+    }
+
+    /// Same as `add_to_layer`, but returns an error if the image is larger than the
+    /// maximum image dimension of the document and can't be downsampled
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    pub fn try_add_to_layer(self, layer: PdfLayerReference,
+                            translate_x: Option<Mm>, translate_y: Option<Mm>,
+                            rotate_cw: Option<f64>,
+                            scale_x: Option<f64>, scale_y: Option<f64>,
+                            dpi: Option<f64>)
+    -> Result<(), Error>
+    {
+        self.add_to_layer_with_limit(layer, translate_x, translate_y, rotate_cw, scale_x, scale_y, dpi, true)
+    }
+
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    fn add_to_layer_with_limit(self, layer: PdfLayerReference,
+                               translate_x: Option<Mm>, translate_y: Option<Mm>,
+                               rotate_cw: Option<f64>,
+                               scale_x: Option<f64>, scale_y: Option<f64>,
+                               dpi: Option<f64>, enforce_limit: bool)
+    -> Result<(), Error>
     {
         // PDF maps an image to a 1x1 square, we have to adjust the transform matrix
         // to fix the distortion
@@ -69,13 +138,82 @@ impl Image {
         let image_w = self.image.width.into_pt(dpi);
         let image_h = self.image.height.into_pt(dpi);
 
-        let image = layer.add_image(self.image);
+        let image = layer.add_image(self.image, enforce_limit)?;
 
         let scale_x = scale_x.unwrap_or(1.);
         let scale_y = scale_y.unwrap_or(1.);
         let image_w = Some(image_w.0 * scale_x);
         let image_h = Some(image_h.0 * scale_y);
 
+        let tagged = layer.begin_auto_tagged_content(Some(StructureRole::Figure));
         layer.use_xobject(image, translate_x, translate_y, rotate_cw, image_w, image_h);
+        if tagged { layer.end_tagged_content(); }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn raw_image(width: usize, height: usize)
+-> Image
+{
+    use {ColorBits, ColorSpace, Px};
+    Image::from(ImageXObject::new(Px(width), Px(height), ColorSpace::Greyscale, ColorBits::Bit8,
+                                  false, None, None, vec![128; width * height]))
+}
+
+#[test]
+fn test_oversized_image_error() {
+    use {PdfDocument, PdfError, XObject};
+
+    let (doc, page, layer) = PdfDocument::new("images", Mm(210.0), Mm(297.0), "Layer 1");
+    assert_eq!(doc.document.borrow().max_image_dimension, DEFAULT_MAX_IMAGE_DIMENSION);
+    assert_eq!(doc.document.borrow().oversized_image_policy, OversizedImagePolicy::Error);
+
+    let doc = doc.with_max_image_dimension(4);
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    match raw_image(8, 2).try_add_to_layer(layer_ref.clone(), None, None, None, None, None, None) {
+        Err(Error::Pdf(PdfError::ImageTooLarge)) => { },
+        other => panic!("expected ImageTooLarge, got {:?}", other),
+    }
+
+    // the old signature doesn't enforce the limit, the image is added as it is
+    raw_image(8, 2).add_to_layer(layer_ref.clone(), None, None, None, None, None, None);
+    raw_image(4, 4).add_to_layer(layer_ref, None, None, None, None, None, None);
+    let document = doc.document.borrow();
+    let widths = document.pages[page.0].resources.xobjects.objects.values()
+        .filter_map(|xobject| match *xobject { XObject::Image(ref image) => Some(image.width.0), _ => None })
+        .collect::<::std::collections::BTreeSet<_>>();
+    assert_eq!(widths.into_iter().collect::<Vec<_>>(), vec![4, 8]);
+}
+
+#[test]
+fn test_oversized_image_downsample() {
+    use {PdfDocument, PdfError, XObject, ImageFilter};
+
+    let (doc, page, layer) = PdfDocument::new("images", Mm(210.0), Mm(297.0), "Layer 1");
+    let doc = doc.with_max_image_dimension(4).with_oversized_image_policy(OversizedImagePolicy::Downsample);
+    let layer_ref = doc.get_page(page).get_layer(layer);
+    raw_image(8, 2).try_add_to_layer(layer_ref.clone(), None, None, None, None, None, Some(72.0)).unwrap();
+
+    {
+        let document = doc.document.borrow();
+        let layer = &document.pages[page.0].layers[layer.0];
+        match document.pages[page.0].resources.xobjects.objects.values().next() {
+            Some(XObject::Image(image)) => assert_eq!((image.width.0, image.height.0), (4, 1)),
+            other => panic!("expected an image, got {:?}", other),
+        }
+        // the image keeps the size of the original image on the page (8 x 2 pt at 72 dpi)
+        let cm = layer.operations.iter().rev().find(|op| op.operator == "cm").unwrap();
+        let scale = cm.operands.iter().map(|o| o.as_f64().unwrap()).collect::<Vec<_>>();
+        assert!((scale[0] - 8.0).abs() < 1e-3 && (scale[3] - 2.0).abs() < 1e-3);
+    }
+
+    // compressed images can't be downsampled
+    let mut jpeg = raw_image(8, 2);
+    jpeg.image.image_filter = Some(ImageFilter::DCT);
+    match jpeg.try_add_to_layer(layer_ref, None, None, None, None, None, None) {
+        Err(Error::Pdf(PdfError::ImageTooLarge)) => { },
+        other => panic!("expected ImageTooLarge, got {:?}", other),
     }
 }
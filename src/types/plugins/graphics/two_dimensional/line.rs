@@ -1,11 +1,12 @@
 use lopdf;
 use glob_defines::{
     OP_PATH_CONST_MOVE_TO, OP_PATH_CONST_3BEZIER_V1, OP_PATH_CONST_3BEZIER_V2, OP_PATH_CONST_4BEZIER,
-    OP_PATH_CONST_LINE_TO, OP_PATH_PAINT_FILL_STROKE_CLOSE_NZ, OP_PATH_PAINT_FILL_NZ,
+    OP_PATH_CONST_LINE_TO, OP_PATH_PAINT_FILL_STROKE_CLOSE_NZ, OP_PATH_PAINT_FILL_STROKE_NZ, OP_PATH_PAINT_FILL_NZ,
     OP_PATH_PAINT_STROKE_CLOSE, OP_PATH_PAINT_STROKE, OP_PATH_PAINT_END, OP_PATH_CONST_CLIP_NZ,
 };
-use Point;
+use {Mm, Point, Pt, Rect};
 use std::iter::{FromIterator, IntoIterator};
+use utils::{calculate_points_for_ellipse, calculate_points_for_rounded_rect};
 
 #[derive(Debug, Clone)]
 pub struct Line {
@@ -48,6 +49,65 @@ impl FromIterator<(Point, bool)> for Line {
 
 impl Line {
 
+    /// Creates a closed rectangle from the lower left corner and the size. The line is neither
+    /// filled nor stroked, use `set_fill` / `set_stroke` before adding it with `add_shape`.
+    pub fn rectangle(x: Mm, y: Mm, width: Mm, height: Mm)
+    -> Self
+    {
+        Self::polygon(vec![
+            Point::new(x, y),
+            Point::new(x + width, y),
+            Point::new(x + width, y + height),
+            Point::new(x, y + height),
+        ])
+    }
+
+    /// Creates a closed polygon from the corner points (without bezier curves), the last
+    /// point is connected to the first one. See `rectangle` for filling / stroking the line.
+    pub fn polygon(points: Vec<Point>)
+    -> Self
+    {
+        Line {
+            points: points.into_iter().map(|p| (p, false)).collect(),
+            is_closed: true,
+            .. Default::default()
+        }
+    }
+
+    /// Creates a closed rectangle with rounded corners from the lower left corner and the size.
+    /// The radius of the corners is clamped to half of the shorter side.
+    /// See `rectangle` for filling / stroking the line.
+    pub fn rounded_rectangle<P: Into<Pt>>(x: P, y: P, width: P, height: P, radius: P)
+    -> Self
+    {
+        Line {
+            points: calculate_points_for_rounded_rect(x, y, width, height, radius),
+            is_closed: true,
+            .. Default::default()
+        }
+    }
+
+    /// Creates a closed circle around the center, approximated with four bezier curves.
+    /// See `rectangle` for filling / stroking the line.
+    pub fn circle<P: Into<Pt>>(center: Point, radius: P)
+    -> Self
+    {
+        let radius: Pt = radius.into();
+        Self::ellipse(center, radius, radius)
+    }
+
+    /// Creates a closed ellipse around the center with the horizontal and vertical radius,
+    /// approximated with four bezier curves. See `rectangle` for filling / stroking the line.
+    pub fn ellipse<P: Into<Pt>>(center: Point, radius_x: P, radius_y: P)
+    -> Self
+    {
+        Line {
+            points: calculate_points_for_ellipse(radius_x.into(), radius_y.into(), center.x, center.y),
+            is_closed: true,
+            .. Default::default()
+        }
+    }
+
     /// Sets if the line is closed or not
     #[inline]
     pub fn set_closed(&mut self, is_closed: bool) {
@@ -72,6 +132,47 @@ impl Line {
         self.is_clipping_path = is_clipping_path;
     }
 
+    /// Returns the bounding box of the line, `None` if the line has no points.
+    /// For bezier curves, the extrema of the curve are used (not the control points).
+    pub fn bbox(&self)
+    -> Option<Rect>
+    {
+        let first = self.points.first()?.0;
+        let mut min = (first.x.0, first.y.0);
+        let mut max = min;
+        let mut extend = |x: f64, y: f64| {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        };
+
+        // same segmentation as in `into_stream_op`
+        let mut current = 1;
+        while current < self.points.len() {
+            let p1 = &self.points[current - 1];
+            let p2 = &self.points[current];
+
+            if p1.1 && p2.1 && current + 2 < self.points.len() {
+                let p3 = &self.points[current + 1];
+                let p4 = &self.points[current + 2];
+                for t in bezier_extrema(p1.0.x.0, p2.0.x.0, p3.0.x.0, p4.0.x.0).iter()
+                    .chain(bezier_extrema(p1.0.y.0, p2.0.y.0, p3.0.y.0, p4.0.y.0).iter())
+                    .filter_map(|t| *t)
+                {
+                    extend(cubic_bezier(p1.0.x.0, p2.0.x.0, p3.0.x.0, p4.0.x.0, t),
+                           cubic_bezier(p1.0.y.0, p2.0.y.0, p3.0.y.0, p4.0.y.0, t));
+                }
+                extend(p4.0.x.0, p4.0.y.0);
+                current += 3;
+                continue;
+            }
+
+            extend(p2.0.x.0, p2.0.y.0);
+            current += 1;
+        }
+
+        Some(Rect::from_extents(Pt(min.0), Pt(min.1), Pt(max.0), Pt(max.1)))
+    }
+
     pub fn into_stream_op(self)
     -> Vec<lopdf::content::Operation>
     {
@@ -96,7 +197,7 @@ impl Line {
             if p1.1 && p2.1 {
                 // current point is a bezier handle
                 // valid bezier curve must have two sequential bezier handles
-                // we also can"t build a valid cubic bezier curve if the cuve contains less than
+                // we also can't build a valid cubic bezier curve if the curve contains less than
                 // four points. If p3 or p4 is marked as "next point is bezier handle" or not, doesn"t matter
                 if let Some(p3) = self.points.get(current + 1){
                     if let Some(p4) = self.points.get(current + 2){
@@ -121,36 +222,179 @@ impl Line {
             current += 1;
         }
 
-        // how to paint the path
-        if self.has_stroke {
-            if self.has_fill {
-                if self.is_closed {
-                    // is filled and stroked and closed
-                    operations.push(Operation::new(OP_PATH_PAINT_FILL_STROKE_CLOSE_NZ, vec![]));
-                } else {
-                    // is filled and stroked but not closed
-                    operations.push(Operation::new(OP_PATH_PAINT_FILL_NZ, vec![]));
-                }
-            } else if self.is_closed {
-                // not filled, but stroked and closed
-                operations.push(Operation::new(OP_PATH_PAINT_STROKE_CLOSE, vec![]));
+        operations.append(&mut paint_operations(self.is_closed, self.has_fill, self.has_stroke, self.is_clipping_path));
+        operations
+    }
+}
+
+/// Returns the operations to paint (or clip with) the path that was constructed before,
+/// shared by `Line` and `Path`
+pub(crate) fn paint_operations(is_closed: bool, has_fill: bool, has_stroke: bool, is_clipping_path: bool)
+-> Vec<lopdf::content::Operation>
+{
+    use lopdf::content::Operation;
+    let mut operations = Vec::<Operation>::new();
+
+    if has_stroke {
+        if has_fill {
+            if is_closed {
+                // is filled and stroked and closed
+                operations.push(Operation::new(OP_PATH_PAINT_FILL_STROKE_CLOSE_NZ, vec![]));
             } else {
-                // not filled, not closed but only stroked (regular path)
-                operations.push(Operation::new(OP_PATH_PAINT_STROKE, vec![]));
+                // is filled and stroked but not closed
+                operations.push(Operation::new(OP_PATH_PAINT_FILL_STROKE_NZ, vec![]));
             }
-        } else if self.has_fill {
-            // is not stroked, only filled
-            // closed-ness doesn't matter in this case, an area is always closed
-            operations.push(Operation::new(OP_PATH_PAINT_FILL_NZ, vec![]));
-        } else if self.is_clipping_path {
-            // set the path as a clipping path
-            operations.push(Operation::new(OP_PATH_CONST_CLIP_NZ, vec![]));
-            operations.push(Operation::new(OP_PATH_PAINT_END, vec![]));
+        } else if is_closed {
+            // not filled, but stroked and closed
+            operations.push(Operation::new(OP_PATH_PAINT_STROKE_CLOSE, vec![]));
         } else {
-            // no painting operation nothing, path is invisible, only end the path
-            operations.push(Operation::new(OP_PATH_PAINT_END, vec![]));
+            // not filled, not closed but only stroked (regular path)
+            operations.push(Operation::new(OP_PATH_PAINT_STROKE, vec![]));
         }
+    } else if has_fill {
+        // is not stroked, only filled
+        // closed-ness doesn't matter in this case, an area is always closed
+        operations.push(Operation::new(OP_PATH_PAINT_FILL_NZ, vec![]));
+    } else if is_clipping_path {
+        // set the path as a clipping path
+        operations.push(Operation::new(OP_PATH_CONST_CLIP_NZ, vec![]));
+        operations.push(Operation::new(OP_PATH_PAINT_END, vec![]));
+    } else {
+        // no painting operation nothing, path is invisible, only end the path
+        operations.push(Operation::new(OP_PATH_PAINT_END, vec![]));
+    }
 
-        operations
+    operations
+}
+
+/// Evaluates one coordinate of a cubic bezier curve at `t`
+pub(crate) fn cubic_bezier(p0: f64, c1: f64, c2: f64, p3: f64, t: f64)
+-> f64
+{
+    let u = 1.0 - t;
+    u * u * u * p0 + 3.0 * u * u * t * c1 + 3.0 * u * t * t * c2 + t * t * t * p3
+}
+
+/// Returns the parameters `t` (in 0..1) where one coordinate of a cubic bezier curve has
+/// its minimum or maximum, by solving the derivative `a * t^2 + b * t + c = 0`
+pub(crate) fn bezier_extrema(p0: f64, c1: f64, c2: f64, p3: f64)
+-> [Option<f64>; 2]
+{
+    let a = -p0 + 3.0 * c1 - 3.0 * c2 + p3;
+    let b = 2.0 * (p0 - 2.0 * c1 + c2);
+    let c = c1 - p0;
+    let in_range = |t: f64| if t > 0.0 && t < 1.0 { Some(t) } else { None };
+
+    if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON { return [None, None]; }
+        return [in_range(-c / b), None];
     }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return [None, None];
+    }
+
+    let root = discriminant.sqrt();
+    [in_range((-b + root) / (2.0 * a)), in_range((-b - root) / (2.0 * a))]
+}
+
+#[test]
+fn test_line_bbox() {
+    // quarter of a circle from (0, 100) to (100, 0) bulging outwards,
+    // the curve never reaches the control points at (55, 100) / (100, 55)
+    let line = Line::from_iter(vec![
+        (Point { x: Pt(0.0), y: Pt(100.0) }, true),
+        (Point { x: Pt(55.0), y: Pt(100.0) }, true),
+        (Point { x: Pt(100.0), y: Pt(55.0) }, true),
+        (Point { x: Pt(100.0), y: Pt(0.0) }, false),
+    ]);
+    let bbox = line.bbox().unwrap();
+    assert_eq!(Pt::from(bbox.width), Pt(100.0));
+    assert_eq!(Pt::from(bbox.height), Pt(100.0));
+
+    // s-shaped curve overshoots its end points
+    let line = Line::from_iter(vec![
+        (Point { x: Pt(0.0), y: Pt(0.0) }, true),
+        (Point { x: Pt(0.0), y: Pt(100.0) }, true),
+        (Point { x: Pt(100.0), y: Pt(-100.0) }, true),
+        (Point { x: Pt(100.0), y: Pt(0.0) }, false),
+    ]);
+    let bbox = line.bbox().unwrap();
+    assert!(bbox.height.0 > 0.0 && Pt::from(bbox.y).0 < -20.0);
+}
+
+#[test]
+fn test_line_paint_operator() {
+    let triangle = |has_fill: bool, has_stroke: bool| {
+        let line = Line {
+            points: vec![
+                (Point { x: Pt(0.0), y: Pt(0.0) }, false),
+                (Point { x: Pt(10.0), y: Pt(0.0) }, false),
+                (Point { x: Pt(5.0), y: Pt(10.0) }, false),
+            ],
+            has_fill,
+            has_stroke,
+            .. Default::default()
+        };
+        line.into_stream_op().iter().map(|op| op.operator.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(triangle(false, true), vec!["m", "l", "l", "S"]);
+    assert_eq!(triangle(true, false), vec!["m", "l", "l", "f"]);
+    assert_eq!(triangle(true, true), vec!["m", "l", "l", "B"]);
+}
+
+#[test]
+fn test_line_rectangle() {
+    let mut line = Line::rectangle(Mm(10.0), Mm(20.0), Mm(30.0), Mm(40.0));
+    assert!(line.is_closed);
+    assert_eq!(line.points.len(), 4);
+    assert!(line.points.iter().all(|p| !p.1));
+    assert_eq!(line.points[2].0, Point::new(Mm(40.0), Mm(60.0)));
+
+    line.set_stroke(true);
+    let ops: Vec<_> = line.into_stream_op().iter().map(|op| op.operator.clone()).collect();
+    assert_eq!(ops, vec!["m", "l", "l", "l", "s"]);
+}
+
+#[test]
+fn test_line_ellipse() {
+    let mut circle = Line::circle(Point::new(Mm(50.0), Mm(50.0)), Mm(10.0));
+    assert!(circle.is_closed);
+    let bbox = circle.bbox().unwrap();
+    assert!((bbox.width.0 - 20.0).abs() < 1e-3 && (bbox.x.0 - 40.0).abs() < 1e-3);
+
+    circle.set_fill(true);
+    let ops: Vec<_> = circle.into_stream_op().iter().map(|op| op.operator.clone()).collect();
+    assert_eq!(ops, vec!["m", "c", "c", "c", "c", "f"]);
+
+    let ellipse = Line::ellipse(Point::new(Mm(50.0), Mm(50.0)), Mm(20.0), Mm(10.0));
+    let bbox = ellipse.bbox().unwrap();
+    assert!((bbox.width.0 - 40.0).abs() < 1e-3 && (bbox.height.0 - 20.0).abs() < 1e-3);
+
+    // the radius can be given in points as well
+    let circle = Line::circle(Point::new(Mm(50.0), Mm(50.0)), Pt(72.0));
+    let bbox = circle.bbox().unwrap();
+    assert!((Pt::from(bbox.width).0 - 144.0).abs() < 1e-3);
+}
+
+#[test]
+fn test_line_rounded_rectangle() {
+    let mut line = Line::rounded_rectangle(Mm(10.0), Mm(20.0), Mm(30.0), Mm(40.0), Mm(5.0));
+    let bbox = line.bbox().unwrap();
+    assert!((bbox.width.0 - 30.0).abs() < 1e-3 && (bbox.height.0 - 40.0).abs() < 1e-3);
+
+    line.set_stroke(true);
+    let ops: Vec<_> = line.into_stream_op().iter().map(|op| op.operator.clone()).collect();
+    assert_eq!(ops, vec!["m", "l", "c", "l", "c", "l", "c", "l", "c", "s"]);
+
+    // the radius is clamped to half of the shorter side: the ends are half circles
+    let line = Line::rounded_rectangle(Mm(0.0), Mm(0.0), Mm(40.0), Mm(10.0), Mm(50.0));
+    let bbox = line.bbox().unwrap();
+    assert!((bbox.width.0 - 40.0).abs() < 1e-3 && (bbox.height.0 - 10.0).abs() < 1e-3);
+
+    // the sizes can be given in points as well
+    let line = Line::rounded_rectangle(Pt(0.0), Pt(0.0), Pt(144.0), Pt(72.0), Pt(10.0));
+    let bbox = line.bbox().unwrap();
+    assert!((Pt::from(bbox.width).0 - 144.0).abs() < 1e-3 && (Pt::from(bbox.height).0 - 72.0).abs() < 1e-3);
 }
@@ -2,12 +2,25 @@
 
 pub mod point;
 pub mod line;
+pub mod path;
+pub mod rect;
 pub mod font;
+pub(crate) mod font_subset;
+pub mod type3_font;
+pub(crate) mod builtin_font_metrics;
 // pub mod svg;
 pub mod image;
+pub mod chart;
+pub(crate) mod svg_export;
+pub(crate) mod svg_import;
+pub(crate) mod text_layout;
 
 pub use self::point::Point;
 pub use self::line::Line;
+pub use self::path::{Path, PathSegment};
+pub use self::rect::{Rect, DrawResult};
 pub use self::font::*;
+pub use self::type3_font::{Type3Font, GlyphProc};
 // pub use self::svg::Svg;
-pub use self::image::Image;
\ No newline at end of file
+pub use self::image::Image;
+pub use self::chart::{BarChart, LineChart, ChartSeries};
\ No newline at end of file
@@ -0,0 +1,226 @@
+//! Paths built from explicit segments (moves, straight lines and cubic bezier curves)
+
+use lopdf;
+use lopdf::content::Operation;
+use glob_defines::{OP_PATH_CONST_MOVE_TO, OP_PATH_CONST_LINE_TO, OP_PATH_CONST_4BEZIER};
+use types::plugins::graphics::two_dimensional::line::{paint_operations, cubic_bezier, bezier_extrema};
+use {Line, Point, Pt, Rect};
+
+/// One segment of a `Path`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    /// Starts a new subpath at the point
+    MoveTo(Point),
+    /// Straight line from the current point to the point
+    LineTo(Point),
+    /// Cubic bezier curve from the current point to `end`, with the control points `c1` and `c2`
+    CurveTo {
+        /// First control point (belongs to the current point)
+        c1: Point,
+        /// Second control point (belongs to `end`)
+        c2: Point,
+        /// End point of the curve
+        end: Point,
+    },
+}
+
+impl PathSegment {
+
+    /// Returns the point the segment ends at (the new current point)
+    #[inline]
+    pub fn end_point(&self)
+    -> Point
+    {
+        match *self {
+            PathSegment::MoveTo(p) | PathSegment::LineTo(p) => p,
+            PathSegment::CurveTo { end, .. } => end,
+        }
+    }
+}
+
+/// Path built from `PathSegment`s, for shapes that can't be expressed unambiguously
+/// with the points of a `Line`. A path should start with a `MoveTo` segment.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    /// Segments of the path
+    pub segments: Vec<PathSegment>,
+    /// Is the (last sub-) path closed or open?
+    pub is_closed: bool,
+    /// Should the path be filled (via winding-number rule)
+    pub has_fill: bool,
+    /// Should the path have an outline (stroke)?
+    pub has_stroke: bool,
+    /// Is this path a clipping path?
+    pub is_clipping_path: bool,
+}
+
+impl Path {
+
+    /// Creates a new path from the segments. The path is not closed, filled or stroked.
+    #[inline]
+    pub fn new(segments: Vec<PathSegment>)
+    -> Self
+    {
+        Path { segments, .. Default::default() }
+    }
+
+    /// Sets if the path is closed or not
+    #[inline]
+    pub fn set_closed(&mut self, is_closed: bool) {
+        self.is_closed = is_closed;
+    }
+
+    /// Sets if the path is filled
+    #[inline]
+    pub fn set_fill(&mut self, has_fill: bool) {
+        self.has_fill = has_fill;
+    }
+
+    /// Sets if the path is stroked (has an outline)
+    #[inline]
+    pub fn set_stroke(&mut self, has_stroke: bool) {
+        self.has_stroke = has_stroke;
+    }
+
+    /// Sets if the path is a clipping path
+    #[inline]
+    pub fn set_as_clipping_path(&mut self, is_clipping_path: bool) {
+        self.is_clipping_path = is_clipping_path;
+    }
+
+    /// Returns the bounding box of the path, `None` if the path has no segments.
+    /// For bezier curves, the extrema of the curve are used (not the control points).
+    pub fn bbox(&self)
+    -> Option<Rect>
+    {
+        let first = self.segments.first()?.end_point();
+        let mut min = (first.x.0, first.y.0);
+        let mut max = min;
+        let mut extend = |x: f64, y: f64| {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        };
+
+        let mut current = first;
+        for segment in &self.segments {
+            if let PathSegment::CurveTo { c1, c2, end } = *segment {
+                let p0 = current;
+                for t in bezier_extrema(p0.x.0, c1.x.0, c2.x.0, end.x.0).iter()
+                    .chain(bezier_extrema(p0.y.0, c1.y.0, c2.y.0, end.y.0).iter())
+                    .filter_map(|t| *t)
+                {
+                    extend(cubic_bezier(p0.x.0, c1.x.0, c2.x.0, end.x.0, t),
+                           cubic_bezier(p0.y.0, c1.y.0, c2.y.0, end.y.0, t));
+                }
+            }
+            current = segment.end_point();
+            extend(current.x.0, current.y.0);
+        }
+
+        Some(Rect::from_extents(Pt(min.0), Pt(min.1), Pt(max.0), Pt(max.1)))
+    }
+
+    /// Returns the operations that construct the path, without painting it
+    pub(crate) fn segment_operations(&self)
+    -> Vec<Operation>
+    {
+        self.segments.iter().map(|segment| {
+            let (operator, points) = match *segment {
+                PathSegment::MoveTo(p) => (OP_PATH_CONST_MOVE_TO, vec![p]),
+                PathSegment::LineTo(p) => (OP_PATH_CONST_LINE_TO, vec![p]),
+                PathSegment::CurveTo { c1, c2, end } => (OP_PATH_CONST_4BEZIER, vec![c1, c2, end]),
+            };
+            let operands = points.iter().flat_map(|p| vec![lopdf::Object::from(p.x), p.y.into()]).collect();
+            Operation::new(operator, operands)
+        }).collect()
+    }
+
+    /// Returns the operations that construct and paint (fill, stroke and / or clip) the path.
+    /// An empty path results in no operations.
+    pub fn into_stream_op(self)
+    -> Vec<Operation>
+    {
+        if self.segments.is_empty() { return Vec::new(); };
+
+        let mut operations = self.segment_operations();
+        operations.append(&mut paint_operations(self.is_closed, self.has_fill, self.has_stroke, self.is_clipping_path));
+        operations
+    }
+}
+
+impl From<Line> for Path {
+    /// Converts the points of the line into segments, with the same handling of bezier
+    /// control points as `Line::into_stream_op`
+    fn from(line: Line)
+    -> Self
+    {
+        let mut segments = Vec::new();
+        if let Some(first) = line.points.first() {
+            segments.push(PathSegment::MoveTo(first.0));
+        }
+
+        let mut current = 1;
+        while current < line.points.len() {
+            let p1 = &line.points[current - 1];
+            let p2 = &line.points[current];
+
+            if p1.1 && p2.1 && current + 2 < line.points.len() {
+                let (c2, end) = (line.points[current + 1].0, line.points[current + 2].0);
+                segments.push(PathSegment::CurveTo { c1: p2.0, c2, end });
+                current += 3;
+                continue;
+            }
+
+            segments.push(PathSegment::LineTo(p2.0));
+            current += 1;
+        }
+
+        Path {
+            segments,
+            is_closed: line.is_closed,
+            has_fill: line.has_fill,
+            has_stroke: line.has_stroke,
+            is_clipping_path: line.is_clipping_path,
+        }
+    }
+}
+
+#[test]
+fn test_path_stream_op() {
+    let point = |x: f64, y: f64| Point { x: Pt(x), y: Pt(y) };
+    let mut path = Path::new(vec![
+        PathSegment::MoveTo(point(0.0, 0.0)),
+        PathSegment::CurveTo { c1: point(0.0, 0.0), c2: point(10.0, 10.0), end: point(10.0, 10.0) },
+        PathSegment::LineTo(point(20.0, 0.0)),
+    ]);
+    path.set_stroke(true);
+
+    let bbox = path.bbox().unwrap();
+    assert_eq!(Pt::from(bbox.width), Pt(20.0));
+    assert_eq!(Pt::from(bbox.height), Pt(10.0));
+
+    // the curve is always written with `c`, even if control points coincide with the end points
+    let ops = path.into_stream_op();
+    let operators: Vec<_> = ops.iter().map(|op| op.operator.as_str()).collect();
+    assert_eq!(operators, vec!["m", "c", "l", "S"]);
+    assert_eq!(ops[1].operands.len(), 6);
+}
+
+#[test]
+fn test_path_from_line() {
+    use Mm;
+    use std::iter::FromIterator;
+
+    let path = Path::from(Line::rectangle(Mm(0.0), Mm(0.0), Mm(10.0), Mm(10.0)));
+    assert_eq!(path.segments.len(), 4);
+    assert!(path.is_closed);
+
+    let point = |x: f64, y: f64| Point { x: Pt(x), y: Pt(y) };
+    let curve = Line::from_iter(vec![
+        (point(1.0, 1.0), true), (point(1.0, 10.0), true), (point(10.0, 10.0), false), (point(10.0, 1.0), false),
+    ]);
+    assert_eq!(Path::from(curve).segments, vec![
+        PathSegment::MoveTo(point(1.0, 1.0)),
+        PathSegment::CurveTo { c1: point(1.0, 10.0), c2: point(10.0, 10.0), end: point(10.0, 1.0) },
+    ]);
+}
@@ -1,4 +1,4 @@
-use {Mm, Pt};
+use Pt;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Point {
@@ -10,11 +10,11 @@ pub struct Point {
 
 impl Point {
 
-    /// Create a new point.
+    /// Create a new point, the coordinates can be in any unit (`Mm`, `Pt` or `In`).
     /// **WARNING: The reference point for a point is the bottom left corner, not the top left**
     #[inline]
-    pub fn new(x: Mm, y: Mm)
-    -> Self
+    pub fn new<X, Y>(x: X, y: Y)
+    -> Self where X: Into<Pt>, Y: Into<Pt>
     {
         Self {
             x: x.into(),
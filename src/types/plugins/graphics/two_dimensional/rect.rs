@@ -0,0 +1,52 @@
+//! Axis-aligned rectangles, used for the bounding boxes of drawn content
+
+use {Mm, Pt};
+
+/// Axis-aligned rectangle, measured from the bottom left corner of the page
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rect {
+    /// x position of the lower left corner
+    pub x: Mm,
+    /// y position of the lower left corner
+    pub y: Mm,
+    /// Width of the rectangle
+    pub width: Mm,
+    /// Height of the rectangle
+    pub height: Mm,
+}
+
+impl Rect {
+
+    /// Creates a new rectangle from the lower left corner and the size
+    #[inline]
+    pub fn new(x: Mm, y: Mm, width: Mm, height: Mm)
+    -> Self
+    {
+        Self { x, y, width, height }
+    }
+
+    /// Returns the smallest rectangle containing both rectangles
+    pub fn union(&self, other: &Rect)
+    -> Rect
+    {
+        let min_x = self.x.0.min(other.x.0);
+        let min_y = self.y.0.min(other.y.0);
+        let max_x = (self.x.0 + self.width.0).max(other.x.0 + other.width.0);
+        let max_y = (self.y.0 + self.height.0).max(other.y.0 + other.height.0);
+        Rect::new(Mm(min_x), Mm(min_y), Mm(max_x - min_x), Mm(max_y - min_y))
+    }
+
+    /// Creates the rectangle from the minimum and maximum coordinates (in points)
+    pub(crate) fn from_extents(min_x: Pt, min_y: Pt, max_x: Pt, max_y: Pt)
+    -> Self
+    {
+        Rect::new(min_x.into(), min_y.into(), (max_x - min_x).into(), (max_y - min_y).into())
+    }
+}
+
+/// Result of a draw call on a layer
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrawResult {
+    /// Bounding box of the drawn content (without the outline thickness)
+    pub bbox: Rect,
+}
@@ -106,7 +106,7 @@ impl Svg {
 
     /// This is similar to the `image.add_to_layer` method, the only difference being
     /// that it calls a different function
-    /// This should be seperated out into a macro
+    /// This should be separated out into a macro
     pub fn add_to_layer(self, layer: PdfLayerReference,
                         translate_x: Option<f64>, translate_y: Option<f64>,
                         rotate_cw: Option<f64>,
@@ -0,0 +1,345 @@
+//! Debug export of a layer to SVG. This is the inverse of the SVG import: it walks the
+//! operations of a layer and translates the ones that printpdf emits back into SVG elements,
+//! so that a layer can be inspected (or diffed) in a browser.
+//!
+//! Only the operators that printpdf itself writes are covered (paths, colors, line styles,
+//! transformation matrices and simple text). Everything else is written out as an XML comment.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use lopdf::Object;
+use lopdf::content::Operation;
+use {Font, FontList, IndirectFontRef, Pt};
+
+/// Graphics state that is tracked while translating, saved / restored with `q` / `Q`
+#[derive(Debug, Clone)]
+struct SvgGraphicsState {
+    fill: String,
+    stroke: String,
+    line_width: f64,
+    line_cap: &'static str,
+    line_join: &'static str,
+    dash_array: Option<String>,
+    /// Number of `<g>` elements opened by `cm` operators in this state
+    open_groups: usize,
+}
+
+impl Default for SvgGraphicsState {
+    fn default() -> Self {
+        Self {
+            fill: "rgb(0,0,0)".into(),
+            stroke: "rgb(0,0,0)".into(),
+            line_width: 1.0,
+            line_cap: "butt",
+            line_join: "miter",
+            dash_array: None,
+            open_groups: 0,
+        }
+    }
+}
+
+/// Text state, only valid between `BT` and `ET`
+#[derive(Debug, Clone)]
+struct SvgTextState {
+    font: Option<String>,
+    font_size: f64,
+    leading: f64,
+    /// Text line matrix (`[a b c d e f]`)
+    line_matrix: [f64; 6],
+}
+
+impl Default for SvgTextState {
+    fn default() -> Self {
+        Self {
+            font: None,
+            font_size: 12.0,
+            leading: 0.0,
+            line_matrix: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Translates a list of layer operations into an SVG document with the given size.
+///
+/// The `fonts` are used to translate glyph IDs of external fonts back into characters.
+pub(crate) fn operations_to_svg(operations: &[Operation], width: Pt, height: Pt, fonts: &FontList)
+-> String
+{
+    let mut svg = String::new();
+    let mut glyph_maps = HashMap::<String, HashMap<u16, char>>::new();
+    let mut stack = vec![SvgGraphicsState::default()];
+    let mut text = SvgTextState::default();
+    let mut path = String::new();
+    // current point and start of the current subpath, for `v` and `h`
+    let mut current_point = (0.0, 0.0);
+    let mut subpath_start = (0.0, 0.0);
+
+    let _ = writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}pt\" height=\"{h}pt\" viewBox=\"0 0 {w} {h}\">",
+                     w = num(width.0), h = num(height.0));
+    // PDF has its origin in the bottom left corner, SVG in the top left corner
+    let _ = writeln!(svg, "<g transform=\"matrix(1 0 0 -1 0 {})\">", num(height.0));
+
+    for op in operations {
+        let nums = op.operands.iter().filter_map(as_number).collect::<Vec<f64>>();
+        let state = stack.last_mut().unwrap();
+
+        match op.operator.as_str() {
+            // --- graphics state
+            "q" => {
+                let mut new_state = state.clone();
+                new_state.open_groups = 0;
+                stack.push(new_state);
+            },
+            "Q" => {
+                if stack.len() > 1 {
+                    let old_state = stack.pop().unwrap();
+                    close_groups(&mut svg, old_state.open_groups);
+                }
+            },
+            "cm" if nums.len() == 6 => {
+                let _ = writeln!(svg, "<g transform=\"matrix({})\">", join(&nums));
+                state.open_groups += 1;
+            },
+            "w" if !nums.is_empty() => state.line_width = nums[0],
+            "J" if !nums.is_empty() => {
+                state.line_cap = match nums[0] as i64 { 1 => "round", 2 => "square", _ => "butt" };
+            },
+            "j" if !nums.is_empty() => {
+                state.line_join = match nums[0] as i64 { 1 => "round", 2 => "bevel", _ => "miter" };
+            },
+            "d" => {
+                state.dash_array = match op.operands.first() {
+                    Some(Object::Array(arr)) if !arr.is_empty() => {
+                        Some(join(&arr.iter().filter_map(as_number).collect::<Vec<f64>>()))
+                    },
+                    _ => None,
+                };
+            },
+
+            // --- colors
+            "rg" | "g" | "k" => state.fill = to_svg_color(&nums),
+            "RG" | "G" | "K" => state.stroke = to_svg_color(&nums),
+
+            // --- path construction
+            "m" if nums.len() == 2 => {
+                let _ = write!(path, "M{} {} ", num(nums[0]), num(nums[1]));
+                current_point = (nums[0], nums[1]);
+                subpath_start = current_point;
+            },
+            "l" if nums.len() == 2 => {
+                let _ = write!(path, "L{} {} ", num(nums[0]), num(nums[1]));
+                current_point = (nums[0], nums[1]);
+            },
+            "c" if nums.len() == 6 => {
+                let _ = write!(path, "C{} ", join(&nums));
+                current_point = (nums[4], nums[5]);
+            },
+            "v" if nums.len() == 4 => {
+                // first control point is the current point (SVG `S` would reflect the previous control point)
+                let _ = write!(path, "C{} {} {} ", num(current_point.0), num(current_point.1), join(&nums));
+                current_point = (nums[2], nums[3]);
+            },
+            "y" if nums.len() == 4 => {
+                // second control point is the end point
+                let _ = write!(path, "C{} {} {} ", join(&nums[0..2]), join(&nums[2..4]), join(&nums[2..4]));
+                current_point = (nums[2], nums[3]);
+            },
+            "re" if nums.len() == 4 => {
+                let _ = write!(path, "M{} {} h{} v{} h{} Z ",
+                               num(nums[0]), num(nums[1]), num(nums[2]), num(nums[3]), num(-nums[2]));
+                current_point = (nums[0], nums[1]);
+                subpath_start = current_point;
+            },
+            "h" => {
+                path.push_str("Z ");
+                current_point = subpath_start;
+            },
+
+            // --- clipping paths are not exported, the path is discarded by the following "n"
+            "W" | "W*" => { },
+            "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" => paint(&mut svg, &mut path, state, op.operator.as_str()),
+            "n" => path.clear(),
+
+            // --- text
+            "BT" => text = SvgTextState { font: text.font.clone(), font_size: text.font_size, leading: text.leading, .. Default::default() },
+            "ET" => { },
+            "Tf" => {
+                if let Some(Object::Name(name)) = op.operands.first() {
+                    text.font = Some(String::from_utf8_lossy(name).to_string());
+                }
+                if let Some(size) = op.operands.get(1).and_then(as_number) {
+                    text.font_size = size;
+                }
+            },
+            "TL" if !nums.is_empty() => text.leading = nums[0],
+            "Td" if nums.len() == 2 => translate_text(&mut text.line_matrix, nums[0], nums[1]),
+            "T*" => { let leading = text.leading; translate_text(&mut text.line_matrix, 0.0, -leading); },
+            "Tm" if nums.len() == 6 => text.line_matrix = [nums[0], nums[1], nums[2], nums[3], nums[4], nums[5]],
+            "Tj" | "TJ" => {
+                let string = decode_text(op, &text, fonts, &mut glyph_maps);
+                let _ = writeln!(svg, "<text transform=\"matrix({}) scale(1 -1)\" font-family=\"{}\" font-size=\"{}\" fill=\"{}\">{}</text>",
+                                 join(&text.line_matrix), escape(text.font.as_deref().unwrap_or("sans-serif")),
+                                 num(text.font_size), state.fill, escape(&string));
+            },
+
+            // --- everything else (XObjects, marked content, graphics state dictionaries)
+            other => {
+                let _ = writeln!(svg, "<!-- unsupported operator: {} -->", escape(other));
+            },
+        }
+    }
+
+    while let Some(state) = stack.pop() {
+        close_groups(&mut svg, state.open_groups);
+    }
+
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// Writes the current path as a `<path>` element, according to the painting operator
+fn paint(svg: &mut String, path: &mut String, state: &SvgGraphicsState, operator: &str) {
+
+    if path.is_empty() { return; }
+
+    let (fill, stroke, close) = match operator {
+        "S"               => (false, true, false),
+        "s"               => (false, true, true),
+        "f" | "F" | "f*"  => (true, false, false),
+        "B" | "B*"        => (true, true, false),
+        "b" | "b*"        => (true, true, true),
+        _                 => (false, false, false),
+    };
+
+    if close { path.push_str("Z "); }
+
+    let fill_rule = if operator.ends_with('*') { "evenodd" } else { "nonzero" };
+
+    let _ = write!(svg, "<path d=\"{}\" fill=\"{}\" fill-rule=\"{}\"", path.trim_end(),
+                   if fill { state.fill.as_str() } else { "none" }, fill_rule);
+
+    if stroke {
+        let _ = write!(svg, " stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"{}\" stroke-linejoin=\"{}\"",
+                       state.stroke, num(state.line_width), state.line_cap, state.line_join);
+        if let Some(ref dash) = state.dash_array {
+            let _ = write!(svg, " stroke-dasharray=\"{}\"", dash);
+        }
+    }
+
+    svg.push_str("/>\n");
+    path.clear();
+}
+
+/// Decodes the string operands of a `Tj` / `TJ` operator
+fn decode_text(op: &Operation, text: &SvgTextState, fonts: &FontList, glyph_maps: &mut HashMap<String, HashMap<u16, char>>)
+-> String
+{
+    let font_name = text.font.clone().unwrap_or_default();
+
+    let glyph_map = glyph_maps.entry(font_name.clone()).or_insert_with(|| {
+        match fonts.get_font(&IndirectFontRef::new(font_name.clone())).map(|f| f.data) {
+            Some(Font::ExternalFont(font)) => font.font_data.glyph_ids(),
+            _ => HashMap::new(),
+        }
+    });
+
+    let mut strings = Vec::new();
+    for operand in &op.operands {
+        match operand {
+            Object::String(bytes, _) => strings.push(bytes.clone()),
+            Object::Array(arr) => strings.extend(arr.iter().filter_map(|o| o.as_str().ok().map(|s| s.to_vec()))),
+            _ => { },
+        }
+    }
+
+    strings.into_iter().map(|bytes| {
+        if glyph_map.is_empty() {
+            // built-in font, encoded as WinAnsiEncoding
+            lopdf::Document::decode_text(Some("WinAnsiEncoding"), &bytes)
+        } else {
+            bytes.chunks(2)
+                 .map(|c| (u16::from(c[0]) << 8) | u16::from(*c.get(1).unwrap_or(&0)))
+                 .map(|gid| *glyph_map.get(&gid).unwrap_or(&'\u{FFFD}'))
+                 .collect()
+        }
+    }).collect()
+}
+
+/// Moves the text line matrix by `tx`, `ty` (same as the `Td` operator)
+fn translate_text(m: &mut [f64; 6], tx: f64, ty: f64) {
+    m[4] += tx * m[0] + ty * m[2];
+    m[5] += tx * m[1] + ty * m[3];
+}
+
+fn close_groups(svg: &mut String, count: usize) {
+    for _ in 0..count {
+        svg.push_str("</g>\n");
+    }
+}
+
+/// Converts the color operands (grey, RGB or CMYK) to an SVG color
+fn to_svg_color(components: &[f64]) -> String {
+    let (r, g, b) = match *components {
+        [grey] => (grey, grey, grey),
+        [r, g, b] => (r, g, b),
+        [c, m, y, k] => ((1.0 - c) * (1.0 - k), (1.0 - m) * (1.0 - k), (1.0 - y) * (1.0 - k)),
+        _ => (0.0, 0.0, 0.0),
+    };
+
+    let to_byte = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("rgb({},{},{})", to_byte(r), to_byte(g), to_byte(b))
+}
+
+fn as_number(obj: &Object) -> Option<f64> {
+    match *obj {
+        Object::Integer(i) => Some(i as f64),
+        Object::Real(r) => Some(r),
+        _ => None,
+    }
+}
+
+/// Formats a number with at most 4 decimal places
+fn num(n: f64) -> String {
+    let s = format!("{:.4}", n);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".into() } else { s.into() }
+}
+
+fn join(nums: &[f64]) -> String {
+    nums.iter().map(|n| num(*n)).collect::<Vec<String>>().join(" ")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[test]
+fn test_svg_export_path() {
+    use glob_defines::{OP_PATH_CONST_MOVE_TO, OP_PATH_CONST_LINE_TO, OP_PATH_PAINT_STROKE};
+
+    let ops = vec![
+        Operation::new("RG", vec![Object::Real(1.0), Object::Real(0.0), Object::Real(0.0)]),
+        Operation::new(OP_PATH_CONST_MOVE_TO, vec![Object::Real(10.0), Object::Real(20.0)]),
+        Operation::new(OP_PATH_CONST_LINE_TO, vec![Object::Real(30.5), Object::Integer(40)]),
+        Operation::new(OP_PATH_PAINT_STROKE, vec![]),
+    ];
+
+    let svg = operations_to_svg(&ops, Pt(100.0), Pt(50.0), &FontList::new());
+    assert!(svg.contains("<path d=\"M10 20 L30.5 40\" fill=\"none\" fill-rule=\"nonzero\" stroke=\"rgb(255,0,0)\""));
+    assert!(svg.contains("matrix(1 0 0 -1 0 50)"));
+}
+
+#[test]
+fn test_svg_export_v_curve() {
+    use glob_defines::{OP_PATH_CONST_MOVE_TO, OP_PATH_CONST_3BEZIER_V1, OP_PATH_PAINT_STROKE};
+
+    let ops = vec![
+        Operation::new(OP_PATH_CONST_MOVE_TO, vec![Object::Real(10.0), Object::Real(20.0)]),
+        Operation::new(OP_PATH_CONST_3BEZIER_V1, vec![Object::Real(30.0), Object::Real(40.0), Object::Real(50.0), Object::Real(20.0)]),
+        Operation::new(OP_PATH_PAINT_STROKE, vec![]),
+    ];
+
+    // the current point (10 20) is the first control point
+    let svg = operations_to_svg(&ops, Pt(100.0), Pt(50.0), &FontList::new());
+    assert!(svg.contains("<path d=\"M10 20 C10 20 30 40 50 20\""));
+}
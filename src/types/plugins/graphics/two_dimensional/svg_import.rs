@@ -0,0 +1,723 @@
+//! Minimal SVG importer: converts the basic shapes of an SVG file into PDF drawing operations.
+//!
+//! Supported are `<path>` (all commands), `<rect>`, `<circle>`, `<ellipse>`, `<line>`,
+//! `<polyline>` and `<polygon>`, nested in `<g>` elements with `transform`s. Shapes are
+//! painted with solid `fill` / `stroke` colors and `stroke-width`, given as attributes or
+//! in the `style` attribute. Text, gradients, clipping, CSS stylesheets and references
+//! (`<use>`) are not supported and ignored.
+
+use lopdf::content::Operation;
+use lopdf::Object::Real;
+use std::f64::consts::PI;
+use {PdfError, Pt};
+
+/// Affine transformation matrix `[a b c d e f]`, maps `(x, y)` to `(ax + cy + e, bx + dy + f)`
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// SVG file, converted into PDF operations
+#[derive(Debug, Clone)]
+pub(crate) struct SvgImage {
+    /// Width of the image (from the `width` attribute of the root element or the view box)
+    pub(crate) width: Pt,
+    /// Height of the image (from the `height` attribute of the root element or the view box)
+    pub(crate) height: Pt,
+    /// Operations that draw the image into the rectangle from (0, 0) to (width, height)
+    pub(crate) operations: Vec<Operation>,
+}
+
+/// Paint of the current element, inherited from the parent elements
+#[derive(Debug, Copy, Clone)]
+struct Style {
+    fill: Option<(f64, f64, f64)>,
+    stroke: Option<(f64, f64, f64)>,
+    stroke_width: f64,
+    even_odd: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self { fill: Some((0.0, 0.0, 0.0)), stroke: None, stroke_width: 1.0, even_odd: false }
+    }
+}
+
+/// State of an open element
+#[derive(Debug, Copy, Clone)]
+struct Group {
+    style: Style,
+    ctm: Matrix,
+    /// Content of non-rendered elements (`<defs>`, `<clipPath>`, ...) is skipped
+    hidden: bool,
+}
+
+/// Parses the SVG and converts it into PDF operations
+pub(crate) fn import_svg(svg: &str)
+-> Result<SvgImage, PdfError>
+{
+    let mut image: Option<SvgImage> = None;
+    let mut stack: Vec<Group> = Vec::new();
+
+    for tag in tags(svg)? {
+        let (name, attributes, self_closing) = match tag {
+            Tag::Close => { stack.pop(); continue; },
+            Tag::Open { name, attributes, self_closing } => (name, attributes, self_closing),
+        };
+
+        let attribute = |key: &str| attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+        let parent = match (stack.last(), image.as_ref()) {
+            (Some(group), _) => *group,
+            (None, None) if name == "svg" => {
+                let (svg_image, ctm) = root_size(&attribute)?;
+                image = Some(svg_image);
+                Group { style: Style::default(), ctm, hidden: false }
+            },
+            // content after the root element
+            _ => continue,
+        };
+
+        let local = attribute("transform").map(parse_transform).unwrap_or(IDENTITY);
+        let ctm = multiply(local, parent.ctm);
+        let style = apply_style(parent.style, &attribute);
+        let hidden = parent.hidden || attribute("display") == Some("none") || matches!(name.as_str(),
+            "defs" | "clipPath" | "mask" | "symbol" | "marker" | "pattern" | "linearGradient" |
+            "radialGradient" | "filter" | "style" | "title" | "desc" | "metadata" | "text");
+
+        if !hidden {
+            if let Some(path) = shape_path(&name, &attribute) {
+                let operations = &mut image.as_mut().unwrap().operations;
+                paint_path(operations, &path, &style, &ctm);
+            }
+        }
+
+        if !self_closing {
+            stack.push(Group { style, ctm, hidden });
+        }
+    }
+
+    image.ok_or(PdfError::InvalidSvg)
+}
+
+/// Determines the size of the image from the root element and returns the transformation from
+/// the view box into the PDF coordinate system (y axis going up)
+fn root_size<'a, F>(attribute: &F)
+-> Result<(SvgImage, Matrix), PdfError> where F: Fn(&str) -> Option<&'a str>
+{
+    let view_box = attribute("viewBox").and_then(|v| {
+        let numbers = parse_numbers(v);
+        if numbers.len() == 4 && numbers[2] > 0.0 && numbers[3] > 0.0 {
+            Some((numbers[0], numbers[1], numbers[2], numbers[3]))
+        } else {
+            None
+        }
+    });
+
+    // percentages can't be resolved, fall back to the view box (in px)
+    let width = attribute("width").and_then(parse_length).or_else(|| view_box.map(|v| v.2));
+    let height = attribute("height").and_then(parse_length).or_else(|| view_box.map(|v| v.3));
+
+    let (width, height) = match (width, height) {
+        (Some(w), Some(h)) if w > 0.0 && h > 0.0 => (Pt(w * 0.75), Pt(h * 0.75)),
+        _ => return Err(PdfError::InvalidSvg),
+    };
+
+    // without a view box, user units are px
+    let (vx, vy, vw, vh) = view_box.unwrap_or((0.0, 0.0, width.0 / 0.75, height.0 / 0.75));
+
+    // preserveAspectRatio="xMidYMid meet" (the default): uniform scaling, centered
+    let scale = (width.0 / vw).min(height.0 / vh);
+    let offset_x = (width.0 - vw * scale) / 2.0;
+    let offset_y = (height.0 - vh * scale) / 2.0;
+    let ctm = [scale, 0.0, 0.0, -scale, offset_x - vx * scale, height.0 - offset_y + vy * scale];
+
+    Ok((SvgImage { width, height, operations: Vec::new() }, ctm))
+}
+
+/// Applies the presentation attributes / `style` of an element to the inherited style
+fn apply_style<'a, F>(mut style: Style, attribute: &F)
+-> Style where F: Fn(&str) -> Option<&'a str>
+{
+    let mut properties: Vec<(&str, &str)> = ["fill", "stroke", "stroke-width", "fill-rule"].iter()
+        .filter_map(|key| attribute(key).map(|value| (*key, value)))
+        .collect();
+
+    // the style attribute has precedence over the presentation attributes
+    if let Some(css) = attribute("style") {
+        properties.extend(css.split(';').filter_map(|declaration| {
+            let mut parts = declaration.splitn(2, ':');
+            Some((parts.next()?.trim(), parts.next()?.trim()))
+        }));
+    }
+
+    for (key, value) in properties {
+        match key {
+            "fill" => if let Some(color) = parse_paint(value) { style.fill = color; },
+            "stroke" => if let Some(color) = parse_paint(value) { style.stroke = color; },
+            "stroke-width" => if let Some(width) = parse_length(value) { style.stroke_width = width; },
+            "fill-rule" => style.even_odd = value == "evenodd",
+            _ => { },
+        }
+    }
+
+    style
+}
+
+/// Segment of a path in user space
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Segment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    CurveTo(f64, f64, f64, f64, f64, f64),
+    Close,
+}
+
+/// Returns the outline of a basic shape element, or `None` if the element is not a shape
+fn shape_path<'a, F>(name: &str, attribute: &F)
+-> Option<Vec<Segment>> where F: Fn(&str) -> Option<&'a str>
+{
+    use self::Segment::*;
+    let length = |key: &str| attribute(key).and_then(parse_length).unwrap_or(0.0);
+
+    match name {
+        "path" => Some(parse_path(attribute("d").unwrap_or(""))),
+        "rect" => {
+            let (x, y, w, h) = (length("x"), length("y"), length("width"), length("height"));
+            if w <= 0.0 || h <= 0.0 { return None; }
+            Some(vec![MoveTo(x, y), LineTo(x + w, y), LineTo(x + w, y + h), LineTo(x, y + h), Close])
+        },
+        "circle" => Some(ellipse(length("cx"), length("cy"), length("r"), length("r"))),
+        "ellipse" => Some(ellipse(length("cx"), length("cy"), length("rx"), length("ry"))),
+        "line" => Some(vec![MoveTo(length("x1"), length("y1")), LineTo(length("x2"), length("y2"))]),
+        "polyline" | "polygon" => {
+            let points = parse_numbers(attribute("points").unwrap_or(""));
+            let mut path: Vec<Segment> = points.chunks(2).filter(|p| p.len() == 2).enumerate()
+                .map(|(i, p)| if i == 0 { MoveTo(p[0], p[1]) } else { LineTo(p[0], p[1]) })
+                .collect();
+            if name == "polygon" && !path.is_empty() { path.push(Close); }
+            Some(path)
+        },
+        _ => None,
+    }
+}
+
+/// Approximates an ellipse with four cubic Bézier curves
+fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64)
+-> Vec<Segment>
+{
+    use self::Segment::*;
+    if rx <= 0.0 || ry <= 0.0 { return Vec::new(); }
+    let (kx, ky) = (rx * 0.552_284_8, ry * 0.552_284_8);
+    vec![
+        MoveTo(cx + rx, cy),
+        CurveTo(cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry),
+        CurveTo(cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy),
+        CurveTo(cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry),
+        CurveTo(cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy),
+        Close,
+    ]
+}
+
+/// Adds the operations to paint the path (in user space) with the style
+fn paint_path(operations: &mut Vec<Operation>, path: &[Segment], style: &Style, ctm: &Matrix)
+{
+    use self::Segment::*;
+
+    let paint = match (style.fill.is_some(), style.stroke.is_some(), style.even_odd) {
+        (true, true, false) => "B",
+        (true, true, true) => "B*",
+        (true, false, false) => "f",
+        (true, false, true) => "f*",
+        (false, true, _) => "S",
+        (false, false, _) => return,
+    };
+
+    if path.is_empty() { return; }
+
+    let point = |x: f64, y: f64| vec![Real(ctm[0] * x + ctm[2] * y + ctm[4]), Real(ctm[1] * x + ctm[3] * y + ctm[5])];
+
+    operations.push(Operation::new("q", vec![]));
+
+    if let Some((r, g, b)) = style.fill {
+        operations.push(Operation::new("rg", vec![Real(r), Real(g), Real(b)]));
+    }
+
+    if let Some((r, g, b)) = style.stroke {
+        // stroke widths are scaled with the (average) scale of the transformation
+        let scale = (ctm[0] * ctm[3] - ctm[1] * ctm[2]).abs().sqrt();
+        operations.push(Operation::new("RG", vec![Real(r), Real(g), Real(b)]));
+        operations.push(Operation::new("w", vec![Real(style.stroke_width * scale)]));
+    }
+
+    for segment in path {
+        operations.push(match *segment {
+            MoveTo(x, y) => Operation::new("m", point(x, y)),
+            LineTo(x, y) => Operation::new("l", point(x, y)),
+            CurveTo(x1, y1, x2, y2, x, y) => {
+                let mut operands = point(x1, y1);
+                operands.extend(point(x2, y2));
+                operands.extend(point(x, y));
+                Operation::new("c", operands)
+            },
+            Close => Operation::new("h", vec![]),
+        });
+    }
+
+    operations.push(Operation::new(paint, vec![]));
+    operations.push(Operation::new("Q", vec![]));
+}
+
+/// Reads numbers and flags from path data / attribute values
+struct NumberReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> NumberReader<'a> {
+
+    fn skip_separators(&mut self) {
+        while self.position < self.data.len() && (self.data[self.position].is_ascii_whitespace() || self.data[self.position] == b',') {
+            self.position += 1;
+        }
+    }
+
+    /// Returns the next command letter, without consuming it
+    fn peek_command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.data.get(self.position).cloned().filter(|c| c.is_ascii_alphabetic() && !c.eq_ignore_ascii_case(&b'e'))
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        self.skip_separators();
+        let start = self.position;
+        let mut end = start;
+        let mut seen_dot = false;
+        let mut seen_exponent = false;
+
+        while end < self.data.len() {
+            let c = self.data[end];
+            let is_sign = (c == b'-' || c == b'+') && (end == start || matches!(self.data[end - 1], b'e' | b'E'));
+            let is_dot = c == b'.' && !seen_dot && !seen_exponent;
+            let is_exponent = c.eq_ignore_ascii_case(&b'e') && !seen_exponent && end > start;
+            if !(c.is_ascii_digit() || is_sign || is_dot || is_exponent) { break; }
+            seen_dot |= is_dot;
+            seen_exponent |= is_exponent;
+            end += 1;
+        }
+
+        let number = ::std::str::from_utf8(&self.data[start..end]).ok()?.parse().ok()?;
+        self.position = end;
+        Some(number)
+    }
+
+    /// Arc flags may be written without separators (`a1 1 0 011 1`)
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.data.get(self.position) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return None,
+        };
+        self.position += 1;
+        Some(flag)
+    }
+
+    fn numbers(&mut self, count: usize) -> Option<Vec<f64>> {
+        (0..count).map(|_| self.number()).collect()
+    }
+}
+
+/// Parses a list of numbers, separated by whitespace and / or commas
+fn parse_numbers(data: &str)
+-> Vec<f64>
+{
+    let mut reader = NumberReader { data: data.as_bytes(), position: 0 };
+    let mut numbers = Vec::new();
+    while let Some(number) = reader.number() {
+        numbers.push(number);
+    }
+    numbers
+}
+
+/// Parses a length (`12`, `12px`, `3mm`, ...) and returns it in px (user units).
+/// Percentages and font-relative units can't be resolved and return `None`.
+fn parse_length(value: &str)
+-> Option<f64>
+{
+    let value = value.trim();
+    let unit_start = value.find(|c: char| c.is_ascii_alphabetic() || c == '%').unwrap_or(value.len());
+    let number: f64 = value[..unit_start].trim().parse().ok()?;
+    let px_per_unit = match &value[unit_start..] {
+        "" | "px" => 1.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "mm" => 96.0 / 25.4,
+        "cm" => 96.0 / 2.54,
+        "in" => 96.0,
+        _ => return None,
+    };
+    Some(number * px_per_unit)
+}
+
+/// Parses a paint (`none`, `#rgb`, `#rrggbb`, `rgb(r, g, b)` or a basic color keyword).
+/// Returns `Some(None)` for `none` and `None` for unsupported paints (which are ignored).
+fn parse_paint(value: &str)
+-> Option<Option<(f64, f64, f64)>>
+{
+    let value = value.trim();
+    let hex = |s: &str| u8::from_str_radix(s, 16).ok().map(|v| v as f64 / 255.0);
+
+    let color = match value {
+        "none" => return Some(None),
+        "black" => (0.0, 0.0, 0.0),
+        "white" => (1.0, 1.0, 1.0),
+        "red" => (1.0, 0.0, 0.0),
+        "lime" => (0.0, 1.0, 0.0),
+        "green" => (0.0, 128.0 / 255.0, 0.0),
+        "blue" => (0.0, 0.0, 1.0),
+        "yellow" => (1.0, 1.0, 0.0),
+        "gray" | "grey" => (128.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0),
+        _ if value.starts_with('#') && value.len() == 7 => (hex(&value[1..3])?, hex(&value[3..5])?, hex(&value[5..7])?),
+        _ if value.starts_with('#') && value.len() == 4 => {
+            let digit = |i: usize| hex(&value[i..i + 1].repeat(2));
+            (digit(1)?, digit(2)?, digit(3)?)
+        },
+        _ if value.starts_with("rgb(") && value.ends_with(')') => {
+            let channels: Vec<f64> = value[4..value.len() - 1].split(',').filter_map(|c| {
+                let c = c.trim();
+                if let Some(percent) = c.strip_suffix('%') {
+                    percent.trim().parse::<f64>().ok().map(|p| p / 100.0)
+                } else {
+                    c.parse::<f64>().ok().map(|v| v / 255.0)
+                }
+            }).collect();
+            if channels.len() != 3 { return None; }
+            (channels[0], channels[1], channels[2])
+        },
+        _ => return None,
+    };
+
+    Some(Some(color))
+}
+
+/// Parses a `transform` attribute (`matrix`, `translate`, `scale`, `rotate`, `skewX`, `skewY`)
+fn parse_transform(value: &str)
+-> Matrix
+{
+    let mut matrix = IDENTITY;
+
+    for function in value.split(')') {
+        let mut parts = function.splitn(2, '(');
+        let (name, arguments) = match (parts.next(), parts.next()) {
+            (Some(name), Some(arguments)) => (name.trim().trim_start_matches(','), parse_numbers(arguments)),
+            _ => continue,
+        };
+        let argument = |i: usize, default: f64| arguments.get(i).cloned().unwrap_or(default);
+
+        let transform = match name.trim() {
+            "matrix" if arguments.len() == 6 => [arguments[0], arguments[1], arguments[2], arguments[3], arguments[4], arguments[5]],
+            "translate" => [1.0, 0.0, 0.0, 1.0, argument(0, 0.0), argument(1, 0.0)],
+            "scale" => [argument(0, 1.0), 0.0, 0.0, argument(1, argument(0, 1.0)), 0.0, 0.0],
+            "rotate" => {
+                let (sin, cos) = argument(0, 0.0).to_radians().sin_cos();
+                let (cx, cy) = (argument(1, 0.0), argument(2, 0.0));
+                // rotate around (cx, cy): translate(cx, cy) rotate(a) translate(-cx, -cy)
+                [cos, sin, -sin, cos, cx - cos * cx + sin * cy, cy - sin * cx - cos * cy]
+            },
+            "skewX" => [1.0, 0.0, argument(0, 0.0).to_radians().tan(), 1.0, 0.0, 0.0],
+            "skewY" => [1.0, argument(0, 0.0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => continue,
+        };
+
+        // the transformations are applied right to left
+        matrix = multiply(transform, matrix);
+    }
+
+    matrix
+}
+
+/// Multiplies two transformation matrices (`a` is applied first)
+fn multiply(a: Matrix, b: Matrix)
+-> Matrix
+{
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+/// Parses path data into absolute segments. Quadratic curves and arcs are converted into
+/// cubic curves. Parsing stops at the first error, as required by the SVG specification.
+fn parse_path(data: &str)
+-> Vec<Segment>
+{
+    use self::Segment::*;
+
+    let mut reader = NumberReader { data: data.as_bytes(), position: 0 };
+    let mut path = Vec::new();
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut start_x, mut start_y) = (0.0, 0.0);
+    // last control point of the previous curve, for the smooth curve commands
+    let mut last_cubic_control: Option<(f64, f64)> = None;
+    let mut last_quad_control: Option<(f64, f64)> = None;
+    let mut command = None;
+
+    loop {
+        if let Some(c) = reader.peek_command() {
+            reader.position += 1;
+            command = Some(c);
+        } else if reader.position >= reader.data.len() {
+            break;
+        }
+
+        let c = match command {
+            Some(c) => c,
+            None => break,
+        };
+
+        let relative = c.is_ascii_lowercase();
+        let (ox, oy) = if relative { (x, y) } else { (0.0, 0.0) };
+        let mut cubic_control = None;
+        let mut quad_control = None;
+
+        match c.to_ascii_uppercase() {
+            b'M' => {
+                let p = match reader.numbers(2) { Some(p) => p, None => break };
+                x = ox + p[0]; y = oy + p[1];
+                start_x = x; start_y = y;
+                path.push(MoveTo(x, y));
+                // subsequent coordinate pairs are implicit line-to commands
+                command = Some(if relative { b'l' } else { b'L' });
+            },
+            b'L' => {
+                let p = match reader.numbers(2) { Some(p) => p, None => break };
+                x = ox + p[0]; y = oy + p[1];
+                path.push(LineTo(x, y));
+            },
+            b'H' => {
+                let p = match reader.number() { Some(p) => p, None => break };
+                x = ox + p;
+                path.push(LineTo(x, y));
+            },
+            b'V' => {
+                let p = match reader.number() { Some(p) => p, None => break };
+                y = oy + p;
+                path.push(LineTo(x, y));
+            },
+            b'C' | b'S' => {
+                let (c1, p) = if c.eq_ignore_ascii_case(&b'C') {
+                    let p = match reader.numbers(6) { Some(p) => p, None => break };
+                    ((ox + p[0], oy + p[1]), p[2..].to_vec())
+                } else {
+                    let p = match reader.numbers(4) { Some(p) => p, None => break };
+                    let c1 = last_cubic_control.map(|(cx, cy)| (2.0 * x - cx, 2.0 * y - cy)).unwrap_or((x, y));
+                    (c1, p)
+                };
+                let c2 = (ox + p[0], oy + p[1]);
+                x = ox + p[2]; y = oy + p[3];
+                path.push(CurveTo(c1.0, c1.1, c2.0, c2.1, x, y));
+                cubic_control = Some(c2);
+            },
+            b'Q' | b'T' => {
+                let (control, end) = if c.eq_ignore_ascii_case(&b'Q') {
+                    let p = match reader.numbers(4) { Some(p) => p, None => break };
+                    ((ox + p[0], oy + p[1]), (ox + p[2], oy + p[3]))
+                } else {
+                    let p = match reader.numbers(2) { Some(p) => p, None => break };
+                    let control = last_quad_control.map(|(cx, cy)| (2.0 * x - cx, 2.0 * y - cy)).unwrap_or((x, y));
+                    (control, (ox + p[0], oy + p[1]))
+                };
+                // degree elevation of the quadratic curve
+                path.push(CurveTo(
+                    x + 2.0 / 3.0 * (control.0 - x), y + 2.0 / 3.0 * (control.1 - y),
+                    end.0 + 2.0 / 3.0 * (control.0 - end.0), end.1 + 2.0 / 3.0 * (control.1 - end.1),
+                    end.0, end.1,
+                ));
+                x = end.0; y = end.1;
+                quad_control = Some(control);
+            },
+            b'A' => {
+                let radii = match reader.numbers(3) { Some(p) => p, None => break };
+                let (large_arc, sweep) = match (reader.flag(), reader.flag()) {
+                    (Some(large_arc), Some(sweep)) => (large_arc, sweep),
+                    _ => break,
+                };
+                let p = match reader.numbers(2) { Some(p) => p, None => break };
+                let (end_x, end_y) = (ox + p[0], oy + p[1]);
+                path.extend(arc_to_curves((x, y), radii[0], radii[1], radii[2], large_arc, sweep, (end_x, end_y)));
+                x = end_x; y = end_y;
+            },
+            b'Z' => {
+                path.push(Close);
+                x = start_x; y = start_y;
+                // a number after Z is an error
+                command = None;
+            },
+            _ => break,
+        }
+
+        last_cubic_control = cubic_control;
+        last_quad_control = quad_control;
+    }
+
+    path
+}
+
+/// Converts an elliptical arc (SVG endpoint parametrization) into cubic curves
+fn arc_to_curves(from: (f64, f64), rx: f64, ry: f64, rotation_deg: f64, large_arc: bool, sweep: bool, to: (f64, f64))
+-> Vec<Segment>
+{
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 || from == to {
+        return vec![Segment::LineTo(to.0, to.1)];
+    }
+
+    // see the SVG specification, appendix F.6.5 (conversion to center parametrization)
+    let (sin_phi, cos_phi) = rotation_deg.to_radians().sin_cos();
+    let dx = (from.0 - to.0) / 2.0;
+    let dy = (from.1 - to.1) / 2.0;
+    let x1 = cos_phi * dx + sin_phi * dy;
+    let y1 = -sin_phi * dx + cos_phi * dy;
+
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = -coefficient * ry * x1 / rx;
+    let cx = cos_phi * cx1 - sin_phi * cy1 + (from.0 + to.0) / 2.0;
+    let cy = sin_phi * cx1 + cos_phi * cy1 + (from.1 + to.1) / 2.0;
+
+    let angle = |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start_angle = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut sweep_angle = angle((x1 - cx1) / rx, (y1 - cy1) / ry, (-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    if !sweep && sweep_angle > 0.0 { sweep_angle -= 2.0 * PI; }
+    if sweep && sweep_angle < 0.0 { sweep_angle += 2.0 * PI; }
+
+    // at most a quarter circle per curve
+    let count = (sweep_angle.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+    let delta = sweep_angle / count as f64;
+    let t = 4.0 / 3.0 * (delta / 4.0).tan();
+    let map = |ux: f64, uy: f64| (
+        cx + rx * cos_phi * ux - ry * sin_phi * uy,
+        cy + rx * sin_phi * ux + ry * cos_phi * uy,
+    );
+
+    (0..count).map(|i| {
+        let a0 = start_angle + delta * i as f64;
+        let a1 = a0 + delta;
+        let (sin0, cos0) = a0.sin_cos();
+        let (sin1, cos1) = a1.sin_cos();
+        let c1 = map(cos0 - t * sin0, sin0 + t * cos0);
+        let c2 = map(cos1 + t * sin1, sin1 - t * cos1);
+        // use the exact end point for the last curve, to avoid gaps from rounding
+        let end = if i == count - 1 { to } else { map(cos1, sin1) };
+        Segment::CurveTo(c1.0, c1.1, c2.0, c2.1, end.0, end.1)
+    }).collect()
+}
+
+/// XML tag
+#[derive(Debug, Clone, PartialEq)]
+enum Tag {
+    Open { name: String, attributes: Vec<(String, String)>, self_closing: bool },
+    Close,
+}
+
+/// Splits the XML into tags. Comments, processing instructions, CDATA and text are skipped.
+fn tags(xml: &str)
+-> Result<Vec<Tag>, PdfError>
+{
+    let mut tags = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+
+        let skip_to = |rest: &str, end: &str| rest.find(end).map(|i| i + end.len()).ok_or(PdfError::InvalidSvg);
+
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->")?..];
+        } else if rest.starts_with("<![CDATA[") {
+            rest = &rest[skip_to(rest, "]]>")?..];
+        } else if rest.starts_with("<?") {
+            rest = &rest[skip_to(rest, "?>")?..];
+        } else if rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">")?..];
+        } else if rest.starts_with("</") {
+            rest = &rest[skip_to(rest, ">")?..];
+            tags.push(Tag::Close);
+        } else {
+            let (tag, length) = parse_open_tag(rest).ok_or(PdfError::InvalidSvg)?;
+            tags.push(tag);
+            rest = &rest[length..];
+        }
+    }
+
+    Ok(tags)
+}
+
+/// Parses an opening tag (starting with `<`), returns the tag and its length
+fn parse_open_tag(tag: &str)
+-> Option<(Tag, usize)>
+{
+    let is_name_end = |c: char| c.is_whitespace() || c == '/' || c == '>';
+    let name_end = tag[1..].find(is_name_end)? + 1;
+    let name = &tag[1..name_end];
+    // strip namespace prefixes (svg:path)
+    let name = name.rsplit(':').next().unwrap_or(name).to_string();
+
+    let mut attributes = Vec::new();
+    let mut position = name_end;
+
+    loop {
+        let rest = &tag[position..];
+        let trimmed = rest.trim_start();
+        position += rest.len() - trimmed.len();
+
+        if trimmed.starts_with("/>") {
+            return Some((Tag::Open { name, attributes, self_closing: true }, position + 2));
+        } else if trimmed.starts_with('>') {
+            return Some((Tag::Open { name, attributes, self_closing: false }, position + 1));
+        }
+
+        let equals = trimmed.find('=')?;
+        let key = trimmed[..equals].trim().to_string();
+        let value_part = trimmed[equals + 1..].trim_start();
+        let quote = value_part.chars().next().filter(|q| *q == '"' || *q == '\'')?;
+        let value_end = value_part[1..].find(quote)? + 1;
+        attributes.push((key, value_part[1..value_end].to_string()));
+
+        position += trimmed.len() - value_part.len() + value_end + 1;
+    }
+}
+
+#[test]
+fn test_parse_path() {
+    use self::Segment::*;
+    assert_eq!(parse_path("M10-20l5.5.5h-1V0zm1 1 2 2"), vec![
+        MoveTo(10.0, -20.0), LineTo(15.5, -19.5), LineTo(14.5, -19.5), LineTo(14.5, 0.0), Close,
+        MoveTo(11.0, -19.0), LineTo(13.0, -17.0),
+    ]);
+    assert_eq!(parse_path("M0 0Q 3 3 6 0"), vec![MoveTo(0.0, 0.0), CurveTo(2.0, 2.0, 4.0, 2.0, 6.0, 0.0)]);
+}
+
+#[test]
+fn test_import_svg_size() {
+    let svg = r##"<?xml version="1.0"?><!-- comment --><svg xmlns="http://www.w3.org/2000/svg" width="100mm" height="50mm" viewBox="0 0 200 100">
+        <rect x="0" y="0" width="200" height="100" fill="none" stroke="#f00"/></svg>"##;
+    let image = import_svg(svg).unwrap();
+    assert!((image.width.0 - 283.46).abs() < 0.01);
+    assert!((image.height.0 - 141.73).abs() < 0.01);
+    assert_eq!(image.operations.len(), 10);
+    assert!(import_svg("<html></html>").is_err());
+}
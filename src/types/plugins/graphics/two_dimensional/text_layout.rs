@@ -0,0 +1,81 @@
+//! Line breaking for flowing text into a box of a fixed width
+
+use Pt;
+
+/// One line of wrapped text
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WrappedLine {
+    /// Byte offset of the first word of the line in the original text
+    pub(crate) start: usize,
+    /// Text of the line (the words, separated by a single space)
+    pub(crate) text: String,
+}
+
+/// Breaks the text into lines that are at most `max_width` wide, breaking at whitespace.
+/// Line breaks (`\n`) in the text start a new line, empty lines are kept. Words that
+/// are wider than `max_width` are put on a line of their own.
+///
+/// `measure` returns the width of a piece of text in the font / size the text is written with.
+pub(crate) fn wrap_text<F>(text: &str, max_width: Pt, measure: F)
+-> Vec<WrappedLine> where F: Fn(&str) -> Pt
+{
+    let mut lines = Vec::new();
+    let mut paragraph_start = 0;
+
+    for paragraph in text.split('\n') {
+        let mut current: Option<WrappedLine> = None;
+
+        for (word_offset, word) in words(paragraph) {
+            let start = paragraph_start + word_offset;
+            current = Some(match current.take() {
+                None => WrappedLine { start, text: word.to_string() },
+                Some(mut line) => {
+                    let candidate = format!("{} {}", line.text, word);
+                    if measure(&candidate).0 <= max_width.0 {
+                        line.text = candidate;
+                        line
+                    } else {
+                        lines.push(line);
+                        WrappedLine { start, text: word.to_string() }
+                    }
+                },
+            });
+        }
+
+        lines.push(current.unwrap_or(WrappedLine { start: paragraph_start, text: String::new() }));
+        paragraph_start += paragraph.len() + 1;
+    }
+
+    lines
+}
+
+/// Splits the text at whitespace, returns the byte offset of each word with the word
+fn words(text: &str)
+-> Vec<(usize, &str)>
+{
+    let mut words = Vec::new();
+    let mut word_start = None;
+
+    for (offset, ch) in text.char_indices() {
+        match (ch.is_whitespace(), word_start) {
+            (true, Some(start)) => { words.push((start, &text[start..offset])); word_start = None; },
+            (false, None) => word_start = Some(offset),
+            _ => { },
+        }
+    }
+
+    if let Some(start) = word_start {
+        words.push((start, &text[start..]));
+    }
+
+    words
+}
+
+#[test]
+fn test_wrap_text() {
+    // every character is 1pt wide
+    let measure = |s: &str| Pt(s.chars().count() as f64);
+    let lines = wrap_text("aaa bb  cc\n\ndddddd e", Pt(6.0), measure);
+    let texts: Vec<_> = lines.iter().map(|l| (l.start, l.text.as_str())).collect();
+    assert_eq!(texts, vec![(0, "aaa bb"), (8, "cc"), (11, ""), (12, "dddddd"), (19, "e")]);
+}
@@ -0,0 +1,158 @@
+//! Type3 fonts, where each glyph is drawn by a small content stream. Useful for icon fonts
+//! and custom symbols, without needing a font file.
+
+use lopdf;
+use lopdf::content::{Content, Operation};
+use lopdf::Object::{Array, Dictionary, Integer, Name, Real, Reference};
+use std::collections::{BTreeMap, HashMap};
+use std::iter::FromIterator;
+use {Error, PdfError, Pt};
+use super::font::simple_to_unicode_stream;
+
+/// Drawing instructions for a single glyph of a Type3 font.
+///
+/// The glyph is drawn in a coordinate system where 1000 units are the font size,
+/// with the origin on the baseline. The glyph is painted in the current fill / outline color,
+/// so the operations must not set any colors themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphProc {
+    /// Horizontal advance of the glyph, in 1/1000 of the font size
+    pub width: f64,
+    /// Bounding box of the glyph (lower left x, lower left y, upper right x, upper right y),
+    /// in 1/1000 of the font size
+    pub bbox: (f64, f64, f64, f64),
+    /// Encoded content stream of the glyph
+    content: Vec<u8>,
+}
+
+impl GlyphProc {
+
+    /// Creates a new glyph from the operations that draw it
+    pub fn new(width: f64, bbox: (f64, f64, f64, f64), operations: Vec<Operation>)
+    -> Self
+    {
+        // lopdf writes reals with two decimals, so the usual font matrix of 0.001 can't be
+        // written. The glyph space is 1/100 of the font size instead and the glyph is scaled down
+        let (llx, lly, urx, ury) = bbox;
+        let mut ops = vec![
+            Operation::new("d1", vec![
+                Real(width / 10.0), Integer(0), Real(llx / 10.0), Real(lly / 10.0), Real(urx / 10.0), Real(ury / 10.0),
+            ]),
+            Operation::new("cm", vec![Real(0.1), Integer(0), Integer(0), Real(0.1), Integer(0), Integer(0)]),
+        ];
+        ops.extend(operations);
+
+        Self {
+            width,
+            bbox,
+            content: Content { operations: ops }.encode().unwrap_or_default(),
+        }
+    }
+}
+
+/// Font made of custom glyphs. The glyphs are encoded with single-byte codes,
+/// so a Type3 font can have at most 256 glyphs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type3Font {
+    /// Name of the font in the resource dictionary (`F0`, `F1`, ...)
+    pub(crate) face_name: String,
+    /// Glyphs, sorted by character. The position in the map is the character code.
+    pub(crate) glyphs: BTreeMap<char, GlyphProc>,
+}
+
+impl Type3Font {
+
+    /// Creates a new Type3 font. The `index` is used for naming / identifying the font
+    pub(crate) fn new(glyphs: HashMap<char, GlyphProc>, font_index: usize)
+    -> Result<Self, Error>
+    {
+        if glyphs.len() > 256 {
+            return Err(PdfError::TooManyGlyphs.into());
+        }
+
+        Ok(Self {
+            face_name: format!("F{}", font_index),
+            glyphs: glyphs.into_iter().collect(),
+        })
+    }
+
+    /// Encodes the text as character codes of this font. Characters without a glyph are ignored.
+    pub(crate) fn encode_text(&self, text: &str)
+    -> Vec<u8>
+    {
+        text.chars()
+            .filter_map(|ch| self.glyphs.keys().position(|c| *c == ch))
+            .map(|code| code as u8)
+            .collect()
+    }
+
+    /// Returns the width of the text in points, if it was written in the given size
+    pub(crate) fn text_width(&self, text: &str, font_size: f64)
+    -> Pt
+    {
+        let width = text.chars()
+            .filter_map(|ch| self.glyphs.get(&ch))
+            .map(|glyph| glyph.width)
+            .sum::<f64>();
+
+        Pt(width * font_size / 1000.0)
+    }
+
+    /// Returns the union of the bounding boxes of all glyphs
+    pub(crate) fn bbox(&self)
+    -> (f64, f64, f64, f64)
+    {
+        self.glyphs.values().map(|g| g.bbox).fold((0.0, 0.0, 0.0, 0.0), |a, b| {
+            (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+        })
+    }
+
+    /// Adds the glyph streams to the document and returns the font dictionary
+    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document)
+    -> lopdf::Dictionary
+    {
+        let (llx, lly, urx, ury) = self.bbox();
+        let codes = self.glyphs.keys().enumerate().map(|(code, ch)| (code as u8, *ch)).collect();
+        let to_unicode = doc.add_object(simple_to_unicode_stream(&self.face_name, codes));
+        let mut char_procs = lopdf::Dictionary::new();
+        let mut differences = vec![Integer(0)];
+        let mut widths = Vec::new();
+
+        for (code, glyph) in self.glyphs.into_values().enumerate() {
+            let glyph_name = format!("g{}", code);
+            let stream = lopdf::Stream::new(lopdf::Dictionary::new(), glyph.content);
+            char_procs.set(glyph_name.clone(), Reference(doc.add_object(stream)));
+            differences.push(Name(glyph_name.into_bytes()));
+            widths.push(Real(glyph.width / 10.0));
+        }
+
+        let last_char = widths.len().max(1) as i64 - 1;
+
+        lopdf::Dictionary::from_iter(vec![
+            ("Type", Name("Font".into())),
+            ("Subtype", Name("Type3".into())),
+            ("FontBBox", Array(vec![Real(llx / 10.0), Real(lly / 10.0), Real(urx / 10.0), Real(ury / 10.0)])),
+            // glyph space is 1/100 of the font size, see `GlyphProc::new`
+            ("FontMatrix", Array(vec![Real(0.01), Integer(0), Integer(0), Real(0.01), Integer(0), Integer(0)])),
+            ("CharProcs", Dictionary(char_procs)),
+            ("Encoding", Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Name("Encoding".into())),
+                ("Differences", Array(differences)),
+            ]))),
+            ("FirstChar", Integer(0)),
+            ("LastChar", Integer(last_char)),
+            ("Widths", Array(widths)),
+            ("Resources", Dictionary(lopdf::Dictionary::new())),
+            ("ToUnicode", Reference(to_unicode)),
+        ])
+    }
+}
+
+#[test]
+fn test_type3_font_encoding() {
+    let glyph = |width| GlyphProc::new(width, (0.0, 0.0, width, 700.0), vec![]);
+    let glyphs = vec![('b', glyph(600.0)), ('a', glyph(500.0))].into_iter().collect();
+    let font = Type3Font::new(glyphs, 0).unwrap();
+    assert_eq!(font.encode_text("abxa"), vec![0, 1, 0]);
+    assert_eq!(font.text_width("ab", 10.0), Pt(11.0));
+}
@@ -4,9 +4,12 @@
 use crate::OffsetDateTime;
 #[cfg(feature = "embedded_images")]
 use image::{DynamicImage, GenericImageView, ImageDecoder, ImageError};
+#[cfg(feature = "embedded_images")]
+use png;
 use lopdf;
-use std::collections::HashMap;
-use {ColorBits, ColorSpace, CurTransMat, Px};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use {ColorBits, ColorSpace, CurTransMat, Px, Error, PdfError};
 
 /* Parent: Resources dictionary of the page */
 /// External object that gets reference outside the PDF content stream
@@ -18,6 +21,8 @@ pub enum XObject {
     /* /Subtype /Image */
     /// Image XObject, for images
     Image(ImageXObject),
+    /// 1-bit stencil mask, painted in the current fill color
+    ImageMask(ImageMaskXObject),
     /* /Subtype /Form */
     /// Form XObject, for PDF forms
     Form(Box<FormXObject>),
@@ -53,6 +58,7 @@ impl Into<lopdf::Object> for XObject {
     {
         match self {
             XObject::Image(image) => { lopdf::Object::Stream(Self::compress_stream(image.into())) }
+            XObject::ImageMask(mask) => { lopdf::Object::Stream(Self::compress_stream(mask.into())) }
             XObject::Form(form) => { let cur_form: FormXObject = *form; lopdf::Object::Stream(Self::compress_stream(cur_form.into())) }
             XObject::PostScript(ps) => { lopdf::Object::Stream(Self::compress_stream(ps.into())) }
         }
@@ -62,7 +68,7 @@ impl Into<lopdf::Object> for XObject {
 /// List of `XObjects`
 #[derive(Debug, Default, Clone)]
 pub struct XObjectList {
-    objects: HashMap<String, XObject>,
+    pub(crate) objects: HashMap<String, XObject>,
 }
 
 impl XObjectList {
@@ -78,12 +84,28 @@ impl XObjectList {
     pub fn add_xobject(&mut self, xobj: XObject)
     -> XObjectRef
     {
-        let len = self.objects.len();
-        let xobj_ref = XObjectRef::new(len);
+        // names of removed XObjects leave gaps, so the length may already be taken
+        let mut index = self.objects.len();
+        while self.objects.contains_key(&format!("X{}", index)) { index += 1; }
+        self.add_xobject_with_index(index, xobj)
+    }
+
+    /// Adds a new XObject under the name for the given index (see `XObjectRef::new`).
+    /// The caller has to make sure that the name is not taken yet.
+    pub(crate) fn add_xobject_with_index(&mut self, index: usize, xobj: XObject)
+    -> XObjectRef
+    {
+        let xobj_ref = XObjectRef::new(index);
         self.objects.insert(xobj_ref.name.clone(), xobj);
         xobj_ref
     }
 
+    /// Removes all XObjects whose names are not in `used_names`
+    pub(crate) fn retain_used(&mut self, used_names: &HashSet<String>)
+    {
+        self.objects.retain(|name, _| used_names.contains(name));
+    }
+
     /// Same as `Into<lopdf::Dictionary>`, but since the dictionary
     /// items in an XObject dictionary are streams and must be added to
     /// the document as __references__, this function needs an additional
@@ -94,7 +116,18 @@ impl XObjectList {
     -> lopdf::Dictionary
     {
         self.objects.into_iter().map(|(name, object)| {
-            let obj: lopdf::Object = object.into();
+            let (object, smask_ref) = match object {
+                // the soft mask is a separate image, referenced from the image dictionary
+                XObject::Image(mut image) => {
+                    let smask_ref = image.smask.take().map(|smask| doc.add_object(XObject::Image(*smask)));
+                    (XObject::Image(image), smask_ref)
+                },
+                other => (other, None),
+            };
+            let mut obj: lopdf::Object = object.into();
+            if let (lopdf::Object::Stream(ref mut stream), Some(smask_ref)) = (&mut obj, smask_ref) {
+                stream.dict.set("SMask", lopdf::Object::Reference(smask_ref));
+            }
             let obj_ref =  doc.add_object(obj);
             (name.to_string(), lopdf::Object::Reference(obj_ref))
         }).collect()
@@ -144,6 +177,12 @@ pub struct ImageXObject {
     /// Required bounds to clip the image, in unit space
     /// Default value: Identity matrix (`[1 0 0 1 0 0]`) - used when value is `None`
     pub clipping_bbox: Option<CurTransMat>,
+    /// Soft mask (alpha channel) of the image: a greyscale image of the same size,
+    /// written as a separate XObject and referenced with `/SMask` (see `with_smask`)
+    pub(crate) smask: Option<Box<ImageXObject>>,
+    /// Color lookup table (RGB triples) for `ColorSpace::Palette` images,
+    /// the image data then contains one index into the table per pixel (see `with_palette`)
+    pub(crate) palette: Option<Vec<u8>>,
 }
 
 impl<'a> ImageXObject {
@@ -165,9 +204,231 @@ impl<'a> ImageXObject {
             image_data: data,
             image_filter,
             clipping_bbox: bbox,
+            smask: None,
+            palette: None,
         }
     }
 
+    /// Sets the soft mask (alpha channel) of the image: a greyscale image with
+    /// the same width and height as this image
+    #[inline]
+    pub fn with_smask(mut self, smask: ImageXObject)
+    -> Self
+    {
+        self.smask = Some(Box::new(smask));
+        self
+    }
+
+    /// Returns the soft mask (alpha channel) of the image, if any
+    #[inline]
+    pub fn smask(&self)
+    -> Option<&ImageXObject>
+    {
+        self.smask.as_deref()
+    }
+
+    /// Sets the color lookup table (RGB triples) of a `ColorSpace::Palette` image,
+    /// the image data then contains one index into the table per pixel
+    #[inline]
+    pub fn with_palette(mut self, palette: Vec<u8>)
+    -> Self
+    {
+        self.palette = Some(palette);
+        self
+    }
+
+    /// Returns the color lookup table (RGB triples) of the image, if any
+    #[inline]
+    pub fn palette(&self)
+    -> Option<&[u8]>
+    {
+        self.palette.as_deref()
+    }
+
+    /// Downsamples the image (nearest neighbor), so that neither the width nor the height
+    /// is larger than `max_dimension` pixels. The aspect ratio is preserved.
+    ///
+    /// Returns `None` if the image data is compressed (has an `image_filter`) or uses less than
+    /// 8 bits per component, since these images can't be resampled without decoding them first.
+    pub fn downsample(&self, max_dimension: usize)
+    -> Option<Self>
+    {
+        if self.image_filter.is_some() || max_dimension == 0 {
+            return None;
+        }
+
+        let bytes_per_component = match self.bits_per_component {
+            ColorBits::Bit1 => return None,
+            ColorBits::Bit8 => 1,
+            ColorBits::Bit16 => 2,
+        };
+
+        let components = match self.color_space {
+            ColorSpace::Greyscale | ColorSpace::Palette => 1,
+            ColorSpace::GreyscaleAlpha => 2,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Rgba | ColorSpace::Cmyk => 4,
+        };
+
+        let bytes_per_pixel = bytes_per_component * components;
+        let (width, height) = (self.width.0, self.height.0);
+
+        if self.image_data.len() < width * height * bytes_per_pixel {
+            return None;
+        }
+
+        let scale = max_dimension as f64 / width.max(height) as f64;
+        if scale >= 1.0 {
+            return Some(self.clone());
+        }
+
+        // the soft mask has the same size as the image, so it ends up with the same size too
+        let smask = match self.smask {
+            Some(ref smask) => Some(Box::new(smask.downsample(max_dimension)?)),
+            None => None,
+        };
+
+        let new_width = ((width as f64 * scale) as usize).max(1);
+        let new_height = ((height as f64 * scale) as usize).max(1);
+        let mut image_data = Vec::with_capacity(new_width * new_height * bytes_per_pixel);
+
+        for y in 0..new_height {
+            let src_y = y * height / new_height;
+            for x in 0..new_width {
+                let src_x = x * width / new_width;
+                let start = (src_y * width + src_x) * bytes_per_pixel;
+                image_data.extend_from_slice(&self.image_data[start..start + bytes_per_pixel]);
+            }
+        }
+
+        Some(Self {
+            width: Px(new_width),
+            height: Px(new_height),
+            color_space: self.color_space,
+            bits_per_component: self.bits_per_component,
+            interpolate: self.interpolate,
+            image_data,
+            image_filter: None,
+            clipping_bbox: self.clipping_bbox,
+            smask,
+            palette: self.palette.clone(),
+        })
+    }
+
+    /// Creates an image from a baseline JPEG file. The JPEG data is not decoded, it is embedded
+    /// as it is (`/DCTDecode` filter), only the headers are read for the size and the color space.
+    ///
+    /// Supports greyscale (1 component) and RGB (3 components) JPEGs. Progressive JPEGs return
+    /// `PdfError::ProgressiveJpeg`, other JPEG variants or invalid data `PdfError::InvalidJpeg`.
+    pub fn try_from_jpeg<R: Read>(mut jpeg: R)
+    -> Result<Self, Error>
+    {
+        let mut data = Vec::new();
+        jpeg.read_to_end(&mut data)?;
+
+        let (width, height, components) = read_jpeg_header(&data)?;
+        let color_space = match components {
+            1 => ColorSpace::Greyscale,
+            3 => ColorSpace::Rgb,
+            _ => return Err(Error::Pdf(PdfError::InvalidJpeg)),
+        };
+
+        Ok(Self {
+            width: Px(width),
+            height: Px(height),
+            color_space,
+            bits_per_component: ColorBits::Bit8,
+            image_data: data,
+            interpolate: true,
+            image_filter: Some(ImageFilter::DCT),
+            clipping_bbox: None,
+            smask: None,
+            palette: None,
+        })
+    }
+
+    /// Creates an image from a PNG file. Greyscale and RGB images keep their bit depth
+    /// (8 or 16 bits per component), indexed images keep their palette. Images with 1, 2 or 4
+    /// bits per component (greyscale and indexed images) are expanded to 8 bits per component.
+    /// The alpha channel (or the transparency of the palette entries) is written as a soft
+    /// mask (`/SMask`). The color key transparency of greyscale / RGB images is ignored.
+    ///
+    /// Returns `PdfError::InvalidPng` if the data can't be decoded.
+    #[cfg(feature = "embedded_images")]
+    pub fn try_from_png<R: Read>(png: R)
+    -> Result<Self, Error>
+    {
+        let invalid = |_| Error::Pdf(PdfError::InvalidPng);
+
+        let mut decoder = png::Decoder::new(png);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let (info, mut reader) = decoder.read_info().map_err(invalid)?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).map_err(invalid)?;
+
+        let (bits, bytes_per_component) = match info.bit_depth {
+            png::BitDepth::Sixteen => (ColorBits::Bit16, 2),
+            _ => (ColorBits::Bit8, 1),
+        };
+
+        // only greyscale and indexed images can have less than 8 bits per sample
+        let data = match info.bit_depth {
+            png::BitDepth::One | png::BitDepth::Two | png::BitDepth::Four => {
+                let scale = info.color_type != png::ColorType::Indexed;
+                unpack_samples(&data, info.width as usize, info.bit_depth as u8, scale)
+            },
+            _ => data,
+        };
+
+        let (color_space, image_data, alpha) = match info.color_type {
+            png::ColorType::Grayscale => (ColorSpace::Greyscale, data, None),
+            png::ColorType::RGB => (ColorSpace::Rgb, data, None),
+            png::ColorType::GrayscaleAlpha => {
+                let (color, alpha) = split_alpha(&data, 1, bytes_per_component);
+                (ColorSpace::Greyscale, color, Some((alpha, bits)))
+            },
+            png::ColorType::RGBA => {
+                let (color, alpha) = split_alpha(&data, 3, bytes_per_component);
+                (ColorSpace::Rgb, color, Some((alpha, bits)))
+            },
+            png::ColorType::Indexed => {
+                // palette entries without an alpha value are opaque
+                let alpha = reader.info().trns.as_ref().map(|trns| {
+                    let alpha = data.iter().map(|index| trns.get(*index as usize).cloned().unwrap_or(255)).collect();
+                    (alpha, ColorBits::Bit8)
+                });
+                (ColorSpace::Palette, data, alpha)
+            },
+        };
+
+        let (width, height) = (Px(info.width as usize), Px(info.height as usize));
+        let smask = alpha.map(|(alpha, alpha_bits)| Box::new(Self {
+            width,
+            height,
+            color_space: ColorSpace::Greyscale,
+            bits_per_component: alpha_bits,
+            interpolate: true,
+            image_data: alpha,
+            image_filter: None,
+            clipping_bbox: None,
+            smask: None,
+            palette: None,
+        }));
+
+        Ok(Self {
+            width,
+            height,
+            color_space,
+            bits_per_component: bits,
+            interpolate: true,
+            image_data,
+            image_filter: None,
+            clipping_bbox: None,
+            smask,
+            palette: reader.info().palette.clone().filter(|_| color_space == ColorSpace::Palette),
+        })
+    }
+
     #[cfg(feature = "embedded_images")]
     pub fn try_from<T: ImageDecoder<'a>>(image: T)
     -> Result<Self, ImageError>
@@ -198,6 +459,8 @@ impl<'a> ImageXObject {
             interpolate: true,
             image_filter: None,
             clipping_bbox: None,
+            smask: None,
+            palette: None,
         })
     }
 
@@ -220,6 +483,8 @@ impl<'a> ImageXObject {
             interpolate: true,
             image_filter: None,
             clipping_bbox: None,
+            smask: None,
+            palette: None,
         }
     }
 }
@@ -247,6 +512,16 @@ impl Into<lopdf::Stream> for ImageXObject {
             ("BBox", bbox),
         ]);
 
+        if let (ColorSpace::Palette, Some(palette)) = (self.color_space, self.palette) {
+            let max_index = (palette.len() / 3).max(1) - 1;
+            dict.set("ColorSpace", Array(vec![
+                Name("Indexed".as_bytes().to_vec()),
+                Name("DeviceRGB".as_bytes().to_vec()),
+                Integer(max_index as i64),
+                String(palette, lopdf::StringFormat::Hexadecimal),
+            ]));
+        }
+
         if let Some(filter) = self.image_filter {
             let params = match filter {
                 // TODO technically we could use multiple filters,
@@ -268,6 +543,172 @@ impl Into<lopdf::Stream> for ImageXObject {
     }
 }
 
+/// Splits interleaved pixels with an alpha channel (after the `components` color components)
+/// into the color data and the alpha data
+#[cfg(feature = "embedded_images")]
+fn split_alpha(data: &[u8], components: usize, bytes_per_component: usize)
+-> (Vec<u8>, Vec<u8>)
+{
+    let color_bytes = components * bytes_per_component;
+    let mut color = Vec::with_capacity(data.len() / (components + 1) * components);
+    let mut alpha = Vec::with_capacity(data.len() / (components + 1));
+
+    for pixel in data.chunks(color_bytes + bytes_per_component) {
+        if pixel.len() == color_bytes + bytes_per_component {
+            color.extend_from_slice(&pixel[..color_bytes]);
+            alpha.extend_from_slice(&pixel[color_bytes..]);
+        }
+    }
+
+    (color, alpha)
+}
+
+/// Expands the rows of 1, 2 or 4 bit samples (each row starts at a byte boundary) to one byte
+/// per sample. If `scale` is set, the values are scaled to 0 - 255 (greyscale values),
+/// otherwise they are kept (palette indices).
+#[cfg(feature = "embedded_images")]
+fn unpack_samples(data: &[u8], width: usize, bits: u8, scale: bool)
+-> Vec<u8>
+{
+    let bits = bits as usize;
+    let row_bytes = ((width * bits + 7) / 8).max(1);
+    let max_value = (1 << bits) - 1;
+    let mut samples = Vec::with_capacity(data.len() / row_bytes * width);
+
+    for row in data.chunks(row_bytes) {
+        for x in 0..width {
+            let bit = x * bits;
+            let byte = row.get(bit / 8).cloned().unwrap_or(0) as usize;
+            let value = (byte >> (8 - bits - bit % 8)) & max_value;
+            samples.push(if scale { (value * 255 / max_value) as u8 } else { value as u8 });
+        }
+    }
+
+    samples
+}
+
+/// Reads the width, height and number of color components from the frame header of a JPEG.
+/// Only 8-bit sequential (baseline / extended) JPEGs are accepted.
+fn read_jpeg_header(data: &[u8])
+-> Result<(usize, usize, u8), Error>
+{
+    let invalid = Error::Pdf(PdfError::InvalidJpeg);
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(invalid);
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return Err(invalid);
+        }
+        let marker = data[pos + 1];
+        pos += 2;
+
+        match marker {
+            // fill bytes
+            0xFF => { pos -= 1; continue; },
+            // markers without a segment
+            0x01 | 0xD0..=0xD8 => continue,
+            _ => { },
+        }
+
+        let length = (usize::from(data[pos]) << 8) | usize::from(data[pos + 1]);
+        let segment = match data.get(pos + 2..pos + length) {
+            Some(segment) if length >= 2 => segment,
+            _ => return Err(invalid),
+        };
+
+        match marker {
+            // baseline / extended sequential, Huffman coded
+            0xC0 | 0xC1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err(invalid);
+                }
+                let height = (usize::from(segment[1]) << 8) | usize::from(segment[2]);
+                let width = (usize::from(segment[3]) << 8) | usize::from(segment[4]);
+                return Ok((width, height, segment[5]));
+            },
+            0xC2 | 0xC6 | 0xCA | 0xCE => return Err(Error::Pdf(PdfError::ProgressiveJpeg)),
+            // lossless, hierarchical or arithmetic coded frames
+            0xC3 | 0xC5 | 0xC7 | 0xC9 | 0xCB | 0xCD | 0xCF => return Err(invalid),
+            // the frame header has to come before the scan
+            0xDA => return Err(invalid),
+            _ => pos += length,
+        }
+    }
+
+    Err(invalid)
+}
+
+/// Stencil mask: a 1-bit image that is not painted itself, but paints the current fill color
+/// through its pixels (`/ImageMask true`). Useful for colored icons from monochrome sources.
+#[derive(Debug, Clone)]
+pub struct ImageMaskXObject {
+    /// Width of the mask in pixels
+    pub width: Px,
+    /// Height of the mask in pixels
+    pub height: Px,
+    /// Should the mask be interpolated when scaled?
+    pub interpolate: bool,
+    /// One bit per pixel, each row starts at a new byte. By default, pixels with
+    /// a 0 bit are painted and pixels with a 1 bit are left unchanged.
+    pub mask_data: Vec<u8>,
+    /// Paints the pixels with a 1 bit instead (writes the `/Decode [1 0]` array)
+    pub invert: bool,
+}
+
+impl ImageMaskXObject {
+
+    /// Creates a new stencil mask, the pixels with a 0 bit are painted
+    pub fn new(width: Px, height: Px, mask_data: Vec<u8>)
+    -> Self
+    {
+        Self { width, height, interpolate: false, mask_data, invert: false }
+    }
+
+    /// Sets whether the pixels with a 1 bit are painted instead of the pixels with a 0 bit
+    #[inline]
+    pub fn with_invert(self, invert: bool)
+    -> Self
+    {
+        Self { invert, .. self }
+    }
+
+    /// Returns true if the mask data contains all pixels (rows are padded to full bytes)
+    pub fn is_valid(&self)
+    -> bool
+    {
+        self.width.0 > 0 && self.height.0 > 0 &&
+        self.mask_data.len() >= (self.width.0 + 7) / 8 * self.height.0
+    }
+}
+
+impl From<ImageMaskXObject> for lopdf::Stream {
+    fn from(mask: ImageMaskXObject)
+    -> Self
+    {
+        use lopdf::Object::*;
+        use std::iter::FromIterator;
+
+        let mut dict = lopdf::Dictionary::from_iter(vec![
+            ("Type", Name("XObject".as_bytes().to_vec())),
+            ("Subtype", Name("Image".as_bytes().to_vec())),
+            ("Width", Integer(mask.width.0 as i64)),
+            ("Height", Integer(mask.height.0 as i64)),
+            ("ImageMask", Boolean(true)),
+            ("BitsPerComponent", Integer(1)),
+            ("Interpolate", mask.interpolate.into()),
+        ]);
+
+        if mask.invert {
+            dict.set("Decode", Array(vec![Integer(1), Integer(0)]));
+        }
+
+        lopdf::Stream::new(dict, mask.mask_data)
+    }
+}
+
 /// Named reference to an image
 #[derive(Debug)]
 pub struct ImageXObjectRef {
@@ -281,7 +722,7 @@ pub enum ImageFilter {
     Ascii85,
     /// Lempel Ziv Welch compression, i.e. zip
     Lzw,
-    /// Discrete Cosinus Transform, JPEG Baseline.
+    /// Discrete Cosine Transform, JPEG Baseline.
     DCT,
     /// JPEG2000 aka JPX wavelet based compression.
     JPX,
@@ -290,7 +731,7 @@ pub enum ImageFilter {
 /// __THIS IS NOT A PDF FORM!__ A form `XObject` can be nearly everything.
 /// PDF allows you to reuse content for the graphics stream in a `FormXObject`.
 /// A `FormXObject` is basically a layer-like content stream and can contain anything
-/// as long as it's a valid strem. A `FormXObject` is intended to be used for reapeated
+/// as long as it's a valid stream. A `FormXObject` is intended to be used for repeated
 /// content on one page.
 #[derive(Debug, Clone)]
 pub struct FormXObject {
@@ -300,6 +741,10 @@ pub struct FormXObject {
     /* /FormType Integer */
     /// Form type (currently only Type1)
     pub form_type: FormType,
+    /* /BBox [Real, 4] */
+    /// Bounding box of the form in form space (lower left x, lower left y, upper right x,
+    /// upper right y, in points). Content outside of the box is clipped.
+    pub(crate) bbox: (f64, f64, f64, f64),
     /// The actual content of this FormXObject
     pub bytes: Vec<u8>,
     /* /Matrix [Integer , 6] */
@@ -385,6 +830,42 @@ pub struct FormXObject {
     pub name: Option<String>,
 }
 
+impl FormXObject {
+
+    /// Creates a form from the operations that draw its content, with the given bounding box.
+    /// The form uses no resources (fonts, images, graphics states).
+    pub fn from_operations(bbox: (f64, f64, f64, f64), operations: Vec<lopdf::content::Operation>)
+    -> Self
+    {
+        Self {
+            form_type: FormType::Type1,
+            bbox,
+            bytes: lopdf::content::Content { operations }.encode().unwrap_or_default(),
+            matrix: None,
+            resources: None,
+            group: None,
+            ref_dict: None,
+            metadata: None,
+            piece_info: None,
+            last_modified: None,
+            struct_parent: None,
+            struct_parents: None,
+            opi: None,
+            oc: None,
+            name: None,
+        }
+    }
+
+    /// Returns the bounding box of the form in form space (lower left x, lower left y,
+    /// upper right x, upper right y, in points)
+    #[inline]
+    pub fn bbox(&self)
+    -> (f64, f64, f64, f64)
+    {
+        self.bbox
+    }
+}
+
 impl Into<lopdf::Stream> for FormXObject {
     fn into(self)
     -> lopdf::Stream
@@ -392,12 +873,21 @@ impl Into<lopdf::Stream> for FormXObject {
         use std::iter::FromIterator;
         use lopdf::Object::*;
 
-        let dict = lopdf::Dictionary::from_iter(vec![
+        let (llx, lly, urx, ury) = self.bbox;
+        let mut dict = lopdf::Dictionary::from_iter(vec![
             ("Type", Name("XObject".as_bytes().to_vec())),
             ("Subtype", Name("Form".as_bytes().to_vec())),
             ("FormType", Integer(self.form_type.into())),
+            ("BBox", Array(vec![Real(llx), Real(lly), Real(urx), Real(ury)])),
         ]);
 
+        if let Some(matrix) = self.matrix {
+            dict.set("Matrix", matrix);
+        }
+
+        // the resource dictionary is required since PDF 1.2, even if it is empty
+        dict.set("Resources", Dictionary(self.resources.unwrap_or_default()));
+
         lopdf::Stream::new(dict, self.bytes)
     }
 }
@@ -590,3 +1080,84 @@ impl Into<lopdf::Stream> for PostScriptXObject {
         lopdf::Stream::new(lopdf::Dictionary::new(), Vec::new())
     }
 }
+
+#[test]
+fn test_read_jpeg_header() {
+    let jpeg = |sof: u8| vec![
+        0xFF, 0xD8,
+        0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46,
+        0xFF, sof, 0x00, 0x0B, 0x08, 0x00, 0x20, 0x00, 0x40, 0x03, 0x01, 0x22, 0x00,
+        0xFF, 0xDA, 0x00, 0x02,
+    ];
+    assert_eq!(read_jpeg_header(&jpeg(0xC0)).ok(), Some((64, 32, 3)));
+    match read_jpeg_header(&jpeg(0xC2)) {
+        Err(Error::Pdf(PdfError::ProgressiveJpeg)) => { },
+        other => panic!("expected ProgressiveJpeg, got {:?}", other),
+    }
+    assert!(read_jpeg_header(&[0x89, 0x50, 0x4E, 0x47]).is_err());
+}
+
+#[cfg(feature = "embedded_images")]
+#[test]
+fn test_split_alpha() {
+    let (color, alpha) = split_alpha(&[1, 2, 3, 4, 5, 6, 7, 8], 3, 1);
+    assert_eq!(color, vec![1, 2, 3, 5, 6, 7]);
+    assert_eq!(alpha, vec![4, 8]);
+    let (color, alpha) = split_alpha(&[1, 2, 3, 4], 1, 2);
+    assert_eq!(color, vec![1, 2]);
+    assert_eq!(alpha, vec![3, 4]);
+}
+
+#[cfg(feature = "embedded_images")]
+#[cfg(test)]
+fn encode_png(color_type: png::ColorType, bit_depth: png::BitDepth, width: u32, height: u32,
+              data: &[u8], palette: Option<(Vec<u8>, Vec<u8>)>)
+-> Vec<u8>
+{
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        if let Some((palette, trns)) = palette {
+            encoder.set_palette(palette);
+            encoder.set_trns(trns);
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+    }
+    bytes
+}
+
+#[cfg(feature = "embedded_images")]
+#[test]
+fn test_png_indexed_low_bit_depth() {
+    // 3x2 pixels with 2 bits per index, the rows are padded to full bytes
+    let palette = vec![255, 0, 0, 0, 255, 0, 0, 0, 255];
+    let data = [0b0001_1000, 0b1001_0000];
+    let png = encode_png(png::ColorType::Indexed, png::BitDepth::Two, 3, 2, &data, Some((palette.clone(), vec![255, 128])));
+
+    let image = ImageXObject::try_from_png(&png[..]).unwrap();
+    assert_eq!(image.color_space, ColorSpace::Palette);
+    assert!(matches!(image.bits_per_component, ColorBits::Bit8));
+    assert_eq!(image.image_data, vec![0, 1, 2, 2, 1, 0]);
+    assert_eq!(image.palette(), Some(&palette[..]));
+    // entries without a transparency value are opaque
+    assert_eq!(image.smask().map(|smask| smask.image_data.clone()), Some(vec![255, 128, 255, 255, 128, 255]));
+}
+
+#[cfg(feature = "embedded_images")]
+#[test]
+fn test_png_greyscale() {
+    let png = encode_png(png::ColorType::GrayscaleAlpha, png::BitDepth::Eight, 2, 1, &[10, 20, 30, 40], None);
+    let image = ImageXObject::try_from_png(&png[..]).unwrap();
+    assert_eq!(image.color_space, ColorSpace::Greyscale);
+    assert_eq!(image.image_data, vec![10, 30]);
+    assert_eq!(image.smask().map(|smask| smask.image_data.clone()), Some(vec![20, 40]));
+
+    // 1 bit greyscale values are scaled to 0 - 255
+    let png = encode_png(png::ColorType::Grayscale, png::BitDepth::One, 3, 1, &[0b1010_0000], None);
+    let image = ImageXObject::try_from_png(&png[..]).unwrap();
+    assert_eq!(image.image_data, vec![255, 0, 255]);
+    assert!(image.smask().is_none());
+}
@@ -0,0 +1,61 @@
+//! Link annotations (clickable areas on a page)
+
+use lopdf;
+use {Pt, Rect};
+
+/// Link annotation that opens a URI when its area is clicked,
+/// see `PdfLayerReference::add_link`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LinkAnnotation {
+    /// Clickable area of the link
+    pub(crate) rect: Rect,
+    /// URI that is opened when the link is clicked
+    pub(crate) uri: String,
+}
+
+impl LinkAnnotation {
+
+    /// Creates the annotation dictionary (`/Annot` with the subtype `/Link`)
+    pub(crate) fn into_dictionary(self)
+    -> lopdf::Dictionary
+    {
+        use lopdf::Object::*;
+        use lopdf::StringFormat::Literal;
+
+        let (x1, y1): (Pt, Pt) = (self.rect.x.into(), self.rect.y.into());
+        let (x2, y2): (Pt, Pt) = ((self.rect.x + self.rect.width).into(), (self.rect.y + self.rect.height).into());
+
+        let mut action = lopdf::Dictionary::new();
+        action.set("Type", Name(b"Action".to_vec()));
+        action.set("S", Name(b"URI".to_vec()));
+        action.set("URI", String(self.uri.into_bytes(), Literal));
+
+        let mut annotation = lopdf::Dictionary::new();
+        annotation.set("Type", Name(b"Annot".to_vec()));
+        annotation.set("Subtype", Name(b"Link".to_vec()));
+        annotation.set("Rect", Array(vec![x1.into(), y1.into(), x2.into(), y2.into()]));
+        // no visible border, the link is usually drawn as text
+        annotation.set("Border", Array(vec![Integer(0), Integer(0), Integer(0)]));
+        // printable (required by PDF/A and PDF/X)
+        annotation.set("F", Integer(4));
+        annotation.set("A", Dictionary(action));
+        annotation
+    }
+}
+
+#[test]
+fn test_link_annotation() {
+    use Mm;
+    let link = LinkAnnotation {
+        rect: Rect::new(Mm(0.0), Mm(0.0), Mm(25.4), Mm(10.0)),
+        uri: "https://example.com".into(),
+    };
+    let dict = link.into_dictionary();
+    let rect = dict.get(b"Rect").and_then(|r| r.as_array()).unwrap();
+    match rect[2] {
+        lopdf::Object::Real(x) => assert!((x - 72.0).abs() < 1e-3),
+        _ => panic!("rectangle coordinates should be reals"),
+    }
+    let action = dict.get(b"A").and_then(|a| a.as_dict()).unwrap();
+    assert_eq!(action.get(b"URI").and_then(|u| u.as_str()).ok(), Some(&b"https://example.com"[..]));
+}
@@ -1 +1,3 @@
 //! Interactive elements such a comment / annotation, etc.
+
+pub(crate) mod link;
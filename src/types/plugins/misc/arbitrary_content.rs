@@ -0,0 +1,125 @@
+//! Extension point for adding custom content (that printpdf doesn't know about) to the document
+
+use lopdf;
+
+/// How the data of a stream added with `PdfDocumentReference::add_stream` is encoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamFilter {
+    /// The data is stored as it is and never compressed
+    None,
+    /// The data is compressed with `/FlateDecode` when it is added
+    Flate,
+    /// The data is already encoded with the given filters (e.g. `FlateDecode`, `DCTDecode`),
+    /// in the order they have to be applied for decoding. The data is stored as it is.
+    PreEncoded(Vec<String>),
+}
+
+/// Objects generated from custom content, see `IntoPdfObjects`
+#[derive(Debug, Clone)]
+pub struct PdfObjects {
+    /// ID of the top-level object, which is returned to the caller of `add_arbitrary_content`
+    pub root: lopdf::ObjectId,
+    /// All objects of the content (including the top-level object)
+    pub objects: Vec<(lopdf::ObjectId, lopdf::Object)>,
+}
+
+/// Custom content that consists of one or more PDF objects, which can reference each other,
+/// for example a font (font dictionary, descriptor, font file stream and widths array).
+///
+/// The content is added to the document with `PdfDocumentReference::add_arbitrary_content`.
+pub trait IntoPdfObjects {
+    /// Converts the content into PDF objects. `new_id` reserves a new object ID in the
+    /// document, which can be used to reference the objects from each other.
+    /// All returned objects should use IDs reserved with `new_id`.
+    fn into_pdf_objects(self, new_id: &mut dyn FnMut() -> lopdf::ObjectId)
+    -> PdfObjects;
+}
+
+impl IntoPdfObjects for lopdf::Object {
+    fn into_pdf_objects(self, new_id: &mut dyn FnMut() -> lopdf::ObjectId)
+    -> PdfObjects
+    {
+        let root = new_id();
+        PdfObjects { root, objects: vec![(root, self)] }
+    }
+}
+
+impl IntoPdfObjects for lopdf::Stream {
+    fn into_pdf_objects(self, new_id: &mut dyn FnMut() -> lopdf::ObjectId)
+    -> PdfObjects
+    {
+        lopdf::Object::Stream(self).into_pdf_objects(new_id)
+    }
+}
+
+/// Creates the stream for `PdfDocumentReference::add_stream`, encoding the data with the filter
+pub(crate) fn encode_stream(data: Vec<u8>, filter: StreamFilter, dict: lopdf::Dictionary)
+-> lopdf::Stream
+{
+    let mut stream = lopdf::Stream::new(dict, data);
+
+    match filter {
+        StreamFilter::None => { },
+        StreamFilter::Flate => {
+            // compression only fails on I/O errors, which can't happen when writing into a Vec.
+            // If the data doesn't get smaller, lopdf keeps it uncompressed.
+            let _ = stream.compress();
+        },
+        StreamFilter::PreEncoded(ref filters) if filters.is_empty() => { },
+        StreamFilter::PreEncoded(filters) => {
+            let filter = match filters.len() {
+                1 => lopdf::Object::Name(filters[0].clone().into_bytes()),
+                _ => lopdf::Object::Array(filters.into_iter().map(|f| lopdf::Object::Name(f.into_bytes())).collect()),
+            };
+            stream.dict.set("Filter", filter);
+        },
+    }
+
+    // don't let the compression pass on save touch the stream
+    stream.with_compression(false)
+}
+
+#[test]
+fn test_encode_stream() {
+    let stream = encode_stream(vec![b'a'; 1000], StreamFilter::Flate, lopdf::Dictionary::new());
+    assert!(stream.content.len() < 1000);
+    assert_eq!(stream.dict.get(b"Filter").and_then(|f| f.as_name_str()).ok(), Some("FlateDecode"));
+
+    let stream = encode_stream(vec![1, 2, 3], StreamFilter::PreEncoded(vec!["FlateDecode".into()]), lopdf::Dictionary::new());
+    assert_eq!(stream.content, vec![1, 2, 3]);
+    assert!(!stream.allows_compression);
+}
+
+#[test]
+fn test_add_arbitrary_content() {
+    use lopdf::Object::{Dictionary, Integer, Name, Reference};
+    use std::iter::FromIterator;
+    use {PdfDocument, Mm};
+
+    /// A dictionary that references a second object
+    struct Linked;
+
+    impl IntoPdfObjects for Linked {
+        fn into_pdf_objects(self, new_id: &mut dyn FnMut() -> lopdf::ObjectId)
+        -> PdfObjects
+        {
+            let (root, child) = (new_id(), new_id());
+            let dict = lopdf::Dictionary::from_iter(vec![("Type", Name(b"Custom".to_vec())), ("Child", Reference(child))]);
+            PdfObjects { root, objects: vec![(root, Dictionary(dict)), (child, Integer(42))] }
+        }
+    }
+
+    let (doc, _, _) = PdfDocument::new("custom", Mm(210.0), Mm(297.0), "Layer 1");
+    let root = doc.add_arbitrary_content(Linked);
+    let single = doc.add_arbitrary_content(Integer(7));
+
+    let document = doc.document.borrow();
+    let inner_doc = &document.inner_doc;
+    let child = inner_doc.get_dictionary(root).and_then(|dict| dict.get(b"Child")).and_then(|child| child.as_reference()).unwrap();
+    assert_ne!(child, root);
+    assert_eq!(inner_doc.get_object(child).and_then(|child| child.as_i64()).ok(), Some(42));
+
+    // the IDs of the next content don't clash with the reserved IDs
+    assert!(single != root && single != child);
+    assert_eq!(inner_doc.get_object(single).and_then(|single| single.as_i64()).ok(), Some(7));
+}
@@ -1,9 +1,11 @@
 //! Info dictionary of a PDF document
 
 use crate::OffsetDateTime;
+use date::utc_offset_minutes;
 use lopdf;
 
 use PdfConformance;
+use utils::text_string;
 
 /// "Info" dictionary of a PDF document.
 /// Actual data is contained in `DocumentMetadata`, to keep it in sync with the `XmpMetadata`
@@ -65,12 +67,18 @@ impl DocumentInfo {
 
     /// This functions is similar to the IntoPdfObject trait method,
     /// but takes additional arguments in order to delay the setting
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     pub(in types) fn into_obj<S>(self,
                                  document_title: S,
                                  trapping: bool,
                                  conformance: PdfConformance,
                                  creation_date: OffsetDateTime,
-                                 modification_date: OffsetDateTime)
+                                 modification_date: OffsetDateTime,
+                                 keywords: &[String],
+                                 subject: &str,
+                                 author: &str,
+                                 creator: &str,
+                                 producer: &str)
     -> lopdf::Object where S: Into<String>
     {
         use lopdf::Dictionary as LoDictionary;
@@ -79,18 +87,42 @@ impl DocumentInfo {
         use std::iter::FromIterator;
 
         let trapping = if trapping { "True" } else { "False" };
-        let gts_pdfx_version = conformance.get_identifier_string();
 
         let info_mod_date = to_pdf_time_stamp_metadata(modification_date);
         let info_create_date = to_pdf_time_stamp_metadata(creation_date);
 
-        Dictionary(LoDictionary::from_iter(vec![
+        let mut info = LoDictionary::from_iter(vec![
             ("Trapped", trapping.into()),
             ("CreationDate", String(info_create_date.into_bytes(), Literal)),
             ("ModDate", String(info_mod_date.into_bytes(), Literal)),
-            ("GTS_PDFXVersion", String(gts_pdfx_version.into(), Literal)),
-            ("Title", String(document_title.into().as_bytes().to_vec(), Literal))
-        ]))
+            ("Title", text_string(&document_title.into()))
+        ]);
+
+        if conformance.is_pdfx() {
+            info.set("GTS_PDFXVersion", String(conformance.get_identifier_string().into(), Literal));
+        }
+
+        if !keywords.is_empty() {
+            info.set("Keywords", text_string(&keywords.join(", ")));
+        }
+
+        if !subject.is_empty() {
+            info.set("Subject", text_string(subject));
+        }
+
+        if !author.is_empty() {
+            info.set("Author", text_string(author));
+        }
+
+        if !creator.is_empty() {
+            info.set("Creator", text_string(creator));
+        }
+
+        if !producer.is_empty() {
+            info.set("Producer", text_string(producer));
+        }
+
+        Dictionary(info)
     }
 }
 
@@ -98,14 +130,51 @@ impl DocumentInfo {
 fn to_pdf_time_stamp_metadata(date: OffsetDateTime)
 -> String
 {
-    // Since the time is in UTC, we know that the time zone
-    // difference to UTC is 0 min, 0 sec, hence the 00'00
-    format!("D:{:04}{:02}{:02}{:02}{:02}{:02}+00'00'",
+    // the time is local to the offset of the date, so the offset has to be written as well
+    let offset = utc_offset_minutes(&date);
+    format!("D:{:04}{:02}{:02}{:02}{:02}{:02}{}{:02}'{:02}'",
         date.year(),
         date.month(),
         date.day(),
         date.hour(),
         date.minute(),
         date.second(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60,
     )
 }
+
+#[test]
+fn test_pdf_time_stamp() {
+    use time::{Date, UtcOffset};
+    use types::plugins::xmp::xmp_metadata::to_pdf_xmp_date;
+
+    let date = Date::try_from_ymd(2020, 1, 2).unwrap().try_with_hms(3, 4, 5).unwrap();
+    assert_eq!(to_pdf_time_stamp_metadata(date.assume_utc()), "D:20200102030405+00'00'");
+    assert_eq!(to_pdf_time_stamp_metadata(date.assume_offset(UtcOffset::minutes(-330))), "D:20200102030405-05'30'");
+
+    // XMP dates are ISO 8601
+    assert_eq!(to_pdf_xmp_date(date.assume_utc()), "2020-01-02T03:04:05+00:00");
+    assert_eq!(to_pdf_xmp_date(date.assume_offset(UtcOffset::minutes(-330))), "2020-01-02T03:04:05-05:30");
+}
+
+#[test]
+fn test_info_text_strings() {
+    use time::Date;
+
+    let date = Date::try_from_ymd(2020, 1, 2).unwrap().try_with_hms(3, 4, 5).unwrap().assume_utc();
+    let keywords = vec!["Kosmologie".to_string(), "Frühzeit".to_string()];
+    let info = DocumentInfo::new().into_obj("Title", false, PdfConformance::X3_2002_PDF_1_3, date, date,
+                                            &keywords, "Über das Licht", "Jürgen Müller", "Berichtsgenerator", "printpdf – Export");
+    let info = info.as_dict().unwrap();
+    let text = |key: &[u8]| info.get(key).and_then(|value| value.as_str()).unwrap().to_vec();
+
+    // ASCII text is written as it is, everything else as UTF-16BE
+    assert_eq!(text(b"Title"), b"Title".to_vec());
+    assert_eq!(&text(b"Subject")[..4], &[0xFE, 0xFF, 0x00, 0xDC][..]);
+    assert_eq!(text(b"Keywords").len(), 2 + 2 * "Kosmologie, Frühzeit".chars().count());
+    assert_eq!(&text(b"Author")[..4], &[0xFE, 0xFF, 0x00, b'J'][..]);
+    assert_eq!(text(b"Creator"), b"Berichtsgenerator".to_vec());
+    assert_eq!(text(b"Producer").len(), 2 + 2 * "printpdf – Export".chars().count());
+}
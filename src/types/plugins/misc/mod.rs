@@ -1,2 +1,11 @@
 //! Other PDF objects that should have their own module, but don't belong into any other category
-pub mod document_info;
\ No newline at end of file
+pub mod arbitrary_content;
+pub mod document_info;
+pub mod optimize;
+pub mod outline;
+pub mod output_intent;
+pub mod page_label;
+pub(crate) mod pdf_import;
+pub(crate) mod resource_names;
+pub mod structure;
+pub mod viewer_preferences;
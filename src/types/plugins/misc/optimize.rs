@@ -0,0 +1,115 @@
+//! Options for `PdfDocumentReference::optimize`, which combines the size reductions of printpdf,
+//! and for `PdfDocumentReference::save_with_options`
+
+use lopdf;
+use {Encryption, PdfPage, XObject};
+
+/// Selects the size reductions that `PdfDocumentReference::optimize` applies.
+/// The default enables all lossless optimizations, images are not downsampled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OptimizeOptions {
+    /// Pages with identical content share one content stream (see `PdfDocumentReference::with_deduplicate_content`)
+    pub deduplicate_content: bool,
+    /// Downsamples images that are larger than the given width / height in pixels
+    /// (see `ImageXObject::downsample`). Compressed images (e.g. JPEGs) are left as they are.
+    pub max_image_dimension: Option<usize>,
+    /// Removes unreferenced objects and empty streams when saving
+    pub prune_objects: bool,
+    /// Compresses all streams with `/FlateDecode` when saving
+    pub compress_streams: bool,
+    /// Embeds only the used glyphs of external fonts (see `SaveOptions::subset_fonts`)
+    pub subset_fonts: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            deduplicate_content: true,
+            max_image_dimension: None,
+            prune_objects: true,
+            compress_streams: true,
+            subset_fonts: true,
+        }
+    }
+}
+
+/// Options for `PdfDocumentReference::save_with_options`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Compresses the streams with `/FlateDecode` (default: true). Turn it off to read
+    /// the streams of the document in a text editor, e.g. for debugging.
+    pub compress: bool,
+    /// Instance ID of the document (second entry of the trailer `/ID`, `xmpMM:InstanceID`).
+    /// If `None` (default), a random ID is generated on every save. Set it together with the
+    /// document ID and the dates to get byte-for-byte identical files, e.g. for golden-file tests.
+    /// The IVs of encrypted documents are derived from the instance ID, so a fixed instance ID
+    /// reuses them across saves, see `Encryption`.
+    pub instance_id: Option<String>,
+    /// Embeds only the glyphs of external fonts that are used in the document (default: false),
+    /// which makes the file much smaller if only a few characters of a large font are used.
+    /// Only TrueType fonts can be subset, other fonts are embedded completely.
+    pub subset_fonts: bool,
+    /// Encrypts the document with the passwords and permissions (default: `None`).
+    /// PDF/A and PDF/X don't allow encryption, saving returns `PdfError::EncryptionNotAllowed`.
+    pub encryption: Option<Encryption>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            compress: true,
+            instance_id: None,
+            subset_fonts: false,
+            encryption: None,
+        }
+    }
+}
+
+/// File size of the document before and after `PdfDocumentReference::optimize`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OptimizeReport {
+    /// Size of the saved document (in bytes) before the optimization
+    pub size_before: usize,
+    /// Size of the saved document (in bytes) with the optimizations applied
+    pub size_after: usize,
+}
+
+impl OptimizeReport {
+
+    /// Returns how many bytes the optimization saved
+    #[inline]
+    pub fn bytes_saved(&self)
+    -> usize
+    {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+/// Downsamples the images of the pages that are larger than `max_dimension` pixels,
+/// see `OptimizeOptions::max_image_dimension`
+pub(crate) fn downsample_images(pages: &mut [PdfPage], max_dimension: usize)
+{
+    for xobject in pages.iter_mut().flat_map(|page| page.resources.xobjects.objects.values_mut()) {
+        let downsampled = match *xobject {
+            XObject::Image(ref image) => image.downsample(max_dimension),
+            _ => None,
+        };
+        if let Some(image) = downsampled {
+            *xobject = XObject::Image(image);
+        }
+    }
+}
+
+/// Applies the optimizations that work on the finished document, right before it is written.
+/// Streams are only compressed if both the optimize and the save options allow it.
+pub(crate) fn optimize_document(doc: &mut lopdf::Document, options: &OptimizeOptions, save_options: &SaveOptions)
+{
+    if options.prune_objects {
+        doc.prune_objects();
+        doc.delete_zero_length_streams();
+    }
+
+    if options.compress_streams && save_options.compress {
+        doc.compress();
+    }
+}
@@ -0,0 +1,163 @@
+//! Document outline (bookmarks), see `PdfDocumentReference::add_bookmark`
+
+use lopdf::{self, ObjectId};
+use lopdf::Object::{Array, Dictionary, Integer, Name, Null, Reference};
+use std::iter::FromIterator;
+use utils::text_string;
+
+/// One entry of the document outline (see `PdfDocument::outline`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    /// Title shown in the outline
+    pub title: String,
+    /// Index of the page the bookmark jumps to
+    pub page: usize,
+    /// Index of the parent bookmark in the outline, `None` for top-level bookmarks
+    pub parent: Option<usize>,
+}
+
+/// Updates the pages of the bookmarks after pages were removed or moved. `new_page` maps
+/// the old page index to the new one, `None` if the page was removed. Bookmarks of removed
+/// pages are removed, their children are moved up to the parent of the removed bookmark.
+pub(crate) fn remap_bookmarks<F>(bookmarks: &mut Vec<Bookmark>, new_page: F)
+where F: Fn(usize) -> Option<usize>
+{
+    let old_bookmarks = ::std::mem::take(bookmarks);
+    let pages = old_bookmarks.iter().map(|bookmark| new_page(bookmark.page)).collect::<Vec<_>>();
+
+    // new index of each bookmark that is kept
+    let mut new_index = vec![None; old_bookmarks.len()];
+    let mut next_index = 0;
+    for (index, page) in pages.iter().enumerate() {
+        if page.is_some() {
+            new_index[index] = Some(next_index);
+            next_index += 1;
+        }
+    }
+
+    // nearest ancestor that is kept
+    let kept_parent = |mut parent: Option<usize>| {
+        while let Some(index) = parent.filter(|index| *index < old_bookmarks.len()) {
+            if let Some(new_parent) = new_index[index] {
+                return Some(new_parent);
+            }
+            parent = old_bookmarks[index].parent;
+        }
+        None
+    };
+
+    for (index, bookmark) in old_bookmarks.iter().enumerate() {
+        if let Some(page) = pages[index] {
+            bookmarks.push(Bookmark { title: bookmark.title.clone(), page, parent: kept_parent(bookmark.parent) });
+        }
+    }
+}
+
+/// Adds the outline items of the bookmarks to the document and returns the outline
+/// dictionary (`/Outlines`, with the ID `outlines_id`). Siblings are ordered by their page
+/// (bookmarks on the same page in the order they were added). `page_ids` are the page
+/// objects in page order, bookmarks of pages that don't exist get no destination.
+pub(crate) fn add_outline(doc: &mut lopdf::Document, outlines_id: ObjectId, bookmarks: &[Bookmark], page_ids: &[ObjectId])
+-> lopdf::Dictionary
+{
+    let item_ids = bookmarks.iter().map(|_| doc.new_object_id()).collect::<Vec<ObjectId>>();
+
+    // children of each bookmark, the last entry are the top-level bookmarks
+    let mut children = vec![Vec::<usize>::new(); bookmarks.len() + 1];
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        let parent = bookmark.parent.filter(|parent| *parent < bookmarks.len()).unwrap_or(bookmarks.len());
+        children[parent].push(index);
+    }
+    for siblings in &mut children {
+        siblings.sort_by_key(|index| bookmarks[*index].page);
+    }
+
+    // all items are open, so the count is the number of all descendants
+    fn descendants(children: &[Vec<usize>], node: usize) -> usize {
+        children[node].iter().map(|child| 1 + descendants(children, *child)).sum()
+    }
+
+    let link_children = |dict: &mut lopdf::Dictionary, node: usize| {
+        if let (Some(first), Some(last)) = (children[node].first(), children[node].last()) {
+            dict.set("First", Reference(item_ids[*first]));
+            dict.set("Last", Reference(item_ids[*last]));
+            dict.set("Count", Integer(descendants(&children, node) as i64));
+        }
+    };
+
+    for (parent, siblings) in children.iter().enumerate() {
+        let parent_id = if parent == bookmarks.len() { outlines_id } else { item_ids[parent] };
+
+        for (position, index) in siblings.iter().enumerate() {
+            let bookmark = &bookmarks[*index];
+            let mut item = lopdf::Dictionary::from_iter(vec![
+                ("Title", text_string(&bookmark.title)),
+                ("Parent", Reference(parent_id)),
+            ]);
+            if position > 0 {
+                item.set("Prev", Reference(item_ids[siblings[position - 1]]));
+            }
+            if let Some(next) = siblings.get(position + 1) {
+                item.set("Next", Reference(item_ids[*next]));
+            }
+            if let Some(page_id) = page_ids.get(bookmark.page) {
+                item.set("Dest", Array(vec![Reference(*page_id), "XYZ".into(), Null, Null, Null]));
+            }
+            link_children(&mut item, *index);
+            doc.objects.insert(item_ids[*index], Dictionary(item));
+        }
+    }
+
+    let mut outlines = lopdf::Dictionary::from_iter(vec![("Type", Name("Outlines".into()))]);
+    link_children(&mut outlines, bookmarks.len());
+    outlines.set("Count", Integer(bookmarks.len() as i64));
+    outlines
+}
+
+#[test]
+fn test_add_outline() {
+    let bookmark = |title: &str, page: usize, parent: Option<usize>| Bookmark { title: title.into(), page, parent };
+    let bookmarks = vec![
+        bookmark("Chapter 2", 1, None),
+        bookmark("Chapter 1", 0, None),
+        bookmark("Section 1.1", 0, Some(1)),
+    ];
+
+    let mut doc = lopdf::Document::new();
+    let outlines_id = doc.new_object_id();
+    let outlines = add_outline(&mut doc, outlines_id, &bookmarks, &[(100, 0), (101, 0)]);
+
+    let first = outlines.get(b"First").and_then(|first| first.as_reference()).unwrap();
+    let chapter_1 = doc.get_dictionary(first).unwrap();
+    assert_eq!(chapter_1.get(b"Title").and_then(|title| title.as_str()).ok(), Some(&b"Chapter 1"[..]));
+    assert!(chapter_1.get(b"Next").is_ok());
+    assert!(chapter_1.get(b"Prev").is_err());
+    assert_eq!(chapter_1.get(b"Count").and_then(|count| count.as_i64()).ok(), Some(1));
+
+    let section = chapter_1.get(b"First").and_then(|first| first.as_reference()).unwrap();
+    let section = doc.get_dictionary(section).unwrap();
+    assert_eq!(section.get(b"Parent").and_then(|parent| parent.as_reference()).ok(), Some(first));
+    assert_eq!(outlines.get(b"Count").and_then(|count| count.as_i64()).ok(), Some(3));
+}
+
+#[test]
+fn test_remap_bookmarks() {
+    let bookmark = |title: &str, page: usize, parent: Option<usize>| Bookmark { title: title.into(), page, parent };
+    let mut bookmarks = vec![
+        bookmark("Chapter 1", 0, None),
+        bookmark("Section 1.1", 1, Some(0)),
+        bookmark("Section 1.1.1", 2, Some(1)),
+    ];
+
+    // page 1 is removed, the pages after it move up
+    remap_bookmarks(&mut bookmarks, |page| match page {
+        1 => None,
+        0 => Some(0),
+        page => Some(page - 1),
+    });
+
+    assert_eq!(bookmarks, vec![
+        bookmark("Chapter 1", 0, None),
+        bookmark("Section 1.1.1", 1, Some(0)),
+    ]);
+}
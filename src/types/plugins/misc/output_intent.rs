@@ -0,0 +1,214 @@
+//! Output intents describe the color characteristics of the output device(s) the document
+//! is intended for (`/OutputIntents` array in the document catalog). PDF/X and PDF/A
+//! require one, PDF 2.0 allows several (e.g. one CMYK profile for print, one RGB profile for screen).
+
+use lopdf;
+use lopdf::Object::{Name, Reference, String as LoString};
+use lopdf::StringFormat::Literal;
+use std::collections::HashSet;
+use {IccProfile, PdfError};
+
+/// Standard the output intent is meant for (`/S` key of the output intent dictionary)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OutputIntentSubtype {
+    /// `GTS_PDFX`, for PDF/X documents
+    PdfX,
+    /// `GTS_PDFA1`, for PDF/A documents
+    PdfA,
+    /// `ISO_PDFE1`, for PDF/E documents
+    PdfE,
+    /// Any other subtype
+    Custom(String),
+}
+
+impl OutputIntentSubtype {
+
+    /// Returns the name of the subtype, as written into the PDF
+    pub fn as_str(&self)
+    -> &str
+    {
+        use self::OutputIntentSubtype::*;
+        match *self {
+            PdfX => "GTS_PDFX",
+            PdfA => "GTS_PDFA1",
+            PdfE => "ISO_PDFE1",
+            Custom(ref name) => name,
+        }
+    }
+}
+
+/// Output intent of the document, optionally with an embedded ICC profile of the output device
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputIntent {
+    /// Standard this output intent is meant for
+    pub subtype: OutputIntentSubtype,
+    /// Identifier of the output condition, e.g. `"FOGRA39"` or `"sRGB"`
+    pub output_condition_identifier: String,
+    /// Human-readable description of the output condition
+    pub output_condition: Option<String>,
+    /// Registry in which the output condition identifier is defined, e.g. `"http://www.color.org"`
+    pub registry_name: Option<String>,
+    /// Additional information about the output condition
+    pub info: Option<String>,
+    /// ICC profile of the output device (`/DestOutputProfile`)
+    pub icc_profile: Option<IccProfile>,
+}
+
+impl OutputIntent {
+
+    /// Creates a new output intent without an ICC profile
+    pub fn new<S>(subtype: OutputIntentSubtype, output_condition_identifier: S)
+    -> Self where S: Into<String>
+    {
+        Self {
+            subtype,
+            output_condition_identifier: output_condition_identifier.into(),
+            output_condition: None,
+            registry_name: None,
+            info: None,
+            icc_profile: None,
+        }
+    }
+
+    /// Sets the human-readable description of the output condition
+    #[inline]
+    pub fn with_output_condition<S>(mut self, output_condition: S)
+    -> Self where S: Into<String>
+    {
+        self.output_condition = Some(output_condition.into());
+        self
+    }
+
+    /// Sets the registry of the output condition identifier
+    #[inline]
+    pub fn with_registry_name<S>(mut self, registry_name: S)
+    -> Self where S: Into<String>
+    {
+        self.registry_name = Some(registry_name.into());
+        self
+    }
+
+    /// Sets additional information about the output condition
+    #[inline]
+    pub fn with_info<S>(mut self, info: S)
+    -> Self where S: Into<String>
+    {
+        self.info = Some(info.into());
+        self
+    }
+
+    /// Sets the ICC profile of the output device
+    #[inline]
+    pub fn with_icc_profile(mut self, icc_profile: IccProfile)
+    -> Self
+    {
+        self.icc_profile = Some(icc_profile);
+        self
+    }
+
+    /// Creates the output intent for the target ICC profile of a PDF/X or PDF/A document, using the
+    /// identifier, description and registry of the profile. The description is also written as
+    /// `/Info`, which PDF/X requires for output conditions that are not in a registry. Profiles
+    /// without an identifier are assumed to be the default profile of printpdf (Coated FOGRA39).
+    pub(crate) fn for_target_profile(profile: IccProfile, subtype: OutputIntentSubtype)
+    -> Self
+    {
+        let intent = match profile.identifier.clone() {
+            Some(identifier) => {
+                let mut intent = OutputIntent::new(subtype, identifier);
+                intent.output_condition = profile.description.clone();
+                intent.info = profile.description.clone();
+                intent.registry_name = profile.registry_name.clone();
+                intent
+            },
+            None => {
+                OutputIntent::new(subtype, "FOGRA39")
+                    .with_output_condition("Commercial and special offset print acccording to ISO \
+                                            12647-2:2004 / Amd 1, paper type 1 or 2 (matte or gloss-coated \
+                                            offset paper, 115 g/m2), screen ruling 60/cm")
+                    .with_registry_name("http://www.color.org")
+                    .with_info("Coated FOGRA39 (ISO 12647-2:2004)")
+            },
+        };
+        intent.with_icc_profile(profile)
+    }
+
+    /// Adds the ICC profile (if any) to the document and returns the output intent dictionary
+    pub(crate) fn into_with_document(self, doc: &mut lopdf::Document)
+    -> lopdf::Dictionary
+    {
+        let mut dict = lopdf::Dictionary::new();
+        dict.set("Type", Name("OutputIntent".into()));
+        dict.set("S", Name(self.subtype.as_str().into()));
+        dict.set("OutputConditionIdentifier", LoString(self.output_condition_identifier.into(), Literal));
+
+        if let Some(output_condition) = self.output_condition {
+            dict.set("OutputCondition", LoString(output_condition.into(), Literal));
+        }
+
+        if let Some(registry_name) = self.registry_name {
+            dict.set("RegistryName", LoString(registry_name.into(), Literal));
+        }
+
+        if let Some(info) = self.info {
+            dict.set("Info", LoString(info.into(), Literal));
+        }
+
+        if let Some(profile) = self.icc_profile {
+            let icc_profile: lopdf::Stream = profile.into();
+            let icc_profile_id = doc.add_object(icc_profile);
+            dict.set("DestOutputProfile", Reference(icc_profile_id));
+        }
+
+        dict
+    }
+}
+
+/// Checks that there is at most one output intent for each of the standard subtypes
+/// (PDF/X, PDF/A and PDF/E each allow only one output intent of their subtype)
+pub(crate) fn check_output_intents(output_intents: &[OutputIntent])
+-> Result<(), PdfError>
+{
+    let mut subtypes = HashSet::new();
+
+    for intent in output_intents {
+        let is_standard = !matches!(intent.subtype, OutputIntentSubtype::Custom(_));
+
+        if is_standard && !subtypes.insert(&intent.subtype) {
+            return Err(PdfError::DuplicateOutputIntent);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_check_output_intents() {
+    let print = OutputIntent::new(OutputIntentSubtype::PdfX, "FOGRA39");
+    let screen = OutputIntent::new(OutputIntentSubtype::Custom("sRGB".into()), "sRGB");
+    assert!(check_output_intents(&[print.clone(), screen.clone(), screen]).is_ok());
+    assert!(check_output_intents(&[print.clone(), print]).is_err());
+}
+
+#[test]
+fn test_output_intent_for_target_profile() {
+    use IccProfileType;
+
+    let gracol = IccProfile::new(vec![0; 16], IccProfileType::Cmyk)
+        .with_identifier("CGATS TR 006")
+        .with_description("GRACoL 2006 Coated #1")
+        .with_registry_name("http://www.color.org");
+    let intent = OutputIntent::for_target_profile(gracol, OutputIntentSubtype::PdfX);
+    assert_eq!(intent.output_condition_identifier, "CGATS TR 006");
+    assert_eq!(intent.output_condition.as_deref(), Some("GRACoL 2006 Coated #1"));
+    assert_eq!(intent.info.as_deref(), Some("GRACoL 2006 Coated #1"));
+    assert!(intent.icc_profile.is_some());
+
+    let default = OutputIntent::for_target_profile(IccProfile::new(vec![0; 16], IccProfileType::Cmyk), OutputIntentSubtype::PdfA);
+    assert_eq!(default.output_condition_identifier, "FOGRA39");
+
+    let mut doc = lopdf::Document::with_version("1.3");
+    let dict = intent.into_with_document(&mut doc);
+    assert_eq!(dict.get(b"Info").and_then(|info| info.as_str()).ok(), Some(&b"GRACoL 2006 Coated #1"[..]));
+    assert!(dict.get(b"DestOutputProfile").and_then(|profile| profile.as_reference()).is_ok());
+}
@@ -0,0 +1,108 @@
+//! Page labels (`/PageLabels`): the page numbers viewers show instead of the page index,
+//! see `PdfDocumentReference::set_page_label`
+
+use lopdf;
+use lopdf::Object::{Array, Dictionary, Integer, Name};
+use std::iter::FromIterator;
+use utils::text_string;
+
+/// Numbering style of a page label range
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageLabelStyle {
+    /// 1, 2, 3, ...
+    Decimal,
+    /// I, II, III, ...
+    UppercaseRoman,
+    /// i, ii, iii, ...
+    LowercaseRoman,
+    /// A, B, ..., Z, AA, BB, ...
+    UppercaseLetters,
+    /// a, b, ..., z, aa, bb, ...
+    LowercaseLetters,
+}
+
+impl PageLabelStyle {
+
+    /// Returns the name of the style, as written into the PDF (`/S`)
+    pub fn as_str(&self)
+    -> &'static str
+    {
+        use self::PageLabelStyle::*;
+        match *self {
+            Decimal => "D",
+            UppercaseRoman => "R",
+            LowercaseRoman => "r",
+            UppercaseLetters => "A",
+            LowercaseLetters => "a",
+        }
+    }
+}
+
+/// Labels of the pages starting at a page, up to the next page that has a label
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLabel {
+    /// Numbering style
+    pub style: PageLabelStyle,
+    /// Text in front of the number, for example `A-` for "A-1, A-2, ..."
+    pub prefix: Option<String>,
+    /// Number of the first page of the range (at least 1, see `set_page_label`)
+    pub start: i64,
+}
+
+impl PageLabel {
+
+    /// Returns the page label dictionary
+    fn into_dictionary(self)
+    -> lopdf::Dictionary
+    {
+        let mut dict = lopdf::Dictionary::from_iter(vec![("S", Name(self.style.as_str().into()))]);
+        if let Some(prefix) = self.prefix {
+            dict.set("P", text_string(&prefix));
+        }
+        if self.start > 1 {
+            dict.set("St", Integer(self.start));
+        }
+        dict
+    }
+}
+
+/// Returns the `/PageLabels` number tree for the labels, given as (page index, label) in page
+/// order. The tree needs an entry for the first page, so if the first page has no label,
+/// it is numbered like in a document without labels (1, 2, 3, ...).
+pub(crate) fn page_labels_tree(labels: Vec<(usize, PageLabel)>)
+-> lopdf::Dictionary
+{
+    let mut nums = Vec::new();
+    if labels.first().map(|(page, _)| *page) != Some(0) {
+        let default = PageLabel { style: PageLabelStyle::Decimal, prefix: None, start: 1 };
+        nums.extend(vec![Integer(0), Dictionary(default.into_dictionary())]);
+    }
+    for (page, label) in labels {
+        nums.extend(vec![Integer(page as i64), Dictionary(label.into_dictionary())]);
+    }
+    lopdf::Dictionary::from_iter(vec![("Nums", Array(nums))])
+}
+
+#[test]
+fn test_page_labels_tree() {
+    let labels = vec![
+        (2, PageLabel { style: PageLabelStyle::LowercaseRoman, prefix: None, start: 1 }),
+        (6, PageLabel { style: PageLabelStyle::Decimal, prefix: Some("A-".into()), start: 3 }),
+    ];
+    let tree = page_labels_tree(labels);
+    let nums = tree.get(b"Nums").and_then(|nums| nums.as_array()).unwrap();
+    assert_eq!(nums.len(), 6);
+
+    // the first page gets the default numbering
+    assert_eq!(nums[0].as_i64().ok(), Some(0));
+    assert_eq!(nums[1].as_dict().and_then(|d| d.get(b"S")).and_then(|s| s.as_name_str()).ok(), Some("D"));
+
+    assert_eq!(nums[2].as_i64().ok(), Some(2));
+    let roman = nums[3].as_dict().unwrap();
+    assert_eq!(roman.get(b"S").and_then(|s| s.as_name_str()).ok(), Some("r"));
+    assert!(roman.get(b"St").is_err());
+
+    let appendix = nums[5].as_dict().unwrap();
+    assert_eq!(appendix.get(b"P").and_then(|p| p.as_str()).ok(), Some(&b"A-"[..]));
+    assert_eq!(appendix.get(b"St").and_then(|st| st.as_i64()).ok(), Some(3));
+}
@@ -0,0 +1,270 @@
+//! Reading the pages and the document information of an existing `lopdf::Document`,
+//! see `impl From<lopdf::Document> for PdfDocumentReference` and `PdfDocumentReference::merge`
+
+use crate::OffsetDateTime;
+use lopdf::{self, Object, ObjectId};
+use Pt;
+
+/// Maximum depth of the page tree, to stop at page trees with cycles
+const MAX_PAGE_TREE_DEPTH: usize = 64;
+
+/// Page of an existing document, with its content turned into a form XObject
+pub(crate) struct ImportedPage {
+    /// Width of the media box
+    pub(crate) width: Pt,
+    /// Height of the media box
+    pub(crate) height: Pt,
+    /// Form XObject with the original content streams and resources of the page
+    pub(crate) content: lopdf::Stream,
+    /// Rotation of the page (`/Rotate`), normalized to 0, 90, 180 or 270 degrees
+    pub(crate) rotation: i64,
+}
+
+/// Reads all pages of the document in page order. The content of each page is kept as it is:
+/// it is wrapped in a form XObject, which still references the original resources, so
+/// all objects of the document have to be kept (with their IDs) for the form to work.
+pub(crate) fn import_pages(doc: &lopdf::Document)
+-> Vec<ImportedPage>
+{
+    use lopdf::Object::*;
+
+    doc.page_iter().map(|page_id| {
+        // US Letter, if the (required) media box is missing
+        let (llx, lly, urx, ury) = inherited(doc, page_id, b"MediaBox")
+            .and_then(|media_box| read_rect(doc, media_box))
+            .unwrap_or((0.0, 0.0, 612.0, 792.0));
+
+        // a single stream is kept as it is (including its filters),
+        // several streams are decoded and joined into one
+        let contents = doc.get_page_contents(page_id);
+        let single_stream = match contents.len() {
+            1 => doc.get_object(contents[0]).and_then(Object::as_stream).ok().cloned(),
+            _ => None,
+        };
+        let mut content = single_stream.unwrap_or_else(|| {
+            lopdf::Stream::new(lopdf::Dictionary::new(), doc.get_page_content(page_id).unwrap_or_default())
+        });
+
+        content.dict.set("Type", Name(b"XObject".to_vec()));
+        content.dict.set("Subtype", Name(b"Form".to_vec()));
+        content.dict.set("FormType", Integer(1));
+        content.dict.set("BBox", Array(vec![Real(llx), Real(lly), Real(urx), Real(ury)]));
+        // the new page starts at (0, 0)
+        content.dict.set("Matrix", Array(vec![Real(1.0), Real(0.0), Real(0.0), Real(1.0), Real(-llx), Real(-lly)]));
+        if let Some(resources) = inherited(doc, page_id, b"Resources") {
+            content.dict.set("Resources", resources.clone());
+        }
+
+        // invalid rotations (not a multiple of 90) are ignored
+        let rotation = inherited(doc, page_id, b"Rotate")
+            .and_then(|rotate| rotate.as_i64().ok())
+            .filter(|rotate| rotate % 90 == 0)
+            .map_or(0, |rotate| rotate.rem_euclid(360));
+
+        ImportedPage {
+            width: Pt(urx - llx),
+            height: Pt(ury - lly),
+            content,
+            rotation,
+        }
+    }).collect()
+}
+
+/// Values of the document information dictionary (`/Info`) of an existing document
+#[derive(Debug, Default)]
+pub(crate) struct ImportedInfo {
+    pub(crate) title: Option<String>,
+    pub(crate) keywords: Vec<String>,
+    pub(crate) subject: Option<String>,
+    pub(crate) author: Option<String>,
+    pub(crate) creator: Option<String>,
+    pub(crate) creation_date: Option<OffsetDateTime>,
+    /// `None` if the document doesn't say whether it is trapped (`/Trapped /Unknown`)
+    pub(crate) trapping: Option<bool>,
+}
+
+/// Reads the document information dictionary. `/Producer` and `/ModDate` are not read,
+/// since they describe the application that writes the document and the time it is saved.
+pub(crate) fn import_info(doc: &lopdf::Document)
+-> ImportedInfo
+{
+    let info = doc.trailer.get(b"Info").ok()
+        .and_then(|info| doc.dereference(info).ok())
+        .and_then(|(_, info)| info.as_dict().ok());
+
+    let text = |key: &[u8]| info
+        .and_then(|info| info.get(key).ok())
+        .and_then(|value| value.as_str().ok())
+        .map(decode_text_string);
+
+    let keywords = text(b"Keywords").map(|keywords| {
+        keywords.split([',', ';'])
+            .map(|keyword| keyword.trim().to_string())
+            .filter(|keyword| !keyword.is_empty())
+            .collect()
+    }).unwrap_or_default();
+
+    let trapping = info
+        .and_then(|info| info.get(b"Trapped").ok())
+        .and_then(|trapped| match *trapped {
+            Object::Name(ref name) => match &name[..] {
+                b"True" => Some(true),
+                b"False" => Some(false),
+                _ => None,
+            },
+            Object::Boolean(trapped) => Some(trapped),
+            _ => None,
+        });
+
+    ImportedInfo {
+        title: text(b"Title"),
+        keywords,
+        subject: text(b"Subject"),
+        author: text(b"Author"),
+        creator: text(b"Creator"),
+        creation_date: text(b"CreationDate").and_then(|date| parse_date(&date)),
+        trapping,
+    }
+}
+
+/// Parses a PDF date (`D:YYYYMMDDHHmmSSOHH'mm'`), everything after the year is optional
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn parse_date(text: &str)
+-> Option<OffsetDateTime>
+{
+    use time::{Date, UtcOffset};
+
+    let text = text.trim_start_matches("D:");
+    let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+    let field = |start: usize, len: usize, default: u8| if start + len <= digits {
+        text[start..start + len].parse::<u8>().ok()
+    } else {
+        Some(default)
+    };
+
+    let year = text.get(..4).filter(|_| digits >= 4)?.parse::<i32>().ok()?;
+    let date = Date::try_from_ymd(year, field(4, 2, 1)?, field(6, 2, 1)?).ok()?
+        .try_with_hms(field(8, 2, 0)?, field(10, 2, 0)?, field(12, 2, 0)?).ok()?;
+
+    // the offset to UTC, e.g. +02'00' (Z or no offset: UTC)
+    let rest = &text[digits..];
+    let offset = match rest.chars().next() {
+        Some(sign) if sign == '+' || sign == '-' => {
+            let offset_digits = rest[1..].chars().filter(char::is_ascii_digit).collect::<String>();
+            let hours = offset_digits.get(..2)?.parse::<i16>().ok()?;
+            let minutes = offset_digits.get(2..4).and_then(|minutes| minutes.parse::<i16>().ok()).unwrap_or(0);
+            if sign == '-' { -(hours * 60 + minutes) } else { hours * 60 + minutes }
+        },
+        _ => 0,
+    };
+
+    Some(date.assume_offset(UtcOffset::minutes(offset)))
+}
+
+/// Dates can't be constructed with the `js_sys` date polyfill, so they are not imported
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn parse_date(_: &str)
+-> Option<OffsetDateTime>
+{
+    None
+}
+
+/// Moves the object IDs of all references in the object (recursively) by `offset`, so that
+/// the objects of another document can be added without colliding with the existing ones
+pub(crate) fn offset_references(object: &mut Object, offset: u32)
+{
+    match *object {
+        Object::Reference(ref mut id) => id.0 += offset,
+        Object::Array(ref mut array) => for item in array.iter_mut() { offset_references(item, offset); },
+        Object::Dictionary(ref mut dict) => for (_, value) in dict.iter_mut() { offset_references(value, offset); },
+        Object::Stream(ref mut stream) => for (_, value) in stream.dict.iter_mut() { offset_references(value, offset); },
+        _ => { },
+    }
+}
+
+/// Returns the value of an inheritable page attribute (e.g. `/MediaBox`, `/Resources`),
+/// looking it up in the parent nodes of the page tree if the page doesn't have it
+fn inherited<'a>(doc: &'a lopdf::Document, page_id: ObjectId, key: &[u8])
+-> Option<&'a Object>
+{
+    let mut node = doc.get_dictionary(page_id).ok();
+    for _ in 0..MAX_PAGE_TREE_DEPTH {
+        let dict = node?;
+        if let Ok(value) = dict.get(key) {
+            return Some(value);
+        }
+        node = dict.get(b"Parent").and_then(Object::as_reference).and_then(|id| doc.get_dictionary(id)).ok();
+    }
+    None
+}
+
+/// Reads a rectangle (`[llx lly urx ury]`, in any corner order)
+fn read_rect(doc: &lopdf::Document, rect: &Object)
+-> Option<(f64, f64, f64, f64)>
+{
+    let (_, rect) = doc.dereference(rect).ok()?;
+    let values = rect.as_array().ok()?.iter().map(|value| match *value {
+        Object::Integer(i) => Some(i as f64),
+        Object::Real(r) => Some(r),
+        _ => None,
+    }).collect::<Option<Vec<f64>>>()?;
+
+    match values[..] {
+        [x1, y1, x2, y2] => Some((x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2))),
+        _ => None,
+    }
+}
+
+/// Decodes a PDF text string: UTF-16BE if it starts with a byte order mark,
+/// otherwise PDFDocEncoding (read as Latin-1, which matches it for all common characters)
+fn decode_text_string(bytes: &[u8])
+-> String
+{
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units = bytes[2..].chunks(2)
+            .filter(|unit| unit.len() == 2)
+            .map(|unit| (u16::from(unit[0]) << 8) | u16::from(unit[1]))
+            .collect::<Vec<u16>>();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|b| char::from(*b)).collect()
+    }
+}
+
+#[test]
+fn test_offset_references() {
+    let mut dict = lopdf::Dictionary::new();
+    dict.set("Font", Object::Reference((5, 0)));
+    let mut object = Object::Array(vec![Object::Reference((3, 0)), Object::Dictionary(dict)]);
+    offset_references(&mut object, 10);
+    let ids: Vec<_> = match object {
+        Object::Array(ref array) => vec![
+            array[0].as_reference().unwrap(),
+            array[1].as_dict().unwrap().get(b"Font").unwrap().as_reference().unwrap(),
+        ],
+        _ => unreachable!(),
+    };
+    assert_eq!(ids, vec![(13, 0), (15, 0)]);
+}
+
+#[test]
+fn test_decode_text_string() {
+    assert_eq!(decode_text_string(b"Report"), "Report");
+    assert_eq!(decode_text_string(&[0xFE, 0xFF, 0x00, 0x52, 0x00, 0xE9]), "R\u{e9}");
+}
+
+#[test]
+fn test_parse_date() {
+    use date::utc_offset_minutes;
+
+    let date = parse_date("D:20200102030405+05'30'").unwrap();
+    assert_eq!((date.year(), date.month(), date.day()), (2020, 1, 2));
+    assert_eq!((date.hour(), date.minute(), date.second()), (3, 4, 5));
+    assert_eq!(utc_offset_minutes(&date), 330);
+
+    let date = parse_date("D:2019").unwrap();
+    assert_eq!((date.year(), date.month(), date.day(), date.hour()), (2019, 1, 1, 0));
+    assert_eq!(utc_offset_minutes(&parse_date("D:20200102030405-08'00").unwrap()), -480);
+    assert!(parse_date("D:20201302").is_none());
+    assert!(parse_date("yesterday").is_none());
+}
@@ -0,0 +1,71 @@
+//! Allocation of resource names (`/F1`, `/X2`, `/GS3`, `/PT4`, ...) that are unique within the document
+
+use std::collections::HashSet;
+
+/// Category of a named resource, determines the prefix of the resource name
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ResourceKind {
+    /// Font (`/Font` dictionary)
+    Font,
+    /// Image or form (`/XObject` dictionary)
+    XObject,
+    /// Graphics state (`/ExtGState` dictionary)
+    ExtGState,
+    /// Pattern, e.g. a gradient (`/Pattern` dictionary)
+    Pattern,
+}
+
+impl ResourceKind {
+
+    /// Returns the resource name for the given index, matching the names generated by
+    /// `ExternalFont`, `XObjectRef`, `ExtendedGraphicsStateRef` and `PatternRef`
+    pub(crate) fn name(&self, index: usize)
+    -> String
+    {
+        let prefix = match *self {
+            ResourceKind::Font => "F",
+            ResourceKind::XObject => "X",
+            ResourceKind::ExtGState => "GS",
+            ResourceKind::Pattern => "PT",
+        };
+        format!("{}{}", prefix, index)
+    }
+}
+
+/// Hands out resource indices from one counter shared by all resource categories,
+/// so that no two resources of the document end up with the same name (or number),
+/// even if resources are removed or added to a page directly.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ResourceNameAllocator {
+    /// Next index to try
+    next_index: usize,
+    /// All names handed out so far
+    allocated: HashSet<String>,
+}
+
+impl ResourceNameAllocator {
+
+    /// Returns the next free index for a resource of the given kind. `is_taken` is called
+    /// with the candidate name, to skip names that were not allocated through this allocator
+    /// (e.g. resources added with `PdfPage::add_xobject`).
+    pub(crate) fn allocate<F>(&mut self, kind: ResourceKind, is_taken: F)
+    -> usize where F: Fn(&str) -> bool
+    {
+        loop {
+            let index = self.next_index;
+            self.next_index += 1;
+            let name = kind.name(index);
+            if !is_taken(&name) && self.allocated.insert(name) {
+                return index;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resource_name_allocator() {
+    let mut allocator = ResourceNameAllocator::default();
+    assert_eq!(allocator.allocate(ResourceKind::Font, |_| false), 0);
+    assert_eq!(allocator.allocate(ResourceKind::XObject, |name| name == "X1"), 2);
+    assert_eq!(allocator.allocate(ResourceKind::ExtGState, |_| false), 3);
+}
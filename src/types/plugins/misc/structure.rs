@@ -0,0 +1,153 @@
+//! Logical structure (tagged PDF). Content on a page is wrapped in marked content sequences
+//! (`/P <</MCID 0>> BDC ... EMC`), which are referenced from the structure tree
+//! in the document catalog. This allows screen readers to read the document in order.
+
+use lopdf;
+use lopdf::Object::{Array, Dictionary, Integer, Name, Reference};
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+/// Standard structure types (PDF Reference 1.7, Section 10.7.3), which don't need to be
+/// mapped in the role map
+const STANDARD_STRUCTURE_TYPES: &[&str] = &[
+    "Document", "Part", "Art", "Sect", "Div", "BlockQuote", "Caption", "TOC", "TOCI", "Index",
+    "NonStruct", "Private", "P", "H", "H1", "H2", "H3", "H4", "H5", "H6", "L", "LI", "Lbl",
+    "LBody", "Table", "TR", "TH", "TD", "THead", "TBody", "TFoot", "Span", "Quote", "Note",
+    "Reference", "BibEntry", "Code", "Link", "Annot", "Ruby", "RB", "RT", "RP", "Warichu",
+    "WT", "WP", "Figure", "Formula", "Form",
+];
+
+/// Role of a piece of tagged content in the structure tree.
+/// Corresponds to the standard structure types (PDF Reference 1.7, Section 10.7.3)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructureRole {
+    /// Paragraph (`/P`), default for text
+    Paragraph,
+    /// Heading with a level from 1 to 6 (`/H1` - `/H6`). Levels outside of this range are
+    /// clamped: `Heading(0)` is written as `/H1`, levels above 6 as `/H6`.
+    Heading(u8),
+    /// Inline text (`/Span`)
+    Span,
+    /// Image or drawing (`/Figure`), default for images
+    Figure,
+    /// Any other (custom) structure type. Custom types should be mapped to
+    /// a standard type in the role map of the document.
+    Custom(String),
+}
+
+impl StructureRole {
+
+    /// Returns the standard structure type this role stands for, following the role map
+    /// (custom types can be mapped to other custom types, as long as the chain ends
+    /// in a standard type). Returns `None` if the role can't be resolved.
+    pub fn resolve<'a>(&'a self, role_map: &'a HashMap<String, String>)
+    -> Option<&'a str>
+    {
+        let mut name = self.as_str();
+        // every step has to use another entry of the map, otherwise the map contains a cycle
+        for _ in 0..=role_map.len() {
+            if STANDARD_STRUCTURE_TYPES.contains(&name) {
+                return Some(name);
+            }
+            name = role_map.get(name)?;
+        }
+        None
+    }
+
+    /// Returns the name of the structure type, as written into the PDF
+    pub fn as_str(&self)
+    -> &str
+    {
+        use self::StructureRole::*;
+        match *self {
+            Paragraph => "P",
+            Heading(0) | Heading(1) => "H1",
+            Heading(2) => "H2",
+            Heading(3) => "H3",
+            Heading(4) => "H4",
+            Heading(5) => "H5",
+            Heading(_) => "H6",
+            Span => "Span",
+            Figure => "Figure",
+            Custom(ref name) => name,
+        }
+    }
+}
+
+/// Adds the structure tree for the tagged content of all pages to the document and returns
+/// the reference to the `/StructTreeRoot` dictionary (to be set on the catalog).
+///
+/// `pages` contains the page object and the roles of the tagged content on the page, where
+/// the index of the role is the MCID of the content. The index of the page in `pages` has to
+/// be the same as the `/StructParents` value of the page. `role_map` maps custom structure
+/// types to standard ones and is written as the `/RoleMap` of the structure tree.
+pub(crate) fn add_structure_tree(doc: &mut lopdf::Document, pages: &[(lopdf::ObjectId, Vec<StructureRole>)],
+                                 role_map: &HashMap<String, String>)
+-> lopdf::ObjectId
+{
+    let struct_tree_root_id = doc.new_object_id();
+    let document_element_id = doc.new_object_id();
+
+    let mut elements = Vec::new();
+    let mut parent_tree = Vec::new();
+
+    for (struct_parents, &(page_id, ref roles)) in pages.iter().enumerate() {
+
+        let page_elements = roles.iter().enumerate().map(|(mcid, role)| {
+            Reference(doc.add_object(Dictionary(lopdf::Dictionary::from_iter(vec![
+                ("Type", Name("StructElem".into())),
+                ("S", Name(role.as_str().into())),
+                ("P", Reference(document_element_id)),
+                ("Pg", Reference(page_id)),
+                ("K", Integer(mcid as i64)),
+            ]))))
+        }).collect::<Vec<lopdf::Object>>();
+
+        elements.extend(page_elements.iter().cloned());
+        parent_tree.push(Integer(struct_parents as i64));
+        parent_tree.push(Array(page_elements));
+    }
+
+    doc.objects.insert(document_element_id, Dictionary(lopdf::Dictionary::from_iter(vec![
+        ("Type", Name("StructElem".into())),
+        ("S", Name("Document".into())),
+        ("P", Reference(struct_tree_root_id)),
+        ("K", Array(elements)),
+    ])));
+
+    let mut struct_tree_root = lopdf::Dictionary::from_iter(vec![
+        ("Type", Name("StructTreeRoot".into())),
+        ("K", Reference(document_element_id)),
+        ("ParentTree", Dictionary(lopdf::Dictionary::from_iter(vec![
+            ("Nums", Array(parent_tree)),
+        ]))),
+        ("ParentTreeNextKey", Integer(pages.len() as i64)),
+    ]);
+
+    if !role_map.is_empty() {
+        let mut roles = role_map.iter().collect::<Vec<_>>();
+        roles.sort();
+        struct_tree_root.set("RoleMap", Dictionary(lopdf::Dictionary::from_iter(
+            roles.into_iter().map(|(custom, standard)| (custom.as_str(), Name(standard.as_bytes().to_vec())))
+        )));
+    }
+
+    doc.objects.insert(struct_tree_root_id, Dictionary(struct_tree_root));
+
+    struct_tree_root_id
+}
+
+#[test]
+fn test_resolve_structure_role() {
+    let mut role_map = HashMap::new();
+    role_map.insert("Chapter".to_string(), "Sect".to_string());
+    role_map.insert("Intro".to_string(), "Chapter".to_string());
+    role_map.insert("Loop".to_string(), "Loop".to_string());
+
+    assert_eq!(StructureRole::Heading(2).resolve(&role_map), Some("H2"));
+    assert_eq!(StructureRole::Heading(0).as_str(), "H1");
+    assert_eq!(StructureRole::Heading(9).as_str(), "H6");
+    assert_eq!(StructureRole::Custom("Intro".into()).resolve(&role_map), Some("Sect"));
+    assert_eq!(StructureRole::Custom("Loop".into()).resolve(&role_map), None);
+    assert_eq!(StructureRole::Custom("Unknown".into()).resolve(&role_map), None);
+}
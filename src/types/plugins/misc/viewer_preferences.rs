@@ -0,0 +1,67 @@
+//! How the document is displayed when it is opened (`/PageMode`, `/ViewerPreferences`)
+
+/// What the viewer shows when the document is opened (`/PageMode` in the document catalog)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PageMode {
+    /// Neither the outline (bookmarks) nor the thumbnails are visible
+    UseNone,
+    /// The outline (bookmarks) is visible
+    UseOutlines,
+    /// The page thumbnails are visible
+    UseThumbs,
+    /// Full-screen mode, without menu bar, window controls or any other window visible.
+    /// See `NonFullScreenPageMode` for what is shown when full-screen mode is left.
+    FullScreen,
+    /// The optional content group panel (layers) is visible
+    UseOC,
+    /// The attachments panel is visible
+    UseAttachments,
+}
+
+impl PageMode {
+
+    /// Returns the name of the page mode, as written into the PDF
+    pub fn as_str(&self)
+    -> &'static str
+    {
+        use self::PageMode::*;
+        match *self {
+            UseNone => "UseNone",
+            UseOutlines => "UseOutlines",
+            UseThumbs => "UseThumbs",
+            FullScreen => "FullScreen",
+            UseOC => "UseOC",
+            UseAttachments => "UseAttachments",
+        }
+    }
+}
+
+/// What the viewer shows after leaving full-screen mode (`/NonFullScreenPageMode` in the
+/// viewer preferences). Only meaningful if the page mode is `PageMode::FullScreen`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NonFullScreenPageMode {
+    /// Neither the outline (bookmarks) nor the thumbnails are visible
+    UseNone,
+    /// The outline (bookmarks) is visible
+    UseOutlines,
+    /// The page thumbnails are visible
+    UseThumbs,
+    /// The optional content group panel (layers) is visible
+    UseOC,
+}
+
+impl NonFullScreenPageMode {
+
+    /// Returns the name of the page mode, as written into the PDF
+    pub fn as_str(&self)
+    -> &'static str
+    {
+        use self::NonFullScreenPageMode::*;
+        match *self {
+            UseNone => "UseNone",
+            UseOutlines => "UseOutlines",
+            UseThumbs => "UseThumbs",
+            UseOC => "UseOC",
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! Password protection with the standard security handler (AES-128, `/V 4 /R 4`),
+//! see `SaveOptions::encryption`. The passwords and keys are derived in `password`.
+
+use lopdf;
+use lopdf::Object::{Boolean, Dictionary, Integer, Name, Reference, Stream};
+use lopdf::StringFormat::Hexadecimal;
+use std::iter::FromIterator;
+use types::plugins::security::password::{KEY_LENGTH, file_key, md5, object_key, owner_entry, user_entry};
+
+/// What a user who opens the document with the user password is allowed to do. Viewers
+/// enforce the permissions, the owner password lifts them. The default allows everything.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Permissions {
+    /// Print the document (bit 3). If `print_high_quality` is false, only a low-resolution
+    /// version can be printed.
+    pub print: bool,
+    /// Modify the contents of the document (bit 4)
+    pub modify: bool,
+    /// Copy or extract text and graphics (bit 5)
+    pub copy: bool,
+    /// Add or modify annotations and fill in form fields (bit 6)
+    pub annotate: bool,
+    /// Fill in existing form fields, even if `annotate` is false (bit 9)
+    pub fill_forms: bool,
+    /// Extract text and graphics for accessibility, e.g. screen readers (bit 10)
+    pub extract_for_accessibility: bool,
+    /// Insert, rotate or delete pages and create bookmarks (bit 11)
+    pub assemble: bool,
+    /// Print in full quality (bit 12), only relevant if `print` is true
+    pub print_high_quality: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            print: true,
+            modify: true,
+            copy: true,
+            annotate: true,
+            fill_forms: true,
+            extract_for_accessibility: true,
+            assemble: true,
+            print_high_quality: true,
+        }
+    }
+}
+
+impl Permissions {
+
+    /// Returns the value of `/P`: the permission bits, with the reserved bits set
+    pub(crate) fn bits(&self)
+    -> i32
+    {
+        let flags = [
+            (self.print, 3), (self.modify, 4), (self.copy, 5), (self.annotate, 6),
+            (self.fill_forms, 9), (self.extract_for_accessibility, 10), (self.assemble, 11),
+            (self.print_high_quality, 12),
+        ];
+        // bits 7, 8 and 13 - 32 are reserved and must be 1
+        let reserved = 0xFFFF_F0C0_u32;
+        flags.iter()
+            .filter(|&&(allowed, _)| allowed)
+            .fold(reserved, |bits, &(_, bit)| bits | (1 << (bit - 1))) as i32
+    }
+}
+
+/// Passwords and permissions for encrypting the document when it is saved.
+/// Strings and streams are encrypted with AES-128 (PDF 1.6). The IVs are derived from
+/// `SaveOptions::instance_id`: leave it `None` unless the output has to be reproducible,
+/// otherwise re-saving a changed document reuses the IVs of the previous version.
+///
+/// PDF/A and PDF/X don't allow encryption. Since new documents are PDF/X-3 by default,
+/// change the conformance first, e.g. to `PdfConformance::Custom(CustomPdfConformance::default())`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encryption {
+    /// Password for opening the document, can be empty (the document opens without a prompt)
+    pub user_password: String,
+    /// Password for opening the document without the restrictions of `permissions`.
+    /// If empty, the user password is used as owner password (as the PDF specification
+    /// defines), so everyone who can open the document can also lift the restrictions.
+    pub owner_password: String,
+    /// What users with the user password are allowed to do
+    pub permissions: Permissions,
+}
+
+impl Encryption {
+
+    /// Creates the encryption settings with the passwords, all permissions are granted
+    pub fn new<U, O>(user_password: U, owner_password: O)
+    -> Self where U: Into<String>, O: Into<String>
+    {
+        Self {
+            user_password: user_password.into(),
+            owner_password: owner_password.into(),
+            permissions: Permissions::default(),
+        }
+    }
+
+    /// Creates encryption settings that only restrict what users can do: the document opens
+    /// without a password prompt (empty user password), viewers enforce the `permissions`
+    /// unless the document is opened with the owner password. For example, to allow viewing
+    /// but only low-resolution printing:
+    ///
+    /// ```rust,ignore
+    /// let permissions = Permissions { print_high_quality: false, .. Permissions::default() };
+    /// let encryption = Encryption::permissions_only("owner password", permissions);
+    /// ```
+    pub fn permissions_only<O>(owner_password: O, permissions: Permissions)
+    -> Self where O: Into<String>
+    {
+        Self::new("", owner_password).with_permissions(permissions)
+    }
+
+    /// Sets the permissions of users that open the document with the user password
+    #[inline]
+    pub fn with_permissions(mut self, permissions: Permissions)
+    -> Self
+    {
+        self.permissions = permissions;
+        self
+    }
+}
+
+/// Encrypts all strings and streams of the document and adds the `/Encrypt` dictionary to
+/// the trailer. `file_id` is the first entry of the trailer `/ID`, `instance_id` the second
+/// one, which the IVs are derived from. Has to be called right before the document is
+/// written, after compressing the streams.
+pub(crate) fn encrypt_document(doc: &mut lopdf::Document, encryption: &Encryption, file_id: &[u8], instance_id: &[u8])
+{
+    let permissions = encryption.permissions.bits();
+    // without an owner password, the user password is used (algorithm 3, step a)
+    let owner_password = if encryption.owner_password.is_empty() { &encryption.user_password } else { &encryption.owner_password };
+    let owner_entry = owner_entry(owner_password.as_bytes(), encryption.user_password.as_bytes());
+    let key = file_key(encryption.user_password.as_bytes(), &owner_entry, permissions, file_id);
+    let user_entry = user_entry(&key, file_id);
+
+    // the encryption dictionary is added afterwards, it must not be encrypted
+    for (id, object) in doc.objects.iter_mut() {
+        let object_key = object_key(&key, *id);
+        let mut ivs = IvSequence { object_key, instance_id, counter: 0 };
+        encrypt_object(object, &object_key, &mut ivs);
+    }
+
+    let crypt_filter = lopdf::Dictionary::from_iter(vec![
+        ("Type", Name("CryptFilter".into())),
+        ("CFM", Name("AESV2".into())),
+        ("AuthEvent", Name("DocOpen".into())),
+        ("Length", Integer(KEY_LENGTH as i64)),
+    ]);
+    let encrypt = lopdf::Dictionary::from_iter(vec![
+        ("Filter", Name("Standard".into())),
+        ("V", Integer(4)),
+        ("R", Integer(4)),
+        ("Length", Integer(KEY_LENGTH as i64 * 8)),
+        ("CF", Dictionary(lopdf::Dictionary::from_iter(vec![("StdCF", Dictionary(crypt_filter))]))),
+        ("StmF", Name("StdCF".into())),
+        ("StrF", Name("StdCF".into())),
+        ("O", lopdf::Object::String(owner_entry.to_vec(), Hexadecimal)),
+        ("U", lopdf::Object::String(user_entry.to_vec(), Hexadecimal)),
+        ("P", Integer(permissions as i64)),
+        ("EncryptMetadata", Boolean(true)),
+    ]);
+    let encrypt_id = doc.add_object(encrypt);
+    doc.trailer.set("Encrypt", Reference(encrypt_id));
+
+    // AES encryption was added in PDF 1.6
+    if doc.version.as_str() < "1.6" {
+        doc.version = "1.6".into();
+    }
+}
+
+/// Encrypts the strings and the stream content of an object in place, with the next IV
+/// of `ivs` for each string / stream
+fn encrypt_object(object: &mut lopdf::Object, object_key: &[u8; 16], ivs: &mut IvSequence)
+{
+    match *object {
+        lopdf::Object::String(ref mut bytes, ref mut format) => {
+            *bytes = aes_encrypt(object_key, &ivs.next_iv(), bytes);
+            *format = Hexadecimal;
+        },
+        lopdf::Object::Array(ref mut items) => {
+            for item in items.iter_mut() {
+                encrypt_object(item, object_key, ivs);
+            }
+        },
+        Dictionary(ref mut dict) => {
+            for (_, value) in dict.iter_mut() {
+                encrypt_object(value, object_key, ivs);
+            }
+        },
+        Stream(ref mut stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                encrypt_object(value, object_key, ivs);
+            }
+            let content = aes_encrypt(object_key, &ivs.next_iv(), &stream.content);
+            stream.set_content(content);
+        },
+        _ => { },
+    }
+}
+
+/// Initialization vectors for the strings and streams of one object.
+///
+/// The IVs are derived from the object key and the instance ID instead of being random.
+/// A new instance ID (the default, see `SaveOptions::instance_id`) gives new IVs on every
+/// save. With a fixed instance ID the output stays byte-for-byte reproducible, but re-saving
+/// changed content then reuses the IVs of the previous file, which reveals which leading
+/// blocks of a string or stream are unchanged. Only fix the instance ID if that is acceptable.
+struct IvSequence<'a> {
+    object_key: [u8; 16],
+    instance_id: &'a [u8],
+    /// Number of strings / streams of the object that were encrypted so far
+    counter: u32,
+}
+
+impl<'a> IvSequence<'a> {
+
+    /// Returns the initialization vector for the next string or stream of the object
+    fn next_iv(&mut self)
+    -> [u8; 16]
+    {
+        let mut input = self.object_key.to_vec();
+        input.extend_from_slice(self.instance_id);
+        input.extend_from_slice(&self.counter.to_le_bytes());
+        input.extend_from_slice(b"IV");
+        self.counter += 1;
+        md5(&input)
+    }
+}
+
+/// Returns the AES S-box, computed from the multiplicative inverse in GF(2^8)
+fn aes_sbox()
+-> [u8; 256]
+{
+    let mut sbox = [0; 256];
+    let (mut p, mut q) = (1_u8, 1_u8);
+    loop {
+        // multiply p by 3, divide q by 3
+        p = p ^ (p << 1) ^ if p & 0x80 != 0 { 0x1B } else { 0 };
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 { q ^= 0x09; }
+        sbox[p as usize] = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4) ^ 0x63;
+        if p == 1 { break; }
+    }
+    sbox[0] = 0x63;
+    sbox
+}
+
+/// Multiplies by x (i.e. 2) in GF(2^8)
+fn xtime(byte: u8)
+-> u8
+{
+    (byte << 1) ^ if byte & 0x80 != 0 { 0x1B } else { 0 }
+}
+
+/// Expands the AES-128 key into the 11 round keys
+fn aes_round_keys(key: &[u8; 16], sbox: &[u8; 256])
+-> [[u8; 16]; 11]
+{
+    let mut round_keys = [[0; 16]; 11];
+    round_keys[0] = *key;
+    let mut rcon = 1_u8;
+    for round in 1..11 {
+        let previous = round_keys[round - 1];
+        let mut word = [sbox[previous[13] as usize] ^ rcon, sbox[previous[14] as usize], sbox[previous[15] as usize], sbox[previous[12] as usize]];
+        for column in 0..4 {
+            for row in 0..4 {
+                word[row] ^= previous[column * 4 + row];
+                round_keys[round][column * 4 + row] = word[row];
+            }
+        }
+        rcon = xtime(rcon);
+    }
+    round_keys
+}
+
+/// Encrypts one 16-byte block with AES-128 (FIPS 197)
+fn aes_encrypt_block(block: &mut [u8; 16], round_keys: &[[u8; 16]; 11], sbox: &[u8; 256])
+{
+    let add_round_key = |block: &mut [u8; 16], round: usize| {
+        for (byte, key) in block.iter_mut().zip(round_keys[round].iter()) {
+            *byte ^= key;
+        }
+    };
+
+    add_round_key(block, 0);
+    for round in 1..11 {
+        // sub bytes + shift rows (row r is rotated left by r columns)
+        let state = *block;
+        for column in 0..4 {
+            for row in 0..4 {
+                block[column * 4 + row] = sbox[state[((column + row) % 4) * 4 + row] as usize];
+            }
+        }
+
+        if round != 10 {
+            for column in block.chunks_mut(4) {
+                let (a0, a1, a2, a3) = (column[0], column[1], column[2], column[3]);
+                column[0] = xtime(a0) ^ xtime(a1) ^ a1 ^ a2 ^ a3;
+                column[1] = a0 ^ xtime(a1) ^ xtime(a2) ^ a2 ^ a3;
+                column[2] = a0 ^ a1 ^ xtime(a2) ^ xtime(a3) ^ a3;
+                column[3] = xtime(a0) ^ a0 ^ a1 ^ a2 ^ xtime(a3);
+            }
+        }
+
+        add_round_key(block, round);
+    }
+}
+
+/// Encrypts the data with AES-128 in CBC mode with PKCS #5 padding, the IV is prepended
+/// to the result (as required by PDF)
+fn aes_encrypt(key: &[u8; 16], iv: &[u8; 16], data: &[u8])
+-> Vec<u8>
+{
+    let sbox = aes_sbox();
+    let round_keys = aes_round_keys(key, &sbox);
+
+    let padding = 16 - data.len() % 16;
+    let mut padded = data.to_vec();
+    padded.resize(data.len() + padding, padding as u8);
+
+    let mut result = iv.to_vec();
+    let mut previous = *iv;
+    for chunk in padded.chunks(16) {
+        let mut block = [0; 16];
+        for (i, byte) in chunk.iter().enumerate() {
+            block[i] = byte ^ previous[i];
+        }
+        aes_encrypt_block(&mut block, &round_keys, &sbox);
+        result.extend_from_slice(&block);
+        previous = block;
+    }
+    result
+}
+
+#[test]
+fn test_encryption_primitives() {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<::std::string::String>();
+
+    // FIPS 197, appendix C.1
+    let key = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+    let sbox = aes_sbox();
+    let mut block = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+    aes_encrypt_block(&mut block, &aes_round_keys(&key, &sbox), &sbox);
+    assert_eq!(hex(&block), "69c4e0d86a7b0430d8cdb78070b4c55a");
+
+    // IV + one block of data + one block of padding
+    assert_eq!(aes_encrypt(&key, &[0; 16], &[7; 16]).len(), 48);
+
+    // reference value computed with Python (AES of the cryptography package), the key is
+    // the object key of object 1 in `password::test_password_entries`
+    let object_key = [0xa5, 0x13, 0x38, 0x1b, 0xc5, 0xbd, 0x6b, 0xd8, 0x4e, 0x99, 0x1d, 0x9e, 0xe6, 0x16, 0x75, 0xe8];
+    assert_eq!(hex(&aes_encrypt(&object_key, &[0; 16], b"Hello")[16..]), "392368fd663ac22392a72ce1bfb91ee6");
+}
+
+#[test]
+fn test_iv_sequence() {
+    let mut first = IvSequence { object_key: [1; 16], instance_id: b"instance 1", counter: 0 };
+    let mut second = IvSequence { object_key: [1; 16], instance_id: b"instance 2", counter: 0 };
+    let iv = first.next_iv();
+    assert_ne!(iv, first.next_iv());
+    // a new instance ID gives new IVs
+    assert_ne!(iv, second.next_iv());
+}
+
+#[test]
+fn test_permission_bits() {
+    assert_eq!(Permissions::default().bits(), -4);
+    let no_copy = Permissions { copy: false, .. Permissions::default() };
+    assert_eq!(no_copy.bits() & (1 << 4), 0);
+    let low_resolution_print = Permissions { print_high_quality: false, .. Permissions::default() };
+    assert_eq!(low_resolution_print.bits(), -4 & !(1 << 11));
+}
+
+#[test]
+fn test_permissions_only() {
+    let encryption = Encryption::permissions_only("owner", Permissions { copy: false, .. Permissions::default() });
+    let mut doc = lopdf::Document::with_version("1.3");
+    encrypt_document(&mut doc, &encryption, b"file id", b"instance id");
+
+    // the empty user password opens the document
+    let encrypt_id = doc.trailer.get(b"Encrypt").and_then(|encrypt| encrypt.as_reference()).unwrap();
+    let encrypt = doc.get_dictionary(encrypt_id).unwrap();
+    let owner = encrypt.get(b"O").and_then(|o| o.as_str()).unwrap();
+    let mut owner_entry = [0; 32];
+    owner_entry.copy_from_slice(owner);
+    let key = file_key(b"", &owner_entry, encryption.permissions.bits(), b"file id");
+    assert_eq!(encrypt.get(b"U").and_then(|u| u.as_str()).ok(), Some(&user_entry(&key, b"file id")[..]));
+    assert_eq!(doc.version, "1.6");
+}
+
+#[test]
+fn test_empty_owner_password() {
+    let encrypt_entries = |encryption: &Encryption| {
+        let mut doc = lopdf::Document::with_version("1.3");
+        encrypt_document(&mut doc, encryption, b"file id", b"instance id");
+        let encrypt_id = doc.trailer.get(b"Encrypt").and_then(|encrypt| encrypt.as_reference()).unwrap();
+        let encrypt = doc.get_dictionary(encrypt_id).unwrap();
+        let entry = |key: &[u8]| encrypt.get(key).and_then(|value| value.as_str()).unwrap().to_vec();
+        (entry(b"O"), entry(b"U"))
+    };
+
+    // the owner entry is derived from the user password, so saving twice gives the same result
+    let encryption = Encryption::new("user", "");
+    let (owner, user) = encrypt_entries(&encryption);
+    assert_eq!(owner, owner_entry(b"user", b"user").to_vec());
+    assert_eq!((owner, user), encrypt_entries(&encryption));
+}
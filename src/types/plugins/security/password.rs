@@ -0,0 +1,170 @@
+//! Password handling of the standard security handler: the `/O` and `/U` entries and the
+//! derivation of the file and object keys (algorithms 1 - 3 and 5 of the PDF specification)
+
+use lopdf::ObjectId;
+
+/// Padding string of the standard security handler, appended to passwords shorter than 32 bytes
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Length of the file encryption key in bytes (128 bit)
+pub(crate) const KEY_LENGTH: usize = 16;
+
+/// Pads or truncates the password to 32 bytes (algorithm 2, step a)
+fn pad_password(password: &[u8])
+-> Vec<u8>
+{
+    password.iter().take(32).chain(PASSWORD_PADDING.iter()).take(32).cloned().collect()
+}
+
+/// Applies RC4 20 times, with the key XOR-ed with the iteration number (algorithms 3 and 5)
+fn rc4_rounds(key: &[u8], data: &[u8])
+-> Vec<u8>
+{
+    (0..20_u8).fold(data.to_vec(), |data, round| {
+        let round_key = key.iter().map(|byte| byte ^ round).collect::<Vec<u8>>();
+        rc4(&round_key, &data)
+    })
+}
+
+/// Computes the `/O` entry from the owner and user password (algorithm 3)
+pub(crate) fn owner_entry(owner_password: &[u8], user_password: &[u8])
+-> [u8; 32]
+{
+    let mut hash = md5(&pad_password(owner_password));
+    for _ in 0..50 {
+        hash = md5(&hash);
+    }
+    let encrypted = rc4_rounds(&hash[..KEY_LENGTH], &pad_password(user_password));
+    let mut entry = [0; 32];
+    entry.copy_from_slice(&encrypted);
+    entry
+}
+
+/// Computes the file encryption key from the user password (algorithm 2)
+pub(crate) fn file_key(user_password: &[u8], owner_entry: &[u8; 32], permissions: i32, file_id: &[u8])
+-> [u8; 16]
+{
+    let mut input = pad_password(user_password);
+    input.extend_from_slice(owner_entry);
+    input.extend_from_slice(&permissions.to_le_bytes());
+    input.extend_from_slice(file_id);
+    let mut key = md5(&input);
+    for _ in 0..50 {
+        key = md5(&key[..KEY_LENGTH]);
+    }
+    key
+}
+
+/// Computes the `/U` entry from the file encryption key (algorithm 5)
+pub(crate) fn user_entry(key: &[u8; 16], file_id: &[u8])
+-> [u8; 32]
+{
+    let mut input = PASSWORD_PADDING.to_vec();
+    input.extend_from_slice(file_id);
+    let encrypted = rc4_rounds(key, &md5(&input));
+    // the last 16 bytes are arbitrary
+    let mut entry = [0; 32];
+    entry[..16].copy_from_slice(&encrypted);
+    entry[16..].copy_from_slice(&PASSWORD_PADDING[..16]);
+    entry
+}
+
+/// Computes the key for the strings and streams of one object (algorithm 1, for AES)
+pub(crate) fn object_key(key: &[u8; 16], id: ObjectId)
+-> [u8; 16]
+{
+    let mut input = key.to_vec();
+    input.extend_from_slice(&id.0.to_le_bytes()[..3]);
+    input.extend_from_slice(&id.1.to_le_bytes()[..2]);
+    input.extend_from_slice(b"sAlT");
+    md5(&input)
+}
+
+/// MD5 hash (RFC 1321)
+pub(crate) fn md5(data: &[u8])
+-> [u8; 16]
+{
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants = (0..64).map(|i| ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32).collect::<Vec<u32>>();
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state = [0x6745_2301_u32, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    for chunk in message.chunks(64) {
+        let words = chunk.chunks(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect::<Vec<u32>>();
+        let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(constants[i]).wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[(i / 16) * 4 + i % 4]));
+        }
+        state = [state[0].wrapping_add(a), state[1].wrapping_add(b), state[2].wrapping_add(c), state[3].wrapping_add(d)];
+    }
+
+    let mut hash = [0; 16];
+    for (i, word) in state.iter().enumerate() {
+        hash[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    hash
+}
+
+/// RC4 stream cipher, only used for computing the password entries
+fn rc4(key: &[u8], data: &[u8])
+-> Vec<u8>
+{
+    let mut s = (0..=255_u8).collect::<Vec<u8>>();
+    let mut j = 0_u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0_u8, 0_u8);
+    data.iter().map(|byte| {
+        i = i.wrapping_add(1);
+        j = j.wrapping_add(s[i as usize]);
+        s.swap(i as usize, j as usize);
+        byte ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+    }).collect()
+}
+
+#[test]
+fn test_hash_primitives() {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<::std::string::String>();
+
+    assert_eq!(hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(hex(&md5(b"The quick brown fox jumps over the lazy dog")), "9e107d9d372bb6826bd81d3542a419d6");
+    assert_eq!(hex(&rc4(b"Key", b"Plaintext")), "bbf316e8d940af0ad3");
+}
+
+#[test]
+fn test_password_entries() {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<::std::string::String>();
+
+    // reference values computed independently with Python (hashlib and the RC4 / AES
+    // cipher of the cryptography package), following algorithms 1 - 3 and 5
+    let file_id = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+    let owner = owner_entry(b"owner", b"user");
+    assert_eq!(hex(&owner), "0ba3835f88f90388e74e54584125ce142be0de24c6b0d37746e075b891756671");
+    let key = file_key(b"user", &owner, -4, &file_id);
+    assert_eq!(hex(&key), "2e76247e4b9d5c6aa46d224a2e740d27");
+    assert_eq!(hex(&user_entry(&key, &file_id)[..16]), "d798239dd347dc3afe3dd10515bb5843");
+
+    assert_eq!(hex(&object_key(&key, (1, 0))), "a513381bc5bd6bd84e991d9ee61675e8");
+}
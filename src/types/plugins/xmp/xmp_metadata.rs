@@ -1,10 +1,11 @@
 //! Stub plugin for XMP Metadata streams, to be expanded later
 
 use crate::OffsetDateTime;
+use date::utc_offset_minutes;
 use lopdf;
 
-use PdfConformance;
-use utils::random_character_string_32;
+use {ImageFilter, ImageXObject, PdfConformance, PdfError, Px};
+use utils::{base64_encode, random_character_string_32};
 
 /// Initial struct for Xmp metatdata. This should be expanded later for XML handling, etc.
 /// Right now it just fills out the necessary fields
@@ -16,6 +17,91 @@ pub struct XmpMetadata {
     pub rendition_class: Option<String>,
     /// Document version
     pub document_version: u32,
+    /// Preview image for asset management systems (`xmp:Thumbnails`)
+    pub thumbnail: Option<XmpThumbnail>,
+    /// Properties of custom schemas, see `PdfMetadata::add_xmp_property`
+    pub custom_properties: Vec<XmpProperty>,
+}
+
+/// Simple (text) property of a custom XMP schema, for example `prism:issn`
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmpProperty {
+    /// Namespace URI of the schema, for example `http://prismstandard.org/namespaces/basic/2.0/`
+    pub namespace: String,
+    /// Preferred prefix of the namespace, for example `prism`
+    pub prefix: String,
+    /// Name of the property (without prefix), for example `issn`
+    pub key: String,
+    /// Value of the property, escaped on save
+    pub value: String,
+}
+
+impl XmpProperty {
+
+    /// Creates a new custom property
+    pub fn new<N, P, K, V>(namespace: N, prefix: P, key: K, value: V)
+    -> Self where N: Into<String>, P: Into<String>, K: Into<String>, V: Into<String>
+    {
+        Self {
+            namespace: namespace.into(),
+            prefix: prefix.into(),
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// JPEG preview image of the document, stored (base64-encoded) in the XMP metadata.
+/// Unlike the `/Thumb` entry of a page, this is read by asset management systems.
+#[derive(Debug, Clone)]
+pub struct XmpThumbnail {
+    /// Width of the image in pixels
+    pub width: Px,
+    /// Height of the image in pixels
+    pub height: Px,
+    /// The JPEG-encoded image data
+    pub jpeg_data: Vec<u8>,
+}
+
+impl XmpThumbnail {
+
+    /// Creates a new thumbnail from JPEG-encoded data
+    pub fn new(width: Px, height: Px, jpeg_data: Vec<u8>)
+    -> Self
+    {
+        Self {
+            width,
+            height,
+            jpeg_data,
+        }
+    }
+
+    /// Creates a thumbnail from an image. Only works if the image is a
+    /// JPEG image (`ImageFilter::DCT`), otherwise returns `None`.
+    pub fn from_image(image: &ImageXObject)
+    -> Option<Self>
+    {
+        match image.image_filter {
+            Some(ImageFilter::DCT) => Some(Self::new(image.width, image.height, image.image_data.clone())),
+            _ => None,
+        }
+    }
+
+    fn into_xml(self)
+    -> String
+    {
+        format!("
+         <xmp:Thumbnails>
+            <rdf:Alt>
+               <rdf:li rdf:parseType=\"Resource\">
+                  <xmpGImg:width>{}</xmpGImg:width>
+                  <xmpGImg:height>{}</xmpGImg:height>
+                  <xmpGImg:format>JPEG</xmpGImg:format>
+                  <xmpGImg:image>{}</xmpGImg:image>
+               </rdf:li>
+            </rdf:Alt>
+         </xmp:Thumbnails>", self.width.0, self.height.0, base64_encode(&self.jpeg_data))
+    }
 }
 
 impl XmpMetadata {
@@ -29,18 +115,35 @@ impl XmpMetadata {
             document_id: document_id,
             rendition_class: rendition_class,
             document_version: document_version,
+            thumbnail: None,
+            custom_properties: Vec::new(),
+        }
+    }
+
+    /// Adds a custom property, replacing a custom property with the same namespace and key
+    pub fn add_property(&mut self, property: XmpProperty) {
+        match self.custom_properties.iter_mut().find(|p| p.namespace == property.namespace && p.key == property.key) {
+            Some(existing) => *existing = property,
+            None => self.custom_properties.push(property),
         }
     }
 
     /// Consumes the XmpMetadata and turns it into a PDF Object.
     /// This is similar to the
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     pub(in types) fn into_obj<S>(self,
                            conformance: PdfConformance,
                            trapping: bool,
                            creation_date: OffsetDateTime,
                            modification_date: OffsetDateTime,
                            metadata_date: OffsetDateTime,
-                           document_title: S)
+                           document_title: S,
+                           instance_id: &str,
+                           keywords: &[String],
+                           subject: &str,
+                           author: &str,
+                           creator: &str,
+                           producer: &str)
     -> lopdf::Object where S: Into<String> + ::std::fmt::Display
     {
         use lopdf::{Stream as LoStream, Dictionary as LoDictionary};
@@ -50,13 +153,21 @@ impl XmpMetadata {
         // Shared between XmpMetadata and DocumentInfo
         let trapping = if trapping { "True" } else { "False" };
 
-        // let xmp_instance_id = "2898d852-f86f-4479-955b-804d81046b19";
-        let instance_id = random_character_string_32();
         let create_date = to_pdf_xmp_date(creation_date);
         let modification_date = to_pdf_xmp_date(modification_date);
         let metadata_date = to_pdf_xmp_date(metadata_date);
 
-        let pdf_x_version = conformance.get_identifier_string();
+        // PDF/A identifies itself with the `pdfaid` schema, the PDF/X schemas aren't allowed there
+        let conformance_id = match conformance.get_pdfa_part_and_conformance() {
+            Some((part, level)) => format!("<pdfaid:part>{}</pdfaid:part>
+         <pdfaid:conformance>{}</pdfaid:conformance>", part, level),
+            None if conformance.is_pdfx() => {
+                let pdf_x_version = conformance.get_identifier_string();
+                format!("<pdfxid:GTS_PDFXVersion>{0}</pdfxid:GTS_PDFXVersion>
+         <pdfx:GTS_PDFXVersion>{0}</pdfx:GTS_PDFXVersion>", pdf_x_version)
+            },
+            None => "".to_string(),
+        };
         let document_version = self.document_version.to_string();
         let document_id = self.document_id.to_string();
 
@@ -65,9 +176,15 @@ impl XmpMetadata {
             None => "".to_string(),
         };
 
+        let thumbnail = self.thumbnail.map(|t| t.into_xml()).unwrap_or_default();
+
+        let description = description_xml(keywords, subject, author, creator, producer);
+        let (namespaces, custom_properties) = custom_properties_xml(&self.custom_properties);
+
         let xmp_metadata = format!(include_str!("../../../templates/catalog_xmp_metadata.txt"),
                            create_date, modification_date, metadata_date, document_title, document_id,
-                           instance_id, rendition_class, document_version, pdf_x_version, trapping);
+                           instance_id, rendition_class, document_version, conformance_id, trapping,
+                           thumbnail, description, namespaces, custom_properties);
 
         Stream(LoStream::new(LoDictionary::from_iter(vec![
             ("Type", "Metadata".into()),
@@ -76,18 +193,188 @@ impl XmpMetadata {
     }
 }
 
-// D:2018-09-19T10:05:05+00'00'
-fn to_pdf_xmp_date(date: OffsetDateTime)
+/// Returns the `dc:subject` (keywords), `pdf:Keywords`, `dc:description` (subject),
+/// `dc:creator` (author), `xmp:CreatorTool` (creator) and `pdf:Producer` entries
+fn description_xml(keywords: &[String], subject: &str, author: &str, creator: &str, producer: &str)
+-> String
+{
+    // keywords are a bag in `dc:subject` and comma-joined in `pdf:Keywords` (like in the Info dictionary),
+    // the subject of the Info dictionary is the `dc:description`
+    let mut description = String::new();
+    if !keywords.is_empty() {
+        let items: String = keywords.iter().map(|k| format!("
+               <rdf:li>{}</rdf:li>", xml_escape(k))).collect();
+        description.push_str(&format!("
+         <dc:subject>
+            <rdf:Bag>{}
+            </rdf:Bag>
+         </dc:subject>
+         <pdf:Keywords>{}</pdf:Keywords>", items, xml_escape(&keywords.join(", "))));
+    }
+    if !subject.is_empty() {
+        description.push_str(&format!("
+         <dc:description>
+            <rdf:Alt>
+               <rdf:li xml:lang=\"x-default\">{}</rdf:li>
+            </rdf:Alt>
+         </dc:description>", xml_escape(subject)));
+    }
+    if !author.is_empty() {
+        description.push_str(&format!("
+         <dc:creator>
+            <rdf:Seq>
+               <rdf:li>{}</rdf:li>
+            </rdf:Seq>
+         </dc:creator>", xml_escape(author)));
+    }
+    if !creator.is_empty() {
+        description.push_str(&format!("
+         <xmp:CreatorTool>{}</xmp:CreatorTool>", xml_escape(creator)));
+    }
+    if !producer.is_empty() {
+        description.push_str(&format!("
+         <pdf:Producer>{}</pdf:Producer>", xml_escape(producer)));
+    }
+    description
+}
+
+/// Namespaces declared by the XMP template, as (prefix, namespace URI)
+const TEMPLATE_NAMESPACES: &[(&str, &str)] = &[
+    ("x", "adobe:ns:meta/"),
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("xml", "http://www.w3.org/XML/1998/namespace"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("pdfxid", "http://www.npes.org/pdfx/ns/id/"),
+    ("pdfx", "http://ns.adobe.com/pdfx/1.3/"),
+    ("pdf", "http://ns.adobe.com/pdf/1.3/"),
+    ("pdfaid", "http://www.aiim.org/pdfa/ns/id/"),
+    ("xmpGImg", "http://ns.adobe.com/xap/1.0/g/img/"),
+];
+
+/// Returns the namespace declarations (for the `rdf:Description`) and the elements of
+/// the custom properties. If the prefix of a property is already used for another
+/// namespace, a number is appended to it, so that every namespace has its own prefix.
+fn custom_properties_xml(properties: &[XmpProperty])
+-> (String, String)
+{
+    let mut prefixes: Vec<(String, String)> = TEMPLATE_NAMESPACES.iter()
+        .map(|(prefix, namespace)| (prefix.to_string(), namespace.to_string()))
+        .collect();
+    let mut declarations = String::new();
+    let mut elements = String::new();
+
+    for property in properties {
+        let prefix = match prefixes.iter().find(|(_, namespace)| *namespace == property.namespace) {
+            Some((prefix, _)) => prefix.clone(),
+            None => {
+                let mut prefix = property.prefix.clone();
+                let mut suffix = 1;
+                while prefixes.iter().any(|(p, _)| *p == prefix) {
+                    prefix = format!("{}{}", property.prefix, suffix);
+                    suffix += 1;
+                }
+                declarations.push_str(&format!("
+            xmlns:{}=\"{}\"", prefix, xml_escape(&property.namespace).replace('"', "&quot;")));
+                prefixes.push((prefix.clone(), property.namespace.clone()));
+                prefix
+            },
+        };
+        elements.push_str(&format!("
+         <{0}:{1}>{2}</{0}:{1}>", prefix, property.key, xml_escape(&property.value)));
+    }
+
+    (declarations, elements)
+}
+
+/// Checks that the prefix and key of every custom property are valid XML names without
+/// a colon (NCNames) and that no property belongs to a schema that printpdf writes itself
+/// (XMP, Dublin Core, PDF, ...), since these properties would clash with the built-in ones
+pub(crate) fn check_custom_properties(properties: &[XmpProperty])
+-> Result<(), PdfError>
+{
+    for property in properties {
+        let is_builtin = TEMPLATE_NAMESPACES.iter().any(|(_, namespace)| *namespace == property.namespace);
+        if is_builtin || !is_ncname(&property.prefix) || !is_ncname(&property.key) {
+            return Err(PdfError::InvalidXmpProperty);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the name is a valid XML name without a colon
+fn is_ncname(name: &str)
+-> bool
+{
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_alphabetic() || first == '_' => {},
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Escapes the characters that are not allowed in XML text
+fn xml_escape(text: &str)
+-> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// 2018-09-19T10:05:05+00:00
+pub(crate) fn to_pdf_xmp_date(date: OffsetDateTime)
 -> String
 {
-    // Since the time is in UTC, we know that the time zone
-    // difference to UTC is 0 min, 0 sec, hence the 00'00
-    format!("D:{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+00'00'",
+    // the time is local to the offset of the date, so the offset has to be written as well
+    let offset = utc_offset_minutes(&date);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
         date.year(),
         date.month(),
         date.day(),
         date.hour(),
         date.minute(),
         date.second(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60,
     )
 }
+
+#[test]
+fn test_custom_properties_xml() {
+    let (namespaces, elements) = custom_properties_xml(&[
+        XmpProperty::new("http://prismstandard.org/namespaces/basic/2.0/", "prism", "issn", "1234-5678"),
+        XmpProperty::new("http://example.com/press/", "press", "Publisher", "Hogan & Sons"),
+        XmpProperty::new("http://example.com/pdf/", "pdf", "Batch", "7"),
+        XmpProperty::new("http://prismstandard.org/namespaces/basic/2.0/", "prism", "volume", "12"),
+    ]);
+
+    // `pdf` is taken by the PDF schema
+    assert_eq!(namespaces, "
+            xmlns:prism=\"http://prismstandard.org/namespaces/basic/2.0/\"
+            xmlns:press=\"http://example.com/press/\"
+            xmlns:pdf1=\"http://example.com/pdf/\"");
+    assert!(elements.contains("<prism:issn>1234-5678</prism:issn>"));
+    assert!(elements.contains("<press:Publisher>Hogan &amp; Sons</press:Publisher>"));
+    assert!(elements.contains("<pdf1:Batch>7</pdf1:Batch>"));
+    assert!(elements.contains("<prism:volume>12</prism:volume>"));
+}
+
+#[test]
+fn test_check_custom_properties() {
+    let prism = "http://prismstandard.org/namespaces/basic/2.0/";
+    assert!(check_custom_properties(&[XmpProperty::new(prism, "prism", "issn", "1234-5678")]).is_ok());
+    assert!(check_custom_properties(&[XmpProperty::new(prism, "prism", "_page-count.v2", "3")]).is_ok());
+
+    // invalid names
+    assert_eq!(check_custom_properties(&[XmpProperty::new(prism, "prism:x", "issn", "")]), Err(PdfError::InvalidXmpProperty));
+    assert_eq!(check_custom_properties(&[XmpProperty::new(prism, "prism", "1issn", "")]), Err(PdfError::InvalidXmpProperty));
+    assert_eq!(check_custom_properties(&[XmpProperty::new(prism, "prism", "is sn", "")]), Err(PdfError::InvalidXmpProperty));
+    assert_eq!(check_custom_properties(&[XmpProperty::new(prism, "", "issn", "")]), Err(PdfError::InvalidXmpProperty));
+
+    // built-in schemas, e.g. dc:title or pdf:Producer
+    assert_eq!(check_custom_properties(&[XmpProperty::new("http://purl.org/dc/elements/1.1/", "dc", "title", "")]), Err(PdfError::InvalidXmpProperty));
+    assert_eq!(check_custom_properties(&[XmpProperty::new("http://ns.adobe.com/pdf/1.3/", "pdf", "Producer", "")]), Err(PdfError::InvalidXmpProperty));
+}
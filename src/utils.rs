@@ -1,5 +1,6 @@
 //! Utility / conveniece functions for commonly use graphical shapes
 
+use lopdf;
 use scale::Pt;
 use Point;
 
@@ -52,6 +53,137 @@ pub fn calculate_points_for_circle<P: Into<Pt>>(
     pts
 }
 
+/// Calculates and returns the points for an approximated ellipse around a center (`offset_x`,
+/// `offset_y`), made of four cubic Bézier curves (one per quadrant). Unlike
+/// `calculate_points_for_circle`, the curves are connected without zero-length lines.
+#[inline]
+pub fn calculate_points_for_ellipse<P: Into<Pt>>(
+    radius_x: P,
+    radius_y: P,
+    offset_x: P,
+    offset_y: P,
+) -> Vec<(Point, bool)> {
+    let (rx, ry, offset_x, offset_y) = (radius_x.into().0, radius_y.into().0, offset_x.into().0, offset_y.into().0);
+    let point = |x: f64, y: f64| Point { x: Pt(offset_x + x * rx), y: Pt(offset_y + y * ry) };
+
+    // starting at the top, clockwise, like `calculate_points_for_circle`
+    vec![
+        (point(0.0, 1.0), true),
+        (point(C, 1.0), true), (point(1.0, C), false), (point(1.0, 0.0), true),
+        (point(1.0, -C), true), (point(C, -1.0), false), (point(0.0, -1.0), true),
+        (point(-C, -1.0), true), (point(-1.0, -C), false), (point(-1.0, 0.0), true),
+        (point(-1.0, C), true), (point(-C, 1.0), false), (point(0.0, 1.0), false),
+    ]
+}
+
+/// Calculates and returns the points for a rectangle with rounded corners, given the lower
+/// left corner and the size. The corners are quarter circles (one Bézier curve each), the
+/// radius is clamped to half of the shorter side.
+#[inline]
+pub fn calculate_points_for_rounded_rect<P: Into<Pt>>(
+    x: P,
+    y: P,
+    width: P,
+    height: P,
+    radius: P,
+) -> Vec<(Point, bool)> {
+    let (x, y, w, h) = (x.into().0, y.into().0, width.into().0, height.into().0);
+    let r = radius.into().0.min(w.abs() / 2.0).min(h.abs() / 2.0).max(0.0);
+    let point = |px: f64, py: f64| Point { x: Pt(px), y: Pt(py) };
+    let k = C * r;
+
+    // counter-clockwise, starting at the bottom edge. Each straight edge ends at the start
+    // of the following corner curve, which is marked as bezier point together with its first
+    // control point.
+    vec![
+        (point(x + r, y), false),
+        (point(x + w - r, y), true), (point(x + w - r + k, y), true), (point(x + w, y + r - k), false), (point(x + w, y + r), false),
+        (point(x + w, y + h - r), true), (point(x + w, y + h - r + k), true), (point(x + w - r + k, y + h), false), (point(x + w - r, y + h), false),
+        (point(x + r, y + h), true), (point(x + r - k, y + h), true), (point(x, y + h - r + k), false), (point(x, y + h - r), false),
+        (point(x, y + r), true), (point(x, y + r - k), true), (point(x + r - k, y), false), (point(x + r, y), false),
+    ]
+}
+
+/// Calculates and returns the points for an approximated arc around a center (`offset_x`,
+/// `offset_y`). Angles are in degrees, counter-clockwise, starting at the positive x axis.
+///
+/// The arc always goes counter-clockwise from `start_deg` to `end_deg`, so angles wrap around
+/// (300° to 60° is a 120° arc). If the angles are 360° or more apart, a full circle is returned.
+/// The arc is split into (at most) 90° Bézier curves, to keep the approximation accurate.
+#[inline]
+pub fn calculate_points_for_arc<P: Into<Pt>>(
+    radius: P,
+    offset_x: P,
+    offset_y: P,
+    start_deg: f64,
+    end_deg: f64,
+) -> Vec<(Point, bool)> {
+    let (radius, offset_x, offset_y) = (radius.into().0, offset_x.into().0, offset_y.into().0);
+
+    let sweep = if end_deg - start_deg >= 360.0 {
+        360.0
+    } else {
+        (end_deg - start_deg).rem_euclid(360.0)
+    };
+
+    let point = |angle: f64, handle_length: f64, is_bezier_handle: bool| {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        (Point {
+            x: Pt(offset_x + radius * (cos - handle_length * sin)),
+            y: Pt(offset_y + radius * (sin + handle_length * cos)),
+        }, is_bezier_handle)
+    };
+
+    let num_segments = ((sweep / 90.0).ceil() as usize).max(1);
+    let segment_sweep = sweep / num_segments as f64;
+    // length of the bezier handles, relative to the radius
+    let k = 4.0 / 3.0 * (segment_sweep.to_radians() / 4.0).tan();
+
+    let mut pts = vec![point(start_deg, 0.0, sweep > 0.0)];
+
+    for i in 0..num_segments {
+        if sweep <= 0.0 { break; }
+        let a = start_deg + segment_sweep * i as f64;
+        let b = a + segment_sweep;
+        pts.push(point(a, k, true));
+        pts.push(point(b, -k, true));
+        pts.push(point(b, 0.0, i + 1 != num_segments));
+    }
+
+    pts
+}
+
+/// Calculates and returns the vertices of a star with `num_points` points around a center
+/// (`offset_x`, `offset_y`). The vertices alternate between the `outer_radius` and the
+/// `inner_radius`. The first point is at the top, rotated counter-clockwise by `rotation_deg`.
+///
+/// If `inner_radius` is `None`, the vertices of a regular polygon with `num_points` corners
+/// are returned instead.
+#[inline]
+pub fn calculate_points_for_star<P: Into<Pt>>(
+    outer_radius: P,
+    inner_radius: Option<P>,
+    offset_x: P,
+    offset_y: P,
+    num_points: usize,
+    rotation_deg: f64,
+) -> Vec<(Point, bool)> {
+    let (outer_radius, offset_x, offset_y) = (outer_radius.into().0, offset_x.into().0, offset_y.into().0);
+    let inner_radius = inner_radius.map(|r| r.into().0);
+    let vertices_per_point = if inner_radius.is_some() { 2 } else { 1 };
+    let num_vertices = num_points * vertices_per_point;
+
+    (0..num_vertices).map(|i| {
+        let angle = 90.0 + rotation_deg + 360.0 * i as f64 / num_vertices as f64;
+        let radius = match inner_radius {
+            Some(inner) if i % 2 == 1 => inner,
+            _ => outer_radius,
+        };
+        let (sin, cos) = angle.to_radians().sin_cos();
+        (Point { x: Pt(offset_x + radius * cos), y: Pt(offset_y + radius * sin) }, false)
+    }).collect()
+}
+
 /// Calculates and returns the points for a rectangle, given a horizontal and vertical scale,
 /// and an offset into the page from the lower left corner.
 #[inline]
@@ -105,29 +237,92 @@ pub(crate) fn rand() -> usize {
     }
 }
 
-/// Returns a string with 32 random characters
+/// Characters of the random strings (document / instance IDs)
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Returns a string with 32 random alphanumeric characters
 pub(crate) fn random_character_string_32() -> String {
+    (0..32).map(|_| ALPHANUMERIC[rand() % ALPHANUMERIC.len()] as char).collect()
+}
+
+/// Encodes the bytes as (standard, padded) base64, for embedding binary data in XML
+pub(crate) fn base64_encode(input: &[u8]) -> String {
 
-    const MAX_CHARS: usize = 32;
-    let mut final_string = String::with_capacity(MAX_CHARS);
-    let mut char_pos = 0;
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity((input.len() + 2) / 3 * 4);
 
-    'outer: while char_pos < MAX_CHARS {
-        let rand = format!("{}", rand());
-        for ch in rand.chars() {
-            if char_pos < MAX_CHARS {
-                final_string.push(u8_to_char(ch.to_digit(10).unwrap() as u8));
-                char_pos += 1;
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
             } else {
-                break 'outer;
+                output.push('=');
             }
         }
     }
 
-    final_string
+    output
 }
 
-#[inline(always)]
-fn u8_to_char(input: u8) -> char {
-    ('A' as u8 + input) as char
+/// Encodes the text as PDF text string: as it is if it only contains ASCII characters,
+/// UTF-16BE (with byte order mark) otherwise
+pub(crate) fn text_string(text: &str)
+-> lopdf::Object
+{
+    use lopdf::StringFormat::{Hexadecimal, Literal};
+
+    if text.is_ascii() {
+        lopdf::Object::String(text.as_bytes().to_vec(), Literal)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(|unit| vec![(unit >> 8) as u8, unit as u8]));
+        lopdf::Object::String(bytes, Hexadecimal)
+    }
+}
+
+#[test]
+fn test_base64_encode() {
+    assert_eq!(base64_encode(b""), "");
+    assert_eq!(base64_encode(b"f"), "Zg==");
+    assert_eq!(base64_encode(b"fo"), "Zm8=");
+    assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+}
+
+#[test]
+fn test_calculate_points_for_arc() {
+    // 300° to 420° wraps around to a 120° arc, split into two 60° curves
+    let pts = calculate_points_for_arc(Pt(10.0), Pt(0.0), Pt(0.0), 300.0, 420.0);
+    assert_eq!(pts.len(), 7);
+    assert!((pts[6].0.x.0 - 5.0).abs() < 1e-9);
+    assert!((pts[6].0.y.0 - 8.660254037844386).abs() < 1e-9);
+    assert_eq!(calculate_points_for_arc(Pt(10.0), Pt(0.0), Pt(0.0), 0.0, 720.0).len(), 13);
+}
+
+#[test]
+fn test_calculate_points_for_star() {
+    let pts = calculate_points_for_star(Pt(10.0), Some(Pt(5.0)), Pt(0.0), Pt(0.0), 5, 0.0);
+    assert_eq!(pts.len(), 10);
+    assert!((pts[0].0.y.0 - 10.0).abs() < 1e-9);
+    // the first inner vertex lies between the first two points
+    assert!((pts[1].0.x.0 + 5.0 * 36f64.to_radians().sin()).abs() < 1e-9);
+    assert_eq!(calculate_points_for_star(Pt(10.0), None, Pt(0.0), Pt(0.0), 6, 0.0).len(), 6);
+}
+
+#[test]
+fn test_random_character_string_32() {
+    let a = random_character_string_32();
+    let b = random_character_string_32();
+    assert_eq!(a.len(), 32);
+    assert!(a.bytes().all(|c| c.is_ascii_alphanumeric()));
+    assert_ne!(a, b);
+}
+
+#[test]
+fn test_text_string() {
+    match text_string("\u{a7}") {
+        lopdf::Object::String(bytes, _) => assert_eq!(bytes, vec![0xFE, 0xFF, 0x00, 0xA7]),
+        other => panic!("expected a string, got {:?}", other),
+    }
 }